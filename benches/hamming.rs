@@ -0,0 +1,36 @@
+// This program is Copyright 2022 Andrew Conway and licensed under the GPL:
+//
+// This program is free software: you can redistribute it and/or modify it under the terms of the GNU General Public License as published by the Free Software Foundation, either version 3 of the License, or (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful, but WITHOUT ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License along with this program. If not, see <https://www.gnu.org/licenses/>.
+
+//! Time making a large number of codes far enough from several years of existing codes, and checking them afterwards.
+//!
+//! Run with `cargo bench`. This is a plain program rather than using a benchmarking framework, to avoid a dependency.
+
+use std::time::Instant;
+use make_exam_numbers::distance::Metric;
+use make_exam_numbers::GenerateCodes;
+use make_exam_numbers::verify::find_problems;
+
+const DIGITS : usize = 9;
+const MIN_DISTANCE : usize = 3;
+const EXISTING : usize = 100000;
+const WANTED : usize = 50000;
+
+fn main() {
+    let mut earlier = GenerateCodes::new(Some(1),DIGITS);
+    let existing : Vec<String> = (0..EXISTING).map(|_|earlier.new_code("",MIN_DISTANCE).unwrap()).collect();
+    let start = Instant::now();
+    let mut generator = GenerateCodes::new(Some(2),DIGITS);
+    generator.load_existing(existing.iter().cloned());
+    for _ in 0..WANTED { generator.new_code("",MIN_DISTANCE).unwrap(); }
+    let made = start.elapsed();
+    let start = Instant::now();
+    let problems = find_problems(generator.used(),MIN_DISTANCE,Metric::Hamming,None);
+    assert!(problems.is_empty());
+    let checked = start.elapsed();
+    println!("Made {} codes with {} existing in {:.2}s, checked all {} in {:.2}s",WANTED,EXISTING,made.as_secs_f64(),generator.used().len(),checked.as_secs_f64());
+}
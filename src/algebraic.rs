@@ -0,0 +1,114 @@
+// This program is Copyright 2022 Andrew Conway and licensed under the GPL:
+//
+// This program is free software: you can redistribute it and/or modify it under the terms of the GNU General Public License as published by the Free Software Foundation, either version 3 of the License, or (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful, but WITHOUT ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License along with this program. If not, see <https://www.gnu.org/licenses/>.
+
+//! Codes built from a Reed–Solomon code, so that the minimum distance is guaranteed by construction.
+//!
+//! A Reed–Solomon code over the prime field GF(p) with `length` ≤ p symbols and `info_len` information symbols consists of
+//! the values at the points 0, 1, ... of all polynomials of degree less than `info_len`. Two different polynomials agree
+//! at fewer than `info_len` points, so any two codewords differ in at least `length-info_len+1` places.
+//!
+//! The field used is the smallest prime at least the alphabet size, e.g. GF(11) for digits or GF(17) for hexadecimal.
+//! Codewords using a symbol that is not in the alphabet are skipped, which keeps the distance guarantee.
+
+use rand::Rng;
+use rand_chacha::ChaCha8Rng;
+
+/// A systematic Reed–Solomon code: the first `info_len` symbols are chosen freely and the rest are computed from them.
+struct ReedSolomon {
+    prime : u64,
+    info_len : usize,
+    /// For each check symbol, the multiplier for each information symbol (Lagrange basis polynomials evaluated at its point).
+    check : Vec<Vec<u64>>,
+}
+
+impl ReedSolomon {
+    fn new(prime:u64,length:usize,info_len:usize) -> Self {
+        let inverse = |a:u64| power(a,prime-2,prime);
+        let check = (info_len..length).map(|x|{
+            let x = x as u64;
+            (0..info_len as u64).map(|i|{
+                let mut value = 1;
+                for m in (0..info_len as u64).filter(|&m|m!=i) {
+                    value = value*((x+prime-m)%prime)%prime*inverse((i+prime-m)%prime)%prime;
+                }
+                value
+            }).collect()
+        }).collect();
+        ReedSolomon{prime,info_len,check}
+    }
+
+    /// The check symbols for the given information symbols.
+    fn check_symbols(&self,info:&[u64]) -> Vec<u64> {
+        self.check.iter().map(|row|row.iter().zip(info).fold(0,|sum,(a,b)|(sum+a*b)%self.prime)).collect()
+    }
+}
+
+/// How to make random parts for one prefix with guaranteed distance between them.
+pub(crate) struct Construction {
+    code : ReedSolomon,
+    alphabet_size : u64,
+    /// The information symbols fixed by the prefix, if it is part of the codeword.
+    fixed : Vec<u64>,
+    /// The number of symbols in the random part.
+    num_digits : usize,
+}
+
+impl Construction {
+    /// A construction for random parts of `num_digits` characters at least `min_distance` apart, from an alphabet of `alphabet_size` characters.
+    ///
+    /// If the prefix is given as alphabet positions (and fits in the field), it is included in the codeword, so codes with
+    /// different prefixes are also at least `min_distance` apart. Otherwise only codes with the same prefix are guaranteed to be.
+    pub(crate) fn new(alphabet_size:usize,prefix:Option<Vec<u64>>,num_digits:usize,min_distance:usize) -> Result<Self,String> {
+        if alphabet_size<2 { return Err("the algebraic strategy needs an alphabet of at least 2 characters".to_string()); }
+        let prime = smallest_prime_at_least(alphabet_size as u64);
+        if num_digits as u64>prime {
+            return Err(format!("the algebraic strategy over GF({}) can make codes of at most {} random characters, not {}",prime,prime,num_digits));
+        }
+        let min_distance = min_distance.max(1);
+        if min_distance>num_digits {
+            return Err(format!("the algebraic strategy can't make random parts of {} characters at least {} apart, as that needs a minimum distance of at most {}",num_digits,min_distance,num_digits));
+        }
+        let fixed = prefix.filter(|p|(p.len()+num_digits) as u64<=prime).unwrap_or_default();
+        let length = fixed.len()+num_digits;
+        let info_len = length+1-min_distance;
+        Ok(Construction{code:ReedSolomon::new(prime,length,info_len),alphabet_size:alphabet_size as u64,fixed,num_digits})
+    }
+
+    /// A random part, as positions in the alphabet, chosen uniformly from the codewords using only symbols in the alphabet.
+    /// None if several attempts only found codewords using other symbols.
+    pub(crate) fn random_part(&self,prng:&mut ChaCha8Rng,accept:impl Fn(&[usize])->bool) -> Option<Vec<usize>> {
+        const MAX_TRIES : usize = 1000;
+        for _ in 0..MAX_TRIES {
+            let mut info = self.fixed.clone();
+            while info.len()<self.code.info_len { info.push(prng.gen_range(0..self.alphabet_size)); }
+            let symbols : Vec<u64> = info[self.fixed.len()..].iter().copied().chain(self.code.check_symbols(&info)).collect();
+            debug_assert_eq!(symbols.len(),self.num_digits);
+            if symbols.iter().all(|&s|s<self.alphabet_size) {
+                let part : Vec<usize> = symbols.into_iter().map(|s|s as usize).collect();
+                if accept(&part) { return Some(part); }
+            }
+        }
+        None
+    }
+}
+
+fn power(base:u64,exponent:u64,modulus:u64) -> u64 {
+    let mut res = 1;
+    let mut base = base%modulus;
+    let mut exponent = exponent;
+    while exponent>0 {
+        if exponent&1==1 { res=res*base%modulus; }
+        base=base*base%modulus;
+        exponent>>=1;
+    }
+    res
+}
+
+fn smallest_prime_at_least(n:u64) -> u64 {
+    (n.max(2)..).find(|&p|(2..).take_while(|d|d*d<=p).all(|d|p%d!=0)).expect("There are infinitely many primes")
+}
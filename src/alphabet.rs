@@ -0,0 +1,218 @@
+// This program is Copyright 2022 Andrew Conway and licensed under the GPL:
+//
+// This program is free software: you can redistribute it and/or modify it under the terms of the GNU General Public License as published by the Free Software Foundation, either version 3 of the License, or (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful, but WITHOUT ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License along with this program. If not, see <https://www.gnu.org/licenses/>.
+
+//! The characters that may be used in the random part of a code.
+
+use std::borrow::Cow;
+use std::collections::BTreeMap;
+use std::fmt::{Display, Formatter};
+use std::str::FromStr;
+
+/// The set of characters each random position of a code is drawn from.
+#[derive(Clone,Debug,PartialEq,Eq)]
+pub struct Alphabet {
+    characters : Vec<char>,
+    normalisation : Normalisation,
+    /// Characters treated as the same as another, after `normalisation`. See [Alphabet::with_confusables].
+    confusables : BTreeMap<char,char>,
+}
+
+/// How codes are converted to a canonical form before being compared.
+#[derive(Clone,Copy,Debug,PartialEq,Eq)]
+enum Normalisation {
+    /// Compare exactly as written.
+    Exact,
+    /// Upper case.
+    UpperCase,
+    /// Upper case, with I and L read as 1 and O read as 0.
+    Crockford,
+}
+
+const DECIMAL : &str = "0123456789";
+const ALPHANUMERIC : &str = "0123456789ABCDEFGHIJKLMNOPQRSTUVWXYZ";
+const HEXADECIMAL : &str = "0123456789ABCDEF";
+const CROCKFORD32 : &str = "0123456789ABCDEFGHJKMNPQRSTVWXYZ";
+/// Characters removed by [Alphabet::unambiguous] from any alphabet, as they look like the letters O and I.
+pub const AMBIGUOUS_DIGITS : &str = "01";
+/// Characters additionally removed by [Alphabet::unambiguous] from alphabets containing letters.
+pub const AMBIGUOUS_WITH_LETTERS : &str = "OILoil5Ss2Zz";
+/// Groups of characters easily confused when printed and hand copied, for [Alphabet::with_confusables].
+pub const CONFUSABLE_CLASSES : [&str;5] = ["0OoQq","1IiLl","5Ss","2Zz","8Bb"];
+
+impl Alphabet {
+    /// The digits 0-9. This is the default.
+    pub fn decimal() -> Self { Alphabet{characters:DECIMAL.chars().collect(),normalisation:Normalisation::Exact,confusables:BTreeMap::new()} }
+    /// The digits 0-9 and upper case letters A-Z. Codes are compared ignoring case.
+    pub fn alphanumeric() -> Self { Alphabet{characters:ALPHANUMERIC.chars().collect(),normalisation:Normalisation::UpperCase,confusables:BTreeMap::new()} }
+    /// The hexadecimal digits 0-9 and A-F. Codes are compared ignoring case.
+    pub fn hexadecimal() -> Self { Alphabet{characters:HEXADECIMAL.chars().collect(),normalisation:Normalisation::UpperCase,confusables:BTreeMap::new()} }
+    /// Crockford's Base32: the digits and upper case letters other than I, L, O and U.
+    ///
+    /// Codes are compared after [Alphabet::normalise], which upper cases and reads I and L as 1 and O as 0, as Crockford decoders do.
+    ///
+    /// ```
+    /// use make_exam_numbers::alphabet::Alphabet;
+    ///
+    /// let crockford = Alphabet::crockford32();
+    /// assert_eq!(crockford.normalise("O0"),"00");
+    /// assert_eq!(crockford.normalise("IiLl1"),"11111");
+    /// assert_eq!(crockford.normalise("s7abz"),"S7ABZ");
+    /// assert_eq!(crockford.normalise("u"),"U"); // upper cased, but still not in the alphabet.
+    /// assert!(!crockford.contains('U'));
+    /// assert_eq!(Alphabet::decimal().normalise("o1"),"o1"); // other alphabets leave these alone.
+    /// ```
+    pub fn crockford32() -> Self { Alphabet{characters:CROCKFORD32.chars().collect(),normalisation:Normalisation::Crockford,confusables:BTreeMap::new()} }
+
+    /// An alphabet consisting of exactly the given characters, in the given order.
+    ///
+    /// Each character is a Unicode scalar value, and Hamming distance compares them one by one, so non-ASCII
+    /// characters are fine. Duplicates, whitespace and control characters are rejected, as is an empty set.
+    pub fn from_characters(characters:&str) -> Result<Self,String> {
+        let mut res : Vec<char> = vec![];
+        for c in characters.chars() {
+            if c.is_whitespace() || c.is_control() { return Err(format!("Character set {:?} contains whitespace or control character {:?}",characters,c)); }
+            if res.contains(&c) { return Err(format!("Character set {:?} contains {:?} more than once",characters,c)); }
+            res.push(c);
+        }
+        if res.is_empty() { return Err("Character set is empty".to_string()); }
+        Ok(Alphabet{characters:res,normalisation:Normalisation::Exact,confusables:BTreeMap::new()})
+    }
+
+    /// This alphabet with visually confusable characters removed.
+    ///
+    /// The digits in [AMBIGUOUS_DIGITS] are always removed; if the alphabet contains any letters, then the characters
+    /// in [AMBIGUOUS_WITH_LETTERS] are removed as well. It is an error if nothing is left.
+    pub fn unambiguous(&self) -> Result<Self,String> {
+        let has_letters = self.characters.iter().any(|c|c.is_alphabetic());
+        let characters : Vec<char> = self.characters.iter().copied().filter(|&c|!(AMBIGUOUS_DIGITS.contains(c) || (has_letters && AMBIGUOUS_WITH_LETTERS.contains(c)))).collect();
+        if characters.is_empty() { Err(format!("No unambiguous characters left in alphabet {}",self)) } else { Ok(Alphabet{characters,normalisation:self.normalisation,confusables:self.confusables.clone()}) }
+    }
+
+    /// This alphabet, but with codes compared ignoring case, as if they were upper case. Its letters are made upper case,
+    /// so codes are made in upper case too. It is an error if two of its characters only differ in case, or if a character's
+    /// upper case is more than one character (like ß).
+    ///
+    /// ```
+    /// use make_exam_numbers::alphabet::Alphabet;
+    /// let alphabet = Alphabet::from_characters("abc123").unwrap().ignoring_case().unwrap();
+    /// assert_eq!(alphabet.to_string(),"ABC123");
+    /// assert_eq!(alphabet.normalise("a1b2"),"A1B2");
+    /// assert!(Alphabet::from_characters("aA1").unwrap().ignoring_case().is_err());
+    /// ```
+    pub fn ignoring_case(&self) -> Result<Self,String> {
+        let mut characters : Vec<char> = vec![];
+        for &c in &self.characters {
+            let mut upper = c.to_uppercase();
+            let upper = match (upper.next(),upper.next()) {
+                (Some(upper),None) => upper,
+                _ => return Err(format!("Alphabet {} can't be used ignoring case, as the upper case of {} is not a single character",self,c)),
+            };
+            if characters.contains(&upper) { return Err(format!("Alphabet {} can't be used ignoring case, as it has both cases of {}",self,upper)); }
+            characters.push(upper);
+        }
+        let normalisation = if self.normalisation==Normalisation::Exact { Normalisation::UpperCase } else { self.normalisation };
+        Ok(Alphabet{characters,normalisation,confusables:self.confusables.clone()})
+    }
+
+    /// This alphabet, but with the characters in each group (such as [CONFUSABLE_CLASSES]) treated as the same character
+    /// when comparing codes, so codes only differing by a confusion are at distance 0 at that position.
+    ///
+    /// Each group is normalised as codes are, then its characters are all read as the first of them in the alphabet
+    /// (or just the first, if none are). White space in the groups is ignored. It is an error for a character to be in two groups.
+    ///
+    /// ```
+    /// use make_exam_numbers::alphabet::{Alphabet, CONFUSABLE_CLASSES};
+    /// let alphabet = Alphabet::alphanumeric().with_confusables(&CONFUSABLE_CLASSES).unwrap();
+    /// assert_eq!(alphabet.normalise("OIL5"),alphabet.normalise("0115"));
+    /// assert_eq!(alphabet.normalise("sB"),"58");
+    /// assert_eq!(alphabet.distinct_size(),36-7);
+    /// ```
+    pub fn with_confusables(&self,groups:&[&str]) -> Result<Self,String> {
+        let mut confusables = self.confusables.clone();
+        let mut grouped : Vec<char> = vec![];
+        for group in groups {
+            let mut members : Vec<char> = vec![];
+            for c in self.normalise(group).chars().filter(|c|!c.is_whitespace()) {
+                if grouped.contains(&c) && !members.contains(&c) { return Err(format!("Character {} is in more than one group of confusable characters",c)); }
+                if !members.contains(&c) { members.push(c); grouped.push(c); }
+            }
+            let representative = members.iter().copied().find(|c|self.characters.contains(c)).or_else(||members.first().copied());
+            if let Some(representative) = representative {
+                for &c in &members { if c!=representative { confusables.insert(c,representative); } }
+            }
+        }
+        Ok(Alphabet{characters:self.characters.clone(),normalisation:self.normalisation,confusables})
+    }
+
+    /// The number of different characters in the alphabet once codes are normalised, which is less than [Alphabet::size]
+    /// with [Alphabet::with_confusables].
+    pub fn distinct_size(&self) -> usize {
+        let mut canonical : Vec<char> = self.characters.iter().map(|&c|self.confusables.get(&c).copied().unwrap_or(c)).collect();
+        canonical.sort_unstable();
+        canonical.dedup();
+        canonical.len()
+    }
+
+    /// Whether codes are compared ignoring case.
+    pub fn ignores_case(&self) -> bool { self.normalisation!=Normalisation::Exact }
+
+    /// Convert a code to the canonical form used for comparisons. For most alphabets this does nothing.
+    pub fn normalise<'a>(&self,code:&'a str) -> Cow<'a,str> {
+        let res = self.normalise_case(code);
+        if self.confusables.is_empty() { return res; }
+        Cow::Owned(res.chars().map(|c|self.confusables.get(&c).copied().unwrap_or(c)).collect())
+    }
+
+    /// [Alphabet::normalise] without [Alphabet::with_confusables].
+    fn normalise_case<'a>(&self,code:&'a str) -> Cow<'a,str> {
+        match self.normalisation {
+            Normalisation::Exact => Cow::Borrowed(code),
+            Normalisation::UpperCase => Cow::Owned(code.to_uppercase()),
+            Normalisation::Crockford => Cow::Owned(code.chars().map(|c|match c.to_ascii_uppercase() {
+                'I' | 'L' => '1',
+                'O' => '0',
+                c => c,
+            }).collect()),
+        }
+    }
+
+    /// Whether `c` is one of the characters in the alphabet.
+    pub fn contains(&self,c:char) -> bool { self.characters.contains(&c) }
+
+    /// The characters in the alphabet, in order.
+    pub fn characters(&self) -> &[char] { &self.characters }
+    /// The number of characters in the alphabet.
+    pub fn size(&self) -> usize { self.characters.len() }
+    /// Whether the alphabet is exactly the digits 0-9, in which case codes are generated as zero padded numbers.
+    pub fn is_decimal(&self) -> bool { self.characters.iter().copied().eq(DECIMAL.chars()) }
+}
+
+impl Default for Alphabet {
+    fn default() -> Self { Alphabet::decimal() }
+}
+
+impl Display for Alphabet {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        for c in &self.characters { write!(f,"{}",c)?; }
+        Ok(())
+    }
+}
+
+impl FromStr for Alphabet {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "digits" => Ok(Alphabet::decimal()),
+            "alnum" => Ok(Alphabet::alphanumeric()),
+            "hex" => Ok(Alphabet::hexadecimal()),
+            "crockford32" => Ok(Alphabet::crockford32()),
+            _ => Err(format!("Unknown alphabet {}, expecting digits, alnum, hex or crockford32",s)),
+        }
+    }
+}
@@ -0,0 +1,198 @@
+// This program is Copyright 2022 Andrew Conway and licensed under the GPL:
+//
+// This program is free software: you can redistribute it and/or modify it under the terms of the GNU General Public License as published by the Free Software Foundation, either version 3 of the License, or (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful, but WITHOUT ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License along with this program. If not, see <https://www.gnu.org/licenses/>.
+
+//! Measure how far apart a set of codes actually are, rather than checking against a threshold as [crate::verify] does.
+
+use std::collections::{BTreeMap, HashMap};
+use crate::distance::{LengthPolicy, Metric};
+use crate::index::HammingIndex;
+use crate::verify::longest_prefix;
+
+/// Two different codes, referred to by their index in the list analyzed, with `first` < `second`.
+#[derive(Clone,Copy,Debug,PartialEq,Eq)]
+pub struct Pair {
+    pub first : usize,
+    pub second : usize,
+    pub distance : usize,
+}
+
+/// What a set of codes is like.
+#[derive(Clone,Debug,PartialEq,Eq)]
+pub struct Analysis {
+    /// The number of codes of each length, in characters.
+    pub lengths : BTreeMap<usize,usize>,
+    /// Codes identical to an earlier one, as (index of the first occurrence, index of the duplicate).
+    pub duplicates : Vec<(usize,usize)>,
+    /// The closest pairs of distinct codes, closest first. Ties are in order of `first` then `second`.
+    pub closest : Vec<Pair>,
+}
+
+impl Analysis {
+    /// The smallest distance between two distinct codes, or None if there are fewer than two (comparable) distinct codes.
+    pub fn min_distance(&self) -> Option<usize> { self.closest.first().map(|p|p.distance) }
+}
+
+/// Analyze a set of codes, finding the `num_closest` closest pairs of distinct codes as measured by `metric`.
+/// Codes of different lengths are compared with Hamming distance (weighted or not) according to `length_policy`.
+///
+/// For Hamming distance this uses the same index as the generator, so tens of thousands of codes can be analyzed quickly
+/// when the closest pairs are not too far apart. Other metrics compare every pair.
+pub fn analyze(codes:&[String],metric:Metric,length_policy:LengthPolicy,num_closest:usize) -> Analysis {
+    let mut lengths : BTreeMap<usize,usize> = BTreeMap::new();
+    let mut seen : HashMap<&str,usize> = HashMap::new();
+    let mut duplicates = vec![];
+    let mut distinct : Vec<usize> = vec![];
+    for (index,code) in codes.iter().enumerate() {
+        *lengths.entry(code.chars().count()).or_default()+=1;
+        if let Some(&first) = seen.get(code.as_str()) { duplicates.push((first,index)); }
+        else { seen.insert(code,index); distinct.push(index); }
+    }
+    let closest = closest_pairs(codes,codes,&distinct,metric,length_policy,num_closest,&|_,_|true);
+    Analysis{lengths,duplicates,closest}
+}
+
+/// Like the closest pairs of [analyze], but comparing each code with the others read backwards, for codes that must not be
+/// confused when read upside down. Reversing both codes doesn't change their distance, so `distance` is between the first
+/// code and the second read backwards, or equally the first read backwards and the second. A code is not compared with
+/// its own reversal, or with the reversal of a duplicate of itself.
+///
+/// ```
+/// use make_exam_numbers::analyze::closest_reversed;
+/// use make_exam_numbers::distance::{LengthPolicy, Metric};
+///
+/// let codes = ["123456","654399","111111"].map(|s|s.to_string());
+/// let closest = closest_reversed(&codes,Metric::Hamming,LengthPolicy::default(),1);
+/// assert_eq!((closest[0].first,closest[0].second,closest[0].distance),(0,1,2));
+/// ```
+pub fn closest_reversed(codes:&[String],metric:Metric,length_policy:LengthPolicy,num_closest:usize) -> Vec<Pair> {
+    let mut seen : HashMap<&str,usize> = HashMap::new();
+    let distinct : Vec<usize> = codes.iter().enumerate().filter(|(index,code)|*seen.entry(code.as_str()).or_insert(*index)==*index).map(|(index,_)|index).collect();
+    let reversed : Vec<String> = codes.iter().map(|c|c.chars().rev().collect()).collect();
+    closest_pairs(codes,&reversed,&distinct,metric,length_policy,num_closest,&|_,_|true)
+}
+
+/// Like the closest pairs of [analyze], but only of codes with the same prefix if `same_prefix`, or only of codes with different
+/// prefixes if not. Each code has the longest of the `prefixes` it starts with, as for [crate::verify::find_problems_per_prefix],
+/// and codes starting with none of them count as having different prefixes to every other code.
+///
+/// ```
+/// use make_exam_numbers::analyze::closest_by_prefix;
+/// use make_exam_numbers::distance::{LengthPolicy, Metric};
+///
+/// let codes = ["A1234","A1299","B1294","B9999"].map(|s|s.to_string());
+/// let prefixes = ["A".to_string(),"B".to_string()];
+/// let within = closest_by_prefix(&codes,&prefixes,true,Metric::Hamming,LengthPolicy::default(),1);
+/// assert_eq!((within[0].first,within[0].second,within[0].distance),(0,1,2));
+/// let across = closest_by_prefix(&codes,&prefixes,false,Metric::Hamming,LengthPolicy::default(),1);
+/// assert_eq!((across[0].first,across[0].second,across[0].distance),(0,2,2));
+/// ```
+pub fn closest_by_prefix(codes:&[String],prefixes:&[String],same_prefix:bool,metric:Metric,length_policy:LengthPolicy,num_closest:usize) -> Vec<Pair> {
+    let mut seen : HashMap<&str,usize> = HashMap::new();
+    let distinct : Vec<usize> = codes.iter().enumerate().filter(|(index,code)|*seen.entry(code.as_str()).or_insert(*index)==*index).map(|(index,_)|index).collect();
+    let prefix : Vec<Option<&str>> = codes.iter().map(|code|longest_prefix(code,prefixes)).collect();
+    let keep = |a:usize,b:usize| (prefix[a].is_some() && prefix[a]==prefix[b])==same_prefix;
+    closest_pairs(codes,codes,&distinct,metric,length_policy,num_closest,&keep)
+}
+
+/// The `num_closest` closest pairs of the `distinct` codes, where each code is compared as `queries[index]` with the codes before it.
+/// Only pairs (first, second) for which `keep` is true are included.
+fn closest_pairs(codes:&[String],queries:&[String],distinct:&[usize],metric:Metric,length_policy:LengthPolicy,num_closest:usize,keep:&dyn Fn(usize,usize)->bool) -> Vec<Pair> {
+    if num_closest==0 { vec![] }
+    else if metric==Metric::Hamming { closest_hamming(codes,queries,distinct,length_policy,num_closest,keep) }
+    else { closest_by_comparing_all(codes,queries,distinct,metric,length_policy,num_closest,keep) }
+}
+
+/// Sort pairs closest first, breaking ties by index, and keep the first `num_closest`.
+fn keep_closest(pairs:&mut Vec<Pair>,num_closest:usize) {
+    pairs.sort_unstable_by_key(|p|(p.distance,p.first,p.second));
+    pairs.truncate(num_closest);
+}
+
+/// Find all pairs closer than some threshold using the index, increasing the threshold until there are enough.
+fn closest_hamming(codes:&[String],queries:&[String],distinct:&[usize],length_policy:LengthPolicy,num_closest:usize,keep:&dyn Fn(usize,usize)->bool) -> Vec<Pair> {
+    let max_length = distinct.iter().map(|&i|codes[i].chars().count()).max().unwrap_or(0);
+    let mut pairs = vec![];
+    // no two codes are further apart than the longest code, so the last threshold finds every comparable pair.
+    for threshold in 1..=max_length+1 {
+        pairs.clear();
+        let mut found = 0;
+        let mut index = HammingIndex::new(threshold,length_policy);
+        for &second in distinct {
+            for (first,distance) in index.close_to(&queries[second],codes).into_iter().filter(|&(first,_)|keep(first,second)) {
+                found+=1;
+                pairs.push(Pair{first,second,distance});
+            }
+            if pairs.len()>2*num_closest+1000 { keep_closest(&mut pairs,num_closest); }
+            index.insert(second,&codes[second]);
+        }
+        if found>=num_closest { break; }
+    }
+    keep_closest(&mut pairs,num_closest);
+    pairs
+}
+
+/// Compare every pair, only working out distances small enough to be among the closest found so far.
+fn closest_by_comparing_all(codes:&[String],queries:&[String],distinct:&[usize],metric:Metric,length_policy:LengthPolicy,num_closest:usize,keep:&dyn Fn(usize,usize)->bool) -> Vec<Pair> {
+    let chars : Vec<Vec<char>> = distinct.iter().map(|&i|codes[i].chars().collect()).collect();
+    let query_chars : Vec<Vec<char>> = distinct.iter().map(|&i|queries[i].chars().collect()).collect();
+    let mut closest : Vec<Pair> = vec![];
+    for (i,&first) in distinct.iter().enumerate() {
+        for (j,&second) in distinct.iter().enumerate().skip(i+1) {
+            if !keep(first,second) { continue; }
+            let bound = if closest.len()<num_closest { usize::MAX } else { closest[num_closest-1].distance };
+            if let Some(distance) = metric.distance_below_with_policy(&chars[i],&query_chars[j],bound,length_policy) {
+                // pairs are considered in order, so a new pair goes after any already found at the same distance.
+                let position = closest.partition_point(|p|p.distance<=distance);
+                closest.insert(position,Pair{first,second,distance});
+                closest.truncate(num_closest);
+            }
+        }
+    }
+    closest
+}
+
+/// The `num_nearest` codes closest to `code`, as (index, distance), closest first. Ties are in order of index, and codes
+/// identical to an earlier one are left out. This is for finding which code a mistyped one was meant to be.
+///
+/// ```
+/// use make_exam_numbers::analyze::nearest;
+/// use make_exam_numbers::distance::{LengthPolicy, Metric};
+///
+/// let codes = ["A1234","A5678","A1299","A1234"].map(|s|s.to_string());
+/// assert_eq!(nearest("A1239",&codes,Metric::Hamming,LengthPolicy::default(),2),vec![(0,1),(2,1)]);
+/// assert_eq!(nearest("A123",&codes,Metric::Levenshtein,LengthPolicy::default(),1),vec![(0,1)]);
+/// ```
+pub fn nearest(code:&str,codes:&[String],metric:Metric,length_policy:LengthPolicy,num_nearest:usize) -> Vec<(usize,usize)> {
+    let code : Vec<char> = code.chars().collect();
+    let mut seen : HashMap<&str,usize> = HashMap::new();
+    let mut nearest : Vec<(usize,usize)> = vec![];
+    if num_nearest==0 { return nearest; }
+    for (index,other) in codes.iter().enumerate() {
+        if *seen.entry(other.as_str()).or_insert(index)!=index { continue; }
+        let bound = if nearest.len()<num_nearest { usize::MAX } else { nearest[num_nearest-1].1 };
+        if let Some(distance) = metric.distance_below_with_policy(&code,&other.chars().collect::<Vec<char>>(),bound,length_policy) {
+            let position = nearest.partition_point(|&(_,d)|d<=distance);
+            nearest.insert(position,(index,distance));
+            nearest.truncate(num_nearest);
+        }
+    }
+    nearest
+}
+
+/// How far a mistyped code can be from the code it was meant to be, when codes are at least `min_distance` apart, for it to
+/// be closer to that code than to any other. That is, the largest distance less than half of `min_distance`.
+///
+/// ```
+/// use make_exam_numbers::analyze::correction_radius;
+///
+/// assert_eq!(correction_radius(3),1);
+/// assert_eq!(correction_radius(4),1);
+/// assert_eq!(correction_radius(5),2);
+/// assert_eq!(correction_radius(1),0);
+/// ```
+pub fn correction_radius(min_distance:usize) -> usize { min_distance.saturating_sub(1)/2 }
@@ -0,0 +1,257 @@
+// This program is Copyright 2022 Andrew Conway and licensed under the GPL:
+//
+// This program is free software: you can redistribute it and/or modify it under the terms of the GNU General Public License as published by the Free Software Foundation, either version 3 of the License, or (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful, but WITHOUT ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License along with this program. If not, see <https://www.gnu.org/licenses/>.
+
+//! Barcodes of codes, for printing on script covers, in Code 128 or Code 39.
+//!
+//! The bars themselves are always available. Drawing them as SVG or PNG images, with a quiet zone either side
+//! and the code written underneath, needs the `barcodes` feature.
+
+use std::fmt::{Display, Formatter};
+use std::str::FromStr;
+
+/// The kind of barcode.
+#[derive(Clone,Copy,Debug,PartialEq,Eq)]
+pub enum BarcodeFormat {
+    /// Code 128, which can have any printable ASCII character. Runs of digits are packed two to a symbol.
+    Code128,
+    /// Code 39, which can only have digits, upper case letters, space and `-.$/+%`, but is read by almost every scanner.
+    Code39,
+}
+
+impl FromStr for BarcodeFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "code128" => Ok(BarcodeFormat::Code128),
+            "code39" => Ok(BarcodeFormat::Code39),
+            _ => Err(format!("Unknown barcode format {}, expecting code128 or code39",s)),
+        }
+    }
+}
+
+impl Display for BarcodeFormat {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            BarcodeFormat::Code128 => "code128",
+            BarcodeFormat::Code39 => "code39",
+        })
+    }
+}
+
+/// The widths of the bar, space, bar, space, bar and space of each Code 128 symbol, by value. 103 to 105 are the start symbols.
+const CODE128 : [&str;106] = [
+    "212222","222122","222221","121223","121322","131222","122213","122312","132212","221213","221312","231212","112232","122132","122231","113222",
+    "123122","123221","223211","221132","221231","213212","223112","312131","311222","321122","321221","312212","322112","322211","212123","212321",
+    "232121","111323","131123","131321","112313","132113","132311","211313","231113","231311","112133","112331","132131","113123","113321","133121",
+    "313121","211331","231131","213113","213311","213131","311123","311321","331121","312113","312311","332111","314111","221411","431111","111224",
+    "111422","121124","121421","141122","141221","112214","112412","122114","122411","142112","142211","241211","221114","413111","241112","134111",
+    "111242","121142","121241","114212","124112","124211","411212","421112","421211","212141","214121","412121","111143","111341","131141","114113",
+    "114311","411113","411311","113141","114131","311141","411131","211412","211214","211232",
+];
+const CODE128_STOP : &str = "2331112";
+const CODE128_CODE_C : u8 = 99;
+const CODE128_CODE_B : u8 = 100;
+const CODE128_START_B : u8 = 104;
+const CODE128_START_C : u8 = 105;
+
+/// The characters of Code 39 and their narrow (n) and wide (w) bar, space, ... elements. `*` is only used to start and stop.
+const CODE39 : [(char,&str);44] = [
+    ('0',"nnnwwnwnn"),('1',"wnnwnnnnw"),('2',"nnwwnnnnw"),('3',"wnwwnnnnn"),('4',"nnnwwnnnw"),('5',"wnnwwnnnn"),('6',"nnwwwnnnn"),('7',"nnnwnnwnw"),
+    ('8',"wnnwnnwnn"),('9',"nnwwnnwnn"),('A',"wnnnnwnnw"),('B',"nnwnnwnnw"),('C',"wnwnnwnnn"),('D',"nnnnwwnnw"),('E',"wnnnwwnnn"),('F',"nnwnwwnnn"),
+    ('G',"nnnnnwwnw"),('H',"wnnnnwwnn"),('I',"nnwnnwwnn"),('J',"nnnnwwwnn"),('K',"wnnnnnnww"),('L',"nnwnnnnww"),('M',"wnwnnnnwn"),('N',"nnnnwnnww"),
+    ('O',"wnnnwnnwn"),('P',"nnwnwnnwn"),('Q',"nnnnnnwww"),('R',"wnnnnnwwn"),('S',"nnwnnnwwn"),('T',"nnnnwnwwn"),('U',"wwnnnnnnw"),('V',"nwwnnnnnw"),
+    ('W',"wwwnnnnnn"),('X',"nwnnwnnnw"),('Y',"wwnnwnnnn"),('Z',"nwwnwnnnn"),('-',"nwnnnnwnw"),('.',"wwnnnnwnn"),(' ',"nwwnnnwnn"),('$',"nwnwnwnnn"),
+    ('/',"nwnwnnnwn"),('+',"nwnnnwnwn"),('%',"nnnwnwnwn"),('*',"nwnnwnwnn"),
+];
+/// How many modules wide a wide Code 39 element is; narrow ones are one.
+const CODE39_WIDE : usize = 3;
+
+impl BarcodeFormat {
+    /// Whether a barcode of this format can have the character `c`.
+    pub fn can_encode(&self,c:char) -> bool {
+        match self {
+            BarcodeFormat::Code128 => (' '..='~').contains(&c),
+            BarcodeFormat::Code39 => c!='*' && CODE39.iter().any(|&(d,_)|d==c),
+        }
+    }
+
+    /// The barcode of `code`, as whether each module (the width of the narrowest bar) is black, from left to right.
+    /// This includes the start and stop symbols, and for Code 128 the check symbol, but not the quiet zone either side.
+    ///
+    /// ```
+    /// use make_exam_numbers::barcode::BarcodeFormat;
+    ///
+    /// let bars = BarcodeFormat::Code39.modules("A").unwrap();
+    /// let text : String = bars.iter().map(|&b|if b { '1' } else { '0' }).collect();
+    /// // start *, A, stop *, separated by narrow spaces.
+    /// assert_eq!(text,concat!("100010111011101","0","111010100010111","0","100010111011101"));
+    /// // start B, S and 0, then code C, 3 pairs of digits, the check symbol, and stop.
+    /// assert_eq!(BarcodeFormat::Code128.modules("S0123456").unwrap().len(),11*8+13);
+    /// assert!(BarcodeFormat::Code39.modules("s01").is_err());
+    /// ```
+    pub fn modules(&self,code:&str) -> Result<Vec<bool>,String> {
+        if let Some(c) = code.chars().find(|&c|!self.can_encode(c)) {
+            return Err(format!("Code {} can't be a {} barcode, as it has the character {:?}",code,self,c));
+        }
+        let mut modules = vec![];
+        match self {
+            BarcodeFormat::Code128 => {
+                for value in code128_values(code) { add_widths(&mut modules,CODE128[value as usize]); }
+                add_widths(&mut modules,CODE128_STOP);
+            }
+            BarcodeFormat::Code39 => {
+                for (i,c) in std::iter::once('*').chain(code.chars()).chain(std::iter::once('*')).enumerate() {
+                    if i>0 { modules.push(false); }
+                    let (_,pattern) = CODE39.iter().find(|&&(d,_)|d==c).expect("Checked above");
+                    for (j,element) in pattern.chars().enumerate() {
+                        let width = if element=='w' { CODE39_WIDE } else { 1 };
+                        modules.extend(std::iter::repeat_n(j%2==0,width));
+                    }
+                }
+            }
+        }
+        Ok(modules)
+    }
+}
+
+/// Add alternating bars and spaces of the given widths, starting with a bar.
+fn add_widths(modules:&mut Vec<bool>,widths:&str) {
+    for (i,width) in widths.bytes().enumerate() { modules.extend(std::iter::repeat_n(i%2==0,(width-b'0') as usize)); }
+}
+
+/// The values of the Code 128 symbols for `code`, from the start symbol to the check symbol. Code set B is used, except
+/// for runs of at least 4 digits (or a whole code of an even number of digits), which use code set C, two digits to a symbol.
+fn code128_values(code:&str) -> Vec<u8> {
+    let bytes = code.as_bytes();
+    let digits_from = |i:usize| bytes[i..].iter().take_while(|b|b.is_ascii_digit()).count();
+    let use_c = |run:usize| run>=4 || (run==bytes.len() && run>=2 && run.is_multiple_of(2));
+    let mut in_c = !bytes.is_empty() && use_c(digits_from(0)) && digits_from(0).is_multiple_of(2);
+    let mut values = vec![if in_c { CODE128_START_C } else { CODE128_START_B }];
+    let mut i = 0;
+    while i<bytes.len() {
+        let run = digits_from(i);
+        if in_c {
+            if run>=2 {
+                values.push((bytes[i]-b'0')*10+bytes[i+1]-b'0');
+                i+=2;
+            } else {
+                values.push(CODE128_CODE_B);
+                in_c = false;
+            }
+        } else if use_c(run) && run.is_multiple_of(2) {
+            values.push(CODE128_CODE_C);
+            in_c = true;
+        } else {
+            values.push(bytes[i]-b' ');
+            i+=1;
+        }
+    }
+    let check = values.iter().enumerate().map(|(position,&value)|position.max(1)*value as usize).sum::<usize>()%103;
+    values.push(check as u8);
+    values
+}
+
+/// How many modules of space there are either side of the bars, so a scanner can find where they start and end.
+#[cfg(feature="barcodes")]
+pub const QUIET_ZONE : usize = 10;
+/// How many modules high the bars are.
+#[cfg(feature="barcodes")]
+pub const BAR_HEIGHT : usize = 50;
+/// The space between the bars and the caption underneath, in modules.
+#[cfg(feature="barcodes")]
+const CAPTION_GAP : usize = 3;
+/// The height of the caption's characters, in modules.
+#[cfg(feature="barcodes")]
+const CAPTION_HEIGHT : usize = 10;
+/// The width of a module in an SVG image, in millimetres, which suits most scanners and printers.
+#[cfg(feature="barcodes")]
+const SVG_MODULE_MM : f64 = 0.33;
+
+/// An SVG image of the bars (from [BarcodeFormat::modules]), with a quiet zone either side and `caption` written underneath.
+#[cfg(feature="barcodes")]
+pub fn svg(modules:&[bool],caption:&str) -> String {
+    let width = modules.len()+2*QUIET_ZONE;
+    let height = BAR_HEIGHT+CAPTION_GAP+CAPTION_HEIGHT+CAPTION_GAP;
+    let mut res = format!("<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{:.2}mm\" height=\"{:.2}mm\" viewBox=\"0 0 {} {}\">\n",width as f64*SVG_MODULE_MM,height as f64*SVG_MODULE_MM,width,height);
+    res.push_str(&format!("<rect width=\"{}\" height=\"{}\" fill=\"white\"/>\n<path fill=\"black\" d=\"",width,height));
+    for (start,length) in bars(modules) { res.push_str(&format!("M{} 0h{}v{}h-{}z",QUIET_ZONE+start,length,BAR_HEIGHT,length)); }
+    res.push_str("\"/>\n");
+    let escaped = caption.replace('&',"&amp;").replace('<',"&lt;").replace('>',"&gt;");
+    res.push_str(&format!("<text x=\"{}\" y=\"{}\" font-family=\"monospace\" font-size=\"{}\" text-anchor=\"middle\">{}</text>\n</svg>\n",width as f64/2.0,BAR_HEIGHT+CAPTION_GAP+CAPTION_HEIGHT*4/5,CAPTION_HEIGHT,escaped));
+    res
+}
+
+/// A PNG image of the bars (from [BarcodeFormat::modules]), `scale` pixels to a module, with a quiet zone either side and
+/// `caption` written underneath. Characters in the caption other than printable ASCII are left as gaps.
+#[cfg(feature="barcodes")]
+pub fn png(modules:&[bool],caption:&str,scale:usize) -> Vec<u8> {
+    const GLYPH_WIDTH : usize = 6; // 5 columns and a gap.
+    let scale = scale.max(1);
+    let bars_width = (modules.len()+2*QUIET_ZONE)*scale;
+    let caption_length = caption.chars().count();
+    // each pixel of the font is as many pixels of the image as fit the caption height, or fit the caption under the bars.
+    let pixel = (CAPTION_HEIGHT*scale/8).min((bars_width-2*QUIET_ZONE*scale)/(GLYPH_WIDTH*caption_length).max(1)).max(1);
+    let width = bars_width.max(caption_length*GLYPH_WIDTH*pixel+2*QUIET_ZONE*scale);
+    let caption_top = (BAR_HEIGHT+CAPTION_GAP)*scale;
+    let height = caption_top+7*pixel+CAPTION_GAP*scale;
+    let mut pixels = vec![255u8;width*height];
+    let left = (width-(modules.len()*scale))/2;
+    for (start,length) in bars(modules) {
+        for row in pixels.chunks_mut(width).take(BAR_HEIGHT*scale) { row[left+start*scale..left+(start+length)*scale].fill(0); }
+    }
+    let caption_left = (width-caption_length*GLYPH_WIDTH*pixel+pixel)/2;
+    for (i,c) in caption.chars().enumerate() {
+        let Some(glyph) = (c as usize).checked_sub(' ' as usize).and_then(|g|FONT.get(g)) else { continue; };
+        for (column,&bits) in glyph.iter().enumerate() {
+            for font_row in (0..7).filter(|r|bits&(1<<r)!=0) {
+                let x = caption_left+(i*GLYPH_WIDTH+column)*pixel;
+                for y in caption_top+font_row*pixel..caption_top+(font_row+1)*pixel { pixels[y*width+x..y*width+x+pixel].fill(0); }
+            }
+        }
+    }
+    crate::png::encode_grey(width,height,&pixels)
+}
+
+/// The black bars in `modules`, as (first module, number of modules).
+#[cfg(feature="barcodes")]
+fn bars(modules:&[bool]) -> Vec<(usize,usize)> {
+    let mut res : Vec<(usize,usize)> = vec![];
+    for (i,_) in modules.iter().enumerate().filter(|(_,&black)|black) {
+        match res.last_mut() {
+            Some((start,length)) if *start+*length==i => *length+=1,
+            _ => res.push((i,1)),
+        }
+    }
+    res
+}
+
+/// A 5 by 7 pixel font for printable ASCII, from space to `~`. Each character is 5 columns, left to right, with the
+/// lowest bit the top row.
+#[cfg(feature="barcodes")]
+const FONT : [[u8;5];95] = [
+    [0x00,0x00,0x00,0x00,0x00],[0x00,0x00,0x5F,0x00,0x00],[0x00,0x07,0x00,0x07,0x00],[0x14,0x7F,0x14,0x7F,0x14],[0x24,0x2A,0x7F,0x2A,0x12],
+    [0x23,0x13,0x08,0x64,0x62],[0x36,0x49,0x55,0x22,0x50],[0x00,0x05,0x03,0x00,0x00],[0x00,0x1C,0x22,0x41,0x00],[0x00,0x41,0x22,0x1C,0x00],
+    [0x08,0x2A,0x1C,0x2A,0x08],[0x08,0x08,0x3E,0x08,0x08],[0x00,0x50,0x30,0x00,0x00],[0x08,0x08,0x08,0x08,0x08],[0x00,0x60,0x60,0x00,0x00],
+    [0x20,0x10,0x08,0x04,0x02],[0x3E,0x51,0x49,0x45,0x3E],[0x00,0x42,0x7F,0x40,0x00],[0x42,0x61,0x51,0x49,0x46],[0x21,0x41,0x45,0x4B,0x31],
+    [0x18,0x14,0x12,0x7F,0x10],[0x27,0x45,0x45,0x45,0x39],[0x3C,0x4A,0x49,0x49,0x30],[0x01,0x71,0x09,0x05,0x03],[0x36,0x49,0x49,0x49,0x36],
+    [0x06,0x49,0x49,0x29,0x1E],[0x00,0x36,0x36,0x00,0x00],[0x00,0x56,0x36,0x00,0x00],[0x08,0x14,0x22,0x41,0x00],[0x14,0x14,0x14,0x14,0x14],
+    [0x00,0x41,0x22,0x14,0x08],[0x02,0x01,0x51,0x09,0x06],[0x32,0x49,0x79,0x41,0x3E],[0x7E,0x11,0x11,0x11,0x7E],[0x7F,0x49,0x49,0x49,0x36],
+    [0x3E,0x41,0x41,0x41,0x22],[0x7F,0x41,0x41,0x22,0x1C],[0x7F,0x49,0x49,0x49,0x41],[0x7F,0x09,0x09,0x09,0x01],[0x3E,0x41,0x49,0x49,0x7A],
+    [0x7F,0x08,0x08,0x08,0x7F],[0x00,0x41,0x7F,0x41,0x00],[0x20,0x40,0x41,0x3F,0x01],[0x7F,0x08,0x14,0x22,0x41],[0x7F,0x40,0x40,0x40,0x40],
+    [0x7F,0x02,0x0C,0x02,0x7F],[0x7F,0x04,0x08,0x10,0x7F],[0x3E,0x41,0x41,0x41,0x3E],[0x7F,0x09,0x09,0x09,0x06],[0x3E,0x41,0x51,0x21,0x5E],
+    [0x7F,0x09,0x19,0x29,0x46],[0x46,0x49,0x49,0x49,0x31],[0x01,0x01,0x7F,0x01,0x01],[0x3F,0x40,0x40,0x40,0x3F],[0x1F,0x20,0x40,0x20,0x1F],
+    [0x3F,0x40,0x38,0x40,0x3F],[0x63,0x14,0x08,0x14,0x63],[0x07,0x08,0x70,0x08,0x07],[0x61,0x51,0x49,0x45,0x43],[0x00,0x7F,0x41,0x41,0x00],
+    [0x02,0x04,0x08,0x10,0x20],[0x00,0x41,0x41,0x7F,0x00],[0x04,0x02,0x01,0x02,0x04],[0x40,0x40,0x40,0x40,0x40],[0x00,0x01,0x02,0x04,0x00],
+    [0x20,0x54,0x54,0x54,0x78],[0x7F,0x48,0x44,0x44,0x38],[0x38,0x44,0x44,0x44,0x20],[0x38,0x44,0x44,0x48,0x7F],[0x38,0x54,0x54,0x54,0x18],
+    [0x08,0x7E,0x09,0x01,0x02],[0x0C,0x52,0x52,0x52,0x3E],[0x7F,0x08,0x04,0x04,0x78],[0x00,0x44,0x7D,0x40,0x00],[0x20,0x40,0x44,0x3D,0x00],
+    [0x7F,0x10,0x28,0x44,0x00],[0x00,0x41,0x7F,0x40,0x00],[0x7C,0x04,0x18,0x04,0x78],[0x7C,0x08,0x04,0x04,0x78],[0x38,0x44,0x44,0x44,0x38],
+    [0x7C,0x14,0x14,0x14,0x08],[0x08,0x14,0x14,0x18,0x7C],[0x7C,0x08,0x04,0x04,0x08],[0x48,0x54,0x54,0x54,0x20],[0x04,0x3F,0x44,0x40,0x20],
+    [0x3C,0x40,0x40,0x20,0x7C],[0x1C,0x20,0x40,0x20,0x1C],[0x3C,0x40,0x30,0x40,0x3C],[0x44,0x28,0x10,0x28,0x44],[0x0C,0x50,0x50,0x50,0x3C],
+    [0x44,0x64,0x54,0x4C,0x44],[0x00,0x08,0x36,0x41,0x00],[0x00,0x00,0x7F,0x00,0x00],[0x00,0x41,0x36,0x08,0x00],[0x10,0x08,0x08,0x10,0x08],
+];
@@ -0,0 +1,102 @@
+// This program is Copyright 2022 Andrew Conway and licensed under the GPL:
+//
+// This program is free software: you can redistribute it and/or modify it under the terms of the GNU General Public License as published by the Free Software Foundation, either version 3 of the License, or (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful, but WITHOUT ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License along with this program. If not, see <https://www.gnu.org/licenses/>.
+
+//! Estimates of how many codes of a given length can be at least some Hamming distance apart.
+//!
+//! The bounds are the standard ones from coding theory, for codes of `length` characters from an alphabet of
+//! `alphabet_size` characters. They are computed as floating point numbers as they can be astronomically large.
+
+use std::ops::Range;
+use crate::GenerateCodes;
+
+/// Upper and lower bounds on the largest possible number of codes.
+#[derive(Clone,Copy,Debug,PartialEq)]
+pub struct Bounds {
+    /// The Singleton upper bound, `q^(n-d+1)`.
+    pub singleton : f64,
+    /// The Hamming (sphere-packing) upper bound, `q^n / V(n,(d-1)/2)` where `V(n,r)` is the number of codes within distance r of a code.
+    pub hamming : f64,
+    /// The Gilbert–Varshamov lower bound, `q^n / V(n,d-1)`. Some set of codes at least this big exists.
+    pub gilbert_varshamov : f64,
+}
+
+impl Bounds {
+    /// The bounds for codes of `length` characters from an alphabet of `alphabet_size` characters, at least `min_distance` apart.
+    pub fn new(length:usize,alphabet_size:usize,min_distance:usize) -> Self {
+        let q = alphabet_size as f64;
+        let all = q.powi(length as i32);
+        let d = min_distance.max(1);
+        let singleton = if d>length { 1.0 } else { q.powi((length-d+1) as i32) };
+        let hamming = all/ball_volume(length,q,(d-1)/2);
+        let gilbert_varshamov = (all/ball_volume(length,q,d-1)).max(1.0).min(all);
+        Bounds{singleton,hamming,gilbert_varshamov}
+    }
+
+    /// The bounds when codes of `length` characters from an alphabet of `alphabet_size` must also satisfy a condition met by about
+    /// one in `modulus` of them, like a digit sum ([crate::filter::DigitSum]). No more codes can fit than meet the condition,
+    /// and the Gilbert–Varshamov argument works among those that do, so its bound is `modulus` times smaller.
+    /// ```
+    /// use make_exam_numbers::capacity::Bounds;
+    ///
+    /// let bounds = Bounds::new(4,10,1).with_modulus(4,10,9);
+    /// assert_eq!(bounds.upper().round(),1111.0);
+    /// let bounds = Bounds::new(4,10,3).with_modulus(4,10,9);
+    /// assert_eq!((bounds.singleton,bounds.upper()),(100.0,Bounds::new(4,10,3).upper()));
+    /// ```
+    pub fn with_modulus(self,length:usize,alphabet_size:usize,modulus:u64) -> Self {
+        let allowed = (alphabet_size as f64).powi(length as i32)/modulus as f64;
+        Bounds{singleton:self.singleton.min(allowed),hamming:self.hamming.min(allowed),gilbert_varshamov:(self.gilbert_varshamov/modulus as f64).max(1.0).min(allowed.max(1.0))}
+    }
+
+    /// The bounds when codes of `length` decimal digits must also, as numbers, be in `range` ([GenerateCodes::set_range]).
+    /// Codes at least `min_distance` apart still differ after leaving out their last `min_distance-1` digits, so the Singleton
+    /// bound is the number of different numbers in the range without those digits. No more codes fit than are in the range,
+    /// and the Gilbert–Varshamov argument works within it.
+    /// ```
+    /// use make_exam_numbers::capacity::Bounds;
+    ///
+    /// let bounds = Bounds::new(6,10,3).with_range(6,&(400000..700000),3);
+    /// assert_eq!(bounds.singleton,3000.0);
+    /// assert_eq!(bounds.upper(),3000.0);
+    /// ```
+    pub fn with_range(self,length:usize,range:&Range<u64>,min_distance:usize) -> Self {
+        let count = range.end.saturating_sub(range.start) as f64;
+        let d = min_distance.max(1);
+        let singleton = match 10u64.checked_pow((d-1) as u32) {
+            _ if count==0.0 => 0.0,
+            Some(block) if d<=length => ((range.end-1)/block-range.start/block+1) as f64,
+            _ => self.singleton,
+        };
+        let gilbert_varshamov = (count/ball_volume(length,10.0,d-1)).max(1.0).min(count);
+        Bounds{singleton:self.singleton.min(singleton).min(count),hamming:self.hamming.min(count),gilbert_varshamov:self.gilbert_varshamov.min(gilbert_varshamov)}
+    }
+
+    /// The smaller of the upper bounds.
+    pub fn upper(&self) -> f64 { self.singleton.min(self.hamming) }
+}
+
+/// The number of codes of length `n` within Hamming distance `radius` of a given code, for an alphabet of `q` characters.
+fn ball_volume(n:usize,q:f64,radius:usize) -> f64 {
+    let mut volume = 0.0;
+    let mut term = 1.0; // C(n,i)(q-1)^i
+    for i in 0..=radius.min(n) {
+        volume+=term;
+        term*=(n-i) as f64*(q-1.0)/(i+1) as f64;
+    }
+    volume
+}
+
+/// Estimate how many codes can be found in practice, by generating codes with no prefix until one can't be found,
+/// or `max_codes` have been found. Returns the number found.
+///
+/// The generator should be set up as for a real run, with [GenerateCodes::set_max_attempts] limiting the effort per code.
+pub fn random_trial(generator:&mut GenerateCodes,min_distance:usize,max_codes:usize) -> usize {
+    let mut found = 0;
+    while found<max_codes && generator.new_code("",min_distance).is_ok() { found+=1; }
+    found
+}
@@ -0,0 +1,208 @@
+// This program is Copyright 2022 Andrew Conway and licensed under the GPL:
+//
+// This program is free software: you can redistribute it and/or modify it under the terms of the GNU General Public License as published by the Free Software Foundation, either version 3 of the License, or (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful, but WITHOUT ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License along with this program. If not, see <https://www.gnu.org/licenses/>.
+
+//! Check digits that can be appended to codes so that most typing errors can be detected.
+
+use std::fmt::{Display, Formatter};
+use std::str::FromStr;
+
+/// A scheme for computing check digit(s) to append to a code.
+///
+/// The check is computed over the decimal digits in the code (prefix and random part); any other characters are ignored,
+/// except by [CheckDigit::Mod97] which also includes letters.
+#[derive(Clone,Copy,Debug,PartialEq,Eq)]
+pub enum CheckDigit {
+    /// The Luhn algorithm, as used on credit cards. Catches all single digit errors and most adjacent transpositions.
+    Luhn,
+    /// The Damm algorithm. Catches all single digit errors and all adjacent transpositions.
+    Damm,
+    /// The Verhoeff algorithm. Catches all single digit errors and all adjacent transpositions.
+    Verhoeff,
+    /// ISO 7064 MOD 97-10, as used by IBANs. Appends two digits such that the whole code is 1 mod 97.
+    /// Letters are included, as A=10, B=11, ... Z=35, ignoring case.
+    Mod97,
+}
+
+impl FromStr for CheckDigit {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "luhn" => Ok(CheckDigit::Luhn),
+            "damm" => Ok(CheckDigit::Damm),
+            "verhoeff" => Ok(CheckDigit::Verhoeff),
+            "mod97" => Ok(CheckDigit::Mod97),
+            _ => Err(format!("Unknown check digit scheme {}, expecting luhn, damm, verhoeff or mod97",s)),
+        }
+    }
+}
+
+/// The name accepted by [FromStr].
+impl Display for CheckDigit {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            CheckDigit::Luhn => "luhn",
+            CheckDigit::Damm => "damm",
+            CheckDigit::Verhoeff => "verhoeff",
+            CheckDigit::Mod97 => "mod97",
+        })
+    }
+}
+
+/// The totally anti-symmetric quasigroup of order 10 used by the Damm algorithm.
+const DAMM_TABLE : [[u8;10];10] = [
+    [0, 3, 1, 7, 5, 9, 8, 6, 4, 2],
+    [7, 0, 9, 2, 1, 5, 4, 8, 6, 3],
+    [4, 2, 0, 6, 8, 7, 1, 3, 5, 9],
+    [1, 7, 5, 0, 9, 8, 3, 4, 2, 6],
+    [6, 1, 2, 3, 0, 4, 5, 9, 7, 8],
+    [3, 6, 7, 4, 2, 0, 9, 5, 8, 1],
+    [5, 8, 6, 9, 7, 2, 0, 1, 3, 4],
+    [8, 9, 4, 5, 3, 6, 2, 0, 1, 7],
+    [9, 4, 3, 8, 6, 1, 7, 2, 0, 5],
+    [2, 5, 8, 1, 4, 3, 6, 7, 9, 0],
+];
+
+/// Multiplication table of the dihedral group D5, used by the Verhoeff algorithm.
+const VERHOEFF_D : [[u8;10];10] = [
+    [0, 1, 2, 3, 4, 5, 6, 7, 8, 9],
+    [1, 2, 3, 4, 0, 6, 7, 8, 9, 5],
+    [2, 3, 4, 0, 1, 7, 8, 9, 5, 6],
+    [3, 4, 0, 1, 2, 8, 9, 5, 6, 7],
+    [4, 0, 1, 2, 3, 9, 5, 6, 7, 8],
+    [5, 9, 8, 7, 6, 0, 4, 3, 2, 1],
+    [6, 5, 9, 8, 7, 1, 0, 4, 3, 2],
+    [7, 6, 5, 9, 8, 2, 1, 0, 4, 3],
+    [8, 7, 6, 5, 9, 3, 2, 1, 0, 4],
+    [9, 8, 7, 6, 5, 4, 3, 2, 1, 0],
+];
+
+/// Position dependent permutations used by the Verhoeff algorithm. Row `i` is the `i`th power of row 1.
+const VERHOEFF_P : [[u8;10];8] = [
+    [0, 1, 2, 3, 4, 5, 6, 7, 8, 9],
+    [1, 5, 7, 6, 2, 8, 3, 0, 9, 4],
+    [5, 8, 0, 3, 7, 9, 6, 1, 4, 2],
+    [8, 9, 1, 6, 0, 4, 3, 5, 2, 7],
+    [9, 4, 5, 3, 1, 2, 6, 8, 7, 0],
+    [4, 2, 8, 6, 5, 7, 3, 9, 0, 1],
+    [2, 7, 9, 3, 8, 0, 6, 4, 1, 5],
+    [7, 0, 4, 6, 9, 1, 3, 2, 5, 8],
+];
+
+/// Inverses in D5 for the Verhoeff algorithm.
+const VERHOEFF_INV : [u8;10] = [0, 4, 3, 2, 1, 5, 6, 7, 8, 9];
+
+fn digits(code:&str) -> impl DoubleEndedIterator<Item=u32> + '_ {
+    code.chars().filter_map(|c|c.to_digit(10))
+}
+
+impl CheckDigit {
+    /// The number of characters appended to a code.
+    pub fn num_characters(&self) -> usize {
+        match self {
+            CheckDigit::Mod97 => 2,
+            _ => 1,
+        }
+    }
+
+    /// The check characters to append to `code`.
+    pub fn compute(&self,code:&str) -> String {
+        match self {
+            CheckDigit::Luhn => {
+                // double every second digit, starting with the rightmost, as the check digit will be appended after it.
+                let sum : u32 = digits(code).rev().enumerate().map(|(i,d)|if i%2==0 { let d2=2*d; if d2>9 { d2-9 } else { d2 } } else { d }).sum();
+                ((10-sum%10)%10).to_string()
+            }
+            CheckDigit::Damm => {
+                // the final interim digit is the check digit, as the table has zeros on the diagonal.
+                digits(code).fold(0,|interim,d|DAMM_TABLE[interim as usize][d as usize]).to_string()
+            }
+            CheckDigit::Verhoeff => {
+                // positions are counted from the right, with the check digit itself at position 0.
+                let c = digits(code).rev().enumerate().fold(0,|c,(i,d)|VERHOEFF_D[c as usize][VERHOEFF_P[(i+1)%8][d as usize] as usize]);
+                VERHOEFF_INV[c as usize].to_string()
+            }
+            CheckDigit::Mod97 => {
+                let remainder = code.chars().filter_map(|c|c.to_digit(36)).fold(0,|r,v|if v<10 { (r*10+v)%97 } else { (r*100+v)%97 });
+                // the two check digits are chosen so that the code followed by them is 1 mod 97.
+                format!("{:02}",98-(remainder*100)%97)
+            }
+        }
+    }
+
+    /// Append the check characters to `code`.
+    pub fn append(&self,code:&str) -> String {
+        format!("{}{}",code,self.compute(code))
+    }
+
+    /// Whether `code` ends with the correct check characters for the rest of it.
+    pub fn is_valid(&self,code:&str) -> bool {
+        match code.char_indices().rev().nth(self.num_characters()-1) {
+            Some((split,_)) => self.compute(&code[..split])==code[split..],
+            None => false,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// The usual worked example of the Luhn algorithm.
+    #[test]
+    fn luhn_matches_the_worked_example() {
+        assert_eq!(CheckDigit::Luhn.compute("7992739871"),"3");
+        assert!(CheckDigit::Luhn.is_valid("79927398713"));
+        assert!(!CheckDigit::Luhn.is_valid("79927398710"));
+        assert!(!CheckDigit::Luhn.is_valid("79927398813"));
+    }
+
+    /// The usual worked example of the Damm algorithm, and every adjacent transposition of a code is caught.
+    #[test]
+    fn damm_matches_the_worked_example_and_catches_transpositions() {
+        assert_eq!(CheckDigit::Damm.compute("572"),"4");
+        assert!(CheckDigit::Damm.is_valid("5724"));
+        let code = CheckDigit::Damm.append("3141592653");
+        let chars : Vec<char> = code.chars().collect();
+        for i in 0..chars.len()-1 {
+            if chars[i]==chars[i+1] { continue; }
+            let mut swapped = chars.clone();
+            swapped.swap(i,i+1);
+            let swapped : String = swapped.into_iter().collect();
+            assert!(!CheckDigit::Damm.is_valid(&swapped),"{} transposed to {} is not caught",code,swapped);
+        }
+    }
+
+    /// The usual worked examples of the Verhoeff algorithm, which fail if any one digit is changed.
+    #[test]
+    fn verhoeff_matches_the_worked_examples_and_catches_changed_digits() {
+        for (code,check) in [("236","3"),("12345","1")] {
+            assert_eq!(CheckDigit::Verhoeff.compute(code),check);
+            let valid = CheckDigit::Verhoeff.append(code);
+            assert!(CheckDigit::Verhoeff.is_valid(&valid));
+            for (i,c) in valid.char_indices() {
+                for d in ('0'..='9').filter(|&d|d!=c) {
+                    let changed = format!("{}{}{}",&valid[..i],d,&valid[i+1..]);
+                    assert!(!CheckDigit::Verhoeff.is_valid(&changed),"{} changed to {} is not caught",valid,changed);
+                }
+            }
+        }
+    }
+
+    /// ISO 7064 MOD 97-10 matches the standard's example and an IBAN with letters, and pads check values below 10 to two digits.
+    #[test]
+    fn mod97_matches_known_values() {
+        assert_eq!(CheckDigit::Mod97.compute("794"),"44");
+        // GB82 WEST 1234 5698 7654 32, with the country code and check digits moved to the end as IBANs are checked.
+        assert_eq!(CheckDigit::Mod97.compute("WEST12345698765432GB"),"82");
+        assert!(CheckDigit::Mod97.is_valid("west12345698765432gb82"));
+        assert_eq!(CheckDigit::Mod97.compute("128"),"05");
+        assert!(CheckDigit::Mod97.is_valid("12805"));
+        assert!(!CheckDigit::Mod97.is_valid("1285"));
+    }
+}
@@ -0,0 +1,115 @@
+// This program is Copyright 2022 Andrew Conway and licensed under the GPL:
+//
+// This program is free software: you can redistribute it and/or modify it under the terms of the GNU General Public License as published by the Free Software Foundation, either version 3 of the License, or (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful, but WITHOUT ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License along with this program. If not, see <https://www.gnu.org/licenses/>.
+
+//! Output files that only appear once they are complete.
+//!
+//! Codes are written to a temporary file next to the real one, which is renamed into place when finished,
+//! so anything reading the file sees either the old complete file or the new complete file.
+
+use std::fs::File;
+use std::io::{Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+
+/// Where an output file is really being written.
+enum Target {
+    File(File),
+    Stdout(std::io::StdoutLock<'static>),
+}
+
+/// An output file being written, either directly, via a temporary file, or to standard output.
+pub struct OutputFile {
+    file : Target,
+    /// If writing atomically, the temporary file being written and the path it will be renamed to.
+    rename : Option<(PathBuf,PathBuf)>,
+}
+
+impl OutputFile {
+    /// Create (or replace) the file at `path`. If `atomic`, the file at `path` is not touched until [OutputFile::finish].
+    pub fn create(path:&Path,atomic:bool) -> std::io::Result<Self> {
+        if atomic {
+            let temp = temporary_path(path);
+            Ok(OutputFile{file:Target::File(File::create(&temp)?),rename:Some((temp,path.to_path_buf()))})
+        } else { Ok(OutputFile{file:Target::File(File::create(path)?),rename:None}) }
+    }
+
+    /// Write to standard output.
+    pub fn stdout() -> Self { OutputFile{file:Target::Stdout(std::io::stdout().lock()),rename:None} }
+
+    /// Open the file at `path` to add to the end of it, creating it if necessary. If it doesn't end with a new line, one is added.
+    /// Returns the file and whether it already had something in it. If `atomic`, a copy is added to and renamed into place by [OutputFile::finish].
+    pub fn append(path:&Path,atomic:bool) -> std::io::Result<(Self,bool)> {
+        if atomic {
+            let temp = temporary_path(path);
+            let opened = (||{
+                if path.exists() { std::fs::copy(path,&temp)?; }
+                open_for_append(&temp)
+            })();
+            match opened {
+                Ok((file,had_contents)) => Ok((OutputFile{file:Target::File(file),rename:Some((temp,path.to_path_buf()))},had_contents)),
+                Err(e) => { let _ = std::fs::remove_file(&temp); Err(e) }
+            }
+        } else { open_for_append(path).map(|(file,had_contents)|(OutputFile{file:Target::File(file),rename:None},had_contents)) }
+    }
+
+    /// Make sure everything is written, and if writing atomically, move the file into place.
+    pub fn finish(mut self) -> std::io::Result<()> {
+        self.flush()?;
+        if let Some((temp,path)) = self.rename.take() {
+            if let Target::File(file) = &self.file { file.sync_all()?; }
+            if let Err(e) = std::fs::rename(&temp,&path) {
+                let _ = std::fs::remove_file(&temp);
+                return Err(std::io::Error::new(e.kind(),format!("Could not rename {} to {} : {}",temp.to_string_lossy(),path.to_string_lossy(),e)));
+            }
+        }
+        Ok(())
+    }
+}
+
+impl Write for OutputFile {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        match &mut self.file {
+            Target::File(file) => file.write(buf),
+            Target::Stdout(stdout) => stdout.write(buf),
+        }
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        match &mut self.file {
+            Target::File(file) => file.flush(),
+            Target::Stdout(stdout) => stdout.flush(),
+        }
+    }
+}
+
+/// If the file was never finished, because of an error, remove the temporary file.
+impl Drop for OutputFile {
+    fn drop(&mut self) {
+        if let Some((temp,_)) = &self.rename { let _ = std::fs::remove_file(temp); }
+    }
+}
+
+/// The temporary file written before renaming it to `path`, in the same directory so the rename can't cross file systems.
+fn temporary_path(path:&Path) -> PathBuf {
+    let mut name = path.file_name().unwrap_or_default().to_os_string();
+    name.push(".tmp");
+    path.with_file_name(name)
+}
+
+/// Open a file to add to the end of it, creating it if necessary. If the file doesn't end with a new line, one is added.
+/// Returns the file and whether it already had something in it.
+fn open_for_append(path:&Path) -> std::io::Result<(File,bool)> {
+    let mut file = std::fs::OpenOptions::new().read(true).append(true).create(true).open(path)?;
+    let length = file.metadata()?.len();
+    if length>0 {
+        let mut last = [0u8];
+        file.seek(SeekFrom::End(-1))?;
+        file.read_exact(&mut last)?;
+        if last[0]!=b'\n' { writeln!(file)?; }
+    }
+    Ok((file,length>0))
+}
@@ -0,0 +1,71 @@
+// This program is Copyright 2022 Andrew Conway and licensed under the GPL:
+//
+// This program is free software: you can redistribute it and/or modify it under the terms of the GNU General Public License as published by the Free Software Foundation, either version 3 of the License, or (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful, but WITHOUT ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License along with this program. If not, see <https://www.gnu.org/licenses/>.
+
+//! Barcode images of each new code, for `--barcodes`. Drawing them needs the `barcodes` feature.
+
+use std::path::{Path, PathBuf};
+use make_exam_numbers::barcode::BarcodeFormat;
+use crate::cli::error::Error;
+
+/// Check that every character that can be in a code can be in a barcode of the format, and in the name of its file.
+pub fn check_characters(format:BarcodeFormat,characters:impl IntoIterator<Item=char>) -> Result<(),Error> {
+    for c in characters {
+        if !format.can_encode(c) { return Err(Error::Arguments(format!("Codes can have the character {:?}, which can't be in a {} barcode",c,format))); }
+        if c=='/' || c=='\\' { return Err(Error::Arguments(format!("Codes can have the character {:?}, which can't be in the name of a barcode file",c))); }
+    }
+    Ok(())
+}
+
+/// Writes the barcode images into a directory, each named after its code.
+pub struct BarcodeWriter {
+    dir : PathBuf,
+    format : BarcodeFormat,
+    png : bool,
+    /// How many codes have had barcodes written.
+    pub count : usize,
+}
+
+impl BarcodeWriter {
+    /// A writer of barcodes into `dir`, which is created if need be. `png` says to write PNG images as well as SVG.
+    #[cfg(feature="barcodes")]
+    pub fn new(dir:&Path,format:BarcodeFormat,png:bool) -> Result<Self,Error> {
+        std::fs::create_dir_all(dir).map_err(Error::io(format!("Could not create barcode directory {}",dir.to_string_lossy())))?;
+        Ok(BarcodeWriter{dir:dir.to_path_buf(),format,png,count:0})
+    }
+
+    #[cfg(not(feature="barcodes"))]
+    pub fn new(_dir:&Path,_format:BarcodeFormat,_png:bool) -> Result<Self,Error> {
+        Err(Error::Arguments("--barcodes needs make_exam_numbers to be built with the barcodes feature, like cargo build --release --features barcodes".to_string()))
+    }
+
+    /// Write the barcode of `code`, with `caption` written underneath.
+    #[cfg(feature="barcodes")]
+    pub fn write(&mut self,code:&str,caption:&str) -> Result<(),Error> {
+        use make_exam_numbers::barcode;
+        /// How many pixels wide a module is in a PNG image, which prints sharply at 300 dpi.
+        const PNG_SCALE : usize = 4;
+        let modules = self.format.modules(code).map_err(Error::Arguments)?;
+        let mut images = vec![("svg",barcode::svg(&modules,caption).into_bytes())];
+        if self.png { images.push(("png",barcode::png(&modules,caption,PNG_SCALE))); }
+        for (extension,image) in images {
+            let path = self.dir.join(format!("{}.{}",code,extension));
+            std::fs::write(&path,image).map_err(Error::io(format!("Could not write barcode {}",path.to_string_lossy())))?;
+        }
+        self.count+=1;
+        Ok(())
+    }
+
+    #[cfg(not(feature="barcodes"))]
+    pub fn write(&mut self,_code:&str,_caption:&str) -> Result<(),Error> { unreachable!("A BarcodeWriter can't be made without the barcodes feature") }
+
+    /// The directory the barcodes are written to.
+    pub fn dir(&self) -> &Path { &self.dir }
+
+    /// A description of what is written for each code, like "code128 SVG and PNG images".
+    pub fn describe(&self) -> String { format!("{} SVG{} images",self.format,if self.png { " and PNG" } else { "" }) }
+}
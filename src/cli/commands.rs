@@ -0,0 +1,588 @@
+// This program is Copyright 2022 Andrew Conway and licensed under the GPL:
+//
+// This program is free software: you can redistribute it and/or modify it under the terms of the GNU General Public License as published by the Free Software Foundation, either version 3 of the License, or (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful, but WITHOUT ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License along with this program. If not, see <https://www.gnu.org/licenses/>.
+
+
+//! The subcommands, which check, merge, assign and look up codes already made rather than making new ones.
+
+use std::collections::{HashMap, HashSet};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use make_exam_numbers::{GenerateCodes, Seed};
+use make_exam_numbers::verify::{find_check_digit_problems, find_digit_sum_problems, find_problems, find_problems_per_prefix, find_reversal_problems, keep_compatible, keep_group_problems, keep_prefix_problems, Problem};
+use make_exam_numbers::analyze::{analyze, closest_by_prefix, closest_reversed, correction_radius, nearest, Pair};
+use make_exam_numbers::capacity::{Bounds, random_trial};
+use make_exam_numbers::correct::{Correction, Corrector};
+use make_exam_numbers::derive::{KeyedDerivation, MAX_BITS};
+use make_exam_numbers::alphabet::Alphabet;
+use make_exam_numbers::filter::Filter;
+use make_exam_numbers::distance::{LengthPolicy, Metric, Scope};
+use crate::cli::atomic::OutputFile;
+use crate::cli::error::Error;
+use crate::cli::input::{check_range, Column, Derivation, describe_source, expand_globs, LengthHandling, read_csv_column, read_existing, read_text, SkipLines, Threshold, with_weights};
+use crate::cli::manifest::Manifest;
+use crate::cli::options::{AnalyzeParameters, AssignParameters, CapacityParameters, CheckDigitAddParameters, CheckDigitVerifyParameters, CorrectParameters, MergeParameters, NearestParameters, VerifyParameters};
+use crate::cli::output::{csv_field, LookupFormat, TableFormat};
+use crate::cli::report::{self, alert, describe_problem, report};
+
+pub fn verify(args:&VerifyParameters) -> Result<(),Error> {
+    let mut files = args.files.clone();
+    let mut min_hamming_distance = args.min_hamming_distance.clone();
+    let mut across = args.min_hamming_across.clone();
+    let mut metric = args.metric;
+    let mut weights = args.weights.as_ref().map(|w|(w.clone(),PathBuf::new()));
+    let mut check_digit = args.check_digit;
+    let mut digit_sum = args.digit_sum_mod;
+    let mut alphabet = args.alphabet.clone();
+    let mut include_reversals = args.include_reversals;
+    let mut scope = args.scope;
+    let mut prefixes = args.prefix.clone();
+    let mut length_policy = args.length_policy;
+    let mut mixed_digits = false;
+    let mut separator = None;
+    // the minimum distance for each prefix, if they are not all the same.
+    let mut group_distances : Vec<(String,String)> = vec![];
+    if let Some(path) = &args.manifest {
+        let manifest = Manifest::read(path)?;
+        let dir = path.parent().unwrap_or(Path::new(""));
+        let mut listed : Vec<PathBuf> = manifest.prefixes.iter().flat_map(|p|p.file.iter().chain(&p.spares_file).chain(p.batches.iter().map(|(file,_)|file))).map(|f|dir.join(f)).collect();
+        // The combined file repeats the codes in the prefix files, so is only checked if there are no prefix files.
+        if listed.is_empty() { listed.extend(manifest.combined.iter().map(|f|dir.join(f))); }
+        report!("Read manifest {} listing {} files",path.to_string_lossy(),listed.len());
+        files.extend(listed);
+        if min_hamming_distance.is_none() && manifest.prefixes.iter().any(|p|p.min_distance!=manifest.min_distance) {
+            group_distances = manifest.prefixes.iter().map(|p|(p.prefix.clone(),p.min_distance.clone())).collect();
+        }
+        if min_hamming_distance.is_none() && args.min_hamming_within.is_none() && across.is_none() { across = manifest.min_distance_across.clone().map(Threshold); }
+        min_hamming_distance = min_hamming_distance.or(Some(Threshold(manifest.min_distance)));
+        if metric.is_none() {
+            metric = Some(manifest.metric.parse().map_err(|e|Error::invalid_input(path,None,e))?);
+            // a file of weights is recorded relative to the manifest.
+            if weights.is_none() { weights = manifest.weights.map(|w|(w,dir.to_path_buf())); }
+        }
+        if check_digit.is_none() { check_digit = manifest.check_digit.map(|c|c.parse()).transpose().map_err(|e|Error::invalid_input(path,None,e))?; }
+        if digit_sum.is_none() { digit_sum = manifest.digit_sum.map(|s|s.parse()).transpose().map_err(|e|Error::invalid_input(path,None,e))?; }
+        include_reversals |= manifest.include_reversals;
+        if alphabet.is_none() && !manifest.alphabet.is_empty() { alphabet = Some(Alphabet::from_characters(&manifest.alphabet).map_err(|e|Error::invalid_input(path,None,e))?); }
+        if scope.is_none() { scope = Some(manifest.scope.parse().map_err(|e|Error::invalid_input(path,None,e))?); }
+        if prefixes.is_empty() { prefixes = manifest.prefixes.iter().map(|p|p.prefix.clone()).collect(); }
+        if length_policy.is_none() { length_policy = Some(manifest.length_policy.parse().map_err(|e|Error::invalid_input(path,None,e))?); }
+        mixed_digits = manifest.prefixes.iter().any(|p|p.digits!=manifest.digits);
+        separator = manifest.grouping.map(|g|g.separator);
+    }
+    let length_policy = match length_policy.unwrap_or(LengthHandling::Error) {
+        LengthHandling::Policy(policy) => Some(policy),
+        LengthHandling::Error if mixed_digits => Some(LengthPolicy::default()),
+        LengthHandling::Error => None,
+    };
+    let scope = scope.unwrap_or_default();
+    if scope==Scope::PerPrefix && prefixes.is_empty() { return Err(Error::Arguments("--scope per-prefix needs the prefixes, from --prefix or --manifest".to_string())); }
+    let metric = with_weights(metric.unwrap_or_default(),weights.as_ref().map(|(w,_)|w.as_str()),weights.as_ref().map(|(_,dir)|dir.as_path()).unwrap_or(Path::new("")))?;
+    let usual = min_hamming_distance.or_else(||args.min_hamming_within.clone()).expect("Required by clap").units(metric).map_err(Error::Arguments)?;
+    let min_hamming_distance = args.min_hamming_within.as_ref().map(|d|d.units(metric)).transpose().map_err(Error::Arguments)?.unwrap_or(usual);
+    let across = Some(across.map(|d|d.units(metric)).transpose().map_err(Error::Arguments)?.unwrap_or(usual)).filter(|&across|across!=min_hamming_distance);
+    if across.is_some() {
+        if prefixes.is_empty() { return Err(Error::Arguments("--min-hamming-across needs the prefixes, from --prefix or --manifest".to_string())); }
+        if scope==Scope::PerPrefix { return Err(Error::Arguments("--min-hamming-across can't be used with --scope per-prefix, which doesn't compare codes with different prefixes at all".to_string())); }
+        if !group_distances.is_empty() { return Err(Error::Arguments("--min-hamming-across can't be used when prefixes have their own minimum distances".to_string())); }
+    }
+    let group_distances : Vec<(String,usize)> = group_distances.into_iter().map(|(p,d)|metric.parse_distance(&d).map(|d|(p,d))).collect::<Result<_,_>>().map_err(Error::Arguments)?;
+    // pairs are found with the largest minimum distance, then those far enough apart for their prefixes are left out.
+    let largest = group_distances.iter().map(|&(_,d)|d).chain([min_hamming_distance]).chain(across).max().unwrap_or(min_hamming_distance);
+    let mut read = CodeFiles::read(files)?;
+    // text files written with --group have the separators in the codes.
+    if let Some(separator) = separator.filter(|s|!s.is_empty()) {
+        for code in &mut read.codes { *code = code.replace(&separator,""); }
+    }
+    let codes = &read.codes;
+    let describe = |index:usize| read.describe(index);
+    let mut problems = match scope {
+        Scope::Global => find_problems(codes,largest,metric,length_policy),
+        Scope::PerPrefix => find_problems_per_prefix(codes,largest,metric,length_policy,&prefixes),
+    };
+    if include_reversals {
+        let prefix_of = |index:usize| prefixes.iter().filter(|p|codes[index].starts_with(p.as_str())).max_by_key(|p|p.len());
+        problems.extend(find_reversal_problems(codes,largest,metric,length_policy).into_iter().filter(|problem| match (scope,problem) {
+            (Scope::PerPrefix,Problem::TooCloseReversed{first,second,..}) => prefix_of(*first)==prefix_of(*second),
+            _ => true,
+        }));
+    }
+    if !group_distances.is_empty() { problems = keep_group_problems(problems,codes,min_hamming_distance,&group_distances); }
+    if let Some(across) = across { problems = keep_prefix_problems(problems,codes,&prefixes,min_hamming_distance,across); }
+    if let Some(check_digit) = check_digit { problems.extend(find_check_digit_problems(codes,check_digit)); }
+    if let Some(digit_sum) = digit_sum { problems.extend(find_digit_sum_problems(codes,digit_sum,alphabet.unwrap_or_default().characters())); }
+    for problem in &problems { print_problem(problem,metric,describe); }
+    if problems.is_empty() {
+        if let Some(across) = across { println!("All {} codes are at least distance {} apart, or {} apart if they have different prefixes.",codes.len(),metric.show_distance(min_hamming_distance),metric.show_distance(across)); }
+        else if group_distances.is_empty() { println!("All {} codes are at least distance {} apart.",codes.len(),metric.show_distance(min_hamming_distance)); }
+        else { println!("All {} codes are at least the minimum distances of their prefixes apart.",codes.len()); }
+        Ok(())
+    } else {
+        println!("Found {} problems in {} codes.",problems.len(),codes.len());
+        Err(Error::Problems(problems.len()))
+    }
+}
+
+/// Print a problem found by [find_problems] with distances in units of `metric`, describing each code referred to with `describe`.
+fn print_problem(problem:&Problem,metric:Metric,describe:impl Fn(usize)->String) {
+    println!("{}",describe_problem(problem,metric,describe));
+}
+
+pub fn merge(args:&MergeParameters) -> Result<(),Error> {
+    if args.out.exists() && !args.force {
+        return Err(Error::Arguments(format!("Output file {} already exists. Use --force to replace it.",args.out.to_string_lossy())));
+    }
+    let metric = with_weights(args.metric,args.weights.as_deref(),Path::new(""))?;
+    let min_distance = args.min_hamming_distance.units(metric).map_err(Error::Arguments)?;
+    let read = CodeFiles::read(args.files.clone())?;
+    let codes = &read.codes;
+    let length_policy = match args.length_policy { LengthHandling::Error => None, LengthHandling::Policy(policy) => Some(policy) };
+    let keep : Vec<bool> = if args.drop_violations {
+        if length_policy.is_none() {
+            let problems : Vec<Problem> = find_problems(codes,0,metric,None).into_iter().filter(|p|matches!(p,Problem::InconsistentLength{..})).collect();
+            if !problems.is_empty() {
+                for problem in &problems { print_problem(problem,metric,|i|read.describe(i)); }
+                println!("Found {} codes of a different length to the first. Use --length-policy to compare codes of different lengths.",problems.len());
+                return Err(Error::Problems(problems.len()));
+            }
+        }
+        let conflicts = keep_compatible(codes,min_distance,metric,length_policy.unwrap_or_default());
+        for (index,conflict) in conflicts.iter().enumerate() {
+            if let Some(kept) = *conflict {
+                println!("Dropped {} : {} {}",read.describe(index),if codes[index]==codes[kept] { "duplicate of" } else { "too close to" },read.describe(kept));
+            }
+        }
+        conflicts.iter().map(|c|c.is_none()).collect()
+    } else {
+        let problems = find_problems(codes,min_distance,metric,length_policy);
+        if !problems.is_empty() {
+            for problem in &problems { print_problem(problem,metric,|i|read.describe(i)); }
+            println!("Found {} problems in {} codes, so nothing was written. Use --drop-violations to drop the codes that conflict with earlier ones.",problems.len(),codes.len());
+            return Err(Error::Problems(problems.len()));
+        }
+        vec![true;codes.len()]
+    };
+    let mut file = OutputFile::create(&args.out,true).map_err(Error::io(format!("Could not create output file {}",args.out.to_string_lossy())))?;
+    for (code,_) in codes.iter().zip(&keep).filter(|(_,&keep)|keep) { writeln!(file,"{}",code)?; }
+    file.finish()?;
+    let num_kept = keep.iter().filter(|&&k|k).count();
+    println!("Wrote {} codes to {}{}.",num_kept,args.out.to_string_lossy(),if num_kept<codes.len() { format!(", dropping {} of the {} read",codes.len()-num_kept,codes.len()) } else { String::new() });
+    Ok(())
+}
+
+pub fn verify_check_digits(args:&CheckDigitVerifyParameters) -> Result<(),Error> {
+    let read = CodeFiles::read(args.files.clone())?;
+    let scheme = args.scheme;
+    let mut num_invalid = 0;
+    for (index,code) in read.codes.iter().enumerate() {
+        if scheme.is_valid(code) { continue; }
+        num_invalid+=1;
+        match code.char_indices().rev().nth(scheme.num_characters()-1) {
+            Some((split,_)) if split>0 => println!("Invalid check digit : {}, expected {} after {}",read.describe(index),scheme.compute(&code[..split]),&code[..split]),
+            _ => println!("Invalid check digit : {}, too short to have {} check characters and something to check",read.describe(index),scheme.num_characters()),
+        }
+    }
+    if num_invalid==0 {
+        println!("All {} codes have valid {} check digits.",read.codes.len(),scheme);
+        Ok(())
+    } else {
+        println!("Found {} invalid {} check digits in {} codes.",num_invalid,scheme,read.codes.len());
+        Err(Error::Problems(num_invalid))
+    }
+}
+
+pub fn add_check_digits(args:&CheckDigitAddParameters) -> Result<(),Error> {
+    if args.out.exists() && !args.force {
+        return Err(Error::Arguments(format!("Output file {} already exists. Use --force to replace it.",args.out.to_string_lossy())));
+    }
+    let read = CodeFiles::read(vec![args.file.clone()])?;
+    let mut file = OutputFile::create(&args.out,true).map_err(Error::io(format!("Could not create output file {}",args.out.to_string_lossy())))?;
+    for code in &read.codes { writeln!(file,"{}",args.scheme.append(code))?; }
+    file.finish()?;
+    println!("Wrote {} codes with {} check digits to {}.",read.codes.len(),args.scheme,args.out.to_string_lossy());
+    Ok(())
+}
+
+/// Give each student in a roster a code, keeping any they already have in the `--existing` mappings.
+pub fn assign(args:&AssignParameters) -> Result<(),Error> {
+    if args.out.exists() && !args.force && !args.existing.contains(&args.out) {
+        return Err(Error::Arguments(format!("Output file {} already exists. Use --force to replace it, or give it to --existing to keep the codes in it.",args.out.to_string_lossy())));
+    }
+    let id_name = match &args.id_column { Column::Name(name) => name.clone(), Column::Index(_) => "id".to_string() };
+    let text = std::fs::read_to_string(&args.roster).map_err(Error::io(format!("Could not read roster {}",args.roster.to_string_lossy())))?;
+    let roster = read_csv_column(&text,&args.id_column).map_err(|e|Error::invalid_input(&args.roster,None,e))?;
+    if roster.empty>0 { alert!("Warning : roster {} has {} rows with nothing in column {}, which were skipped",args.roster.to_string_lossy(),roster.empty,args.id_column); }
+    let mut lines : HashMap<&str,usize> = HashMap::new();
+    for (line,id) in &roster.codes {
+        if let Some(first) = lines.insert(id,*line) {
+            return Err(Error::invalid_input(&args.roster,Some(*line),format!("ID {:?} is already on line {}. Each student must only be in the roster once.",id,first)));
+        }
+    }
+    let mut generator = GenerateCodes::from_seed(Some(args.seed.unwrap_or_else(||Seed::Key(rand::random()))),args.digits);
+    generator.set_max_attempts(Some(1_000_000));
+    if args.derive.is_some() && !KeyedDerivation::can_derive(Alphabet::default().size(),args.digits) {
+        return Err(Error::Arguments(format!("--derive can make codes with at most {} random digits, not {}",(MAX_BITS/(Alphabet::default().size() as f64).log2()).floor(),args.digits)));
+    }
+    // each student's code from the --existing mappings, and the ID each code belongs to, with where it was found.
+    let mut codes : HashMap<String,(String,String)> = HashMap::new();
+    let mut owners : HashMap<String,String> = HashMap::new();
+    for path in &expand_globs("--existing",&args.existing)? {
+        let mapping = read_mapping(path,&id_name)?;
+        let count = match &mapping {
+            Some(mapping) => {
+                for Assigned{line,id,code} in mapping {
+                    let source = format!("file {} line {}",path.to_string_lossy(),line);
+                    if let Some((other,other_source)) = codes.get(id).filter(|(other,_)|other!=code) {
+                        return Err(Error::invalid_input(path,Some(*line),format!("ID {:?} has code {:?}, but was given {:?} in {}",id,code,other,other_source)));
+                    }
+                    if let Some(other) = owners.get(code).filter(|other|*other!=id) {
+                        return Err(Error::invalid_input(path,Some(*line),format!("Code {:?} is given to ID {:?}, but is already given to ID {:?}",code,id,other)));
+                    }
+                    codes.insert(id.clone(),(code.clone(),source));
+                    owners.insert(code.clone(),id.clone());
+                }
+                generator.load_existing(mapping.iter().map(|a|a.code.as_str()))
+            }
+            None => generator.load_existing(read_existing(path,None,&SkipLines::default())?.codes.iter().map(|(_,c)|c.as_str())),
+        };
+        report!("Read {} containing {} codes{}",describe_source(path),count,if mapping.is_some() { " given to students" } else { "" });
+    }
+    let prefix_file = if args.from.is_some() { None } else { Some(args.prefix_file.clone().unwrap_or_else(||PathBuf::from(format!("prefix_{}.txt",args.prefix)))) };
+    if let Some(path) = prefix_file.as_ref().filter(|p|p.exists()) {
+        let existing = read_existing(path,None,&SkipLines::default())?;
+        generator.load_existing(existing.codes.iter().map(|(_,c)|c.as_str()));
+        report!("Read prefix file {} {}",path.to_string_lossy(),existing.describe(existing.codes.len()));
+    }
+    let mut pool = match &args.from {
+        Some(path) => read_existing(path,None,&SkipLines::default())?.codes.into_iter().map(|(_,c)|c).collect(),
+        None => vec![],
+    }.into_iter();
+    let mut used : HashSet<String> = generator.used().iter().cloned().collect();
+    let mut new_codes : Vec<String> = vec![];
+    let mut mapping : Vec<(&str,String)> = vec![];
+    for (_,id) in &roster.codes {
+        let code = match codes.get(id) {
+            Some((code,_)) => code.clone(),
+            None => {
+                let code = match &args.from {
+                    Some(path) => pool.by_ref().find(|c|!used.contains(c)).ok_or_else(||Error::Infeasible(format!("File {} doesn't have enough unused codes for the {} students without one",path.to_string_lossy(),roster.codes.iter().filter(|(_,id)|!codes.contains_key(id)).count())))?,
+                    None => match (args.derive,&args.key) {
+                        (Some(Derivation::Hmac),Some(key)) => generator.new_derived_code(&args.prefix,id,args.min_hamming_distance,key)?,
+                        _ => generator.new_code(&args.prefix,args.min_hamming_distance)?,
+                    },
+                };
+                used.insert(code.clone());
+                new_codes.push(code.clone());
+                code
+            }
+        };
+        mapping.push((id,code));
+    }
+    let left_out = codes.keys().filter(|id|!lines.contains_key(id.as_str())).count();
+    if left_out>0 { alert!("Warning : {} students in the --existing mappings are not in the roster, so are left out of {}. Their codes are still not reused.",left_out,args.out.to_string_lossy()); }
+    if let Some(path) = &prefix_file {
+        if !new_codes.is_empty() {
+            let (mut file,_) = OutputFile::append(path,true).map_err(Error::io(format!("Could not open prefix file {}",path.to_string_lossy())))?;
+            for code in &new_codes { writeln!(file,"{}",code)?; }
+            file.finish()?;
+        }
+    }
+    let mut file = OutputFile::create(&args.out,true).map_err(Error::io(format!("Could not create output file {}",args.out.to_string_lossy())))?;
+    writeln!(file,"{},code",csv_field(&id_name))?;
+    for (id,code) in &mapping { writeln!(file,"{},{}",csv_field(id),csv_field(code))?; }
+    file.finish()?;
+    println!("Wrote codes for {} students to {}, {} of them new{}.",mapping.len(),args.out.to_string_lossy(),new_codes.len(),
+        match (&prefix_file,new_codes.is_empty()) { (Some(path),false) => format!(" (also added to {})",path.to_string_lossy()), _ => String::new() });
+    Ok(())
+}
+
+/// A student's code from a mapping written by `assign`, with the (1 based) line it is on.
+struct Assigned {
+    line : usize,
+    id : String,
+    code : String,
+}
+
+/// If a file is a CSV file with a header row including the ID column and a `code` column, such as written by `assign`, each student's code in it.
+fn read_mapping(path:&Path,id_name:&str) -> Result<Option<Vec<Assigned>>,Error> {
+    let text = read_text(path)?;
+    let header : Vec<&str> = text.lines().next().unwrap_or("").split(',').map(|f|f.trim()).collect();
+    if !(header.contains(&id_name) && header.contains(&"code")) { return Ok(None); }
+    let column = |name:&str| read_csv_column(&text,&Column::Name(name.to_string())).map_err(|e|Error::invalid_input(path,None,e));
+    let ids : HashMap<usize,String> = column(id_name)?.codes.into_iter().collect();
+    Ok(Some(column("code")?.codes.into_iter().filter_map(|(line,code)|ids.get(&line).map(|id|Assigned{line,id:id.clone(),code})).collect()))
+}
+
+/// The codes in some files, remembering where each came from.
+struct CodeFiles {
+    files : Vec<PathBuf>,
+    codes : Vec<String>,
+    /// (file index, 1 based line number) for each code.
+    sources : Vec<(usize,usize)>,
+}
+
+impl CodeFiles {
+    /// Read all the codes in the files, as for `--existing`.
+    fn read(files:Vec<PathBuf>) -> Result<Self,Error> {
+        let mut codes : Vec<String> = vec![];
+        let mut sources : Vec<(usize,usize)> = vec![];
+        for (file_index,path) in files.iter().enumerate() {
+            let existing = read_existing(path,None,&SkipLines::default())?;
+            report!("Read {} {}",describe_source(path),existing.describe(existing.codes.len()));
+            for (line,code) in existing.codes {
+                sources.push((file_index,line));
+                codes.push(code);
+            }
+        }
+        Ok(CodeFiles{files,codes,sources})
+    }
+
+    /// The file a code came from, and its line number in that file.
+    fn source(&self,index:usize) -> (std::borrow::Cow<'_,str>,usize) {
+        let (file_index,line) = self.sources[index];
+        (self.files[file_index].to_string_lossy(),line)
+    }
+
+    /// A code and where it came from, like "AB123 (file a.txt line 7)".
+    fn describe(&self,index:usize) -> String {
+        let (file_index,line) = self.sources[index];
+        format!("{} (file {} line {})",self.codes[index],self.files[file_index].to_string_lossy(),line)
+    }
+}
+
+pub fn analyze_files(args:&AnalyzeParameters) -> Result<(),Error> {
+    const MAX_DUPLICATES_LISTED : usize = 20;
+    // with CSV, standard output is just the pairs, so everything else goes to standard error.
+    report::configure(false,args.top.is_some() && args.format==TableFormat::Csv);
+    let metric = with_weights(args.metric,args.weights.as_deref(),Path::new(""))?;
+    let read = CodeFiles::read(args.files.clone())?;
+    let num_closest = args.top.unwrap_or(1).max(1);
+    let analysis = analyze(&read.codes,metric,args.length_policy,num_closest);
+    let reversed = if args.include_reversals { closest_reversed(&read.codes,metric,args.length_policy,num_closest) } else { vec![] };
+    report!("Codes : {}",read.codes.len());
+    let lengths : Vec<String> = analysis.lengths.iter().map(|(length,count)|if analysis.lengths.len()==1 { length.to_string() } else { format!("{} ({} codes)",length,count) }).collect();
+    report!("Length : {}",if lengths.is_empty() { "none".to_string() } else { lengths.join(", ") });
+    report!("Duplicates : {}",analysis.duplicates.len());
+    for &(first,second) in analysis.duplicates.iter().take(MAX_DUPLICATES_LISTED) { report!("    {} and {}",read.describe(first),read.describe(second)); }
+    if analysis.duplicates.len()>MAX_DUPLICATES_LISTED { report!("    ... and {} more",analysis.duplicates.len()-MAX_DUPLICATES_LISTED); }
+    match analysis.closest.first() {
+        Some(pair) => report!("Minimum {} distance : {}, between {} and {}",metric,metric.show_distance(pair.distance),read.describe(pair.first),read.describe(pair.second)),
+        None => report!("Minimum {} distance : none, as there are not two different codes to compare",metric),
+    }
+    if !args.prefix.is_empty() {
+        let within = args.min_hamming_within.as_ref().map(|d|d.units(metric)).transpose().map_err(Error::Arguments)?;
+        let across = args.min_hamming_across.as_ref().map(|d|d.units(metric)).transpose().map_err(Error::Arguments)?;
+        for (same_prefix,which,min) in [(true,"the same prefix",within),(false,"different prefixes",across)] {
+            match closest_by_prefix(&read.codes,&args.prefix,same_prefix,metric,args.length_policy,1).first() {
+                Some(pair) => {
+                    let below = min.filter(|&min|pair.distance<min).map(|min|format!(", less than the minimum of {}",metric.show_distance(min))).unwrap_or_default();
+                    report!("Minimum {} distance with {} : {}, between {} and {}{}",metric,which,metric.show_distance(pair.distance),read.describe(pair.first),read.describe(pair.second),below);
+                }
+                None => report!("Minimum {} distance with {} : none, as there are not two such codes to compare",metric,which),
+            }
+        }
+    }
+    if args.include_reversals {
+        match reversed.first() {
+            Some(pair) => report!("Minimum {} distance to a reversed code : {}, between {} and {} read backwards",metric,metric.show_distance(pair.distance),read.describe(pair.first),read.describe(pair.second)),
+            None => report!("Minimum {} distance to a reversed code : none, as there are not two different codes to compare",metric),
+        }
+    }
+    if let Some(top) = args.top {
+        let mut header = vec!["rank","distance"];
+        if args.include_reversals { header.push("reversed"); }
+        header.extend(["first_code","first_file","first_line","second_code","second_file","second_line"]);
+        let mut rows : Vec<Vec<String>> = vec![header.iter().map(|h|h.to_string()).collect()];
+        // ties are in order of the codes, with a pair compared forwards before the same pair with one read backwards.
+        let mut pairs : Vec<(Pair,bool)> = analysis.closest.iter().map(|&p|(p,false)).chain(reversed.iter().map(|&p|(p,true))).collect();
+        pairs.sort_by_key(|(p,is_reversed)|(p.distance,p.first,p.second,*is_reversed));
+        for (rank,(pair,is_reversed)) in pairs.iter().take(top).enumerate() {
+            let (first_file,first_line) = read.source(pair.first);
+            let (second_file,second_line) = read.source(pair.second);
+            let mut row = vec![(rank+1).to_string(),metric.show_distance(pair.distance)];
+            if args.include_reversals { row.push(if *is_reversed { "yes" } else { "no" }.to_string()); }
+            row.extend([read.codes[pair.first].clone(),first_file.to_string(),first_line.to_string(),read.codes[pair.second].clone(),second_file.to_string(),second_line.to_string()]);
+            rows.push(row);
+        }
+        if args.format==TableFormat::Table { report!("Closest {} pairs :",rows.len()-1); }
+        args.format.print(&rows);
+    }
+    Ok(())
+}
+
+pub fn nearest_codes(args:&NearestParameters) -> Result<(),Error> {
+    report::configure(false,args.format==LookupFormat::Json);
+    let mut files = expand_globs("--existing",&args.existing)?;
+    let mut metric = args.metric;
+    let mut weights = args.weights.as_ref().map(|w|(w.clone(),PathBuf::new()));
+    let mut manifest_distances : Vec<String> = vec![];
+    let mut separator = None;
+    if let Some(path) = &args.manifest {
+        let manifest = Manifest::read(path)?;
+        let dir = path.parent().unwrap_or(Path::new(""));
+        let mut listed : Vec<PathBuf> = manifest.prefixes.iter().flat_map(|p|p.file.iter().chain(&p.spares_file).chain(p.batches.iter().map(|(file,_)|file))).map(|f|dir.join(f)).collect();
+        if listed.is_empty() { listed.extend(manifest.combined.iter().map(|f|dir.join(f))); }
+        report!("Read manifest {} listing {} files",path.to_string_lossy(),listed.len());
+        files.extend(listed);
+        manifest_distances = manifest.prefixes.iter().map(|p|p.min_distance.clone()).chain([manifest.min_distance]).chain(manifest.min_distance_across).collect();
+        if metric.is_none() {
+            metric = Some(manifest.metric.parse().map_err(|e|Error::invalid_input(path,None,e))?);
+            if weights.is_none() { weights = manifest.weights.map(|w|(w,dir.to_path_buf())); }
+        }
+        separator = manifest.grouping.map(|g|g.separator).filter(|s|!s.is_empty());
+    }
+    let metric = with_weights(metric.unwrap_or_default(),weights.as_ref().map(|(w,_)|w.as_str()),weights.as_ref().map(|(_,dir)|dir.as_path()).unwrap_or(Path::new("")))?;
+    let mut read = CodeFiles::read(files)?;
+    let mut code = args.code.trim().to_string();
+    if let Some(separator) = &separator {
+        for c in &mut read.codes { *c = c.replace(separator,""); }
+        code = code.replace(separator,"");
+    }
+    // with different minimum distances for different prefixes, the smallest is the one that can be relied on for every code.
+    let smallest = manifest_distances.iter().map(|d|metric.parse_distance(d)).collect::<Result<Vec<_>,_>>().map_err(Error::Arguments)?.into_iter().min();
+    let min_distance = match (&args.min_hamming_distance,smallest) {
+        (Some(d),_) => Some((d.units(metric).map_err(Error::Arguments)?,"given")),
+        (None,Some(d)) => Some((d,"manifest")),
+        (None,None) => analyze(&read.codes,metric,args.length_policy,1).min_distance().map(|d|(d,"measured")),
+    };
+    // two codes within the radius would be too close to each other to be valid, so any other code is further away.
+    let radius = min_distance.map(|(d,_)|correction_radius(d));
+    let found = nearest(&code,&read.codes,metric,args.length_policy,args.top.max(2));
+    let within : Vec<(usize,usize)> = found.iter().copied().filter(|&(_,d)|Some(d)<=radius).collect();
+    let correction = if within.len()==1 { Some(within[0]) } else { None };
+    let found = &found[..found.len().min(args.top)];
+    match args.format {
+        LookupFormat::Table => {
+            let mut rows = vec![["rank","distance","code","file","line"].map(|h|h.to_string()).to_vec()];
+            for (rank,&(index,distance)) in found.iter().enumerate() {
+                let (file,line) = read.source(index);
+                rows.push(vec![(rank+1).to_string(),metric.show_distance(distance),read.codes[index].clone(),file.to_string(),line.to_string()]);
+            }
+            TableFormat::Table.print(&rows);
+            match (min_distance,radius) {
+                (Some((d,source)),Some(radius)) => {
+                    let apart = format!("the codes are at least {} apart{}",metric.show_distance(d),if source=="measured" { " (the smallest distance between any two of them)" } else { "" });
+                    match correction {
+                        Some((index,0)) => {
+                            let (file,line) = read.source(index);
+                            println!("{} is a valid code, in file {} line {}.",code,file,line);
+                        }
+                        Some((index,distance)) => println!("Unambiguous correction : {}, at distance {}, is the only code within {} of {}, as {}.",read.describe(index),metric.show_distance(distance),metric.show_distance(radius),code,apart),
+                        None if within.is_empty() => println!("No unambiguous correction : no code is within {} of {}, as {}.",metric.show_distance(radius),code,apart),
+                        None => println!("No unambiguous correction : {} codes are within {} of {}, so the codes are not really at least {} apart.",within.len(),metric.show_distance(radius),code,metric.show_distance(d)),
+                    }
+                }
+                _ => println!("No unambiguous correction : there are not two different codes to find how far apart the codes are."),
+            }
+        }
+        LookupFormat::Json => {
+            let distance = |d:usize| serde_json::from_str::<serde_json::Value>(&metric.show_distance(d)).expect("Distances are numbers");
+            let describe = |(index,d):(usize,usize)| {
+                let (file,line) = read.source(index);
+                serde_json::json!({ "code" : read.codes[index], "distance" : distance(d), "file" : file, "line" : line })
+            };
+            let value = serde_json::json!({
+                "code" : code,
+                "metric" : metric.to_string(),
+                "min_distance" : min_distance.map(|(d,_)|distance(d)),
+                "min_distance_source" : min_distance.map(|(_,source)|source),
+                "correction_radius" : radius.map(distance),
+                "nearest" : found.iter().map(|&pair|describe(pair)).collect::<Vec<_>>(),
+                "correction" : correction.map(describe),
+                "valid" : matches!(correction,Some((_,0))),
+            });
+            println!("{}",serde_json::to_string_pretty(&value).expect("Values made by json! can be written"));
+        }
+    }
+    Ok(())
+}
+
+pub fn correct(args:&CorrectParameters) -> Result<(),Error> {
+    if args.out.exists() && !args.force {
+        return Err(Error::Arguments(format!("Output file {} already exists. Use --force to replace it.",args.out.to_string_lossy())));
+    }
+    let metric = with_weights(args.metric,args.weights.as_deref(),Path::new(""))?;
+    let max_errors = args.max_errors.units(metric).map_err(Error::Arguments)?;
+    let issued = CodeFiles::read(expand_globs("--codes",&args.codes)?)?;
+    let text = std::fs::read_to_string(&args.input).map_err(Error::io(format!("Could not read input {}",args.input.to_string_lossy())))?;
+    let written = read_csv_column(&text,&args.column).map_err(|e|Error::invalid_input(&args.input,None,e))?;
+    if written.empty>0 { alert!("Warning : input {} has {} rows with nothing in column {}, which were skipped",args.input.to_string_lossy(),written.empty,args.column); }
+    report!("Read input {} containing {} codes",args.input.to_string_lossy(),written.codes.len());
+    let corrector = Corrector::new(&issued.codes,metric,args.length_policy,max_errors);
+    let mut counts : [usize;4] = [0;4]; // exact, corrected, ambiguous, not found
+    let mut file = OutputFile::create(&args.out,true).map_err(Error::io(format!("Could not create output file {}",args.out.to_string_lossy())))?;
+    writeln!(file,"line,value,code,status,distance")?;
+    for (line,value) in &written.codes {
+        let correction = corrector.correct(value);
+        let (code,status,distance) = match &correction {
+            Correction::Exact(index) => { counts[0]+=1; (issued.codes[*index].as_str(),"exact",Some(0)) }
+            Correction::Corrected{index,distance} => { counts[1]+=1; (issued.codes[*index].as_str(),"corrected",Some(*distance)) }
+            Correction::Ambiguous{indices,distance} => {
+                counts[2]+=1;
+                report!("Ambiguous : {} on line {} is equally close to {}",value,line,indices.iter().map(|&i|issued.describe(i)).collect::<Vec<_>>().join(" and "));
+                ("","ambiguous",Some(*distance))
+            }
+            Correction::NotFound => { counts[3]+=1; ("","not_found",None) }
+        };
+        writeln!(file,"{},{},{},{},{}",line,csv_field(value),csv_field(code),status,distance.map(|d|metric.show_distance(d)).unwrap_or_default())?;
+    }
+    file.finish()?;
+    println!("Wrote {} codes to {} : {} exact, {} corrected, {} ambiguous, {} not found.",written.codes.len(),args.out.to_string_lossy(),counts[0],counts[1],counts[2],counts[3]);
+    Ok(())
+}
+
+pub fn capacity(args:&CapacityParameters) -> Result<(),Error> {
+    let mut alphabet = args.charset.clone().unwrap_or_else(||args.alphabet.clone());
+    if args.unambiguous { alphabet = alphabet.unambiguous().map_err(Error::Arguments)?; }
+    let mut bounds = Bounds::new(args.digits,alphabet.size(),args.min_hamming_distance);
+    if let Some(sum) = args.digit_sum_mod { bounds = bounds.with_modulus(args.digits,alphabet.size(),sum.modulus); }
+    if let Some(range) = &args.range {
+        check_range(range,&alphabet,args.digits,false)?;
+        bounds = bounds.with_range(args.digits,range,args.min_hamming_distance);
+    }
+    let trial = if args.no_trial { None } else {
+        let mut generator = GenerateCodes::from_seed(args.seed,args.digits);
+        generator.set_alphabet(alphabet.clone());
+        generator.set_range(args.range.clone());
+        if let Some(sum) = args.digit_sum_mod { generator.add_filter(Filter::DigitSum{sum,values:alphabet.characters().to_vec()}); }
+        generator.set_max_attempts(Some(args.trial_attempts));
+        Some(random_trial(&mut generator,args.min_hamming_distance,args.trial_limit))
+    };
+    let trial_limited = trial==Some(args.trial_limit);
+    if args.json {
+        let value = serde_json::json!({
+            "digits" : args.digits,
+            "alphabet" : alphabet.to_string(),
+            "alphabet_size" : alphabet.size(),
+            "min_hamming_distance" : args.min_hamming_distance,
+            "digit_sum_mod" : args.digit_sum_mod.map(|s|s.to_string()),
+            "range" : args.range.as_ref().map(|r|format!("{}..{}",r.start,r.end)),
+            "singleton_bound" : bounds.singleton,
+            "hamming_bound" : bounds.hamming,
+            "gilbert_varshamov_bound" : bounds.gilbert_varshamov,
+            "trial" : trial,
+            "trial_limited" : trial_limited,
+        });
+        println!("{}",serde_json::to_string_pretty(&value).expect("Values made by json! can be written"));
+    } else {
+        println!("{} random characters from {} ({} characters), minimum Hamming distance {}{}",args.digits,alphabet,alphabet.size(),args.min_hamming_distance,
+            args.digit_sum_mod.map(|s|format!(", digit sum {} modulo {}",s.remainder,s.modulus)).unwrap_or_default()
+            +&args.range.as_ref().map(|r|format!(", in the range {}..{}",r.start,r.end)).unwrap_or_default());
+        println!("{:<40} {:>15}","Singleton upper bound",format_count(bounds.singleton));
+        println!("{:<40} {:>15}","Hamming (sphere packing) upper bound",format_count(bounds.hamming));
+        println!("{:<40} {:>15}","Gilbert-Varshamov lower bound",format_count(bounds.gilbert_varshamov));
+        if let Some(trial) = trial {
+            println!("{:<40} {:>15}",if trial_limited { "Found by random trial (stopped at limit)" } else { "Found by random trial" },trial);
+        }
+    }
+    Ok(())
+}
+
+/// A possibly huge number of codes, in a readable form.
+fn format_count(count:f64) -> String {
+    if count<1e15 { format!("{:.0}",count.floor()) } else { format!("{:.3e}",count) }
+}
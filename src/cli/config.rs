@@ -0,0 +1,135 @@
+// This program is Copyright 2022 Andrew Conway and licensed under the GPL:
+//
+// This program is free software: you can redistribute it and/or modify it under the terms of the GNU General Public License as published by the Free Software Foundation, either version 3 of the License, or (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful, but WITHOUT ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License along with this program. If not, see <https://www.gnu.org/licenses/>.
+
+//! TOML configuration files giving the same parameters as the command line.
+//!
+//! Each key is the name of a command line option with `_` instead of `-`, such as `min_hamming_distance` or `no_leading_zero`.
+//! The configuration is turned into command line arguments, which are parsed along with the real ones, so exactly the same
+//! checks are done. Anything given on the command line replaces the same key in the configuration.
+
+use std::path::Path;
+use clap::{Arg, ArgAction, ArgMatches, Command};
+use clap::parser::ValueSource;
+use toml::{Table, Value};
+use crate::cli::error::Error;
+
+/// Options that only make sense on the command line, so can't be in a configuration file.
+const COMMAND_LINE_ONLY : [&str;5] = ["help","version","config","print-config","example-config"];
+
+/// A documented example configuration, printed by `--example-config`.
+pub const EXAMPLE_CONFIG : &str = r#"# Configuration for make_exam_numbers, used with --config <file>.
+# Each key is a command line option with _ instead of -. Options given on the command line override these.
+# Unknown keys are an error. Use --print-config to see the configuration actually used.
+
+# The minimum Hamming distance between codes, the number of random digits, and the codes wanted for each prefix.
+min_hamming_distance = 3
+digits = 6
+prefixes = ["S0:600", "P0:250", "S1:100", "P1:=50"]
+
+# The random seed, so the run can be reproduced.
+seed = 12345
+
+# Files of codes that have already been used, which new codes must be far enough from.
+existing = ["last_year.txt", "resits.txt"]
+
+# Where to write the codes.
+output_dir = "codes"
+format = "csv"
+combined = "codes/all.csv"
+
+# Flags are true or false.
+no_leading_zero = true
+unambiguous = false
+"#;
+
+/// Read a configuration file.
+pub fn read(path:&Path) -> Result<Table,Error> {
+    let text = std::fs::read_to_string(path).map_err(Error::io(format!("Could not read configuration {}",path.to_string_lossy())))?;
+    text.parse::<Table>().map_err(|e|Error::invalid_input(path,None,format!("not valid TOML : {}",e)))
+}
+
+/// The command line arguments equivalent to the configuration, leaving out any given on the command line (in `given`).
+///
+/// Returns the options, which can go anywhere, and the positional arguments, which must go after those on the command line.
+pub fn to_arguments(config:&Table,command:&Command,given:&ArgMatches) -> Result<(Vec<String>,Vec<String>),String> {
+    let mut options = vec![];
+    let mut positionals : Vec<(usize,&str,Vec<String>)> = vec![]; // (position, key, values)
+    for (key,value) in config {
+        let id = key.replace('_',"-");
+        let arg = command.get_arguments().find(|a|a.get_id()==id && !COMMAND_LINE_ONLY.contains(&a.get_id())).ok_or_else(||format!("Unknown configuration key {}",key))?;
+        if given.value_source(arg.get_id())==Some(ValueSource::CommandLine) { continue; }
+        let values : Vec<&Value> = match value {
+            Value::Array(values) if takes_several(arg) => values.iter().collect(),
+            Value::Array(_) => return Err(format!("Configuration key {} takes a single value, not a list",key)),
+            value => vec![value],
+        };
+        let mut strings = vec![];
+        for value in values {
+            strings.push(match value {
+                Value::String(s) => s.clone(),
+                Value::Integer(i) => i.to_string(),
+                Value::Float(f) => f.to_string(),
+                Value::Boolean(b) if !arg.is_takes_value_set() => b.to_string(),
+                _ => return Err(format!("Configuration key {} should be {}",key,if arg.is_takes_value_set() { "a string or number" } else { "true or false" })),
+            });
+        }
+        if arg.is_positional() { positionals.push((position(command,arg.get_id()),key,strings)); }
+        else if !arg.is_takes_value_set() {
+            let long = arg.get_long().expect("Options from a configuration are not positional so have a long name");
+            match (value,arg.get_action()) {
+                (Value::Boolean(true),_) => options.push(format!("--{}",long)),
+                (Value::Boolean(false),_) => {}
+                (Value::Integer(n),ArgAction::Count) if *n>=0 => options.extend((0..*n).map(|_|format!("--{}",long))),
+                _ => return Err(format!("Configuration key {} should be true or false",key)),
+            }
+        } else {
+            let long = arg.get_long().expect("Options from a configuration are not positional so have a long name");
+            options.extend(strings.into_iter().map(|s|format!("--{}={}",long,s)));
+        }
+    }
+    positionals.sort_by_key(|(index,_,_)|*index);
+    // positional arguments are filled in order, so the configuration can only give those after the last one on the command line, without gaps.
+    let positional_key = |index:usize| command.get_positionals().nth(index).map(|a|a.get_id().replace('-',"_")).unwrap_or_default();
+    let first = command.get_positionals().enumerate().filter(|(_,a)|given.value_source(a.get_id())==Some(ValueSource::CommandLine)).map(|(i,_)|i+1).max().unwrap_or(0);
+    for (next,(index,key,_)) in (first..).zip(&positionals) {
+        if *index!=next { return Err(format!("Configuration key {} can only be used if {} is given too, in the configuration or on the command line",key,positional_key(next))); }
+    }
+    Ok((options,positionals.into_iter().flat_map(|(_,_,values)|values).collect()))
+}
+
+/// Whether an argument can be given several values, so is a list in a configuration.
+fn takes_several(arg:&Arg) -> bool {
+    arg.is_multiple_occurrences_set() || arg.is_multiple_values_set() || matches!(arg.get_action(),ArgAction::Append)
+}
+
+/// The position of a positional argument among the positional arguments, starting from 0.
+fn position(command:&Command,id:&str) -> usize {
+    command.get_positionals().position(|a|a.get_id()==id).expect("Only called for positional arguments")
+}
+
+/// The configuration equivalent to the parsed arguments, with everything given explicitly (on the command line or in a configuration file).
+pub fn effective(command:&Command,matches:&ArgMatches) -> Table {
+    let mut config = Table::new();
+    for arg in command.get_arguments() {
+        let id = arg.get_id();
+        if COMMAND_LINE_ONLY.contains(&id) || matches.value_source(id)!=Some(ValueSource::CommandLine) { continue; }
+        let key = id.replace('-',"_");
+        let value = if !arg.is_takes_value_set() {
+            if let ArgAction::Count = arg.get_action() { Value::Integer(matches.occurrences_of(id) as i64) } else { Value::Boolean(true) }
+        } else {
+            let values : Vec<String> = matches.get_raw(id).map(|values|values.map(|v|v.to_string_lossy().to_string()).collect()).unwrap_or_default();
+            if takes_several(arg) { Value::Array(values.into_iter().map(Value::String).collect()) }
+            else {
+                let value = values.into_iter().next().unwrap_or_default();
+                match value.parse::<i64>() { Ok(i) => Value::Integer(i), Err(_) => Value::String(value) }
+            }
+        };
+        config.insert(key,value);
+    }
+    config
+}
@@ -0,0 +1,126 @@
+// This program is Copyright 2022 Andrew Conway and licensed under the GPL:
+//
+// This program is free software: you can redistribute it and/or modify it under the terms of the GNU General Public License as published by the Free Software Foundation, either version 3 of the License, or (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful, but WITHOUT ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License along with this program. If not, see <https://www.gnu.org/licenses/>.
+
+//! Errors that stop the program, each saying what was at fault, and each kind with its own exit status.
+
+use std::fmt::{Display, Formatter};
+use std::path::{Path, PathBuf};
+use make_exam_numbers::GenerationError;
+use crate::cli::interrupt::EXIT_INTERRUPTED;
+
+/// The exit status when `verify` or `merge` finds problems with the codes.
+pub const EXIT_PROBLEMS : i32 = 1;
+/// The exit status when the arguments are invalid, as for errors found by clap.
+pub const EXIT_ARGUMENTS : i32 = 2;
+/// The exit status when an input file is invalid.
+pub const EXIT_INVALID_INPUT : i32 = 3;
+/// The exit status when the codes wanted can't be made or couldn't all be found.
+pub const EXIT_GENERATION : i32 = 4;
+/// The exit status when reading or writing a file failed.
+pub const EXIT_IO : i32 = 5;
+
+/// The exit statuses (those above and [EXIT_INTERRUPTED]), as listed at the end of `--help`.
+pub const EXIT_STATUS_HELP : &str = "EXIT STATUS:
+    0      Success
+    1      verify or merge found problems with the codes
+    2      Invalid arguments or configuration, or output files that already exist
+    3      An invalid input file, such as a file of existing codes or a manifest
+    4      The codes wanted can't fit, or not all of them could be found
+    5      Reading or writing a file failed
+    130    Interrupted by Ctrl-C";
+
+#[derive(Debug)]
+pub enum Error {
+    /// The arguments (or configuration) are invalid, or can't be used together.
+    Arguments(String),
+    /// Something in an input file, such as a file of existing codes, is wrong. `line` is 1 based.
+    InvalidInput { path : PathBuf, line : Option<usize>, reason : String },
+    /// More codes are wanted than can fit.
+    Infeasible(String),
+    /// The generator couldn't find a code.
+    Generation(GenerationError),
+    /// Reading or writing failed. `context` says what was being done, like "Could not create output file prefix_A.txt".
+    Io { context : String, source : std::io::Error },
+    /// Checking codes found this many problems, which have been printed.
+    Problems(usize),
+}
+
+impl Error {
+    pub fn invalid_input(path:&Path,line:Option<usize>,reason:impl Into<String>) -> Self {
+        Error::InvalidInput{path:path.to_path_buf(),line,reason:reason.into()}
+    }
+
+    /// A function for map_err, adding context to an I/O error.
+    pub fn io(context:impl Into<String>) -> impl FnOnce(std::io::Error) -> Self {
+        let context = context.into();
+        move |source| Error::Io{context,source}
+    }
+
+    /// The status the program should exit with.
+    pub fn exit_code(&self) -> i32 {
+        match self {
+            Error::Problems(_) => EXIT_PROBLEMS,
+            Error::Arguments(_) => EXIT_ARGUMENTS,
+            Error::InvalidInput{..} => EXIT_INVALID_INPUT,
+            Error::Infeasible(_) => EXIT_GENERATION,
+            Error::Generation(GenerationError::Interrupted{..}) => EXIT_INTERRUPTED,
+            Error::Generation(_) => EXIT_GENERATION,
+            Error::Io{..} => EXIT_IO,
+        }
+    }
+}
+
+impl Display for Error {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Error::Arguments(message) | Error::Infeasible(message) => f.write_str(message),
+            Error::InvalidInput { path, line:Some(line), reason } => write!(f,"File {} line {} : {}",path.to_string_lossy(),line,reason),
+            Error::InvalidInput { path, line:None, reason } => write!(f,"File {} : {}",path.to_string_lossy(),reason),
+            Error::Generation(e) => write!(f,"{}",e),
+            Error::Io { context, source } => write!(f,"{} : {}",context,source),
+            Error::Problems(count) => write!(f,"Found {} problems",count),
+        }
+    }
+}
+
+/// Writing to an output that has already been checked, such as a file just created or standard output.
+impl From<std::io::Error> for Error {
+    fn from(source: std::io::Error) -> Self { Error::Io{context:"Could not write output".to_string(),source} }
+}
+
+impl From<GenerationError> for Error {
+    fn from(e: GenerationError) -> Self { Error::Generation(e) }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Each kind of error exits with the status listed in [EXIT_STATUS_HELP].
+    #[test]
+    fn each_error_has_its_documented_exit_status() {
+        let prefix = || "A".to_string();
+        let errors = [
+            (Error::Problems(3),1),
+            (Error::Arguments("bad".to_string()),2),
+            (Error::invalid_input(Path::new("codes.txt"),Some(2),"bad"),3),
+            (Error::Infeasible("too many".to_string()),4),
+            (Error::Generation(GenerationError::TooManyAttempts{prefix:prefix(),attempts:10}),4),
+            (Error::Generation(GenerationError::Exhausted{prefix:prefix()}),4),
+            (Error::Generation(GenerationError::TimedOut{prefix:prefix()}),4),
+            (Error::Generation(GenerationError::NoConstruction{prefix:prefix(),reason:"bad".to_string()}),4),
+            (Error::Generation(GenerationError::Interrupted{prefix:prefix()}),130),
+            (Error::io("Could not read codes.txt")(std::io::Error::other("bad")),5),
+        ];
+        for (error,status) in errors {
+            assert_eq!(error.exit_code(),status,"{}",error);
+            assert!(EXIT_STATUS_HELP.lines().any(|line|line.trim_start().starts_with(&format!("{} ",status))),"{} is not in the help",status);
+        }
+        assert_eq!(EXIT_INTERRUPTED,130);
+    }
+}
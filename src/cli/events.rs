@@ -0,0 +1,122 @@
+// This program is Copyright 2022 Andrew Conway and licensed under the GPL:
+//
+// This program is free software: you can redistribute it and/or modify it under the terms of the GNU General Public License as published by the Free Software Foundation, either version 3 of the License, or (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful, but WITHOUT ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License along with this program. If not, see <https://www.gnu.org/licenses/>.
+
+//! Machine readable progress for `--progress-format json`, as one JSON object per line, so a program wrapping this one
+//! doesn't have to read the messages meant for people.
+//!
+//! The events are:
+//! * `{"event":"prefix_start","prefix":"A","of":500}` before looking for the codes for a prefix.
+//! * `{"event":"code_found","prefix":"A","index":17,"of":500,"attempts":42}` for each code, `index` counting from 1 and `attempts` the candidates tried for it.
+//! * `{"event":"prefix_done","prefix":"A","found":500,"of":500,"candidates":12345,"elapsed_ms":250}` when all the codes for a prefix have been found.
+//! * `{"event":"prefix_failed","prefix":"A","found":17,"of":500,"reason":"..."}` if they couldn't all be found, after which the run stops.
+//! * `{"event":"run_done","codes":850,"elapsed_ms":1200}` at the end of a successful run.
+//!
+//! Each line is flushed as soon as it is written, so the events can be read as they happen.
+
+use std::fs::File;
+use std::io::Write;
+use std::path::Path;
+use std::str::FromStr;
+use std::time::Duration;
+use serde_json::{json, Value};
+use crate::cli::error::Error;
+
+/// How progress is shown while making codes.
+#[derive(Clone,Copy,Debug,PartialEq,Eq)]
+pub enum ProgressFormat {
+    /// A progress line for people to read.
+    Human,
+    /// JSON lines, as described in [crate::cli::events].
+    Json,
+}
+
+impl FromStr for ProgressFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "human" => Ok(ProgressFormat::Human),
+            "json" => Ok(ProgressFormat::Json),
+            _ => Err(format!("Unknown progress format {}, expecting human or json",s)),
+        }
+    }
+}
+
+/// Where progress events are written, if anywhere.
+pub struct Events {
+    out : Option<Box<dyn Write>>,
+}
+
+impl Events {
+    /// No events.
+    pub fn none() -> Self { Events{out:None} }
+
+    /// Events written to stderr.
+    pub fn stderr() -> Self { Events{out:Some(Box::new(std::io::stderr()))} }
+
+    /// Events written to a new file, replacing any already there.
+    pub fn file(path:&Path) -> Result<Self,Error> {
+        let file = File::create(path).map_err(Error::io(format!("Could not create progress file {}",path.to_string_lossy())))?;
+        Ok(Events{out:Some(Box::new(file))})
+    }
+
+    pub fn prefix_start(&mut self,prefix:&str,of:usize) -> Result<(),Error> {
+        self.write(json!({"event":"prefix_start","prefix":prefix,"of":of}))
+    }
+
+    pub fn code_found(&mut self,prefix:&str,index:usize,of:usize,attempts:u64) -> Result<(),Error> {
+        self.write(json!({"event":"code_found","prefix":prefix,"index":index,"of":of,"attempts":attempts}))
+    }
+
+    pub fn prefix_done(&mut self,prefix:&str,found:usize,of:usize,candidates:u64,elapsed:Duration) -> Result<(),Error> {
+        self.write(json!({"event":"prefix_done","prefix":prefix,"found":found,"of":of,"candidates":candidates,"elapsed_ms":elapsed.as_millis() as u64}))
+    }
+
+    pub fn prefix_failed(&mut self,prefix:&str,found:usize,of:usize,reason:&str) -> Result<(),Error> {
+        self.write(json!({"event":"prefix_failed","prefix":prefix,"found":found,"of":of,"reason":reason}))
+    }
+
+    pub fn run_done(&mut self,codes:u64,elapsed:Duration) -> Result<(),Error> {
+        self.write(json!({"event":"run_done","codes":codes,"elapsed_ms":elapsed.as_millis() as u64}))
+    }
+
+    fn write(&mut self,event:Value) -> Result<(),Error> {
+        if let Some(out) = &mut self.out {
+            writeln!(out,"{}",event).and_then(|_|out.flush()).map_err(Error::io("Could not write progress event"))?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Each kind of event is written as one line of JSON that reads back as the object in the module documentation.
+    #[test]
+    fn events_are_json_lines() {
+        let path = std::env::temp_dir().join(format!("make_exam_numbers_events_{}.jsonl",std::process::id()));
+        let mut events = Events::file(&path).unwrap();
+        events.prefix_start("A",500).unwrap();
+        events.code_found("A",17,500,42).unwrap();
+        events.prefix_done("A",500,500,12345,Duration::from_millis(250)).unwrap();
+        events.prefix_failed("A",17,500,"out of candidates").unwrap();
+        events.run_done(850,Duration::from_millis(1200)).unwrap();
+        drop(events);
+        let text = std::fs::read_to_string(&path);
+        std::fs::remove_file(&path).unwrap();
+        let read : Vec<Value> = text.unwrap().lines().map(|line|serde_json::from_str(line).unwrap()).collect();
+        assert_eq!(read,vec![
+            json!({"event":"prefix_start","prefix":"A","of":500}),
+            json!({"event":"code_found","prefix":"A","index":17,"of":500,"attempts":42}),
+            json!({"event":"prefix_done","prefix":"A","found":500,"of":500,"candidates":12345,"elapsed_ms":250}),
+            json!({"event":"prefix_failed","prefix":"A","found":17,"of":500,"reason":"out of candidates"}),
+            json!({"event":"run_done","codes":850,"elapsed_ms":1200}),
+        ]);
+    }
+}
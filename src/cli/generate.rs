@@ -0,0 +1,516 @@
+// This program is Copyright 2022 Andrew Conway and licensed under the GPL:
+//
+// This program is free software: you can redistribute it and/or modify it under the terms of the GNU General Public License as published by the Free Software Foundation, either version 3 of the License, or (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful, but WITHOUT ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License along with this program. If not, see <https://www.gnu.org/licenses/>.
+
+
+//! Making the codes asked for on the command line, and writing them to the output files.
+
+use std::collections::VecDeque;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+use make_exam_numbers::{GenerateCodes, GenerationError, WantedPrefix};
+use make_exam_numbers::capacity::Bounds;
+use make_exam_numbers::alphabet::Alphabet;
+use make_exam_numbers::distance::{Metric, Scope, Weights};
+use crate::cli::atomic::OutputFile;
+use crate::cli::error::Error;
+use crate::cli::events::{Events, ProgressFormat};
+use crate::cli::input::{load_pins, MergeRoster, read_existing_files, read_valid_existing, relative_to, SkipLines};
+use crate::cli::layout::{canonical_layout, describe_layout, has_fixed_characters};
+use crate::cli::manifest::{Manifest, ManifestPrefix};
+use crate::cli::options::Parameters;
+use crate::cli::output::{Batches, Format, OutputInfo, timestamp};
+use crate::cli::progress::Progress;
+use crate::cli::report::{self, alert, report};
+use crate::cli::setup::Setup;
+use crate::cli::summary::{describe, describe_first_characters, PrefixSummary, RunSummary};
+use crate::cli::writer::{destination, OutputPaths, PrefixWriter, SharedOutputs};
+
+/// Make the codes asked for by `args`, and write them out.
+pub fn generate(args:&Parameters) -> Result<(),Error> {
+    let run_start = Instant::now();
+    // JSON events on stderr replace the routine messages.
+    report::configure(args.quiet || (args.progress_format==ProgressFormat::Json && args.progress_file.is_none()),true);
+    let mut setup = Setup::new(args)?;
+    let start_time = timestamp();
+    let outputs = OutputPaths::new(args,setup.digits)?;
+    if args.interleave && args.timeout_per_prefix.is_some() { return Err(Error::Arguments("--timeout-per-prefix can't be used with --interleave, as the prefixes are made together. Use --timeout instead.".to_string())); }
+    let existing_read = read_existing_files(args,&mut setup,&outputs)?;
+    check_outputs(args,&outputs)?;
+    let manifest_path = if args.no_manifest { None } else if let Some(path) = &args.manifest { Some(path.clone()) } else if args.stdout { None } else { Some(Manifest::default_path(&outputs.dir,&start_time)) };
+    let append = args.append || args.resume;
+    let already = read_output_files(args,&mut setup,&outputs)?;
+    let plans : Vec<PrefixPlan> = args.prefixes.iter().zip(&already).zip(&outputs.spares).map(|((p,&already),&spares)| {
+        let counting = args.resume || p.total;
+        let issued = if counting { p.number.saturating_sub(already) } else { p.number };
+        PrefixPlan{appending:append || p.total,already,issued,wanted:issued+spares}
+    }).collect();
+    for (p,plan) in args.prefixes.iter().zip(&plans) {
+        if args.resume || p.total {
+            if plan.issued==0 { report!("Prefix {} : {} already present, at least the {} wanted, so nothing to do",p.name(),plan.already,p.number); }
+            else { report!("Prefix {} : {} of {} already present, generating {} more",p.name(),plan.already,p.number,plan.issued); }
+        }
+    }
+    let roster = match &args.merge_with {
+        Some(path) => {
+            let text = std::fs::read_to_string(path).map_err(Error::io(format!("Could not read roster {}",path.to_string_lossy())))?;
+            let roster = MergeRoster::new(text).map_err(|e|Error::invalid_input(path,None,e))?;
+            if roster.rows()!=plans[0].issued {
+                return Err(Error::Arguments(format!("Roster {} has {} students, but {} codes are wanted for prefix {}. There must be one code for each student.",path.to_string_lossy(),roster.rows(),plans[0].issued,args.prefixes[0].name())));
+            }
+            report!("Read roster {} of {} students to give the new codes to",path.to_string_lossy(),roster.rows());
+            Some(roster)
+        }
+        None => None,
+    };
+    let to_find : Vec<usize> = plans.iter().map(|plan|plan.wanted).collect();
+    check_feasibility(args,&setup.generator,&setup.alphabet,setup.digits,&setup.min_distances.iter().map(|&d|setup.metric.min_hamming(d)).collect::<Vec<_>>(),&to_find)?;
+    if let Some(pins) = &setup.pins { check_pin_feasibility(args,pins,to_find.iter().sum())?; }
+    if args.dry_run {
+        print_plan(args,&setup,&outputs,&plans,&existing_read,manifest_path.as_deref());
+        return Ok(());
+    }
+    let atomic = !args.no_atomic;
+    let shared = SharedOutputs::open(args,&mut setup,&outputs.date,append,atomic)?;
+    let manifest_dir = manifest_path.as_ref().and_then(|p|p.parent()).map(|p|p.to_path_buf()).unwrap_or_default();
+    let manifest = new_manifest(args,&setup,existing_read,&manifest_dir,start_time);
+    let mut events = match (&args.progress_file,args.progress_format) {
+        (Some(path),_) => Events::file(path)?,
+        (None,ProgressFormat::Json) => Events::stderr(),
+        (None,ProgressFormat::Human) => Events::none(),
+    };
+    let interleaved = if args.interleave { Some(find_interleaved(args,&mut setup,&to_find,&mut events,run_start)?) } else { None };
+    let mut run = Run{args,setup,outputs,shared,manifest,manifest_path,manifest_dir,summary:RunSummary::default(),events,interleaved,run_start};
+    let mut stopped : Option<GenerationError> = None; // why the codes for a prefix couldn't all be found, if they couldn't.
+    for (index,plan) in plans.iter().enumerate() {
+        stopped = run.make_prefix(index,plan)?;
+        if stopped.is_some() { break; }
+    }
+    run.finish(stopped,roster)
+}
+
+/// Check the options about where the codes are written make sense together, and that no output file would be replaced without `--force`.
+fn check_outputs(args:&Parameters,outputs:&OutputPaths) -> Result<(),Error> {
+    if args.stdout && args.format==Format::Json && args.prefixes.len()>1 {
+        return Err(Error::Arguments("--stdout with --format json can only be used with a single prefix, as each prefix is a separate JSON document. Try --format jsonl.".to_string()));
+    }
+    let append = args.append || args.resume;
+    if let Some(p) = args.prefixes.iter().find(|p|p.total) {
+        let problem = if args.stdout { Some("--stdout") } else if args.no_prefix_files { Some("--no-prefix-files") } else if args.format==Format::Json { Some("--format json") } else if args.combined.is_some() && !append { Some("--combined without --append or --resume") } else if args.batch_size.is_some() { Some("--batch-size") } else { None };
+        if let Some(problem) = problem { return Err(Error::Arguments(format!("{}:={} adds to the prefix's own output file, so can't be used with {}",p.name(),p.number,problem))); }
+    }
+    if let (Some(roster),Some(out)) = (&args.merge_with,&args.merge_out) {
+        let problem = if args.prefixes.len()!=1 { Some("more than one prefix") } else if append || args.prefixes[0].total { Some("codes already in the prefix's file") } else if args.stdout { Some("--stdout") } else if args.no_prefix_files { Some("--no-prefix-files") } else { None };
+        if let Some(problem) = problem { return Err(Error::Arguments(format!("--merge-with gives a new code to each student, which needs a single prefix written to its own file, so can't be used with {}",problem))); }
+        if roster==out { return Err(Error::Arguments(format!("--merge-out can't replace the roster {}",roster.to_string_lossy()))); }
+    }
+    if !(args.force || append || args.stdout) {
+        let prefix_files : Vec<PathBuf> = if args.no_prefix_files { vec![] } else { args.prefixes.iter().enumerate().filter(|(_,p)|!p.total).flat_map(|(index,_)|outputs.files(args,index)).collect() };
+        let clashes : Vec<String> = prefix_files.iter().chain(outputs.spare_paths.iter().flatten()).chain(args.combined.iter()).chain(args.manifest.iter()).chain(args.merge_out.iter()).filter(|p|p.exists()).map(|p|p.to_string_lossy().to_string()).collect();
+        if !clashes.is_empty() {
+            return Err(Error::Arguments(format!("Output files already exist : {}. Use --force to replace them or --append to add to them.",clashes.join(", "))));
+        }
+    }
+    if append && args.format==Format::Json { return Err(Error::Arguments(format!("--{} can't be used with --format json. Try --format jsonl.",if args.resume { "resume" } else { "append" }))); }
+    Ok(())
+}
+
+/// How the codes for a prefix are written.
+struct PrefixPlan {
+    /// Whether the prefix's file is added to rather than replaced.
+    appending : bool,
+    /// How many codes are already in the prefix's file, if it is added to.
+    already : usize,
+    /// How many new codes to write to the prefix's file.
+    issued : usize,
+    /// How many codes to find, including spares.
+    wanted : usize,
+}
+
+/// Read the codes already in the output files that are added to, into the generator. Returns how many codes are already in each prefix's file.
+fn read_output_files(args:&Parameters,setup:&mut Setup,outputs:&OutputPaths) -> Result<Vec<usize>,Error> {
+    let append = args.append || args.resume;
+    let mut already : Vec<usize> = vec![0;outputs.paths.len()];
+    let prefix_files = if args.no_prefix_files { &[] } else { &outputs.paths[..] };
+    for (index,path) in prefix_files.iter().enumerate() {
+        let p = &args.prefixes[index];
+        // whether the file is added to, and whether the codes already in it count towards the number wanted.
+        let appending = append || p.total;
+        let counting = args.resume || p.total;
+        if !appending || !path.exists() { continue; }
+        let existing = read_valid_existing(path,None,&SkipLines::default(),&setup.validity,args.skip_invalid)?;
+        if counting { check_resumable(path,&existing.codes,p,&setup.alphabet,setup.digits,setup.check_len)?; }
+        already[index]=existing.codes.len();
+        if counting && args.balance_first {
+            for (_,code) in &existing.codes { setup.generator.count_first(&p.name(),&p.layout(setup.digits),code); }
+        }
+        let count = setup.generator.load_existing_with_prefix(existing.codes.iter().map(|(_,c)|c.as_str()),&p.prefix);
+        report!("Read output file {} {}",path.to_string_lossy(),existing.describe(count));
+        if let Some(pins) = &mut setup.pins { load_pins(pins,path)?; }
+    }
+    // the combined file holds codes for every prefix, so each is given the prefix it starts with, and none count towards the number wanted.
+    if let Some(path) = args.combined.as_ref().filter(|path|append && path.exists()) {
+        let existing = read_valid_existing(path,None,&SkipLines::default(),&setup.validity,args.skip_invalid)?;
+        let count = setup.generator.load_existing(existing.codes.iter().map(|(_,c)|c.as_str()));
+        report!("Read output file {} {}",path.to_string_lossy(),existing.describe(count));
+        if let Some(pins) = &mut setup.pins { load_pins(pins,path)?; }
+    }
+    Ok(already)
+}
+
+/// With `--dry-run`, say what would be made and which files would be written.
+fn print_plan(args:&Parameters,setup:&Setup,outputs:&OutputPaths,plans:&[PrefixPlan],existing_read:&[(String,usize)],manifest_path:Option<&Path>) {
+    let append = args.append || args.resume;
+    let status = |path:&Path,appending:bool| if !path.exists() { "would be created" } else if appending { "would be added to" } else { "would be replaced" };
+    println!("Dry run, so no codes have been made and no files written.");
+    println!("Seed : {}{}",setup.seed,if args.seed.is_none() { " (chosen at random; a run without --seed would choose another, so give --seed to get this plan)" } else { "" });
+    println!("Codes already used : {} from {} --existing files, {} from output files",existing_read.iter().map(|(_,count)|count).sum::<usize>(),existing_read.len(),setup.generator.used().len()-existing_read.iter().map(|(_,count)|count).sum::<usize>());
+    for (index,((p,plan),&min_distance)) in args.prefixes.iter().zip(plans).zip(&setup.min_distances).enumerate() {
+        let width = p.digits.map(|d|format!(" with {} random digits",d)).unwrap_or_default();
+        let distance = p.min_distance.as_ref().map(|_|format!(" at minimum distance {}",setup.metric.show_distance(min_distance))).unwrap_or_default();
+        let spares = outputs.spares[index];
+        let with_spares = if spares>0 { format!(" and {} spares",spares) } else { String::new() };
+        println!("Prefix {} : {} codes{}{}{}",p.name(),plan.issued,with_spares,width,distance);
+        if args.stdout { println!("    written to standard output"); }
+        else if !args.no_prefix_files {
+            for file in outputs.files(args,index) { println!("    {} {}",file.to_string_lossy(),status(&file,plan.appending)); }
+        }
+        if let Some(file) = &outputs.spare_paths[index] { println!("    spares to {} {}",file.to_string_lossy(),status(file,false)); }
+    }
+    if let Some(path) = &args.combined { println!("Combined file {} {}",path.to_string_lossy(),status(path,append)); }
+    if let Some(path) = &args.merge_out { println!("Mail merge file {} {}",path.to_string_lossy(),status(path,false)); }
+    if let Some(dir) = &args.barcodes { println!("Barcodes : {} SVG{} images in {}",args.barcode_format,if args.barcode_png { " and PNG" } else { "" },dir.to_string_lossy()); }
+    if let Some(path) = manifest_path { println!("Manifest {} {}",path.to_string_lossy(),status(path,false)); }
+    if let Some(path) = &args.stats_json { println!("Statistics {} {}",path.to_string_lossy(),status(path,false)); }
+    if let Some(path) = &args.progress_file { println!("Progress events {} {}",path.to_string_lossy(),status(path,false)); }
+    println!("Nothing was found wrong. Run again without --dry-run to make the codes.");
+}
+
+/// The manifest for a run, before any prefixes are added to it. Paths in it are relative to `manifest_dir`.
+fn new_manifest(args:&Parameters,setup:&Setup,existing_read:Vec<(String,usize)>,manifest_dir:&Path,start_time:String) -> Manifest {
+    let metric = setup.metric;
+    Manifest{
+        seed:setup.seed.to_string(),
+        digits:setup.digits,
+        min_distance:metric.show_distance(setup.min_hamming_distance),
+        min_distance_across:setup.min_distance_across.map(|d|metric.show_distance(d)),
+        metric:metric.to_string(),
+        weights:match (metric,&args.weights) {
+            (Metric::WeightedHamming(_),None) => Some("keypad".to_string()),
+            (Metric::WeightedHamming(_),Some(name)) if Weights::builtin(name).is_some() => Some(name.clone()),
+            (Metric::WeightedHamming(_),Some(path)) => Some(relative_to(Path::new(path),manifest_dir)),
+            _ => None,
+        },
+        scope:args.scope.to_string(),
+        length_policy:args.length_policy.to_string(),
+        alphabet:setup.alphabet.to_string(),
+        check_digit:args.check_digit.map(|c|c.to_string()),
+        digit_sum:args.digit_sum_mod.map(|s|s.to_string()),
+        range:args.range.as_ref().map(|r|format!("{}..{}",r.start,r.end)),
+        include_reversals:args.include_reversals,
+        pin:args.with_pin.map(|pin_digits|(pin_digits,args.pin_min_hamming_distance)),
+        grouping:setup.grouping.clone(),
+        prefixes:vec![],
+        existing:existing_read,
+        combined:args.combined.as_ref().map(|p|relative_to(p,manifest_dir)),
+        command_line:std::env::args().collect(),
+        timestamp:start_time,
+        complete:false,
+        stopped:None,
+    }
+}
+
+/// Everything the codes are written to or recorded in as each prefix's codes are made.
+struct Run<'a> {
+    args : &'a Parameters,
+    setup : Setup,
+    outputs : OutputPaths,
+    shared : SharedOutputs,
+    manifest : Manifest,
+    manifest_path : Option<PathBuf>,
+    /// The directory paths in the manifest are relative to.
+    manifest_dir : PathBuf,
+    summary : RunSummary,
+    events : Events,
+    /// The codes already found for every prefix, with `--interleave`.
+    interleaved : Option<Interleaved>,
+    run_start : Instant,
+}
+
+impl Run<'_> {
+    /// Find and write the codes for prefix `index`. Returns why they couldn't all be found, if they couldn't.
+    fn make_prefix(&mut self,index:usize,plan:&PrefixPlan) -> Result<Option<GenerationError>,Error> {
+        let args = self.args;
+        let p = &args.prefixes[index];
+        let name = p.name();
+        let path = &self.outputs.paths[index];
+        let digits = self.setup.digits;
+        let min_distance = self.setup.min_distances[index];
+        let spare_path = self.outputs.spare_paths[index].as_ref();
+        let spares = self.outputs.spares[index];
+        self.manifest.prefixes.push(ManifestPrefix{prefix:p.prefix.clone(),suffix:p.suffix.clone(),mask:p.mask.clone(),wanted:p.number,found:0,digits:p.digits_or(digits),min_distance:self.setup.metric.show_distance(min_distance),file:if args.stdout || args.no_prefix_files || args.batch_size.is_some() { None } else { Some(relative_to(path,&self.manifest_dir)) },batches:vec![],spares,spares_found:0,spares_file:spare_path.map(|s|relative_to(s,&self.manifest_dir))});
+        let filename = destination(args,p,path);
+        let width = match p.digits { Some(d) => format!(" with {} random digits",d), None => String::new() };
+        let spares_to = spare_path.map(|s|format!(" and {} spares to {}",spares,s.to_string_lossy())).unwrap_or_default();
+        match &self.interleaved {
+            Some(interleaved) => report!("Writing {} codes for prefix {} to {}{}.",interleaved.codes[index].len().min(plan.issued),name,filename,spares_to),
+            None => {
+                report!("Processing prefix {} trying to find {}{}, writing {} to {}{}.",name,plan.wanted,width,plan.issued,filename,spares_to);
+                self.events.prefix_start(&name,plan.wanted)?;
+            }
+        }
+        let shown_distance = self.manifest.min_distance.clone();
+        let info = OutputInfo{prefix:&name,digits:p.digits_or(digits),min_distance:&shown_distance,seed:Some(self.setup.seed),pin_digits:args.with_pin,display:self.setup.grouping.is_some(),date:args.write_header.then_some(self.outputs.date.as_str())};
+        let mut writer = PrefixWriter::open(args,info,p,index,path,plan.appending,plan.already)?;
+        let mut progress = (!args.interleave).then(||Progress::new(&name,plan.wanted,args.verbose==0));
+        let prefix_start = Instant::now();
+        if !args.interleave { self.setup.generator.set_deadline([args.timeout.map(|t|self.run_start+t.0),args.timeout_per_prefix.map(|t|prefix_start+t.0)].into_iter().flatten().min()); }
+        let mut stopped : Option<GenerationError> = None;
+        let mut found : Vec<String> = vec![]; // codes found but not yet written, when shuffling or sorting.
+        let mut spare_codes : Vec<String> = vec![]; // the spare codes found, which are written after the others.
+        let mut num_found = plan.wanted;
+        for i in 0..plan.wanted {
+            let next = match &mut self.interleaved {
+                Some(interleaved) => interleaved.codes[index].pop_front(),
+                None => {
+                    let mut rejected = 0;
+                    let code = self.setup.generator.new_code_with_layout(&name,&p.layout(digits),min_distance,|candidate,rejection|{
+                        if args.verbose>1 { report!("Rejected {} : {}",candidate,rejection) }
+                        rejected+=1;
+                        if let Some(progress) = &mut progress { progress.rejected(); }
+                    });
+                    match code {
+                        Ok(code) => Some((code,rejected+1)),
+                        Err(e) => { stopped=Some(e); None }
+                    }
+                }
+            };
+            // with --interleave, the codes for a prefix run out early if the search stopped.
+            let Some((code,candidates)) = next else { num_found=i; break; };
+            if !args.interleave {
+                if args.verbose>0 { report!("Found {} after {} candidates",code,candidates); }
+                self.events.code_found(&name,i+1,plan.wanted,candidates)?;
+            }
+            if i>=plan.issued { spare_codes.push(code); }
+            else if args.shuffle_output || args.sort_output { found.push(code); } else { writer.write(&[code],&mut self.shared)?; }
+            if let Some(progress) = &mut progress { progress.found(); }
+        }
+        if let Some(progress) = &mut progress { progress.finish(); }
+        arrange(args,&mut found,&mut self.setup.generator);
+        writer.write(&found,&mut self.shared)?;
+        writer.finish()?;
+        if let Some(spare_path) = spare_path {
+            arrange(args,&mut spare_codes,&mut self.setup.generator);
+            writer.write_spares(spare_path,&spare_codes,&mut self.shared)?;
+        }
+        if let Some(last) = self.manifest.prefixes.last_mut() {
+            last.found=p.number-plan.issued+num_found.min(plan.issued);
+            last.spares_found=spare_codes.len();
+            last.batches=batch_files(&writer.batches,&self.manifest_dir);
+        }
+        if let Some(batches) = &writer.batches {
+            for (path,count) in batches.written() { report!("Wrote {} codes to {}",count,path.to_string_lossy()); }
+        }
+        let time = match &self.interleaved { Some(interleaved) => interleaved.times[index], None => prefix_start.elapsed() };
+        let prefix_summary = PrefixSummary{prefix:name.clone(),statistics:self.setup.generator.take_statistics(&name),time,first_characters:self.setup.generator.first_counts(&name)};
+        if num_found<plan.wanted {
+            let reason = stopped.as_ref().or(self.interleaved.as_ref().and_then(|i|i.stopped.as_ref())).map(|e|e.to_string()).unwrap_or_default();
+            self.events.prefix_failed(&name,num_found,plan.wanted,&reason)?;
+            alert!("Found {} of {} codes for prefix {}, which have been written to {}.",num_found,plan.wanted,name,filename);
+        } else {
+            report!("Prefix {} : {}",name,describe(&prefix_summary.statistics,prefix_summary.time));
+            self.events.prefix_done(&name,plan.wanted,plan.wanted,prefix_summary.statistics.candidates,prefix_summary.time)?;
+        }
+        if !prefix_summary.first_characters.is_empty() { report!("Prefix {} first random characters : {}",name,describe_first_characters(&prefix_summary.first_characters)); }
+        self.summary.prefixes.push(prefix_summary);
+        Ok(stopped)
+    }
+
+    /// Finish the outputs and write the manifest, statistics and mail merge file, after the codes for every prefix have been made
+    /// or the search `stopped`.
+    fn finish(self,stopped:Option<GenerationError>,roster:Option<MergeRoster>) -> Result<(),Error> {
+        let args = self.args;
+        let merged = self.shared.finish()?;
+        let mut manifest = self.manifest;
+        if let Some(e) = stopped.or(self.interleaved.and_then(|i|i.stopped)) {
+            manifest.stopped=Some(e.to_string());
+            if let Some(path) = &self.manifest_path { manifest.write(path)?; }
+            if let Some(path) = &args.stats_json { self.summary.write(path)?; }
+            if let Some(path) = &args.merge_out { alert!("Mail merge file {} was not written, as not every student has a code.",path.to_string_lossy()); }
+            match e {
+                GenerationError::Exhausted{..} => alert!("No other code is far enough from those already chosen. Try using more digits or a smaller minimum distance."),
+                GenerationError::Interrupted{..} | GenerationError::TimedOut{..} if self.outputs.spare_paths.iter().any(|s|s.is_some()) => alert!("Runs making spare codes can't be resumed, so run the same command again to start over."),
+                GenerationError::Interrupted{..} | GenerationError::TimedOut{..} => alert!("Run the same command{} with --resume{} to carry on.",if args.force { ", without --force," } else { "" },if args.seed.is_none() { format!(" --seed {}",self.setup.seed) } else { String::new() }),
+                _ => alert!("The parameters are probably infeasible. Try using more digits or a smaller minimum distance."),
+            }
+            return Err(Error::Generation(e));
+        }
+        if self.summary.prefixes.len()>1 {
+            let (total,time) = self.summary.total();
+            report!("Total : {}",describe(&total,time));
+        }
+        if let Some(path) = &args.stats_json {
+            self.summary.write(path)?;
+            report!("Wrote statistics {}",path.to_string_lossy());
+        }
+        if let (Some(roster),Some(path)) = (&roster,&args.merge_out) {
+            let mut file = OutputFile::create(path,!args.no_atomic).map_err(Error::io(format!("Could not create mail merge file {}",path.to_string_lossy())))?;
+            file.write_all(roster.merge(&merged).as_bytes())?;
+            file.finish()?;
+            report!("Wrote {} students with their codes to {}",merged.len(),path.to_string_lossy());
+        }
+        manifest.complete=true;
+        if let Some(path) = &self.manifest_path {
+            manifest.write(path)?;
+            report!("Wrote manifest {}",path.to_string_lossy());
+        }
+        let mut events = self.events;
+        events.run_done(self.summary.total().0.accepted,self.run_start.elapsed())?;
+        report!("All finished!");
+        Ok(())
+    }
+}
+
+/// Shuffle or sort the codes found for a prefix before they are written, as asked for.
+fn arrange(args:&Parameters,found:&mut [String],generator:&mut GenerateCodes) {
+    if args.shuffle_output { generator.shuffle(found); }
+    if args.sort_output { found.sort(); }
+}
+
+/// The codes found with `--interleave`, before any are written.
+struct Interleaved {
+    /// For each prefix, the codes found and how many candidates each took.
+    codes : Vec<VecDeque<(String,u64)>>,
+    /// How long was spent finding each prefix's codes.
+    times : Vec<Duration>,
+    /// Why the search stopped before all the codes were found, if it did.
+    stopped : Option<GenerationError>,
+}
+
+/// Find the `remaining` codes for each prefix for `--interleave`, each time for the prefix with the smallest share of its codes
+/// found so far (the first of them, if there are several), so all the prefixes finish together.
+fn find_interleaved(args:&Parameters,setup:&mut Setup,remaining:&[usize],events:&mut Events,run_start:Instant) -> Result<Interleaved,Error> {
+    let num_prefixes = args.prefixes.len();
+    let mut res = Interleaved{codes:vec![VecDeque::new();num_prefixes],times:vec![Duration::ZERO;num_prefixes],stopped:None};
+    let total : usize = remaining.iter().sum();
+    report!("Finding {} codes for {} prefixes in turn.",total,num_prefixes);
+    for (p,&wanted) in args.prefixes.iter().zip(remaining) { events.prefix_start(&p.name(),wanted)?; }
+    setup.generator.set_deadline(args.timeout.map(|t|run_start+t.0));
+    let mut progress = Progress::all_prefixes(total,args.verbose==0);
+    for _ in 0..total {
+        let found = |index:usize| res.codes[index].len();
+        let index = (0..num_prefixes).filter(|&i|found(i)<remaining[i]).min_by(|&i,&j|(found(i)*remaining[j]).cmp(&(found(j)*remaining[i]))).expect("Some prefix still needs codes");
+        let p = &args.prefixes[index];
+        let start = Instant::now();
+        let mut rejected = 0;
+        let code = setup.generator.new_code_with_layout(&p.name(),&p.layout(setup.digits),setup.min_distances[index],|candidate,rejection|{
+            if args.verbose>1 { report!("Rejected {} : {}",candidate,rejection) }
+            rejected+=1;
+            progress.rejected();
+        });
+        res.times[index]+=start.elapsed();
+        match code {
+            Ok(code) => {
+                if args.verbose>0 { report!("Found {} for prefix {} after {} candidates",code,p.name(),rejected+1); }
+                events.code_found(&p.name(),found(index)+1,remaining[index],rejected+1)?;
+                res.codes[index].push_back((code,rejected+1));
+                progress.found();
+            }
+            Err(e) => { res.stopped=Some(e); break; }
+        }
+    }
+    progress.finish();
+    Ok(res)
+}
+
+/// The files written with `--batch-size`, relative to `dir`, and how many codes are in each.
+fn batch_files(batches:&Option<Batches>,dir:&Path) -> Vec<(String,usize)> {
+    batches.iter().flat_map(|b|b.written()).map(|(path,count)|(relative_to(&path,dir),count)).collect()
+}
+
+/// Check that the codes already in a prefix's output file could have been made with the current parameters, for `--resume`.
+fn check_resumable(path:&Path,codes:&[(usize,String)],p:&WantedPrefix,alphabet:&Alphabet,digits:usize,check_len:usize) -> Result<(),Error> {
+    let layout = canonical_layout(p,alphabet,digits);
+    let length = layout.len()+check_len;
+    for (line,code) in codes {
+        let code = alphabet.normalise(code);
+        let without_check : String = code.chars().take(code.chars().count().saturating_sub(check_len)).collect();
+        if !has_fixed_characters(&without_check,&layout) || code.chars().count()!=length {
+            return Err(Error::invalid_input(path,Some(*line),format!("{:?} is not a code with {} {} and {} characters, as the current parameters would make, so the file can't be added to. Check they are the same as when the file was made.",code,if p.mask.is_some() { "mask" } else { "prefix" },p.name(),length)));
+        }
+    }
+    Ok(())
+}
+
+/// As [check_feasibility], for the PINs from `--with-pin`, which are all in one space.
+fn check_pin_feasibility(args:&Parameters,pins:&GenerateCodes,wanted:usize) -> Result<(),Error> {
+    let pin_digits = args.with_pin.expect("Only called with --with-pin");
+    let bound = Bounds::new(pin_digits,10,args.pin_min_hamming_distance).upper();
+    let existing = pins.used().len();
+    let total = (wanted+existing) as f64;
+    let description = format!("Asked for {} PINs{} with {} digits at minimum distance {}",wanted,if existing>0 { format!(" (plus {} existing)",existing) } else { String::new() },pin_digits,args.pin_min_hamming_distance);
+    if total>bound {
+        let message = format!("{}, but at most {} can fit. Try using more PIN digits or a smaller --pin-min-hamming-distance.",description,bound.floor());
+        if args.i_know_what_im_doing { alert!("Warning : {}",message); }
+        else { return Err(Error::Infeasible(message+" Use --i-know-what-im-doing to try anyway.")); }
+    } else if 2.0*total>bound {
+        alert!("Warning : {}, which is more than half of the at most {} that can fit. Finding them will probably be very slow, or fail.",description,bound.floor());
+    }
+    Ok(())
+}
+
+/// Stop, unless `--i-know-what-im-doing`, if more codes are wanted for some prefix than can possibly fit with the codes already used,
+/// and warn if it is close, as random search gets very slow long before the bound.
+///
+/// Codes with the same prefix and length only differ in their random part and check digits, so the random parts must be
+/// at least the minimum distance less the number of check digits apart. The Singleton and Hamming bounds on that hold for any metric,
+/// as other metrics are never bigger than Hamming distance, so `min_hamming_distance` is the Hamming distance the metric implies ([Metric::min_hamming]).
+/// `min_hamming_distances` is the minimum Hamming distance for each prefix.
+fn check_feasibility(args:&Parameters,generator:&GenerateCodes,alphabet:&Alphabet,digits:usize,min_hamming_distances:&[usize],wanted:&[usize]) -> Result<(),Error> {
+    let check_len = args.check_digit.map(|c|c.num_characters()).unwrap_or(0);
+    // With distances measured on the random part across all prefixes, all prefixes with the same number of digits share one space.
+    let shared = args.distance_on_body && args.scope==Scope::Global;
+    let mut groups : Vec<(Vec<Option<char>>,usize,usize,usize)> = vec![]; // (canonical layout, digits, number wanted, minimum distance)
+    for ((p,&number),&min_hamming_distance) in args.prefixes.iter().zip(wanted).zip(min_hamming_distances) {
+        let p_digits = p.digits_or(digits);
+        let layout = if shared { vec![None;p_digits] } else { canonical_layout(p,alphabet,digits) };
+        match groups.iter_mut().find(|(l,_,_,_)|*l==layout) {
+            // codes sharing a space are at least the larger of their minimum distances apart.
+            Some(group) => { group.2+=number; group.3=group.3.max(min_hamming_distance); }
+            None => groups.push((layout,p_digits,number,min_hamming_distance)),
+        }
+    }
+    for (layout,p_digits,wanted,min_hamming_distance) in groups {
+        let min_body_distance = min_hamming_distance.saturating_sub(check_len).max(1);
+        let existing = generator.used().iter().filter(|code| {
+            let without_check : String = code.chars().take(code.chars().count().saturating_sub(check_len)).collect();
+            if shared { without_check.chars().rev().take(p_digits).all(|c|alphabet.contains(c)) }
+            else { has_fixed_characters(&without_check,&layout) && without_check.chars().zip(&layout).all(|(c,l)|l.is_some() || alphabet.contains(c)) }
+        }).count();
+        let mut bounds = Bounds::new(p_digits,alphabet.distinct_size(),min_body_distance);
+        if let Some(sum) = args.digit_sum_mod { bounds = bounds.with_modulus(p_digits,alphabet.distinct_size(),sum.modulus); }
+        if let Some(range) = &args.range { bounds = bounds.with_range(p_digits,range,min_body_distance); }
+        let bound = bounds.upper();
+        let total = (wanted+existing) as f64;
+        let description = format!("{} {} codes{} with {} random characters from an alphabet of {} at minimum distance {}{}{}",
+            if layout.iter().all(|c|c.is_none()) { "Asked for".to_string() } else { format!("{} : asked for",describe_layout(&layout)) },wanted,
+            if existing>0 { format!(" (plus {} existing)",existing) } else { String::new() },
+            p_digits,alphabet.distinct_size(),min_body_distance,
+            if check_len>0 { " (not counting check digits)" } else { "" },
+            args.digit_sum_mod.map(|s|format!(" and digit sum {} modulo {}",s.remainder,s.modulus)).unwrap_or_default())
+            +&args.range.as_ref().map(|r|format!(" in the range {}..{}",r.start,r.end)).unwrap_or_default();
+        if total>bound {
+            let message = format!("{}, but at most {} can fit, by the Singleton and sphere packing bounds. Try using more digits or a smaller minimum distance.",description,bound.floor());
+            if args.i_know_what_im_doing { alert!("Warning : {}",message); }
+            else { return Err(Error::Infeasible(message+" Use --i-know-what-im-doing to try anyway.")); }
+        } else if 2.0*total>bound {
+            alert!("Warning : {}, which is more than half of the at most {} that can fit. Finding them will probably be very slow, or fail. Try using more digits or a smaller minimum distance.",description,bound.floor());
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,731 @@
+// This program is Copyright 2022 Andrew Conway and licensed under the GPL:
+//
+// This program is free software: you can redistribute it and/or modify it under the terms of the GNU General Public License as published by the Free Software Foundation, either version 3 of the License, or (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful, but WITHOUT ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License along with this program. If not, see <https://www.gnu.org/licenses/>.
+
+//! Reading the files of codes given on the command line, and the other options that say how to read them.
+
+
+use std::collections::HashMap;
+use std::fmt::{Display, Formatter};
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::sync::OnceLock;
+use std::time::Duration;
+use regex::Regex;
+use make_exam_numbers::{GenerateCodes, WantedPrefix};
+use make_exam_numbers::verify::Problem;
+use make_exam_numbers::check_digit::CheckDigit;
+use make_exam_numbers::alphabet::Alphabet;
+use make_exam_numbers::distance::{LengthPolicy, Metric, Weights};
+use crate::cli::error::Error;
+use crate::cli::layout::has_fixed_characters;
+use crate::cli::manifest::Manifest;
+use crate::cli::options::Parameters;
+use crate::cli::output::{csv_code_column, Format, template_pattern, SPARES_SUFFIX, parse_json_codes, parse_json_pins};
+use crate::cli::report::{alert, describe_problem, report};
+use crate::cli::setup::Setup;
+use crate::cli::writer::OutputPaths;
+
+/// What to do about existing codes with a different length to the codes being made.
+#[derive(Clone,Copy,Debug,PartialEq,Eq)]
+pub enum LengthHandling {
+    /// Treat them as invalid.
+    Error,
+    /// Compare them according to the policy.
+    Policy(LengthPolicy),
+}
+
+impl FromStr for LengthHandling {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if s=="error" { Ok(LengthHandling::Error) }
+        else { s.parse().map(LengthHandling::Policy).map_err(|_|format!("Unknown length policy {}, expecting error, pad, distinct or truncate",s)) }
+    }
+}
+
+impl Display for LengthHandling {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            LengthHandling::Error => f.write_str("error"),
+            LengthHandling::Policy(policy) => policy.fmt(f),
+        }
+    }
+}
+
+/// A minimum distance as given on the command line, which can only be a fraction like 2.5 for `--metric weighted-hamming`.
+///
+/// It is kept as given, as what it means depends on the metric, which may not be known until a manifest has been read.
+#[derive(Clone,Debug,PartialEq,Eq)]
+pub struct Threshold(pub String);
+
+impl FromStr for Threshold {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if s.chars().all(|c|c.is_ascii_digit() || c=='.') && s.parse::<f64>().is_ok() { Ok(Threshold(s.to_string())) }
+        else { Err(format!("Minimum distance {} should be a number like 3, or for weighted-hamming 2.5",s)) }
+    }
+}
+
+impl Threshold {
+    /// The distance in units of the metric (see [Metric::unit]).
+    pub fn units(&self,metric:Metric) -> Result<usize,String> { metric.parse_distance(&self.0) }
+}
+
+impl Display for Threshold {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result { f.write_str(&self.0) }
+}
+
+/// A length of time such as `30s`, `10m` or `2h`. A number with no unit is seconds.
+#[derive(Clone,Copy,Debug,PartialEq,Eq)]
+pub struct TimeLimit(pub Duration);
+
+impl FromStr for TimeLimit {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (number,unit) = s.split_at(s.find(|c:char|!c.is_ascii_digit()).unwrap_or(s.len()));
+        let seconds : u64 = match unit { "" | "s" => 1, "m" => 60, "h" => 3600, _ => 0 };
+        match number.parse::<u64>() {
+            Ok(number) if seconds>0 && number>0 => Ok(TimeLimit(Duration::from_secs(number.saturating_mul(seconds)))),
+            _ => Err(format!("Unknown time {}, expecting a whole number of seconds, minutes or hours like 30s, 10m or 2h",s)),
+        }
+    }
+}
+
+/// How `assign --derive` makes codes from IDs.
+#[derive(Clone,Copy,Debug,PartialEq,Eq)]
+pub enum Derivation {
+    /// HMAC-SHA256 of the prefix and ID with a secret key.
+    Hmac,
+}
+
+impl FromStr for Derivation {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "hmac" => Ok(Derivation::Hmac),
+            _ => Err(format!("Unknown derivation {}, expecting hmac",s)),
+        }
+    }
+}
+
+/// Which column of a CSV file contains the codes.
+#[derive(Clone,Debug,PartialEq,Eq)]
+pub enum Column {
+    /// The 0 based index of the column. There is no header row.
+    Index(usize),
+    /// The name of the column in the header row.
+    Name(String),
+}
+
+/// A number is an index, anything else is a name.
+impl FromStr for Column {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if s.is_empty() { return Err("The column can't be empty".to_string()); }
+        Ok(match s.parse::<usize>() {
+            Ok(index) => Column::Index(index),
+            Err(_) => Column::Name(s.to_string()),
+        })
+    }
+}
+
+impl Display for Column {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Column::Index(index) => write!(f,"{}",index),
+            Column::Name(name) => write!(f,"{}",name),
+        }
+    }
+}
+
+/// The codes in one column of a CSV file.
+pub struct CsvColumn {
+    /// Each code, with the (1 based) line number its row starts on.
+    pub codes : Vec<(usize,String)>,
+    /// The number of rows where the column was empty or missing, which are not included in `codes`.
+    pub empty : usize,
+}
+
+/// Get the (trimmed) contents of the given column from each row of a CSV file, skipping the header row if the column is given by name.
+pub fn read_csv_column(text:&str,column:&Column) -> Result<CsvColumn,String> {
+    let mut reader = csv::ReaderBuilder::new().has_headers(false).flexible(true).from_reader(text.as_bytes());
+    let mut records = reader.records();
+    let index = match column {
+        Column::Index(index) => *index,
+        Column::Name(name) => {
+            let header = records.next().ok_or_else(||"Expecting a header row, but there is nothing in the file".to_string())?.map_err(|e|e.to_string())?;
+            header.iter().position(|h|h.trim()==name).ok_or_else(||format!("No column called {} in the header row {}",name,header.iter().collect::<Vec<_>>().join(",")))?
+        }
+    };
+    let mut res = CsvColumn{codes:vec![],empty:0};
+    for record in records {
+        let record = record.map_err(|e|e.to_string())?;
+        let line = record.position().map(|p|p.line() as usize).unwrap_or(0);
+        match record.get(index).map(|f|f.trim()).filter(|f|!f.is_empty()) {
+            Some(code) => res.codes.push((line,code.to_string())),
+            None => res.empty+=1,
+        }
+    }
+    Ok(res)
+}
+
+/// The column added to a roster by `--merge-with`.
+pub const MERGE_COLUMN : &str = "exam_number";
+
+/// A CSV file with a header row, kept as the original text so that rows can be written back with a code added but otherwise
+/// exactly as they were, quoting and all.
+pub struct MergeRoster {
+    text : String,
+    /// Where each row ends in `text`, before its line ending. The first is the header row.
+    ends : Vec<usize>,
+}
+
+impl MergeRoster {
+    /// Read a roster from the text of a CSV file, which must have a header row without a column [MERGE_COLUMN].
+    pub fn new(text:String) -> Result<Self,String> {
+        let mut reader = csv::ReaderBuilder::new().has_headers(false).flexible(true).from_reader(text.as_bytes());
+        let mut record = csv::ByteRecord::new();
+        let mut ends = vec![];
+        while reader.read_byte_record(&mut record).map_err(|e|e.to_string())? {
+            if ends.is_empty() && record.iter().any(|h|h==MERGE_COLUMN.as_bytes()) { return Err(format!("The header row already has a column {}",MERGE_COLUMN)); }
+            let end = reader.position().byte() as usize;
+            let line_ending = if text[..end].ends_with("\r\n") { 2 } else if text[..end].ends_with(['\n','\r']) { 1 } else { 0 };
+            ends.push(end-line_ending);
+        }
+        if ends.is_empty() { return Err("Expecting a header row, but there is nothing in the file".to_string()); }
+        Ok(MergeRoster{text,ends})
+    }
+
+    /// The number of rows, not counting the header row.
+    pub fn rows(&self) -> usize { self.ends.len()-1 }
+
+    /// The roster with a column [MERGE_COLUMN] added with the `codes`, one for each row in order.
+    pub fn merge(&self,codes:&[String]) -> String {
+        assert_eq!(codes.len(),self.rows(),"There must be a code for every row");
+        let mut res = String::with_capacity(self.text.len()+codes.iter().map(|c|c.len()+1).sum::<usize>()+MERGE_COLUMN.len()+1);
+        let mut start = 0;
+        for (&end,added) in self.ends.iter().zip(std::iter::once(MERGE_COLUMN).chain(codes.iter().map(|c|c.as_str()))) {
+            res.push_str(&self.text[start..end]);
+            res.push(',');
+            res.push_str(&crate::cli::output::csv_field(added));
+            start = end;
+        }
+        res.push_str(&self.text[start..]);
+        res
+    }
+}
+
+/// Read a file of groups of confusable characters for `--confusable-classes-file`, one group per line.
+pub fn read_confusable_classes(path:&Path) -> Result<Vec<String>,Error> {
+    Ok(read_codes(path)?.into_iter().map(|l|l.trim().to_string()).filter(|l|!(l.is_empty() || l.starts_with('#'))).collect())
+}
+
+/// The metric to use, with the `--weights` given for `weighted-hamming`. A file of weights is read relative to `dir`.
+pub fn with_weights(metric:Metric,weights:Option<&str>,dir:&Path) -> Result<Metric,Error> {
+    match (metric,weights) {
+        (_,None) => Ok(metric),
+        (Metric::WeightedHamming(_),Some(name)) => Ok(Metric::WeightedHamming(match Weights::builtin(name) {
+            Some(weights) => weights,
+            None => read_weights(&dir.join(name))?.leak(),
+        })),
+        (_,Some(_)) => Err(Error::Arguments(format!("--weights can only be used with --metric weighted-hamming, not {}",metric))),
+    }
+}
+
+/// Read a file of weights for `--weights`, each line giving two characters and how much they count as different, like `3 4 0.5`.
+fn read_weights(path:&Path) -> Result<Weights,Error> {
+    let mut weights = Weights::default();
+    for (index,line) in read_codes(path)?.iter().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') { continue; }
+        let invalid = |reason:String| Error::invalid_input(path,Some(index+1),reason);
+        let character = |s:&str| {
+            let mut chars = s.chars();
+            match (chars.next(),chars.next()) {
+                (Some(c),None) => Ok(c),
+                _ => Err(invalid(format!("{:?} should be a single character",s))),
+            }
+        };
+        let (a,b,weight) = match line.split_whitespace().collect::<Vec<_>>()[..] {
+            [a,b,weight] => (character(a)?,character(b)?,weight),
+            _ => return Err(invalid(format!("{:?} should be two characters and a weight, like 3 4 0.5",line))),
+        };
+        let weight : f64 = weight.parse().map_err(|_|invalid(format!("weight {:?} should be a number from 0 to 1",weight)))?;
+        weights.set(a,b,weight).map_err(invalid)?;
+    }
+    Ok(weights)
+}
+
+/// Whether a path is `-`, meaning standard input.
+fn is_stdin(path:&Path) -> bool { path.as_os_str()=="-" }
+
+/// How to refer to a file read in messages, like "file codes.txt" or "standard input".
+pub fn describe_source(path:&Path) -> String {
+    if is_stdin(path) { "standard input".to_string() } else { format!("file {}",path.to_string_lossy()) }
+}
+
+/// Read a whole file, or standard input if the path is `-`.
+///
+/// Standard input can only be read once, so it is kept, and reading it again gives the same text.
+pub fn read_text(path:&Path) -> Result<String,Error> {
+    static STDIN : OnceLock<Result<String,String>> = OnceLock::new();
+    if !is_stdin(path) { return std::fs::read_to_string(path).map_err(Error::io(format!("Could not read {}",path.to_string_lossy()))); }
+    let text = STDIN.get_or_init(||std::io::read_to_string(std::io::stdin()).map_err(|e|e.to_string()));
+    text.clone().map_err(|e|Error::Io{context:"Could not read standard input".to_string(),source:std::io::Error::other(e)})
+}
+
+/// Replace each path containing a glob metacharacter (`*`, `?` or `[`) by the files matching it, in alphabetical order.
+/// A pattern matching no files is an error, rather than quietly reading nothing. `option` is the one the paths were given to.
+pub fn expand_globs(option:&str,paths:&[PathBuf]) -> Result<Vec<PathBuf>,Error> {
+    let mut res = vec![];
+    for path in paths {
+        let pattern = path.to_string_lossy();
+        if !pattern.contains(['*','?','[']) { res.push(path.clone()); continue; }
+        let matches = glob::glob(&pattern).map_err(|e|Error::Arguments(format!("Invalid pattern {} given to {} : {}",pattern,option,e)))?;
+        let matches : Vec<PathBuf> = matches.collect::<Result<_,_>>().map_err(|e|Error::io(format!("Could not read {}",e.path().to_string_lossy()))(e.into()))?;
+        if matches.is_empty() { return Err(Error::Arguments(format!("Pattern {} given to {} matches no files",pattern,option))); }
+        res.extend(matches);
+    }
+    Ok(res)
+}
+
+/// The files under `dir`, in any subdirectory, whose names match the glob pattern `filter`, in alphabetical order of path.
+/// Symbolic links are followed, though a link to a directory containing it is skipped with a warning.
+/// A directory with no such files is an error.
+fn files_in_dir(dir:&Path,filter:&str) -> Result<Vec<PathBuf>,Error> {
+    let pattern = glob::Pattern::new(filter).map_err(|e|Error::Arguments(format!("Invalid pattern {} given to --existing-filter : {}",filter,e)))?;
+    let mut res = vec![];
+    for entry in walkdir::WalkDir::new(dir).follow_links(true).sort_by_file_name() {
+        let entry = match entry {
+            Ok(entry) => entry,
+            Err(e) if e.loop_ancestor().is_some() => {
+                alert!("Warning : skipping {}, as it is a link to a directory containing it",e.path().unwrap_or(dir).to_string_lossy());
+                continue;
+            }
+            Err(e) => {
+                let context = format!("Could not read directory {}",e.path().unwrap_or(dir).to_string_lossy());
+                return Err(Error::io(context)(e.into_io_error().expect("Errors other than loops come from I/O")));
+            }
+        };
+        if entry.file_type().is_file() && pattern.matches(&entry.file_name().to_string_lossy()) { res.push(entry.into_path()); }
+    }
+    if res.is_empty() { return Err(Error::Arguments(format!("Directory {} given to --existing-dir contains no files matching {}",dir.to_string_lossy(),filter))); }
+    Ok(res)
+}
+
+/// The files in `dir` that `--existing-auto` reads, as they are named like this run's output files, by `template` or as
+/// `prefix_*`, `suffix_*` or `mask_*` with the format's extension. Files in `written` (this run's own output files) and manifests are left out.
+fn earlier_output_files(dir:&Path,template:Option<&str>,format:Format,written:&[PathBuf]) -> Result<Vec<PathBuf>,Error> {
+    let names = match template {
+        Some(template) => vec![template_pattern(template).map_err(|e|Error::Arguments(format!("--output-template : {}",e)))?],
+        None => ["prefix","suffix","mask"].iter().map(|kind|format!("{}_*.{}",kind,format.extension())).collect(),
+    };
+    let manifest = glob::Pattern::new("manifest_*.json").expect("A valid pattern");
+    let mut res = vec![];
+    for name in names {
+        let pattern = Path::new(&glob::Pattern::escape(&dir.to_string_lossy())).join(name).to_string_lossy().to_string();
+        for path in glob::glob(&pattern).map_err(|e|Error::Arguments(format!("Can't look for earlier output files matching {} : {}",pattern,e)))? {
+            let path = path.map_err(|e|Error::io(format!("Could not read {}",e.path().to_string_lossy()))(e.into()))?;
+            let is_manifest = path.file_name().map(|n|manifest.matches(&n.to_string_lossy())).unwrap_or(false);
+            if path.is_file() && !is_manifest && !written.contains(&path) { res.push(path); }
+        }
+    }
+    Ok(res)
+}
+
+/// Read a file of codes, one per line.
+fn read_codes(path:&Path) -> Result<Vec<String>,Error> {
+    Ok(read_text(path)?.lines().map(|l|l.to_string()).collect())
+}
+
+/// Lines of a file of codes which are not codes, and are skipped when reading it.
+pub struct SkipLines {
+    /// Lines starting with this (after any white space) are comments. Comments can't be empty.
+    pub comment : Option<String>,
+    /// Whether the first line of a file of one code per line, that isn't blank or a comment, is a header.
+    pub header : bool,
+}
+
+impl Default for SkipLines {
+    /// Skip comments starting with `#`, as written by `--write-header`.
+    fn default() -> Self { SkipLines{comment:Some("#".to_string()),header:false} }
+}
+
+/// Codes read from a file, each with the (1 based) line number it was on.
+pub struct ExistingCodes {
+    pub codes : Vec<(usize,String)>,
+    /// The number of lines (or entries) ignored because they were blank.
+    pub blank : usize,
+    /// The number of lines ignored because they were comments.
+    pub comments : usize,
+    /// Whether a header line was ignored.
+    pub header : bool,
+}
+
+impl ExistingCodes {
+    /// Trim the codes, and remove any that are then empty.
+    pub fn without_blanks(codes:Vec<(usize,String)>) -> Self {
+        let total = codes.len();
+        let codes : Vec<(usize,String)> = codes.into_iter().map(|(i,c)|(i,c.trim().to_string())).filter(|(_,c)|!c.is_empty()).collect();
+        ExistingCodes{blank:total-codes.len(),codes,comments:0,header:false}
+    }
+
+    /// A description of what was read for the user, like "containing 5 entries" or "containing 5 entries (4 unique) and 2 blank lines,
+    /// 3 comment lines and a header line which were skipped", where `unique` is the number of entries that were not duplicates.
+    pub fn describe(&self,unique:usize) -> String {
+        let mut res = format!("containing {} entries",self.codes.len());
+        if unique!=self.codes.len() { res.push_str(&format!(" ({} unique)",unique)); }
+        let skipped : Vec<String> = [(self.blank,"blank lines"),(self.comments,"comment lines")].into_iter().filter(|&(n,_)|n>0).map(|(n,what)|format!("{} {}",n,what))
+            .chain(self.header.then(||"a header line".to_string())).collect();
+        if let Some((last,rest)) = skipped.split_last() {
+            let list = if rest.is_empty() { last.clone() } else { format!("{} and {}",rest.join(", "),last) };
+            res.push_str(&format!(" and {} which were skipped",list));
+        }
+        res
+    }
+}
+
+/// Read a file of existing codes. This is one code per line, a file written by `--format` or `--combined`,
+/// or if `column` is given and the file name ends in `.csv` (or is `-` for standard input), that column of a CSV file.
+/// White space (including a carriage return from a file with Windows line endings) is removed from around each code, and blank lines are skipped,
+/// as are the lines that `skip` says are not codes.
+pub fn read_existing(path:&Path,column:Option<&Column>,skip:&SkipLines) -> Result<ExistingCodes,Error> {
+    let error = |e:String| Error::invalid_input(path,None,e);
+    // comments are replaced by blank lines, so the other lines keep their line numbers.
+    let mut lines = read_codes(path)?;
+    let mut comments = 0;
+    if let Some(comment) = &skip.comment {
+        for line in lines.iter_mut().filter(|l|l.trim_start().starts_with(comment.as_str())) { line.clear(); comments+=1; }
+    }
+    let counted = |mut existing:ExistingCodes,header:bool| { existing.blank-=comments.min(existing.blank); existing.comments=comments; existing.header=header; existing };
+    let is_csv = is_stdin(path) || path.extension().map(|e|e.eq_ignore_ascii_case("csv")).unwrap_or(false);
+    if let (Some(column),true) = (column,is_csv) { return Ok(counted(read_existing_csv(path,&lines.join("\n"),column)?,false)); }
+    let first = lines.iter().position(|l|!l.trim().is_empty());
+    if first.map(|i|lines[i].trim_start().starts_with('{')).unwrap_or(false) {
+        let codes = parse_json_codes(&lines.join("\n")).map_err(error)?;
+        return Ok(counted(ExistingCodes::without_blanks(codes),false));
+    }
+    if first.and_then(|i|csv_code_column(&lines[i])).is_some() { return Ok(counted(read_existing_csv(path,&lines.join("\n"),&Column::Name("code".to_string()))?,false)); }
+    let header = skip.header && first.is_some();
+    let codes = lines.into_iter().enumerate().filter(|&(i,_)|!(header && Some(i)==first)).map(|(i,l)|(i+1,l)).collect();
+    Ok(counted(ExistingCodes::without_blanks(codes),header))
+}
+
+/// Read one column of the `text` of a CSV file of existing codes, warning about any rows where that column is empty.
+/// Blank lines are skipped without being counted.
+fn read_existing_csv(path:&Path,text:&str,column:&Column) -> Result<ExistingCodes,Error> {
+    let res = read_csv_column(text,column).map_err(|e|Error::invalid_input(path,None,e))?;
+    if res.empty>0 { alert!("Warning : {} has {} rows with nothing in column {}, which were skipped",describe_source(path),res.empty,column); }
+    Ok(ExistingCodes{codes:res.codes,blank:0,comments:0,header:false})
+}
+
+/// Read the PINs from a file written with `--with-pin`, which are in a `pin` column or field. Other files have none.
+fn read_pins(path:&Path) -> Result<Vec<String>,Error> {
+    let text = read_text(path)?;
+    if text.trim_start().starts_with('{') { return Ok(parse_json_pins(&text)); }
+    if !text.lines().next().unwrap_or("").split(',').any(|f|f.trim()=="pin") { return Ok(vec![]); }
+    let res = read_csv_column(&text,&Column::Name("pin".to_string())).map_err(|e|Error::invalid_input(path,None,e))?;
+    Ok(res.codes.into_iter().map(|(_,pin)|pin).collect())
+}
+
+/// The number of invalid or duplicate codes in a file reported individually, before just giving how many more there are.
+const MAX_REPORTED : usize = 20;
+
+/// Read a file of existing codes, checking each is valid (see [Validity::invalid_reason]). Invalid ones are reported, then either
+/// dropped if `skip_invalid` is true or cause an error.
+pub fn read_valid_existing(path:&Path,column:Option<&Column>,skip:&SkipLines,validity:&Validity,skip_invalid:bool) -> Result<ExistingCodes,Error> {
+    let existing = read_existing(path,column,skip)?;
+    let mut valid = Vec::with_capacity(existing.codes.len());
+    let mut num_invalid = 0;
+    for (line,code) in existing.codes {
+        let code : String = code.chars().filter(|c|!validity.separators.contains(c)).collect();
+        match validity.invalid_reason(&code) {
+            None => valid.push((line,code)),
+            Some(reason) => {
+                num_invalid+=1;
+                if num_invalid<=MAX_REPORTED { alert!("{} : file {} line {} : {:?} {}",if skip_invalid { "Skipping invalid code" } else { "Invalid code" },path.to_string_lossy(),line,code,reason); }
+            }
+        }
+    }
+    if num_invalid>MAX_REPORTED { alert!("... and {} more invalid codes in file {}",num_invalid-MAX_REPORTED,path.to_string_lossy()); }
+    if num_invalid>0 && !skip_invalid {
+        return Err(Error::invalid_input(path,None,format!("contains {} invalid codes. Fix them, or use --skip-invalid to ignore them.",num_invalid)));
+    }
+    Ok(ExistingCodes{codes:valid,..existing})
+}
+
+/// Report codes in `existing` (read from `files[file_index]`) that were in an earlier file or earlier in the same one,
+/// then either allow them or, if `strict`, cause an error. `seen` is where each canonical code was first seen, as (file index, line).
+fn check_duplicates(files:&[PathBuf],file_index:usize,existing:&ExistingCodes,alphabet:&Alphabet,seen:&mut HashMap<String,(usize,usize)>,strict:bool) -> Result<(),Error> {
+    let path = &files[file_index];
+    let mut num_duplicates = 0;
+    for (line,code) in &existing.codes {
+        let canonical = alphabet.normalise(code).into_owned();
+        if let Some(&(first_file,first_line)) = seen.get(&canonical) {
+            num_duplicates+=1;
+            if num_duplicates<=MAX_REPORTED { alert!("{} : file {} line {} : {:?} is already in file {} line {}",if strict { "Duplicate code" } else { "Ignoring duplicate code" },path.to_string_lossy(),line,code,files[first_file].to_string_lossy(),first_line); }
+        } else { seen.insert(canonical,(file_index,*line)); }
+    }
+    if num_duplicates>MAX_REPORTED { alert!("... and {} more duplicate codes in file {}",num_duplicates-MAX_REPORTED,path.to_string_lossy()); }
+    if num_duplicates>0 && strict {
+        return Err(Error::invalid_input(path,None,format!("contains {} codes that are already in --existing files. Remove them, or leave out --strict-existing to ignore them.",num_duplicates)));
+    }
+    Ok(())
+}
+
+/// Warn about existing codes that new codes would not be allowed to be, because an `--exclude-regex` pattern matches them.
+/// They are still loaded, so new codes are far enough from them.
+fn warn_excluded(path:&Path,existing:&ExistingCodes,patterns:&[Regex]) {
+    if patterns.is_empty() { return; }
+    let mut num_excluded = 0;
+    for (line,code) in &existing.codes {
+        if let Some(pattern) = patterns.iter().find(|p|p.is_match(code)) {
+            num_excluded+=1;
+            if num_excluded<=MAX_REPORTED { alert!("Warning, existing code matches --exclude-regex {} : file {} line {} : {:?}",pattern,path.to_string_lossy(),line,code); }
+        }
+    }
+    if num_excluded>MAX_REPORTED { alert!("... and {} more codes in file {} matching --exclude-regex",num_excluded-MAX_REPORTED,path.to_string_lossy()); }
+}
+
+/// The manifests written by default (named `manifest_*.json`) in `dir`, with the paths they are read from, oldest first.
+/// Any that can't be read are left out, as they are only used for warnings.
+fn manifests_in(dir:&Path) -> Vec<(PathBuf,Manifest)> {
+    let pattern = Path::new(&glob::Pattern::escape(&dir.to_string_lossy())).join("manifest_*.json").to_string_lossy().to_string();
+    let mut paths : Vec<PathBuf> = glob::glob(&pattern).map(|paths|paths.flatten().collect()).unwrap_or_default();
+    paths.sort();
+    paths.into_iter().filter_map(|path|Manifest::read(&path).ok().map(|manifest|(path,manifest))).collect()
+}
+
+/// Warn if the latest manifest in the same directory as the existing codes file `path` that lists it says it was made with a
+/// different alphabet to `alphabet`, such as without `--unambiguous`. `manifests` holds the manifests already read for each directory.
+fn warn_different_alphabet(path:&Path,alphabet:&Alphabet,manifests:&mut HashMap<PathBuf,Vec<(PathBuf,Manifest)>>) {
+    if is_stdin(path) { return; }
+    let dir = path.parent().unwrap_or(Path::new("")).to_path_buf();
+    let same_file = |other:&Path| other==path || matches!((other.canonicalize(),path.canonicalize()),(Ok(a),Ok(b)) if a==b);
+    let found = manifests.entry(dir.clone()).or_insert_with(||manifests_in(&dir));
+    let listing = found.iter().rev().find(|(_,manifest)| {
+        let mut listed = manifest.prefixes.iter().flat_map(|p|p.file.iter().chain(&p.spares_file).chain(p.batches.iter().map(|(file,_)|file))).chain(&manifest.combined);
+        listed.any(|file|same_file(&dir.join(file)))
+    });
+    if let Some((manifest_path,manifest)) = listing {
+        if !manifest.alphabet.is_empty() && manifest.alphabet!=alphabet.to_string() {
+            alert!("Warning : {} was made with alphabet {}, according to manifest {}, but this run uses alphabet {}. Check --alphabet, --charset and --unambiguous are the same as when it was made.",path.to_string_lossy(),manifest.alphabet,manifest_path.to_string_lossy(),alphabet);
+        }
+    }
+}
+
+/// Report pairs of the codes loaded from `--existing` files (which should be all of `generator`'s used codes) that are closer
+/// than `min_hamming_distance` apart (in units of the generator's metric), then either allow them or, if `strict`, cause an error. `seen` is as for [check_duplicates].
+fn check_existing_distances(files:&[PathBuf],generator:&GenerateCodes,alphabet:&Alphabet,seen:&HashMap<String,(usize,usize)>,min_hamming_distance:usize,prefixes:&[WantedPrefix],strict:bool) -> Result<(),Error> {
+    let prefixes : Vec<String> = prefixes.iter().map(|p|p.prefix.clone()).collect();
+    let metric = generator.metric();
+    let problems = generator.used_problems(min_hamming_distance,&prefixes);
+    if problems.is_empty() { return Ok(()); }
+    let codes = generator.used();
+    let source = |index:usize| seen[&alphabet.normalise(&codes[index]).into_owned()];
+    let describe = |index:usize| { let (file,line) = source(index); format!("{} (file {} line {})",codes[index],files[file].to_string_lossy(),line) };
+    for problem in problems.iter().take(MAX_REPORTED) { alert!("{} : {}",if strict { "Existing codes too close" } else { "Warning, existing codes too close" },describe_problem(problem,metric,describe)); }
+    if problems.len()>MAX_REPORTED { alert!("... and {} more pairs of existing codes that are too close",problems.len()-MAX_REPORTED); }
+    if strict {
+        let index = match problems[0] { Problem::TooClose{second,..} | Problem::TooCloseReversed{second,..} | Problem::Duplicate{second,..} => second, Problem::InconsistentLength{index,..} | Problem::InvalidCheckDigit{index} | Problem::WrongDigitSum{index,..} => index };
+        let (file,line) = source(index);
+        return Err(Error::invalid_input(&files[file],Some(line),format!("{:?} is in one of {} pairs of --existing codes closer than distance {}. Leave out --strict-existing to allow them.",codes[index],problems.len(),metric.show_distance(min_hamming_distance))));
+    }
+    alert!("The --existing codes include {} pairs closer than distance {}. New codes will still be at least that far from all of them.",problems.len(),metric.show_distance(min_hamming_distance));
+    Ok(())
+}
+
+/// What existing codes need to be like to be compared against properly.
+pub struct Validity {
+    pub alphabet : Alphabet,
+    pub digits : usize,
+    pub check_digit : Option<CheckDigit>,
+    /// If present, the lengths of the codes being made, which existing codes must have one of.
+    pub lengths : Option<Vec<usize>>,
+    /// Characters removed from existing codes before they are checked, as they may be written with `--group`.
+    pub separators : Vec<char>,
+    /// The (canonical) layouts of the codes being made with fixed characters after random ones. An existing code with one
+    /// of these layouts has its random characters checked, rather than the last ones.
+    pub layouts : Vec<Vec<Option<char>>>,
+}
+
+impl Validity {
+    /// Why an existing code is not one that this run could be compared against properly, or None if it is fine.
+    ///
+    /// It must be at least as long as the random part and any check digits, have no white space, and the random part
+    /// (the characters before any check digits, after whatever prefix there is, or the random characters of a layout it has) must be from the alphabet.
+    /// If `lengths` is given, it must also be one of those lengths.
+    pub fn invalid_reason(&self,code:&str) -> Option<String> {
+        let check_len = self.check_digit.map(|c|c.num_characters()).unwrap_or(0);
+        if code.is_empty() { return Some("is empty".to_string()); }
+        if code.chars().any(|c|c.is_whitespace()||c.is_control()) { return Some("contains white space".to_string()); }
+        let normalised = self.alphabet.normalise(code);
+        let length = normalised.chars().count();
+        if length<self.digits+check_len { return Some(format!("is {} characters long, shorter than the {} random characters{}",length,self.digits,if check_len>0 { " and check digits" } else { "" })); }
+        if let Some(lengths) = &self.lengths {
+            if !lengths.contains(&length) {
+                let expected : Vec<String> = lengths.iter().map(|l|l.to_string()).collect();
+                return Some(format!("is {} characters long, but the codes being made are {} long. Use --length-policy to compare codes of different lengths",length,expected.join(" or ")));
+            }
+        }
+        let without_check : String = normalised.chars().take(length-check_len).collect();
+        let mut unexpected : Vec<char> = match self.layouts.iter().find(|layout|has_fixed_characters(&without_check,layout)) {
+            Some(layout) => without_check.chars().zip(layout.iter()).filter(|(c,l)|l.is_none() && !self.alphabet.contains(*c)).map(|(c,_)|c).collect(),
+            None => without_check.chars().rev().take(self.digits).filter(|&c|!self.alphabet.contains(c)).collect(),
+        };
+        if !unexpected.is_empty() {
+            unexpected.sort_unstable();
+            unexpected.dedup();
+            return Some(format!("contains characters {} not in the alphabet {}. It may have been made with a different alphabet",unexpected.iter().collect::<String>(),self.alphabet));
+        }
+        None
+    }
+}
+
+/// Read the `--existing` files, along with those found by `--existing-dir` and `--existing-auto`, into the generator, and their PINs
+/// into the PIN generator. Returns each file read, and how many codes were used from it.
+pub fn read_existing_files(args:&Parameters,setup:&mut Setup,outputs:&OutputPaths) -> Result<Vec<(String,usize)>,Error> {
+    let skip_lines = SkipLines{comment:Some(args.existing_comment.clone()).filter(|c|!c.is_empty()),header:args.existing_skip_header};
+    let mut existing_read : Vec<(String,usize)> = vec![];
+    let mut seen : HashMap<String,(usize,usize)> = HashMap::new();
+    let mut manifests : HashMap<PathBuf,Vec<(PathBuf,Manifest)>> = HashMap::new();
+    let mut existing_files = expand_globs("--existing",&args.existing)?;
+    for dir in &args.existing_dir { existing_files.extend(files_in_dir(dir,&args.existing_filter)?); }
+    if args.existing_auto {
+        // this run's own output files are read (if appending) or replaced, so aren't existing codes.
+        let written = outputs.written(args);
+        let same_file = |a:&PathBuf,b:&PathBuf| a==b || matches!((a.canonicalize(),b.canonicalize()),(Ok(a),Ok(b)) if a==b);
+        let auto : Vec<PathBuf> = earlier_output_files(&outputs.dir,args.output_template.as_deref(),args.format,&written)?.into_iter().filter(|p|!existing_files.iter().any(|e|same_file(e,p))).collect();
+        let dir = if outputs.dir.as_os_str().is_empty() { ".".into() } else { outputs.dir.to_string_lossy() };
+        if auto.is_empty() { report!("--existing-auto : no earlier output files in {}",dir); }
+        else { report!("--existing-auto : reading {} earlier output files in {} : {}",auto.len(),dir,auto.iter().map(|p|p.to_string_lossy()).collect::<Vec<_>>().join(", ")); }
+        existing_files.extend(auto);
+    }
+    if existing_files.iter().filter(|p|is_stdin(p)).count()>1 { return Err(Error::Arguments("Standard input (-) can only be given to --existing once".to_string())); }
+    if let Some((_,file)) = args.existing_prefix.iter().find(|(_,file)|!existing_files.contains(file)) {
+        return Err(Error::Arguments(format!("--existing-prefix gives a prefix for {}, which is not an --existing file",file.to_string_lossy())));
+    }
+    for (file_index,path) in existing_files.iter().enumerate() {
+        let same_file = |other:&PathBuf| other==path || matches!((other.canonicalize(),path.canonicalize()),(Ok(a),Ok(b)) if a==b);
+        if existing_files[..file_index].iter().any(same_file) {
+            if args.strict_existing { return Err(Error::Arguments(format!("File {} is given to --existing more than once",path.to_string_lossy()))); }
+            alert!("Ignoring file {}, as it is given to --existing more than once",path.to_string_lossy());
+            continue;
+        }
+        let existing = read_valid_existing(path,args.existing_column.as_ref(),&skip_lines,&setup.validity,args.skip_invalid)?;
+        check_duplicates(&existing_files,file_index,&existing,&setup.alphabet,&mut seen,args.strict_existing)?;
+        warn_excluded(path,&existing,&args.exclude_regex);
+        warn_different_alphabet(path,&setup.alphabet,&mut manifests);
+        let prefix = args.existing_prefix.iter().rev().find(|(_,file)|file==path).map(|(prefix,_)|prefix.clone()).or_else(||prefix_from_file_name(path));
+        let codes = existing.codes.iter().map(|(_,c)|c.as_str());
+        let count = match &prefix { Some(prefix) => setup.generator.load_existing_with_prefix(codes,prefix), None => setup.generator.load_existing(codes) };
+        report!("Read {} {}",describe_source(path),existing.describe(count));
+        if let Some(pins) = &mut setup.pins { load_pins(pins,path)?; }
+        existing_read.push((path.to_string_lossy().to_string(),count));
+    }
+    if !args.existing_dir.is_empty() { report!("Read {} codes from {} files",existing_read.iter().map(|(_,count)|count).sum::<usize>(),existing_read.len()); }
+    check_existing_distances(&existing_files,&setup.generator,&setup.alphabet,&seen,setup.min_hamming_distance,&args.prefixes,args.strict_existing)?;
+    Ok(existing_read)
+}
+
+/// Add the PINs in a file to those that `--with-pin` won't reuse.
+pub fn load_pins(pins:&mut GenerateCodes,path:&Path) -> Result<(),Error> {
+    let found = read_pins(path)?;
+    if !found.is_empty() {
+        let count = pins.load_existing(found.iter().map(|p|p.as_str()));
+        report!("Read {} PINs from file {}",count,path.to_string_lossy());
+    }
+    Ok(())
+}
+
+/// How to refer to a path in a manifest in the directory `dir`: relative to that directory if the path is inside it, otherwise absolute.
+pub fn relative_to(path:&Path,dir:&Path) -> String {
+    match path.strip_prefix(dir) {
+        Ok(relative) if !dir.as_os_str().is_empty() => relative.to_string_lossy().to_string(),
+        _ if dir.as_os_str().is_empty() && path.is_relative() => path.to_string_lossy().to_string(),
+        _ => std::path::absolute(path).unwrap_or_else(|_|path.to_path_buf()).to_string_lossy().to_string(),
+    }
+}
+
+/// Read a file of forbidden substrings, one per line, ignoring blank lines and comments starting with '#'. Returns them in lower case.
+pub fn read_blocklist(path:&Path) -> Result<Vec<String>,Error> {
+    let lines = read_codes(path)?;
+    let patterns : Vec<String> = lines.iter().map(|l|l.trim()).filter(|l|!(l.is_empty()||l.starts_with('#'))).map(|l|l.to_lowercase()).collect();
+    if patterns.is_empty() { return Err(Error::invalid_input(path,None,"is a blocklist but contains no patterns")); }
+    Ok(patterns)
+}
+
+/// Parse a range of numbers given as START..END, for `--range`.
+pub fn parse_range(s:&str) -> Result<Range<u64>,String> {
+    let (start,end) = s.split_once("..").ok_or_else(||format!("Invalid range {:?} : expected START..END, like 400000..700000",s))?;
+    let number = |n:&str| n.trim().parse::<u64>().map_err(|e|format!("Invalid range {:?} : {:?} is not a number : {}",s,n,e));
+    let range = number(start)?..number(end)?;
+    if range.is_empty() { return Err(format!("Invalid range {:?} : it has no numbers, as the end must be after the start",s)); }
+    Ok(range)
+}
+
+/// Parse the prefix of an existing file given as PREFIX=FILE, for `--existing-prefix`.
+pub fn parse_existing_prefix(s:&str) -> Result<(String,PathBuf),String> {
+    let (prefix,file) = s.split_once('=').ok_or_else(||format!("Invalid existing prefix {:?} : expected PREFIX=FILE, like S0=last_year.txt",s))?;
+    if file.is_empty() { return Err(format!("Invalid existing prefix {:?} : there is no file",s)); }
+    Ok((prefix.to_string(),PathBuf::from(file)))
+}
+
+/// The prefix of the codes in a file named like an output file, such as `prefix_S0.txt`, `prefix_S0_suffix_X.csv` or `prefix_S0_spares.txt`.
+fn prefix_from_file_name(path:&Path) -> Option<String> {
+    let stem = path.file_stem()?.to_str()?;
+    let stem = stem.strip_suffix(SPARES_SUFFIX).unwrap_or(stem);
+    let prefix = stem.strip_prefix("prefix_")?;
+    Some(prefix.split_once("_suffix_").map(|(prefix,_)|prefix).unwrap_or(prefix).to_string())
+}
+
+/// Check that `--range` can be used for random parts of `digits` digits.
+pub fn check_range(range:&Range<u64>,alphabet:&Alphabet,digits:usize,no_leading_zero:bool) -> Result<(),Error> {
+    let describe = format!("--range {}..{}",range.start,range.end);
+    if !alphabet.is_decimal() { return Err(Error::Arguments(format!("{} can only be used with the default alphabet of digits, not {}",describe,alphabet))); }
+    let limit = 10u64.checked_pow(digits as u32);
+    if limit.map(|limit|range.end>limit).unwrap_or(false) { return Err(Error::Arguments(format!("{} doesn't fit in {} digits, so the end can be at most {}",describe,digits,limit.unwrap_or_default()))); }
+    if no_leading_zero && digits>0 && range.end<=10u64.pow(digits as u32-1) { return Err(Error::Arguments(format!("{} has no numbers with {} digits that don't start with 0, as --no-leading-zero needs",describe,digits))); }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// An existing code of a length the codes being made don't have is rejected, pointing at `--length-policy`, unless lengths aren't checked.
+    #[test]
+    fn existing_code_of_the_wrong_length_is_invalid() {
+        let mut validity = Validity{alphabet:Alphabet::decimal(),digits:4,check_digit:None,lengths:Some(vec![5]),separators:vec![],layouts:vec![]};
+        assert_eq!(validity.invalid_reason("S1234"),None);
+        let reason = validity.invalid_reason("S12345").unwrap();
+        assert!(reason.starts_with("is 6 characters long, but the codes being made are 5 long."),"{}",reason);
+        assert!(reason.contains("--length-policy"),"{}",reason);
+        validity.lengths=Some(vec![5,7]);
+        assert!(validity.invalid_reason("S12345").unwrap().contains("are 5 or 7 long"));
+        validity.lengths=None;
+        assert_eq!(validity.invalid_reason("S12345"),None);
+    }
+
+    /// Windows line endings, surrounding white space and blank lines don't end up in the codes, and each code keeps the line it was on.
+    #[test]
+    fn read_existing_trims_lines() {
+        let path = std::env::temp_dir().join(format!("make_exam_numbers_crlf_{}.txt",std::process::id()));
+        std::fs::write(&path,"S01\r\n  S02 \r\n\r\n").unwrap();
+        let existing = read_existing(&path,None,&SkipLines::default());
+        std::fs::remove_file(&path).unwrap();
+        let existing = existing.unwrap();
+        assert_eq!(existing.codes,vec![(1,"S01".to_string()),(2,"S02".to_string())]);
+        assert_eq!(existing.blank,1);
+    }
+}
@@ -0,0 +1,31 @@
+// This program is Copyright 2022 Andrew Conway and licensed under the GPL:
+//
+// This program is free software: you can redistribute it and/or modify it under the terms of the GNU General Public License as published by the Free Software Foundation, either version 3 of the License, or (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful, but WITHOUT ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License along with this program. If not, see <https://www.gnu.org/licenses/>.
+
+//! Handling Ctrl-C, so codes found so far are kept.
+//!
+//! The first Ctrl-C sets a flag, which makes the generator stop so the output files and manifest can be finished.
+//! A second one quits immediately.
+
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use crate::cli::error::Error;
+use crate::cli::report::alert;
+
+/// The exit status after being interrupted, the usual one for a program killed by SIGINT.
+pub const EXIT_INTERRUPTED : i32 = 130;
+
+/// Install the Ctrl-C handler, returning the flag it sets.
+pub fn install() -> Result<Arc<AtomicBool>,Error> {
+    let interrupted = Arc::new(AtomicBool::new(false));
+    let flag = interrupted.clone();
+    ctrlc::set_handler(move||{
+        if flag.swap(true,Ordering::Relaxed) { std::process::exit(EXIT_INTERRUPTED); }
+        alert!("\nInterrupted. Saving the codes found so far. Press Ctrl-C again to quit immediately.");
+    }).map_err(|e|Error::Io{context:"Could not handle Ctrl-C".to_string(),source:std::io::Error::other(e)})?;
+    Ok(interrupted)
+}
@@ -0,0 +1,97 @@
+// This program is Copyright 2022 Andrew Conway and licensed under the GPL:
+//
+// This program is free software: you can redistribute it and/or modify it under the terms of the GNU General Public License as published by the Free Software Foundation, either version 3 of the License, or (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful, but WITHOUT ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License along with this program. If not, see <https://www.gnu.org/licenses/>.
+
+
+//! How the prefixes, suffixes and masks lay out the fixed and random characters of the codes.
+
+use make_exam_numbers::WantedPrefix;
+use make_exam_numbers::alphabet::Alphabet;
+use crate::cli::error::Error;
+
+/// Check the prefixes (and suffixes and masks) make sense: they contain no white space or control characters, none is given twice,
+/// the fixed characters of masks are in the alphabet, and no code for one could be the same string as a code for another, as happens
+/// if one prefix starts with another and codes with both have the same length. If `min_distance` is given, prefixes of the same length
+/// (with suffixes of the same length, or masks with fixed characters in the same places) must also be at least that far apart,
+/// counting the suffixes too. They are compared as the alphabet compares codes, so ignoring case if it does.
+pub fn check_prefixes(prefixes:&[WantedPrefix],alphabet:&Alphabet,digits:usize,min_distance:Option<usize>) -> Result<(),Error> {
+    if let Some(p) = prefixes.iter().find(|p|p.name().chars().any(|c|c.is_whitespace()||c.is_control())) {
+        return Err(Error::Arguments(format!("Prefix {:?} contains white space or control characters",p.name())));
+    }
+    let layouts : Vec<Vec<Option<char>>> = prefixes.iter().map(|p|canonical_layout(p,alphabet,digits)).collect();
+    for (p,layout) in prefixes.iter().zip(&layouts) {
+        if let Some(c) = layout.iter().flatten().find(|&&c|p.mask.is_some() && !alphabet.contains(c)) {
+            return Err(Error::Arguments(format!("Mask {:?} contains {:?}, which is not in the alphabet {}",p.name(),c,alphabet)));
+        }
+    }
+    // the runs of fixed characters between the random ones, which prefixes must have the same lengths of to be compared.
+    let fixed_runs = |layout:&[Option<char>]| -> Vec<Vec<char>> {
+        let mut runs = vec![vec![]];
+        for (i,c) in layout.iter().enumerate() {
+            match c {
+                Some(c) => runs.last_mut().expect("Starts with a run").push(*c),
+                None if layout.get(i+1).is_some_and(|next|next.is_some()) => runs.push(vec![]),
+                None => {}
+            }
+        }
+        runs
+    };
+    let runs : Vec<Vec<Vec<char>>> = layouts.iter().map(|l|fixed_runs(l)).collect();
+    // a random character can be any character in the alphabet.
+    let could_match = |a:&Option<char>,b:&Option<char>| match (a,b) { (Some(a),Some(b)) => a==b, (Some(c),None) | (None,Some(c)) => alphabet.contains(*c), (None,None) => true };
+    for (i,p) in prefixes.iter().enumerate() {
+        for (j,q) in prefixes.iter().enumerate().take(i) {
+            if layouts[i]==layouts[j] {
+                return Err(Error::Arguments(format!("Prefix {:?} is given more than once{}",p.name(),if p.name()!=q.name() { format!(" (as {:?})",q.name()) } else { String::new() })));
+            }
+            if layouts[i].len()==layouts[j].len() && layouts[i].iter().zip(&layouts[j]).all(|(a,b)|could_match(a,b)) {
+                return Err(Error::Arguments(format!("Codes for prefixes {:?} and {:?} are the same length, and their prefixes don't rule out a code for one being the same as a code for the other. Give them different numbers of digits or change one.",q.name(),p.name())));
+            }
+            if let Some(min) = min_distance {
+                let (a,b) = (&runs[j],&runs[i]);
+                if a.len()==b.len() && a.iter().zip(b).all(|(x,y)|x.len()==y.len()) {
+                    let distance = a.iter().zip(b).flat_map(|(x,y)|x.iter().zip(y)).filter(|(x,y)|x!=y).count();
+                    if distance<min { return Err(Error::Arguments(format!("Prefixes {:?} and {:?} differ in {} characters, fewer than the {} required by --check-prefix-distance",q.name(),p.name(),distance,min))); }
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Each character of the codes wanted, in canonical form: `Some` for a fixed character, and None for a random one.
+pub fn canonical_layout(p:&WantedPrefix,alphabet:&Alphabet,digits:usize) -> Vec<Option<char>> {
+    let mut res = vec![];
+    for c in p.layout(digits) {
+        match c {
+            Some(c) => res.extend(alphabet.normalise(c.encode_utf8(&mut [0;4])).chars().map(Some)),
+            None => res.push(None),
+        }
+    }
+    res
+}
+
+/// Whether the random characters of a layout are all after its fixed ones, as for a prefix without a suffix.
+pub fn random_part_last(layout:&[Option<char>]) -> bool {
+    layout.iter().skip_while(|c|c.is_some()).all(|c|c.is_none())
+}
+
+/// Whether a (canonical) code, without any check digits, is as long as a layout and has its fixed characters.
+pub fn has_fixed_characters(code:&str,layout:&[Option<char>]) -> bool {
+    code.chars().count()==layout.len() && code.chars().zip(layout).all(|(c,l)|l.is_none_or(|l|l==c))
+}
+
+/// How to refer to the codes with a layout in messages, like "Prefix S0", "Prefix S0*X" or "Mask B#2###7#".
+pub fn describe_layout(layout:&[Option<char>]) -> String {
+    let show = |part:&[Option<char>]| part.iter().map(|c|c.unwrap_or('#')).collect::<String>();
+    let start = layout.iter().take_while(|c|c.is_some()).count();
+    let end = layout.iter().rposition(|c|c.is_none()).map(|last|last+1).unwrap_or(start);
+    if layout[start..end].iter().all(|c|c.is_none()) {
+        let suffix = show(&layout[end..]);
+        format!("Prefix {}{}",show(&layout[..start]),if suffix.is_empty() { String::new() } else { format!("*{}",suffix) })
+    } else { format!("Mask {}",show(layout)) }
+}
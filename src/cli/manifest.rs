@@ -0,0 +1,197 @@
+// This program is Copyright 2022 Andrew Conway and licensed under the GPL:
+//
+// This program is free software: you can redistribute it and/or modify it under the terms of the GNU General Public License as published by the Free Software Foundation, either version 3 of the License, or (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful, but WITHOUT ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License along with this program. If not, see <https://www.gnu.org/licenses/>.
+
+//! A record of the parameters of a run, written alongside the output files so the run can be understood and reproduced later.
+
+use std::path::{Path, PathBuf};
+use serde_json::{json, Value};
+use crate::cli::error::Error;
+use crate::cli::output::Grouping;
+
+/// What was done for one prefix.
+pub struct ManifestPrefix {
+    pub prefix : String,
+    /// What each code ends with, if anything, before any check digits.
+    pub suffix : String,
+    /// The mask the codes were made from, if they were, such as `B#2###7#`.
+    pub mask : Option<String>,
+    pub wanted : usize,
+    pub found : usize,
+    /// The number of random digits in each code.
+    pub digits : usize,
+    /// The minimum distance the codes were made with, as written for the metric, which is the usual one unless the prefix gave its own.
+    pub min_distance : String,
+    /// The file the codes were written to, if any.
+    pub file : Option<String>,
+    /// With `--batch-size`, the files the codes were written to instead of `file`, and how many codes are in each.
+    pub batches : Vec<(String,usize)>,
+    /// The number of spare codes wanted, and found, after the others.
+    pub spares : usize,
+    pub spares_found : usize,
+    /// The file the spare codes were written to, if there are any.
+    pub spares_file : Option<String>,
+}
+
+/// The parameters and results of a run.
+pub struct Manifest {
+    /// The seed, as accepted by `--seed`. If no seed was given, the one chosen from entropy.
+    pub seed : String,
+    pub digits : usize,
+    /// The minimum distance as written for the metric, like 3 or 2.5.
+    pub min_distance : String,
+    /// With `--min-hamming-across`, the minimum distance between codes with different prefixes, if different from `min_distance`.
+    pub min_distance_across : Option<String>,
+    pub metric : String,
+    /// For `--metric weighted-hamming`, the `--weights` used: a built in name, or a file relative to the manifest.
+    pub weights : Option<String>,
+    pub scope : String,
+    /// How codes of different lengths were compared, as accepted by `--length-policy`.
+    pub length_policy : String,
+    pub alphabet : String,
+    pub check_digit : Option<String>,
+    /// With `--digit-sum-mod`, the condition on the digit sum of each code, as `M:R`.
+    pub digit_sum : Option<String>,
+    /// With `--range`, the numbers the random digits were drawn from, as `START..END`.
+    pub range : Option<String>,
+    /// With `--include-reversals`, codes were also kept far enough from each other read backwards.
+    pub include_reversals : bool,
+    /// With `--with-pin`, the number of digits in each PIN and the minimum distance between them.
+    pub pin : Option<(usize,usize)>,
+    /// With `--group`, how the codes in text files are shown.
+    pub grouping : Option<Grouping>,
+    pub prefixes : Vec<ManifestPrefix>,
+    /// The `--existing` files read, and how many codes were in each.
+    pub existing : Vec<(String,usize)>,
+    pub combined : Option<String>,
+    /// The full command line.
+    pub command_line : Vec<String>,
+    /// When the run started, as an ISO 8601 like UTC timestamp.
+    pub timestamp : String,
+    /// Whether all the codes wanted were found.
+    pub complete : bool,
+    /// If not, why the run stopped, such as running out of time.
+    pub stopped : Option<String>,
+}
+
+impl Manifest {
+    pub fn to_json(&self) -> Value {
+        let mut value = json!({
+            "program" : env!("CARGO_PKG_NAME"),
+            "version" : env!("CARGO_PKG_VERSION"),
+            "timestamp" : self.timestamp,
+            "command_line" : self.command_line,
+            "seed" : self.seed,
+            "digits" : self.digits,
+            "min_hamming_distance" : serde_json::from_str::<Value>(&self.min_distance).unwrap_or_else(|_|json!(self.min_distance)),
+            "metric" : self.metric,
+            "scope" : self.scope,
+            "length_policy" : self.length_policy,
+            "alphabet" : self.alphabet,
+            "check_digit" : self.check_digit,
+            "prefixes" : self.prefixes.iter().map(|p|{
+                let mut value = json!({"prefix":p.prefix,"wanted":p.wanted,"found":p.found,"digits":p.digits,"file":p.file,
+                    "min_hamming_distance":serde_json::from_str::<Value>(&p.min_distance).unwrap_or_else(|_|json!(p.min_distance))});
+                if !p.suffix.is_empty() { value["suffix"] = json!(p.suffix); }
+                if let Some(mask) = &p.mask { value["mask"] = json!(mask); }
+                if !p.batches.is_empty() { value["batches"] = p.batches.iter().map(|(file,codes)|json!({"file":file,"codes":codes})).collect(); }
+                if p.spares>0 { value["spares"] = json!({"wanted":p.spares,"found":p.spares_found,"file":p.spares_file}); }
+                value
+            }).collect::<Vec<_>>(),
+            "existing" : self.existing.iter().map(|(file,entries)|json!({"file":file,"entries":entries})).collect::<Vec<_>>(),
+            "combined" : self.combined,
+            "complete" : self.complete,
+        });
+        if let Some(stopped) = &self.stopped { value["stopped"] = json!(stopped); }
+        if let Some(weights) = &self.weights { value["weights"] = json!(weights); }
+        if let Some(digit_sum) = &self.digit_sum { value["digit_sum"] = json!(digit_sum); }
+        if let Some(range) = &self.range { value["range"] = json!(range); }
+        if let Some(across) = &self.min_distance_across { value["min_hamming_across"] = serde_json::from_str::<Value>(across).unwrap_or_else(|_|json!(across)); }
+        if self.include_reversals { value["include_reversals"] = json!(true); }
+        if let Some((digits,min_distance)) = self.pin {
+            value["pin_digits"] = json!(digits);
+            value["pin_min_hamming_distance"] = json!(min_distance);
+        }
+        if let Some(grouping) = &self.grouping {
+            value["group"] = json!(grouping.size);
+            value["separator"] = json!(grouping.separator);
+        }
+        value
+    }
+
+    /// The manifest written by default for a run started at `time` : `manifest_<time>.json` in `dir`, or if a run started in the
+    /// same second already wrote that, `manifest_<time>_2.json` and so on, so an earlier run's manifest is never replaced.
+    pub fn default_path(dir:&Path,time:&str) -> PathBuf {
+        (1..).map(|n|dir.join(if n==1 { format!("manifest_{}.json",time) } else { format!("manifest_{}_{}.json",time,n) })).find(|path|!path.exists()).expect("Some number is unused")
+    }
+
+    pub fn write(&self,path:&Path) -> Result<(),Error> {
+        let text = serde_json::to_string_pretty(&self.to_json()).expect("Values made by json! can be written");
+        std::fs::write(path,text+"\n").map_err(Error::io(format!("Could not write manifest {}",path.to_string_lossy())))
+    }
+
+    /// Read the parts of a manifest needed to check the output files it lists.
+    pub fn read(path:&Path) -> Result<Manifest,Error> {
+        let error = |message:String| Error::invalid_input(path,None,format!("not a valid manifest : {}",message));
+        let text = std::fs::read_to_string(path).map_err(Error::io(format!("Could not read manifest {}",path.to_string_lossy())))?;
+        let value : Value = serde_json::from_str(&text).map_err(|e|error(e.to_string()))?;
+        let string = |name:&str| value.get(name).and_then(|v|v.as_str()).map(|s|s.to_string());
+        let number = |v:&Value,name:&str| v.get(name).and_then(|v|v.as_u64()).map(|n|n as usize).ok_or_else(||error(format!("missing {}",name)));
+        let digits = number(&value,"digits")?;
+        let distance = |v:&Value| v.get("min_hamming_distance").filter(|v|v.is_number()).map(|v|v.to_string());
+        let min_distance = distance(&value).ok_or_else(||error("missing min_hamming_distance".to_string()))?;
+        let mut prefixes = vec![];
+        for p in value.get("prefixes").and_then(|v|v.as_array()).ok_or_else(||error("missing prefixes".to_string()))? {
+            let mut batches = vec![];
+            for b in p.get("batches").and_then(|v|v.as_array()).into_iter().flatten() {
+                batches.push((b.get("file").and_then(|v|v.as_str()).unwrap_or_default().to_string(),number(b,"codes")?));
+            }
+            prefixes.push(ManifestPrefix{
+                prefix:p.get("prefix").and_then(|v|v.as_str()).unwrap_or_default().to_string(),
+                suffix:p.get("suffix").and_then(|v|v.as_str()).unwrap_or_default().to_string(),
+                mask:p.get("mask").and_then(|v|v.as_str()).map(|s|s.to_string()),
+                wanted:number(p,"wanted")?,
+                found:number(p,"found")?,
+                digits:number(p,"digits").unwrap_or(digits),
+                min_distance:distance(p).unwrap_or_else(||min_distance.clone()),
+                file:p.get("file").and_then(|v|v.as_str()).map(|s|s.to_string()),
+                batches,
+                spares:p.get("spares").map(|s|number(s,"wanted")).transpose()?.unwrap_or(0),
+                spares_found:p.get("spares").map(|s|number(s,"found")).transpose()?.unwrap_or(0),
+                spares_file:p.get("spares").and_then(|s|s.get("file")).and_then(|v|v.as_str()).map(|s|s.to_string()),
+            });
+        }
+        let mut existing = vec![];
+        for e in value.get("existing").and_then(|v|v.as_array()).into_iter().flatten() {
+            existing.push((e.get("file").and_then(|v|v.as_str()).unwrap_or_default().to_string(),number(e,"entries")?));
+        }
+        Ok(Manifest{
+            seed:string("seed").unwrap_or_default(),
+            digits,
+            min_distance,
+            min_distance_across:value.get("min_hamming_across").filter(|v|v.is_number()).map(|v|v.to_string()),
+            metric:string("metric").unwrap_or_else(||"hamming".to_string()),
+            weights:string("weights"),
+            scope:string("scope").unwrap_or_else(||"global".to_string()),
+            length_policy:string("length_policy").unwrap_or_else(||"error".to_string()),
+            alphabet:string("alphabet").unwrap_or_default(),
+            check_digit:string("check_digit"),
+            digit_sum:string("digit_sum"),
+            range:string("range"),
+            include_reversals:value.get("include_reversals").and_then(|v|v.as_bool()).unwrap_or(false),
+            pin:number(&value,"pin_digits").ok().map(|digits|(digits,number(&value,"pin_min_hamming_distance").unwrap_or(0))),
+            grouping:number(&value,"group").ok().map(|size|Grouping{size,separator:string("separator").unwrap_or_else(||" ".to_string())}),
+            prefixes,
+            existing,
+            combined:string("combined"),
+            command_line:value.get("command_line").and_then(|v|v.as_array()).into_iter().flatten().filter_map(|v|v.as_str()).map(|s|s.to_string()).collect(),
+            timestamp:string("timestamp").unwrap_or_default(),
+            complete:value.get("complete").and_then(|v|v.as_bool()).unwrap_or(false),
+            stopped:string("stopped"),
+        })
+    }
+}
@@ -0,0 +1,28 @@
+// This program is Copyright 2022 Andrew Conway and licensed under the GPL:
+//
+// This program is free software: you can redistribute it and/or modify it under the terms of the GNU General Public License as published by the Free Software Foundation, either version 3 of the License, or (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful, but WITHOUT ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License along with this program. If not, see <https://www.gnu.org/licenses/>.
+
+//! Parts of the command line program that are not part of the library, such as reporting to the user.
+
+pub mod atomic;
+pub mod barcodes;
+pub mod commands;
+pub mod config;
+pub mod error;
+pub mod events;
+pub mod generate;
+pub mod input;
+pub mod interrupt;
+pub mod layout;
+pub mod manifest;
+pub mod options;
+pub mod output;
+pub mod progress;
+pub mod report;
+pub mod setup;
+pub mod summary;
+pub mod writer;
@@ -0,0 +1,1062 @@
+// This program is Copyright 2022 Andrew Conway and licensed under the GPL:
+//
+// This program is free software: you can redistribute it and/or modify it under the terms of the GNU General Public License as published by the Free Software Foundation, either version 3 of the License, or (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful, but WITHOUT ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License along with this program. If not, see <https://www.gnu.org/licenses/>.
+
+
+//! The command line options, and reading them along with any `--config` file.
+
+use std::ops::Range;
+use std::path::PathBuf;
+use clap::{CommandFactory, FromArgMatches, Parser, Subcommand};
+use regex::Regex;
+use make_exam_numbers::{Seed, Strategy, WantedPrefix};
+use make_exam_numbers::barcode::BarcodeFormat;
+use make_exam_numbers::check_digit::CheckDigit;
+use make_exam_numbers::derive::KeyedDerivation;
+use make_exam_numbers::alphabet::Alphabet;
+use make_exam_numbers::filter::DigitSum;
+use make_exam_numbers::distance::{LengthPolicy, Metric, Scope};
+use crate::cli::error::{Error, EXIT_STATUS_HELP};
+use crate::cli::events::ProgressFormat;
+use crate::cli::input::{Column, Derivation, LengthHandling, parse_existing_prefix, parse_range, Threshold, TimeLimit};
+use crate::cli::output::{Case, Format, LookupFormat, TableFormat};
+
+
+/// Program to produce a set of randomish exam numbers such that no two exam numbers are very similar.
+///
+/// Similar means that number of characters that need to be different between any pair of exam numbers
+/// (the Hamming distance) is at least some specified number, like 3.
+///
+/// Progress, warnings and other messages are written to standard error, so standard output is left for data such as `--stdout`.
+/// Earlier versions wrote them to standard output.
+#[derive(Parser, Debug)]
+#[clap(author, about, subcommand_negates_reqs=true, args_conflicts_with_subcommands=true, after_help=EXIT_STATUS_HELP)]
+pub struct Parameters {
+    /// An optional random seed (64 bit unsigned integer). This can be used to make a reproducible list.
+    ///
+    /// This may also be 64 hexadecimal characters, which are used directly as the 256 bit key of the random number generator.
+    #[clap(long, value_parser)]
+    pub seed: Option<Seed>,
+
+    /// Use one random stream for all prefixes, so the codes for a prefix depend on the prefixes before it.
+    ///
+    /// Normally each prefix has its own stream derived from the seed and the prefix, so adding or reordering prefixes
+    /// doesn't change the candidates tried for the others. Which of those are accepted can still change, as one may be too close
+    /// to another prefix's code, unless `--scope per-prefix`. Use this to reproduce codes made by versions before that.
+    /// With `--strategy exhaustive` every prefix still tries every random part, in the same order for each.
+    #[clap(long)]
+    pub shared_seed_stream : bool,
+
+    /// Take turns between the prefixes, each time finding a code for the prefix with the smallest share of its codes found so far,
+    /// rather than finding all the codes for one prefix before starting the next.
+    ///
+    /// Later prefixes then don't get only the hardest part of the search, and a run that is stopped early has about the same
+    /// share of each prefix's codes. The codes are written once they have all been found, or the run stops.
+    #[clap(long)]
+    pub interleave : bool,
+    /// The minimum number of characters different any code is allowed to be from
+    /// any other code.
+    ///
+    /// With `--metric weighted-hamming` this may be a fraction, like 2.5. A prefix can have its own minimum distance, like "PG:300:d5".
+    #[clap(value_parser, required=true)]
+    pub min_hamming_distance : Option<Threshold>,
+    /// The number of digits (random characters) in the code, unless given for a particular prefix
+    #[clap(value_parser, required=true)]
+    pub digits : Option<usize>,
+
+    /// Existing numbers that you want to avoid
+    ///
+    /// This is typically used when you used this program to create some numbers, and then decided you want some more,
+    /// and want to avoid the old numbers. Give `-` (at most once) to read them from standard input.
+    /// A glob pattern like `'archive/prefix_*.txt'` reads every file matching it, and must match at least one.
+    #[clap(long,value_parser)]
+    pub existing : Vec<PathBuf>,
+
+    /// Directories containing files of existing codes, read as for `--existing`. Every file under the directory (including in
+    /// subdirectories) whose name matches `--existing-filter` is read. May be given more than once, and with `--existing`.
+    #[clap(long,value_parser)]
+    pub existing_dir : Vec<PathBuf>,
+
+    /// The glob pattern the names of files in an `--existing-dir` must match to be read.
+    #[clap(long,value_parser,default_value="*.txt")]
+    pub existing_filter : String,
+
+    /// Also read, as `--existing` files, the files in the output directory named like output files (`prefix_*.txt`, or as
+    /// given by `--output-template`) from earlier runs. The files this run writes are not included. Each file read is listed.
+    #[clap(long,action)]
+    pub existing_auto : bool,
+
+    /// For `--existing` files whose names end in `.csv` (or standard input), the column containing the codes.
+    ///
+    /// This is either a 0 based index, or the name of the column, in which case the first row is the header
+    /// containing the names. Rows where that column is empty are skipped with a warning. Other files are read as usual.
+    #[clap(long,value_parser)]
+    pub existing_column : Option<Column>,
+
+    /// Lines of `--existing` files starting with this (after any white space) are comments, which are skipped and counted
+    /// separately. Defaults to `#`, as written by `--write-header`. An empty value means no lines are comments.
+    #[clap(long,value_parser,default_value="#")]
+    pub existing_comment : String,
+
+    /// Skip the first line of each `--existing` file of one code per line, not counting blank lines and comments, as a header.
+    /// Files written with `--format csv`, `json` or `jsonl`, and CSV files with `--existing-column`, have their headers recognised anyway.
+    #[clap(long)]
+    pub existing_skip_header : bool,
+
+    /// The directory to write the `prefix_*.txt` files into, created (with any parents) if it doesn't exist.
+    /// Defaults to the current directory.
+    ///
+    /// This does not affect where `--existing` files are read from.
+    #[clap(long,value_parser)]
+    pub output_dir : Option<PathBuf>,
+
+    /// The name of the file to write each prefix's codes to, such as `{date}_{prefix}_codes.csv`. Defaults to `prefix_{prefix}.txt` (or `.csv` with `--format csv`),
+    /// or for codes with a suffix, `prefix_{prefix}_suffix_{suffix}.txt` (just `suffix_{suffix}.txt` if there is no prefix).
+    ///
+    /// The placeholders are `{prefix}` (`all` if the prefix is empty), `{suffix}` (empty if none), `{count}` (the number of codes wanted),
+    /// `{digits}` and `{date}` (today's UTC date as YYYY-MM-DD). It is an error if two prefixes would be written to the same file.
+    #[clap(long,value_parser)]
+    pub output_template : Option<String>,
+
+    /// Also write every code to this one CSV file, in the order generated, with columns `code` (the full code) and `prefix`.
+    ///
+    /// Such a file can be given to `--existing` in a later run. This path does not depend on `--output-dir`.
+    #[clap(long,value_parser)]
+    pub combined : Option<PathBuf>,
+
+    /// Start each file of codes (and `--combined`) with a comment line recording how the codes were made, like
+    /// `# generated 2023-05-01 by make_exam_numbers 0.1.0, prefix A, 5 random digits, seed 42, distance 3`.
+    ///
+    /// Such files can still be read back by `--existing`, `--append`, `--resume` and `verify`, which skip lines starting with `#`.
+    /// It can't be used with `--format json`, which records these in the file anyway.
+    #[clap(long)]
+    pub write_header : bool,
+
+    /// How to write each prefix's file, `text` (one code per line, the default), `csv`, `json` or `jsonl`.
+    ///
+    /// `csv` gives a header row then columns `prefix`, `code` and `sequence` (the position, starting at 1, within the prefix).
+    /// Beware that spreadsheets may treat purely numeric codes as numbers and drop leading zeros; import the code column as text.
+    /// `json` gives an object with the prefix, digits, minimum distance, seed and an array of codes.
+    /// `jsonl` gives one object per line for each code, with its prefix, code and sequence.
+    /// All of these can be given to `--existing` in a later run.
+    #[clap(long,value_parser,default_value="text")]
+    pub format : Format,
+
+    /// Write the codes to standard output instead of to a file for each prefix, using `--format`.
+    ///
+    /// With several prefixes, the codes for each prefix follow those of the previous one,
+    /// each full code including its prefix. A CSV header is only written once. `--format json` can only be used with a single prefix.
+    #[clap(long,conflicts_with_all=&["output-dir","output-template","no-prefix-files"])]
+    pub stdout : bool,
+
+    /// Don't print progress or routine messages. Warnings and errors are still printed.
+    ///
+    /// All messages go to standard error (earlier versions wrote them to standard output).
+    #[clap(short,long)]
+    pub quiet : bool,
+
+    /// Ignore, with a warning, invalid lines in `--existing` files instead of stopping with an error.
+    ///
+    /// White space around each line is always removed, and blank lines skipped.
+    /// A line is invalid if it contains white space, is shorter than the random part (plus any check digits),
+    /// or has characters not in the alphabet in its random part. Such lines usually mean the file is not what was intended,
+    /// such as a spreadsheet export with a header row.
+    #[clap(long)]
+    pub skip_invalid : bool,
+
+    /// Stop with an error if the `--existing` codes have problems among themselves, rather than just warning about them.
+    ///
+    /// The problems are codes appearing more than once, within one file or across several, which usually means the same file
+    /// was given twice or files overlap, and pairs of codes closer than the minimum distance, such as codes made with a smaller
+    /// distance in an earlier year. Without this, repeats are ignored, and new codes are still far enough from all the existing ones.
+    #[clap(long)]
+    pub strict_existing : bool,
+
+    /// Add to the output files rather than replacing them, avoiding the codes already in them.
+    ///
+    /// Any codes already in the output files (including `--combined`) are loaded as if given with `--existing`.
+    /// Missing files are created. This can't be used with `--format json`.
+    #[clap(long,conflicts_with="stdout")]
+    pub append : bool,
+
+    /// Write each output file directly, rather than to a temporary file that is renamed into place once it is complete.
+    ///
+    /// This lets codes be watched as they are found (e.g. with `tail -f`), but an interrupted run leaves incomplete files.
+    #[clap(long)]
+    pub no_atomic : bool,
+
+    /// Carry on from an interrupted run with the same parameters, only making the codes still missing from each prefix's file.
+    ///
+    /// Like `--append`, the codes already in all the output files are loaded and new codes are added to the end, but only
+    /// enough to bring each prefix up to the number requested. Every code already in a prefix's file must have that prefix
+    /// and the length the current parameters give.
+    #[clap(long,conflicts_with_all=&["stdout","append","force","sort-output","no-prefix-files"])]
+    pub resume : bool,
+
+    /// Where to write the manifest, a JSON file recording the parameters (including the seed) and output files of this run.
+    ///
+    /// Defaults to `manifest_<time>.json` in the output directory (numbered, like `manifest_<time>_2.json`, if a run in the same
+    /// second already wrote that), or no manifest with `--stdout`. If no `--seed` was given,
+    /// the manifest records the random one chosen, so the run can be reproduced.
+    #[clap(long,value_parser,conflicts_with="no-manifest")]
+    pub manifest : Option<PathBuf>,
+
+    /// Don't write a manifest.
+    #[clap(long)]
+    pub no_manifest : bool,
+
+    /// Write statistics on how hard the codes were to find (candidates tried, why they were rejected, and time taken) to this JSON file.
+    ///
+    /// The same statistics are printed at the end of each prefix and of the run, unless `--quiet`.
+    #[clap(long,value_parser)]
+    pub stats_json : Option<PathBuf>,
+
+    /// How to show progress, `human` (the default) or `json`, which writes one JSON object per line to standard error for each event, such as a code being found.
+    ///
+    /// This is for programs that wrap this one. Each object has an `event` field, which is `prefix_start`, `code_found`, `prefix_done`,
+    /// `prefix_failed` or `run_done`, and each line is flushed as soon as it is written. The events replace the routine messages, as with
+    /// `--quiet`, though warnings and errors are still printed as plain text.
+    #[clap(long,value_parser,default_value="human")]
+    pub progress_format : ProgressFormat,
+
+    /// Write the `--progress-format json` events to this file, leaving the usual messages on standard error. Implies `--progress-format json`.
+    #[clap(long,value_parser)]
+    pub progress_file : Option<PathBuf>,
+
+    /// Replace output files that already exist. Without this (or `--append`), the program stops before generating anything if any output file exists.
+    #[clap(long,conflicts_with="append")]
+    pub force : bool,
+
+    /// Write each prefix's codes in a random order, rather than the order they were found, so the order carries no information.
+    ///
+    /// This doesn't change which codes are chosen, and is reproducible with `--seed`. With `--append`, only the new codes are shuffled.
+    /// Codes are only written once all of a prefix's codes have been found.
+    #[clap(long)]
+    pub shuffle_output : bool,
+
+    /// Write each prefix's codes sorted into lexicographic order (numeric order for codes of just digits), for easy auditing.
+    ///
+    /// This doesn't change which codes are chosen. It can't be used with `--append`, as the new codes would not be merged into the old.
+    #[clap(long,conflicts_with_all=&["shuffle-output","append"])]
+    pub sort_output : bool,
+
+    /// Split each prefix's codes between files of at most this many codes, like `prefix_A_001.txt`, `prefix_A_002.txt`, ...
+    ///
+    /// The batch number is added to the end of the name the file would otherwise have (from `--output-template` if given),
+    /// with enough digits for the number of files. The last file may have fewer codes. With `--shuffle-output` each file is
+    /// a random selection of the prefix's codes. The manifest lists every file and how many codes are in it.
+    #[clap(long,value_parser,conflicts_with_all=&["stdout","no-prefix-files","append","resume"])]
+    pub batch_size : Option<usize>,
+
+    /// Also make this many spare codes for each prefix, after its other codes, for students who enrol late.
+    ///
+    /// They are written to a file of their own, named like the prefix's file with `_spares` added (like `prefix_A_spares.txt`),
+    /// so that they aren't given out by mistake along with the others, and are not written to `--combined`, `--barcodes` or
+    /// `--merge-out`. They are as far from every other code as usual. A prefix like "A:500+50" has its own number of spares.
+    /// The manifest records how many spares were made for each prefix. Give the spares file to `--existing` in later runs.
+    #[clap(long,value_parser,conflicts_with_all=&["stdout","no-prefix-files","append","resume"])]
+    pub spares : Option<usize>,
+
+    /// Give each code a separate PIN of this many digits, such as a confirmation number to be given to the candidate with their exam number.
+    ///
+    /// PINs are chosen independently of the codes, with a random number generator derived from `--seed`, and are all at least
+    /// `--pin-min-hamming-distance` apart from each other. Each is written next to its code, so `--format` must be `csv`, `json` or `jsonl`.
+    /// PINs in `--existing` files (a `pin` column or field) are not reused.
+    #[clap(long,value_parser)]
+    pub with_pin : Option<usize>,
+
+    /// The minimum Hamming distance between PINs given by `--with-pin`.
+    #[clap(long,value_parser,default_value_t=2,requires="with-pin")]
+    pub pin_min_hamming_distance : usize,
+
+    /// Write codes in groups of this many characters, like `123 456 789` with `--group 3`, so they are easier to read and copy.
+    ///
+    /// Groups are counted from the end of the code, so only the first may be shorter. Distances are still measured between
+    /// the codes without separators. With `--format csv`, `json` or `jsonl` both the plain `code` and the grouped `display`
+    /// are written. Spaces and dashes (unless in the alphabet) are removed from codes in `--existing` files, so grouped files can be used.
+    #[clap(long,value_parser)]
+    pub group : Option<usize>,
+
+    /// What goes between the groups with `--group`.
+    #[clap(long,value_parser,default_value=" ",requires="group")]
+    pub separator : String,
+
+    /// Don't write a separate file for each prefix, just the `--combined` file.
+    #[clap(long,requires="combined")]
+    pub no_prefix_files : bool,
+
+    /// The characters each random position is drawn from, `digits` (0-9, the default), `alnum` (0-9 and A-Z), `hex` (0-9 and A-F) or `crockford32`.
+    ///
+    /// Using `alnum` gives vastly more possible codes for the same number of characters.
+    /// With `alnum` and `hex`, codes are upper case (see `--output-case`) and are compared ignoring case, so codes only differing in case are the same code.
+    /// `crockford32` is Crockford's Base32, 0-9 and A-Z without I, L, O or U. With it, existing codes are upper cased
+    /// and I and L read as 1 and O as 0 before comparison, so lower case transcriptions count as the same code.
+    #[clap(long,value_parser,default_value="digits")]
+    pub alphabet : Alphabet,
+
+    /// Draw each random character uniformly from exactly these characters, e.g. `23456789ABCDEFGH`.
+    ///
+    /// Characters may not be repeated. This is an alternative to `--alphabet`.
+    #[clap(long,value_parser=Alphabet::from_characters,conflicts_with="alphabet")]
+    pub charset : Option<Alphabet>,
+
+    /// Remove visually confusable characters from the alphabet (or charset) in use.
+    ///
+    /// The digits 0 and 1 are always removed. If the alphabet contains letters, then O, I, L, 5, S, 2, Z
+    /// and lower case o, i, l, s, z are removed as well. A later run reading the codes with `--existing`
+    /// warns if it uses a different alphabet to the one recorded in the manifest next to them.
+    #[clap(long)]
+    pub unambiguous : bool,
+
+    /// Compare codes ignoring case, for a `--charset` with letters, so codes only differing in case count as the same code,
+    /// including codes in `--existing` files. The charset's letters are made upper case. The `alnum`, `hex` and `crockford32`
+    /// alphabets always ignore case.
+    #[clap(long)]
+    pub case_insensitive : bool,
+
+    /// Treat easily confused characters as the same when comparing codes: 0, O and Q; 1, I and L; 5 and S; 2 and Z; 8 and B.
+    ///
+    /// Codes only differing by such confusions count as the same code, so are too close, and `--existing` codes are compared
+    /// in the same way. Unlike `--unambiguous`, this keeps all the characters of the alphabet. Lower case letters are included.
+    #[clap(long)]
+    pub confusable_classes : bool,
+
+    /// Like `--confusable-classes`, but with the groups of confusable characters from this file, one group per line like `0OQ`.
+    /// Blank lines and lines starting with `#` are ignored.
+    #[clap(long,value_parser,conflicts_with="confusable-classes")]
+    pub confusable_classes_file : Option<PathBuf>,
+
+    /// With an alphabet that ignores case, write codes in `upper` case (the default) or `lower` case.
+    #[clap(long,value_parser,default_value="upper")]
+    pub output_case : Case,
+
+    /// Never make the first random character (after the prefix) a '0', for spreadsheets that strip leading zeros.
+    ///
+    /// Existing codes starting with a zero are still compared against as usual.
+    #[clap(long)]
+    pub no_leading_zero : bool,
+
+    /// Reject codes with more than this many identical characters in a row, like "AB3444441".
+    ///
+    /// The whole code is checked, so runs may span the prefix and the random part. By default there is no limit.
+    #[clap(long,value_parser)]
+    pub max_run : Option<usize>,
+
+    /// Reject codes with more than this many characters in a row going up or down by one, like "12345" or "98765".
+    ///
+    /// The order is that of the alphabet in use, and does not wrap around, so 9 followed by 0 is not ascending.
+    /// Only the random part is checked unless `--sequential-include-prefix` is given. By default there is no limit.
+    #[clap(long,value_parser)]
+    pub max_sequential : Option<usize>,
+
+    /// Include the prefix when checking `--max-sequential`.
+    #[clap(long,requires="max-sequential")]
+    pub sequential_include_prefix : bool,
+
+    /// Reject codes whose random part, as a number, differs by less than this from that of another code with the same prefix.
+    ///
+    /// This keeps codes like 123456 and 123457 from being next to each other on lists sorted numerically, as they invite
+    /// mix-ups even when the Hamming distance is large enough. It can only be used with an alphabet of digits.
+    #[clap(long,value_parser)]
+    pub min_numeric_gap : Option<u64>,
+
+    /// Only draw the random digits from this range of numbers, given as START..END including START but not END, so
+    /// `400000..700000` gives random parts from 400000 to 699999. Smaller numbers are still padded with zeros to the number of digits.
+    ///
+    /// END can be at most 10 to the power of the number of digits. Fewer codes can fit in a smaller range, which the check
+    /// that the codes wanted can fit allows for. It can only be used with the default alphabet of digits, and not with `--strategy algebraic`.
+    #[clap(long,value_parser=parse_range)]
+    pub range : Option<Range<u64>>,
+
+    /// Never make codes whose random digits, as a number, are in this range, given as START..END like `--range`. May be given more than once.
+    ///
+    /// Each range must be within the numbers the random digits are drawn from (see `--range`). Candidates in them are rejected before
+    /// the distance check, and counted separately in the statistics. It can only be used with the default alphabet of digits.
+    #[clap(long,value_parser=parse_range)]
+    pub exclude_range : Vec<Range<u64>>,
+
+    /// Spread the codes for each prefix evenly over the characters the random part can start with, so the numbers of codes
+    /// starting with each differ by at most one, as when codes are split into groups by their first random digit.
+    ///
+    /// Each code starts with the character used least so far, and the rest of the random part is random as usual, so the
+    /// distance check is unchanged. The numbers starting with each character are printed at the end of each prefix.
+    /// It can't be used with `--range` or with `--strategy exhaustive` or `algebraic`.
+    #[clap(long)]
+    pub balance_first : bool,
+
+    /// Reject codes whose random part has fewer than this many different characters, like "353535" or "AAA111".
+    ///
+    /// This can be at most the number of random digits and the number of characters in the alphabet. By default there is no limit.
+    #[clap(long,value_parser)]
+    pub min_distinct : Option<usize>,
+
+    /// Only make codes whose character values add up to R modulo M, given as M:R, or just M for sums divisible by M, so `9` gives codes like 123453.
+    ///
+    /// Each character's value is its position in the alphabet, so digits are worth themselves. The whole code is added up, including
+    /// the prefix and any check digits, with characters not in the alphabet counting as 0. Only about one in M codes satisfy
+    /// this, which the check that the codes wanted can fit takes into account.
+    #[clap(long,value_parser)]
+    pub digit_sum_mod : Option<DigitSum>,
+
+    /// A file of substrings that may not appear anywhere in a code, one per line, ignoring case.
+    ///
+    /// Lines starting with '#' and blank lines are ignored. May be given more than once.
+    #[clap(long,value_parser)]
+    pub blocklist : Vec<PathBuf>,
+
+    /// Reject codes matched by this regular expression, such as '000$' for codes ending in 000. May be given more than once.
+    ///
+    /// The whole code (prefix, random part and any check digits) is matched, and the pattern may match any part of it
+    /// unless anchored with '^' or '$'. Codes from `--existing` that match are reported, but still avoided as usual.
+    #[clap(long,value_parser)]
+    pub exclude_regex : Vec<Regex>,
+
+    /// How to measure the distance between codes, `hamming` (the default), `levenshtein`, `damerau` or `weighted-hamming`.
+    ///
+    /// Levenshtein distance also counts a dropped or inserted character as a single difference, so it protects
+    /// against those copying errors too, but is slower. Damerau additionally counts swapping two adjacent
+    /// characters as a single difference. Weighted Hamming counts some differences, such as between neighbouring
+    /// keys, as less than one (see `--weights`). The minimum distance then refers to this metric.
+    #[clap(long,value_parser,default_value="hamming")]
+    pub metric : Metric,
+
+    /// For `--metric weighted-hamming`, how much each pair of different characters counts: `keypad` (the default), `number-row`, or a file.
+    ///
+    /// `keypad` counts digits next to each other on a numeric keypad as half a difference, and `number-row` digits next to each
+    /// other along the top of a keyboard. A file has a line like `3 4 0.5` for each pair of characters that counts as less than
+    /// a full difference (from 0 to 1), and may have blank lines and comments starting with '#'.
+    #[clap(long,value_parser)]
+    pub weights : Option<String>,
+
+    /// Which codes each new code must be far enough from, `global` (all of them, the default) or `per-prefix`.
+    ///
+    /// With `per-prefix`, a new code is only compared against codes (generated or from `--existing`) that start with the same prefix.
+    /// This is for when codes with different prefixes can never be confused, and leaves much more room for codes.
+    #[clap(long,value_parser,default_value="global")]
+    pub scope : Scope,
+
+    /// The minimum distance between codes with the same prefix, instead of `min_hamming_distance`. See `--min-hamming-across`.
+    #[clap(long,value_parser)]
+    pub min_hamming_within : Option<Threshold>,
+
+    /// The minimum distance between codes with different prefixes, instead of `min_hamming_distance`, as mixing up codes for
+    /// the same exam is usually worse than mixing up codes for different ones. So `--min-hamming-within 4 --min-hamming-across 2`
+    /// keeps codes with the same prefix 4 apart, and codes with different prefixes 2 apart.
+    ///
+    /// An existing code has the prefix given for its file by `--existing-prefix`, or if the file is named like an output file,
+    /// such as `prefix_S0.txt`, the prefix in the name, or otherwise the longest of the prefixes being made that the code starts with.
+    /// Codes with none of these count as having a different prefix to every other code. This can't be used with `--scope per-prefix`,
+    /// or with prefixes that have their own minimum distance.
+    #[clap(long,value_parser)]
+    pub min_hamming_across : Option<Threshold>,
+
+    /// The prefix of the codes in an `--existing` file, as PREFIX=FILE, for `--min-hamming-across`. May be given more than once.
+    #[clap(long,value_parser=parse_existing_prefix)]
+    pub existing_prefix : Vec<(String,PathBuf)>,
+
+    /// Measure distances between just the random parts (plus any check digits) of codes, ignoring their prefixes.
+    ///
+    /// The random part is taken to be the last `digits` characters (plus check digits), so this works for `--existing` codes too.
+    /// With the default `--scope global`, this means codes with different prefixes still need random parts far enough apart.
+    /// With `--scope per-prefix`, only codes with the same prefix are compared, and the prefix no longer counts towards their distance.
+    #[clap(long)]
+    pub distance_on_body : bool,
+
+    /// Also keep each code at least the minimum distance from every other code read backwards, as a scanner might with the page upside down.
+    ///
+    /// Reversing both codes doesn't change their distance, so this applies equally to each new code read backwards and
+    /// to the used codes read backwards. It roughly doubles the work of checking each candidate, and applies to `--existing` codes too.
+    /// This compares whole codes, so can't be used with `--distance-on-body`.
+    #[clap(long)]
+    pub include_reversals : bool,
+
+    /// Also keep every rotation of each code, like `345612` for `123456`, at least the minimum distance from the other codes of the same length,
+    /// as a misaligned label wrapped round something might be read from the wrong place.
+    ///
+    /// This multiplies the work of checking each candidate by the length of the code, with the rotations compared given in the statistics.
+    /// It also means far fewer codes can fit, which the check that the codes wanted can fit does not allow for, so a run may fail
+    /// partway through if too many codes are asked for. This compares whole codes, so can't be used with `--distance-on-body`.
+    #[clap(long)]
+    pub include_rotations : bool,
+
+    /// What to do about existing codes whose length is different to that of the codes being made, `error` (the default), `pad`, `distinct` or `truncate`.
+    ///
+    /// With `error` such codes are treated as invalid (see `--skip-invalid`), unless `--distance-on-body` is given.
+    /// For Hamming distance, `pad` counts each extra character of the longer code as a difference, `distinct` treats codes of
+    /// different lengths as always far enough apart, and `truncate` (the behaviour of earlier versions) only compares up to the
+    /// length of the shorter code. Other metrics handle different lengths themselves.
+    #[clap(long,value_parser,default_value="error")]
+    pub length_policy : LengthHandling,
+
+    /// Give up, with an error, if no acceptable code has been found after this many candidates. 0 means never give up.
+    ///
+    /// Codes found before giving up are still written to the output file.
+    #[clap(long,value_parser,default_value_t=1_000_000)]
+    pub max_attempts : u64,
+
+    /// Stop, as if `--max-attempts` had been reached, if the whole run takes longer than this, like `30s`, `10m` or `2h`.
+    ///
+    /// The codes found so far are written, and the manifest records how many were found for each prefix and why the run stopped,
+    /// so it can be carried on with `--resume`. The time is checked before each candidate, so it is not overrun by much.
+    #[clap(long,value_parser)]
+    pub timeout : Option<TimeLimit>,
+
+    /// Stop in the same way if the codes for any one prefix take longer than this.
+    #[clap(long,value_parser)]
+    pub timeout_per_prefix : Option<TimeLimit>,
+
+    /// How to choose candidates, `random` (the default), `exhaustive` or `algebraic`.
+    ///
+    /// With `exhaustive`, every possible random part is tried once, in a random order reproducible with `--seed`.
+    /// This never tries the same candidate twice, and when every one has been tried the program stops, knowing
+    /// that no more codes are possible. It needs all the possible random parts in memory, see `--exhaustive-limit`.
+    ///
+    /// With `algebraic`, random parts are chosen at random from a Reed-Solomon code, so codes with the same prefix are
+    /// always at least the minimum Hamming distance apart without any searching. Prefixes made of characters from the alphabet
+    /// are included in the code, so all such codes are far enough apart. This needs the minimum distance to be at most the number
+    /// of digits, and the number of digits to be at most 11 for digits (more for bigger alphabets). If the code can't give
+    /// random parts using only characters from the alphabet, the program stops with an error.
+    #[clap(long,value_parser,default_value="random")]
+    pub strategy : Strategy,
+
+    /// The largest number of possible random parts (the alphabet size to the power of the number of digits) allowed with `--strategy exhaustive`.
+    #[clap(long,value_parser,default_value_t=1_000_000)]
+    pub exhaustive_limit : u64,
+
+    /// Run even if more codes are wanted for some prefix than can possibly fit, by the Singleton and sphere packing bounds (see the `capacity` subcommand).
+    #[clap(long)]
+    pub i_know_what_im_doing : bool,
+
+    /// Check that every two prefixes of the same length differ in at least this many characters, so a mistake in the
+    /// prefix of a code is unlikely to give another valid prefix.
+    #[clap(long,value_parser)]
+    pub check_prefix_distance : Option<usize>,
+
+    /// Do everything up to generating the codes, then print what would be done instead of doing it.
+    ///
+    /// The arguments and all the input files are read and checked, and the feasibility check is done, so the exit status is
+    /// the same as a real run would have if it failed before starting. Nothing is written: the plan lists the output files
+    /// that would be created, replaced or added to, the codes already used, and the seed.
+    #[clap(long)]
+    pub dry_run : bool,
+
+    /// Also write a barcode image of each new code into this directory, named after the code, like `S0123456.svg`,
+    /// with a quiet zone either side and the code written underneath (as displayed with `--group`). This needs the
+    /// program to be built with the `barcodes` feature.
+    #[clap(long,value_parser)]
+    pub barcodes : Option<PathBuf>,
+
+    /// The kind of barcode, `code128` (the default) or `code39`. Code 39 can only have digits, upper case letters,
+    /// space and `-.$/+%`, so the alphabet, prefixes and suffixes are checked before anything is generated.
+    #[clap(long,value_parser,default_value="code128",requires="barcodes")]
+    pub barcode_format : BarcodeFormat,
+
+    /// Write each barcode as a PNG image as well as an SVG image.
+    #[clap(long,requires="barcodes")]
+    pub barcode_png : bool,
+
+    /// A CSV file of students, with a header row, to give the new codes to for a mail merge. Each row is given one new code
+    /// in order, and written to `--merge-out` exactly as it was but with an `exam_number` column added. There must be a
+    /// single prefix, wanting as many codes as there are students.
+    #[clap(long,value_parser,requires="merge-out")]
+    pub merge_with : Option<PathBuf>,
+
+    /// Where to write the students of `--merge-with` with their codes. The codes are also written to the prefix's file as
+    /// usual, so that later runs can be given it with `--existing`.
+    #[clap(long,value_parser,requires="merge-with")]
+    pub merge_out : Option<PathBuf>,
+
+    /// Check candidates on this many threads at once, which helps when most candidates are being rejected.
+    ///
+    /// The codes produced are exactly the same whatever the number of threads, so `--seed` still gives reproducible results.
+    #[clap(long,value_parser,default_value_t=1)]
+    pub threads : usize,
+
+    /// Print more about the search to standard error: `-v` prints each code as it is found with how many candidates it took,
+    /// and `-vv` also prints each rejected candidate and why it was rejected.
+    ///
+    /// With either, progress lines are printed every few seconds rather than updated in place.
+    #[clap(short,long,action=clap::ArgAction::Count)]
+    pub verbose : u8,
+
+    /// Append a check digit to each code, so most typing errors can be detected. The scheme may be `luhn`, `damm`, `verhoeff` or `mod97`.
+    ///
+    /// The check digit is computed over all the decimal digits in the code, including any in the prefix.
+    /// `mod97` (ISO 7064 MOD 97-10) appends two digits, and also includes letters as A=10, B=11, ... Z=35.
+    /// It is not counted in `digits`, so 6 digits with a check digit gives 7 characters after the prefix.
+    /// It is included when computing the Hamming distance to other codes.
+    #[clap(long,value_parser)]
+    pub check_digit : Option<CheckDigit>,
+
+    /// How many codes you want, possibly with prefixes.
+    ///
+    /// A simple number means that many codes, with no prefix. The results will be stored in a file called `prefix_.txt`
+    ///
+    /// A value of the form "AB3:78" means get 78 codes, each with the prefix "AB3", stored in a file called `prefix_AB3.txt`.
+    ///
+    /// Multiple values would be allowed, so "A:500 B:200" means get 500 codes starting with "A" (stored in `prefix_A.txt`) and 200 starting with "B" (stored in `prefix_B.txt`).
+    ///
+    /// A value of the form "AB3:78:8" means get 78 codes with the prefix "AB3", each with 8 random digits instead of the number given by `digits`.
+    ///
+    /// A value of the form "AB3:=78" means make sure `prefix_AB3.txt` contains 78 codes: the codes already in the file are read and
+    /// avoided, and only enough new ones to make 78 are added to the end. If it already has at least 78, nothing is done for that prefix.
+    ///
+    /// A value of the form "AB3*X:78" means get 78 codes starting with "AB3" and ending with "X", with the random digits in between.
+    /// Quote it, so the shell doesn't treat the `*` as a file name pattern.
+    ///
+    /// Numbers of codes can be written with separators or in thousands, so "AB3:6_000", "AB3:6,000" and "AB3:6k" are all 6000 codes.
+    ///
+    /// A value ending like "PG:300:d5" or "PG:300:8:d5" gives the prefix's codes their own minimum distance, 5, instead of `min_hamming_distance`.
+    /// Each pair of codes is then at least the larger of their two minimum distances apart, so codes with a larger minimum distance are
+    /// that far from every other code, while the other codes need only be their own minimum distance from each other. Existing codes
+    /// starting with the prefix count as having its minimum distance. The manifest records each prefix's minimum distance.
+    ///
+    /// A value of the form "AB3:500+50" means 500 codes and 50 spares, as for `--spares`.
+    #[clap(value_parser)]
+    pub prefixes : Vec<WantedPrefix>,
+
+    /// Codes laid out by a mask, such as "B#2###7#:150" for 150 codes with a random character for each `#` and the other characters
+    /// kept, stored in a file called `mask_B#2###7#.txt`. The fixed characters must be in the alphabet, and the number of random digits
+    /// is the number of `#`s. It can be given several times, and used along with the usual prefixes, whose codes come first.
+    #[clap(long,value_parser=WantedPrefix::from_mask)]
+    pub mask : Vec<WantedPrefix>,
+
+    /// The most codes that can be asked for with one prefix, to catch mistakes like an extra 0. Give a larger number if more are really wanted.
+    #[clap(long,value_parser,default_value_t=10_000_000)]
+    pub max_codes : usize,
+
+    /// Read parameters from a TOML file, such as `min_hamming_distance = 3` or `existing = ["a.txt","b.txt"]`.
+    ///
+    /// Each key is the name of an option (or `min_hamming_distance`, `digits` and `prefixes`) with `_` instead of `-`.
+    /// Anything given on the command line replaces the same key in the file. Unknown keys are an error. See `--example-config`.
+    #[clap(long,value_parser)]
+    pub config : Option<PathBuf>,
+
+    /// Print the parameters given, from the command line and any `--config` file, as a TOML configuration, and stop.
+    #[clap(long)]
+    pub print_config : bool,
+
+    /// Print an example configuration file, with comments, and stop.
+    #[clap(long)]
+    pub example_config : bool,
+
+    #[clap(subcommand)]
+    pub command : Option<Command>,
+}
+
+#[derive(Subcommand, Debug)]
+pub enum Command {
+    /// Check that some files of codes satisfy the minimum Hamming distance.
+    ///
+    /// All the codes in all the files are checked against each other. Exact duplicates and codes
+    /// of inconsistent length are also reported. Exits with a non-zero status if any problem is found.
+    Verify(VerifyParameters),
+    /// Estimate how many codes with the given number of digits can be at least the given Hamming distance apart.
+    ///
+    /// Prints upper and lower bounds from coding theory, and the number found by a short random trial with the
+    /// same generator as used to make codes, which is the best guide to what a real run can achieve.
+    Capacity(CapacityParameters),
+    /// Report the actual minimum distance between any two codes in some files, and the pair of codes that achieves it.
+    ///
+    /// Also prints the number of codes, their lengths, and any exact duplicates, which are not counted when finding the minimum distance.
+    /// With `--top`, lists the closest pairs of codes.
+    Analyze(AnalyzeParameters),
+    /// Combine several files of codes into one, checking that all the codes are far enough apart.
+    ///
+    /// The codes are written in order, first file first, one per line. If any two codes (including exact duplicates)
+    /// are too close, the problems are reported and nothing is written, unless `--drop-violations` is given.
+    Merge(MergeParameters),
+    /// Check or add the check digits of codes in files, such as lists from other systems or made before check digits were used.
+    CheckDigit(CheckDigitParameters),
+    /// Give each student in a roster a code, writing a CSV file of each student's ID and code in roster order.
+    ///
+    /// Students already given a code in an `--existing` mapping keep it, so running again with the same roster and the
+    /// previous mapping only gives codes to new students. New codes are generated, and added to the prefix's file of codes,
+    /// or taken from a file of codes made earlier with `--from`.
+    Assign(AssignParameters),
+    /// Find the codes closest to a possibly mistyped code, such as one written unclearly on an exam paper.
+    ///
+    /// Lists the nearest codes with their distances, and says whether one of them is an unambiguous correction: the only
+    /// code less than half the minimum distance between the codes away, so closer to the code given than any other code can be.
+    Nearest(NearestParameters),
+    /// Match a CSV file of codes written down, such as exam numbers copied by hand by markers, against the codes issued.
+    ///
+    /// Writes a CSV file with a row for each code written down, giving the issued code it matches and a status: `exact`,
+    /// `corrected` if it is closer to one issued code than any other and within `--max-errors` of it, `ambiguous` if several
+    /// issued codes are equally close, or `not_found`. Ambiguous codes are never corrected.
+    Correct(CorrectParameters),
+}
+
+#[derive(clap::Args, Debug)]
+pub struct CorrectParameters {
+    /// The files of codes issued, one code per line. May be given more than once, or be a glob pattern like `'prefix_*.txt'`.
+    #[clap(long,value_parser,required=true)]
+    pub codes : Vec<PathBuf>,
+    /// The CSV file of codes written down.
+    #[clap(long,value_parser)]
+    pub input : PathBuf,
+    /// The column of the input with the codes written down, by name from the header row, or as a 0 based number if there is no header row.
+    #[clap(long,value_parser)]
+    pub column : Column,
+    /// The most characters a code written down can have wrong for it to be corrected. With `--metric weighted-hamming` this may be a fraction, like 1.5.
+    #[clap(long,value_parser,default_value="1")]
+    pub max_errors : Threshold,
+    /// How to measure the distance between codes, `hamming` (the default), `levenshtein`, `damerau` or `weighted-hamming`.
+    /// Levenshtein distance also corrects codes with a character missed out or written twice.
+    #[clap(long,value_parser,default_value="hamming")]
+    pub metric : Metric,
+    /// For `--metric weighted-hamming`, the weights: `keypad` (the default), `number-row`, or a file. See `--weights` when making codes.
+    #[clap(long,value_parser)]
+    pub weights : Option<String>,
+    /// How Hamming distance compares codes of different lengths, `pad` (the default), `distinct` or `truncate`. See `--length-policy` when making codes.
+    #[clap(long,value_parser,default_value="pad")]
+    pub length_policy : LengthPolicy,
+    /// The CSV file to write, with columns `line`, `value`, `code`, `status` and `distance`.
+    #[clap(long,value_parser)]
+    pub out : PathBuf,
+    /// Replace the output file if it already exists.
+    #[clap(long)]
+    pub force : bool,
+}
+
+#[derive(clap::Args, Debug)]
+pub struct NearestParameters {
+    /// The code to look up.
+    #[clap(value_parser)]
+    pub code : String,
+    /// The files of valid codes, one code per line. May be given more than once, or be a glob pattern like `'prefix_*.txt'`.
+    #[clap(long,value_parser,required_unless_present="manifest")]
+    pub existing : Vec<PathBuf>,
+    /// A manifest written by a previous run. The codes in the output files it lists are valid, and are at least its minimum distance apart.
+    #[clap(long,value_parser)]
+    pub manifest : Option<PathBuf>,
+    /// The minimum distance between the valid codes, which says how far away a correction can be. By default this is the
+    /// manifest's minimum distance, or if there is no manifest, the smallest distance between any two valid codes.
+    #[clap(long,value_parser)]
+    pub min_hamming_distance : Option<Threshold>,
+    /// How to measure the distance between codes, `hamming` (the default), `levenshtein`, `damerau` or `weighted-hamming`.
+    /// Levenshtein distance also finds codes with a character missed out or written twice.
+    ///
+    /// With a manifest, the metric it records is used unless this is given.
+    #[clap(long,value_parser)]
+    pub metric : Option<Metric>,
+    /// For `--metric weighted-hamming`, the weights: `keypad` (the default), `number-row`, or a file. See `--weights` when making codes.
+    #[clap(long,value_parser)]
+    pub weights : Option<String>,
+    /// How Hamming distance compares codes of different lengths, `pad` (the default), `distinct` or `truncate`. See `--length-policy` when making codes.
+    #[clap(long,value_parser,default_value="pad")]
+    pub length_policy : LengthPolicy,
+    /// How many of the nearest codes to list.
+    #[clap(long,value_parser,default_value_t=5)]
+    pub top : usize,
+    /// How to print the result, `table` (the default) or `json`. With `json`, only the result is printed to standard output
+    /// and the other information goes to standard error.
+    #[clap(long,value_parser,default_value="table")]
+    pub format : LookupFormat,
+}
+
+#[derive(clap::Args, Debug)]
+pub struct AssignParameters {
+    /// The CSV file with a row for each student.
+    #[clap(long,value_parser)]
+    pub roster : PathBuf,
+    /// The column of the roster with each student's ID, by name from the header row, or as a 0 based number if there is no header row.
+    /// Each ID must only be in the roster once.
+    #[clap(long,value_parser)]
+    pub id_column : Column,
+    /// The prefix of the new codes.
+    #[clap(long,value_parser)]
+    pub prefix : String,
+    /// The minimum number of characters different any new code is allowed to be from any other code.
+    #[clap(long,value_parser,default_value_t=3)]
+    pub min_hamming_distance : usize,
+    /// The number of random digits in each new code.
+    #[clap(long,value_parser,default_value_t=6)]
+    pub digits : usize,
+    /// Seed for the random number generator, so the new codes are reproducible.
+    #[clap(long,value_parser)]
+    pub seed : Option<Seed>,
+    /// A file of codes that have already been used. If it is a CSV file with the ID column and a `code` column, such as a mapping
+    /// written before, each student in it keeps their code. May be given more than once, or be a glob pattern like `'codes_*.csv'`.
+    #[clap(long,value_parser)]
+    pub existing : Vec<PathBuf>,
+    /// Take new codes from this file, in order, instead of generating them, skipping any already used. This could be a prefix's file made earlier.
+    #[clap(long,value_parser)]
+    pub from : Option<PathBuf>,
+    /// Derive each new code from the student's ID and a secret `--key`, instead of choosing it at random, so it can be worked out again from the ID.
+    ///
+    /// The only method is `hmac`, which uses HMAC-SHA256 of the prefix and ID. If a derived code is too close to one already used,
+    /// a counter is added and it is derived again, so codes depend on the order of the roster as well as the key and IDs.
+    #[clap(long,value_parser,requires="key",conflicts_with_all=&["from","seed"])]
+    pub derive : Option<Derivation>,
+    /// The secret key for `--derive`, as at least 32 hexadecimal characters. Anyone with the key and an ID can work out the student's code.
+    #[clap(long,value_parser,requires="derive")]
+    pub key : Option<KeyedDerivation>,
+    /// The file of codes for the prefix, one code per line, which new codes must be far enough from and are added to.
+    /// The default is `prefix_<prefix>.txt`, as when making codes. Not used with `--from`.
+    #[clap(long,value_parser,conflicts_with="from")]
+    pub prefix_file : Option<PathBuf>,
+    /// Where to write the mapping of IDs to codes.
+    #[clap(long,value_parser)]
+    pub out : PathBuf,
+    /// Replace the mapping file if it already exists. This isn't needed if it is also given to `--existing`.
+    #[clap(long)]
+    pub force : bool,
+}
+
+#[derive(clap::Args, Debug)]
+pub struct CheckDigitParameters {
+    #[clap(subcommand)]
+    pub command : CheckDigitCommand,
+}
+
+#[derive(Subcommand, Debug)]
+pub enum CheckDigitCommand {
+    /// Check that every code in some files ends with the correct check digit(s).
+    ///
+    /// Each invalid code is printed with its file, line and the check digit(s) it should have.
+    /// Exits with a non-zero status if any are invalid.
+    Verify(CheckDigitVerifyParameters),
+    /// Write a new file with the check digit(s) appended to each code in an existing file.
+    Add(CheckDigitAddParameters),
+}
+
+#[derive(clap::Args, Debug)]
+pub struct CheckDigitVerifyParameters {
+    /// The check digit scheme, `luhn`, `damm`, `verhoeff` or `mod97`.
+    #[clap(long,value_parser)]
+    pub scheme : CheckDigit,
+    /// The files of codes to check, one code per line.
+    #[clap(value_parser, required=true)]
+    pub files : Vec<PathBuf>,
+}
+
+#[derive(clap::Args, Debug)]
+pub struct CheckDigitAddParameters {
+    /// The check digit scheme, `luhn`, `damm`, `verhoeff` or `mod97`.
+    #[clap(long,value_parser)]
+    pub scheme : CheckDigit,
+    /// Where to write the codes with check digits.
+    #[clap(long,value_parser)]
+    pub out : PathBuf,
+    /// Replace the output file if it already exists.
+    #[clap(long)]
+    pub force : bool,
+    /// The file of codes without check digits, one code per line.
+    #[clap(value_parser)]
+    pub file : PathBuf,
+}
+
+#[derive(clap::Args, Debug)]
+pub struct MergeParameters {
+    /// The minimum number of characters different any code is allowed to be from
+    /// any other code. With `--metric weighted-hamming` this may be a fraction, like 2.5.
+    #[clap(long,value_parser)]
+    pub min_hamming_distance : Threshold,
+    /// How to measure the distance between codes, `hamming` (the default), `levenshtein`, `damerau` or `weighted-hamming`.
+    #[clap(long,value_parser,default_value="hamming")]
+    pub metric : Metric,
+    /// For `--metric weighted-hamming`, the weights: `keypad` (the default), `number-row`, or a file. See `--weights` when making codes.
+    #[clap(long,value_parser)]
+    pub weights : Option<String>,
+    /// How to compare codes of different lengths, `error` (report them as the wrong length, the default), `pad`, `distinct` or `truncate`.
+    #[clap(long,value_parser,default_value="error")]
+    pub length_policy : LengthHandling,
+    /// Where to write the merged codes.
+    #[clap(long,value_parser)]
+    pub out : PathBuf,
+    /// Replace the output file if it already exists.
+    #[clap(long)]
+    pub force : bool,
+    /// Rather than failing, keep each code unless it is too close to one already kept, and drop it otherwise.
+    ///
+    /// As the codes are considered in order, all of the first file's codes are kept if they are far enough apart themselves.
+    /// Each dropped code is reported along with the code it conflicts with.
+    #[clap(long)]
+    pub drop_violations : bool,
+    /// The files of codes to merge, one code per line.
+    #[clap(value_parser, required=true)]
+    pub files : Vec<PathBuf>,
+}
+
+#[derive(clap::Args, Debug)]
+pub struct AnalyzeParameters {
+    /// How to measure the distance between codes, `hamming` (the default), `levenshtein`, `damerau` or `weighted-hamming`.
+    #[clap(long,value_parser,default_value="hamming")]
+    pub metric : Metric,
+    /// For `--metric weighted-hamming`, the weights: `keypad` (the default), `number-row`, or a file. See `--weights` when making codes.
+    #[clap(long,value_parser)]
+    pub weights : Option<String>,
+    /// How Hamming distance compares codes of different lengths, `pad` (the default), `distinct` or `truncate`. See `--length-policy` when making codes.
+    #[clap(long,value_parser,default_value="pad")]
+    pub length_policy : LengthPolicy,
+    /// List this many of the closest pairs of codes, closest first, with the file and line each code came from.
+    ///
+    /// Pairs at the same distance are listed in the order their codes appear in the files.
+    #[clap(long,value_parser)]
+    pub top : Option<usize>,
+    /// How to print the closest pairs, `table` (the default) or `csv`. With `csv`, only the pairs are printed to
+    /// standard output and the other information goes to standard error, so the output can be redirected to a file.
+    #[clap(long,value_parser,default_value="table",requires="top")]
+    pub format : TableFormat,
+    /// Also find how close codes are to the other codes read backwards. See `--include-reversals` when making codes.
+    ///
+    /// With `--top`, the closest pairs either way are listed together, with a column saying which pairs are close when one is read backwards.
+    #[clap(long)]
+    pub include_reversals : bool,
+    /// A prefix, to also find how close codes with the same prefix are, and how close codes with different prefixes are. May be given more than once.
+    #[clap(long,value_parser)]
+    pub prefix : Vec<String>,
+    /// Say if codes with the same prefix are closer than this. See `--min-hamming-within` when making codes.
+    #[clap(long,value_parser,requires="prefix")]
+    pub min_hamming_within : Option<Threshold>,
+    /// Say if codes with different prefixes are closer than this. See `--min-hamming-across` when making codes.
+    #[clap(long,value_parser,requires="prefix")]
+    pub min_hamming_across : Option<Threshold>,
+    /// The files of codes to analyze, one code per line.
+    #[clap(value_parser, required=true)]
+    pub files : Vec<PathBuf>,
+}
+
+#[derive(clap::Args, Debug)]
+pub struct CapacityParameters {
+    /// The minimum number of characters different any code is allowed to be from
+    /// any other code.
+    #[clap(value_parser)]
+    pub min_hamming_distance : usize,
+    /// The number of digits (random characters) in the code. Prefixes don't change how many codes fit.
+    #[clap(value_parser)]
+    pub digits : usize,
+    /// The characters to draw each random character from, as for making codes.
+    #[clap(long,value_parser,default_value="digits")]
+    pub alphabet : Alphabet,
+    /// Draw each random character from exactly these characters. This is an alternative to `--alphabet`.
+    #[clap(long,value_parser=Alphabet::from_characters,conflicts_with="alphabet")]
+    pub charset : Option<Alphabet>,
+    /// Remove visually confusable characters from the alphabet (or charset) in use.
+    #[clap(long)]
+    pub unambiguous : bool,
+    /// In the random trial, give up after this many candidates have been rejected for one code.
+    #[clap(long,value_parser,default_value_t=10000)]
+    pub trial_attempts : u64,
+    /// Stop the random trial after finding this many codes.
+    #[clap(long,value_parser,default_value_t=100000)]
+    pub trial_limit : usize,
+    /// Don't do the random trial, just print the bounds.
+    #[clap(long)]
+    pub no_trial : bool,
+    /// Seed for the random trial, so its result is reproducible.
+    #[clap(long,value_parser)]
+    pub seed : Option<Seed>,
+    /// Only count codes whose character values add up to R modulo M, as for making codes.
+    #[clap(long,value_parser)]
+    pub digit_sum_mod : Option<DigitSum>,
+    /// Only count codes whose random digits, as a number, are in START..END, as for making codes.
+    #[clap(long,value_parser=parse_range)]
+    pub range : Option<Range<u64>>,
+    /// Print the results as a JSON object instead of a table.
+    #[clap(long)]
+    pub json : bool,
+}
+
+#[derive(clap::Args, Debug)]
+pub struct VerifyParameters {
+    /// The minimum number of characters different any code is allowed to be from
+    /// any other code. With `--metric weighted-hamming` this may be a fraction, like 2.5.
+    #[clap(long, value_parser, required_unless_present_any=&["manifest","min-hamming-within"])]
+    pub min_hamming_distance : Option<Threshold>,
+    /// How to measure the distance between codes, `hamming` (the default), `levenshtein`, `damerau` or `weighted-hamming`.
+    #[clap(long,value_parser)]
+    pub metric : Option<Metric>,
+    /// For `--metric weighted-hamming`, the weights: `keypad` (the default), `number-row`, or a file. See `--weights` when making codes.
+    ///
+    /// With a manifest, the weights it records are used unless the metric is given explicitly.
+    #[clap(long,value_parser)]
+    pub weights : Option<String>,
+    /// Also check that each code ends with a valid check digit for this scheme.
+    #[clap(long,value_parser)]
+    pub check_digit : Option<CheckDigit>,
+    /// Also check that the character values of each code add up to R modulo M, given as M:R or just M. See `--digit-sum-mod` when making codes.
+    #[clap(long,value_parser)]
+    pub digit_sum_mod : Option<DigitSum>,
+    /// The alphabet the codes were made from, which gives the character values for `--digit-sum-mod`, as for making codes.
+    ///
+    /// With a manifest, the alphabet it records is used unless this is given.
+    #[clap(long,value_parser)]
+    pub alphabet : Option<Alphabet>,
+    /// Also check that each code is far enough from every other code read backwards. See `--include-reversals` when making codes.
+    ///
+    /// With a manifest, this is done if it was done when the codes were made.
+    #[clap(long)]
+    pub include_reversals : bool,
+    /// Which codes are required to be far enough apart, `global` (all of them, the default) or `per-prefix`.
+    ///
+    /// With `per-prefix`, the prefixes come from `--prefix` or the manifest, and each code is only compared with others with the same prefix.
+    #[clap(long,value_parser)]
+    pub scope : Option<Scope>,
+    /// How to compare codes of different lengths, `error` (report them as the wrong length, the default), `pad`, `distinct` or `truncate`.
+    ///
+    /// See the `--length-policy` option when making codes. With a manifest listing prefixes with different numbers of digits, `error` means `pad`,
+    /// as that is how codes of different lengths were compared when they were made.
+    #[clap(long,value_parser)]
+    pub length_policy : Option<LengthHandling>,
+    /// A prefix, for `--scope per-prefix` or `--min-hamming-across`. May be given more than once.
+    #[clap(long,value_parser)]
+    pub prefix : Vec<String>,
+    /// The minimum distance between codes with the same prefix, instead of `--min-hamming-distance`. See `--min-hamming-within` when making codes.
+    #[clap(long,value_parser)]
+    pub min_hamming_within : Option<Threshold>,
+    /// The minimum distance between codes with different prefixes, instead of `--min-hamming-distance`. See `--min-hamming-across` when making codes.
+    ///
+    /// With a manifest, the distance it records is used unless `--min-hamming-distance` or this is given.
+    #[clap(long,value_parser)]
+    pub min_hamming_across : Option<Threshold>,
+    /// A manifest written by a previous run. The output files it lists are checked, with its minimum distance,
+    /// metric and check digit scheme unless these are given explicitly.
+    #[clap(long,value_parser)]
+    pub manifest : Option<PathBuf>,
+    /// The files of codes to check, one code per line.
+    #[clap(value_parser, required_unless_present="manifest")]
+    pub files : Vec<PathBuf>,
+}
+
+/*
+fn default_wanted() -> Parameters {
+    Parameters{ min_hamming_distance: 3, prefixes: vec!["S0:600".parse().unwrap(),"P0:250".parse().unwrap(),"S1:100".parse().unwrap(),"P1:50".parse().unwrap(),], digits:6 }
+}*/
+
+
+/// Parse the command line, along with any `--config` file, and handle `--print-config` and `--example-config`.
+pub fn parse_parameters() -> Result<Parameters,Error> {
+    // clap moves arguments changed with mut_arg to the end, so all the positional arguments are changed to keep their order.
+    let relaxed = Parameters::command().mut_arg("min-hamming-distance",|a|a.required(false)).mut_arg("digits",|a|a.required(false)).mut_arg("prefixes",|a|a);
+    let given = relaxed.clone().get_matches();
+    if given.contains_id("example-config") {
+        print!("{}",crate::cli::config::EXAMPLE_CONFIG);
+        std::process::exit(0);
+    }
+    let mut arguments : Vec<std::ffi::OsString> = std::env::args_os().collect();
+    if let Some(path) = given.get_one::<PathBuf>("config") {
+        if given.subcommand().is_some() { return Err(Error::Arguments("--config can only be used when making codes, not with a subcommand".to_string())); }
+        let config = crate::cli::config::read(path)?;
+        let (options,positionals) = crate::cli::config::to_arguments(&config,&relaxed,&given).map_err(|e|Error::invalid_input(path,None,e))?;
+        let has_separator = arguments.iter().any(|a|a=="--");
+        arguments.splice(1..1,options.into_iter().map(|o|o.into()));
+        if !positionals.is_empty() && !has_separator { arguments.push("--".into()); }
+        arguments.extend(positionals.into_iter().map(|p|p.into()));
+    }
+    let command = Parameters::command();
+    let matches = command.clone().get_matches_from(arguments);
+    if matches.contains_id("print-config") {
+        print!("{}",toml::to_string(&crate::cli::config::effective(&command,&matches)).expect("A table of strings, numbers and lists can be written"));
+        std::process::exit(0);
+    }
+    let mut args = Parameters::from_arg_matches(&matches).unwrap_or_else(|e|e.exit());
+    let masks = std::mem::take(&mut args.mask);
+    args.prefixes.extend(masks);
+    Ok(args)
+}
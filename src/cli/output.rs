@@ -0,0 +1,490 @@
+// This program is Copyright 2022 Andrew Conway and licensed under the GPL:
+//
+// This program is free software: you can redistribute it and/or modify it under the terms of the GNU General Public License as published by the Free Software Foundation, either version 3 of the License, or (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful, but WITHOUT ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License along with this program. If not, see <https://www.gnu.org/licenses/>.
+
+//! Working out the names of the output files.
+
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use make_exam_numbers::Seed;
+use std::str::FromStr;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// The name used for `{prefix}` in a template when the prefix is empty.
+const EMPTY_PREFIX_NAME : &str = "all";
+
+/// The values that may be substituted into an output file name template.
+pub struct TemplateValues<'a> {
+    pub prefix : &'a str,
+    pub suffix : &'a str,
+    pub count : usize,
+    pub digits : usize,
+    pub date : &'a str,
+}
+
+/// The files a prefix's codes are split between with `--batch-size`, like `prefix_A_001.txt`, `prefix_A_002.txt`, ...
+pub struct Batches {
+    /// The file name there would be without batches, which the batch number is added to.
+    base : PathBuf,
+    /// The number of digits in each batch number.
+    width : usize,
+    /// The most codes in each file.
+    pub size : usize,
+    /// The number of codes written to each file so far, starting with the first file, which is always written.
+    pub counts : Vec<usize>,
+}
+
+impl Batches {
+    /// Batches of `size` codes for `total` codes that would otherwise be written to `base`. The batch numbers have at least 3 digits.
+    pub fn new(base:&Path,total:usize,size:usize) -> Self {
+        let num_batches = Self::num_batches(total,size);
+        Batches{base:base.to_path_buf(),width:num_batches.to_string().len().max(3),size,counts:vec![0]}
+    }
+
+    /// The number of files needed for `total` codes, in batches of `size`. There is always at least one.
+    pub fn num_batches(total:usize,size:usize) -> usize { total.div_ceil(size).max(1) }
+
+    /// The name of the file for batch `index`, starting from 0, which is numbered from 1.
+    pub fn path(&self,index:usize) -> PathBuf {
+        let stem = self.base.file_stem().unwrap_or_default().to_string_lossy();
+        let name = match self.base.extension() {
+            Some(extension) => format!("{}_{:0width$}.{}",stem,index+1,extension.to_string_lossy(),width=self.width),
+            None => format!("{}_{:0width$}",stem,index+1,width=self.width),
+        };
+        self.base.with_file_name(name)
+    }
+
+    /// Each file started, and how many codes have been written to it.
+    pub fn written(&self) -> Vec<(PathBuf,usize)> {
+        self.counts.iter().enumerate().map(|(index,&count)|(self.path(index),count)).collect()
+    }
+
+    /// Count another code about to be written. Returns the file to start writing to first, if the current one is full.
+    pub fn add(&mut self) -> Option<PathBuf> {
+        let full = self.counts.last()==Some(&self.size);
+        if full { self.counts.push(0); }
+        *self.counts.last_mut().expect("Never empty")+=1;
+        if full { Some(self.path(self.counts.len()-1)) } else { None }
+    }
+}
+
+/// What is added to the name of a prefix's file for the file of its spare codes.
+pub const SPARES_SUFFIX : &str = "_spares";
+
+/// The file the spare codes are written to for a prefix whose other codes are written to `path`, like `prefix_A_spares.txt` for `prefix_A.txt`.
+pub fn spares_path(path:&Path) -> PathBuf {
+    let stem = path.file_stem().unwrap_or_default().to_string_lossy();
+    let name = match path.extension() {
+        Some(extension) => format!("{}{}.{}",stem,SPARES_SUFFIX,extension.to_string_lossy()),
+        None => format!("{}{}",stem,SPARES_SUFFIX),
+    };
+    path.with_file_name(name)
+}
+
+/// Expand a template like `{date}_{prefix}_codes.csv`. An empty prefix is written as `all`.
+///
+/// `{{` and `}}` give literal braces. Unknown placeholders are an error.
+pub fn expand_template(template:&str,values:&TemplateValues) -> Result<String,String> {
+    let value = |name:&str| Some(match name {
+        "prefix" => if values.prefix.is_empty() { EMPTY_PREFIX_NAME } else { values.prefix }.to_string(),
+        "suffix" => values.suffix.to_string(),
+        "count" => values.count.to_string(),
+        "digits" => values.digits.to_string(),
+        "date" => values.date.to_string(),
+        _ => return None,
+    });
+    parse_template(template,value,|c,res|res.push(c))
+}
+
+/// A glob pattern matching every name `template` expands to, whatever the values, such as `*_*_codes.csv`.
+pub fn template_pattern(template:&str) -> Result<String,String> {
+    let value = |name:&str| ["prefix","suffix","count","digits","date"].contains(&name).then(||"*".to_string());
+    parse_template(template,value,|c,res|res.push_str(&glob::Pattern::escape(&c.to_string())))
+}
+
+/// Go through a template, replacing each placeholder with its `value` and adding other characters with `literal`.
+fn parse_template(template:&str,value:impl Fn(&str)->Option<String>,literal:impl Fn(char,&mut String)) -> Result<String,String> {
+    let mut res = String::new();
+    let mut chars = template.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '{' => {
+                let mut name = String::new();
+                loop {
+                    match chars.next() {
+                        Some('{') if name.is_empty() => { literal('{',&mut res); break; }
+                        Some('}') => {
+                            let value = value(&name).ok_or_else(||format!("Unknown placeholder {{{}}} in output template {}, expecting {{prefix}}, {{suffix}}, {{count}}, {{digits}} or {{date}}",name,template))?;
+                            res.push_str(&value);
+                            break;
+                        }
+                        Some(c) => name.push(c),
+                        None => return Err(format!("Unterminated placeholder in output template {}",template)),
+                    }
+                }
+            }
+            '}' => {
+                if chars.next()!=Some('}') { return Err(format!("Unmatched }} in output template {}, use }}}} for a literal }}",template)); }
+                literal('}',&mut res);
+            }
+            _ => literal(c,&mut res),
+        }
+    }
+    Ok(res)
+}
+
+/// The first line of a file written by `--combined`, which is how such files are recognised when read back.
+/// With `--with-pin` or `--group`, it is followed by `,pin` and `,display` (see [with_extra_columns]).
+pub const COMBINED_HEADER : &str = "code,prefix";
+
+/// The columns that may come after the usual ones in the CSV files written, in this order.
+const EXTRA_COLUMNS : [&str;2] = ["pin","display"];
+
+/// The header for a CSV file, with a `pin` column (for `--with-pin`) and a `display` column (for `--group`) if wanted.
+pub fn with_extra_columns(header:&str,pin:bool,display:bool) -> String {
+    let mut res = header.to_string();
+    for (column,wanted) in EXTRA_COLUMNS.iter().zip([pin,display]) {
+        if wanted { res.push(','); res.push_str(column); }
+    }
+    res
+}
+
+/// Quote a field for a CSV file if it contains anything that needs quoting.
+pub fn csv_field(s:&str) -> std::borrow::Cow<'_,str> {
+    if s.contains([',','"','\n','\r']) { format!("\"{}\"",s.replace('"',"\"\"")).into() } else { s.into() }
+}
+
+/// If a file's first line is the header of one of the CSV formats written by this program, the column the codes are in.
+pub fn csv_code_column(header:&str) -> Option<usize> {
+    let header = header.trim_end();
+    let extras_ok = |rest:&str| {
+        let mut extras = EXTRA_COLUMNS.iter();
+        rest.is_empty() || rest.starts_with(',') && rest[1..].split(',').all(|f|extras.any(|e|*e==f))
+    };
+    let known = [COMBINED_HEADER,CSV_HEADER].iter().any(|base|header.strip_prefix(base).map(extras_ok).unwrap_or(false));
+    if known { header.split(',').position(|f|f=="code") } else { None }
+}
+
+/// The first line of a per prefix file written with `--format csv`, followed by any extra columns as for [COMBINED_HEADER].
+pub const CSV_HEADER : &str = "prefix,code,sequence";
+
+/// How codes are shown with `--group`, such as `123 456 789`, to make them easier to read and copy.
+#[derive(Clone,Debug,PartialEq,Eq)]
+pub struct Grouping {
+    /// The number of characters in each group.
+    pub size : usize,
+    pub separator : String,
+}
+
+impl Grouping {
+    /// The code split into groups of `size` characters, counting from the end as for thousands separators, so only the first group may be shorter.
+    pub fn display(&self,code:&str) -> String {
+        let chars : Vec<char> = code.chars().collect();
+        let first = match chars.len()%self.size { 0 => self.size, n => n };
+        let mut res : String = chars.iter().take(first).collect();
+        for group in chars[first.min(chars.len())..].chunks(self.size) {
+            res.push_str(&self.separator);
+            res.extend(group);
+        }
+        res
+    }
+}
+
+/// One code to be written, with what goes with it.
+pub struct Record<'a> {
+    pub prefix : &'a str,
+    pub code : &'a str,
+    /// The (1 based) position of the code within its prefix.
+    pub sequence : usize,
+    /// The code's PIN, with `--with-pin`.
+    pub pin : Option<&'a str>,
+    /// The code as it is shown, with `--group`.
+    pub display : Option<&'a str>,
+}
+
+impl Record<'_> {
+    /// The PIN and how the code is shown, as (name, value), for those it has.
+    fn extras(&self) -> impl Iterator<Item=(&'static str,&str)> {
+        EXTRA_COLUMNS.into_iter().zip([self.pin,self.display]).filter_map(|(name,value)|value.map(|v|(name,v)))
+    }
+}
+
+/// Things written at the start of some output formats.
+pub struct OutputInfo<'a> {
+    pub prefix : &'a str,
+    pub digits : usize,
+    /// The minimum distance as written for the metric, like 3 or 2.5.
+    pub min_distance : &'a str,
+    pub seed : Option<Seed>,
+    /// With `--with-pin`, the number of digits in each PIN.
+    pub pin_digits : Option<usize>,
+    /// Whether codes are also written as shown, with `--group`.
+    pub display : bool,
+    /// With `--write-header`, today's date, for a comment line recording how the codes were made.
+    pub date : Option<&'a str>,
+}
+
+/// A comment line recording how codes were made, like `# generated 2023-05-01 by make_exam_numbers 0.1.0, prefix A, 5 random digits, seed 42, distance 3`,
+/// for `--write-header`. `prefix` is the prefix of every code and how many random digits they have, if they share them.
+pub fn provenance(date:&str,seed:Seed,min_distance:&str,prefix:Option<(&str,usize)>) -> String {
+    let prefix = match prefix {
+        Some(("",digits)) => format!(", {} random digits",digits),
+        Some((prefix,digits)) => format!(", prefix {}, {} random digits",prefix,digits),
+        None => String::new(),
+    };
+    format!("# generated {} by make_exam_numbers {}{}, seed {}, distance {}",date,env!("CARGO_PKG_VERSION"),prefix,seed,min_distance)
+}
+
+/// How the per prefix output files are written.
+#[derive(Clone,Copy,Debug,PartialEq,Eq)]
+pub enum Format {
+    /// One code per line.
+    Text,
+    /// A CSV file with columns prefix, code and sequence (the 1 based position within the prefix).
+    Csv,
+    /// A JSON object with the prefix, parameters and an array of codes. It is written as the codes are found.
+    Json,
+    /// One JSON object per line for each code, with the prefix, code and sequence.
+    JsonLines,
+}
+
+impl FromStr for Format {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "text" => Ok(Format::Text),
+            "csv" => Ok(Format::Csv),
+            "json" => Ok(Format::Json),
+            "jsonl" => Ok(Format::JsonLines),
+            _ => Err(format!("Unknown format {}, expecting text, csv, json or jsonl",s)),
+        }
+    }
+}
+
+/// The case codes are written in, for alphabets that ignore case.
+#[derive(Clone,Copy,Debug,PartialEq,Eq)]
+pub enum Case {
+    Upper,
+    Lower,
+}
+
+impl FromStr for Case {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "upper" => Ok(Case::Upper),
+            "lower" => Ok(Case::Lower),
+            _ => Err(format!("Unknown case {}, expecting upper or lower",s)),
+        }
+    }
+}
+
+impl Case {
+    /// A code, made in upper case, in this case.
+    pub fn apply<'a>(&self,code:&'a str) -> std::borrow::Cow<'a,str> {
+        match self {
+            Case::Upper => code.into(),
+            Case::Lower => code.to_lowercase().into(),
+        }
+    }
+}
+
+/// How a report listing several things is printed.
+#[derive(Clone,Copy,Debug,PartialEq,Eq)]
+pub enum TableFormat {
+    /// Aligned columns, for reading.
+    Table,
+    /// CSV with a header line, for other programs.
+    Csv,
+}
+
+impl FromStr for TableFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "table" => Ok(TableFormat::Table),
+            "csv" => Ok(TableFormat::Csv),
+            _ => Err(format!("Unknown format {}, expecting table or csv",s)),
+        }
+    }
+}
+
+impl TableFormat {
+    /// Print the rows (the first being the column headings) to standard output.
+    pub fn print(&self,rows:&[Vec<String>]) {
+        match self {
+            TableFormat::Table => {
+                let mut widths : Vec<usize> = vec![];
+                for row in rows {
+                    for (i,field) in row.iter().enumerate() {
+                        let width = field.chars().count();
+                        if i<widths.len() { widths[i]=widths[i].max(width); } else { widths.push(width); }
+                    }
+                }
+                for row in rows {
+                    let fields : Vec<String> = row.iter().zip(&widths).map(|(field,&width)|format!("{:<width$}",field,width=width)).collect();
+                    println!("{}",fields.join("  ").trim_end());
+                }
+            }
+            TableFormat::Csv => {
+                for row in rows { println!("{}",row.iter().map(|f|csv_field(f)).collect::<Vec<_>>().join(",")); }
+            }
+        }
+    }
+}
+
+/// How the result of looking up a code is printed.
+#[derive(Clone,Copy,Debug,PartialEq,Eq)]
+pub enum LookupFormat {
+    /// A table of the nearest codes and a sentence saying whether there is an unambiguous correction, for reading.
+    Table,
+    /// A JSON object, for other programs.
+    Json,
+}
+
+impl FromStr for LookupFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "table" => Ok(LookupFormat::Table),
+            "json" => Ok(LookupFormat::Json),
+            _ => Err(format!("Unknown format {}, expecting table or json",s)),
+        }
+    }
+}
+
+fn json_string(s:&str) -> String { serde_json::Value::from(s).to_string() }
+
+impl Format {
+    /// The usual file extension for this format.
+    pub fn extension(&self) -> &'static str {
+        match self {
+            Format::Text => "txt",
+            Format::Csv => "csv",
+            Format::Json => "json",
+            Format::JsonLines => "jsonl",
+        }
+    }
+
+    /// Write whatever comes before the first code.
+    pub fn write_header(&self,w:&mut impl Write,info:&OutputInfo) -> std::io::Result<()> {
+        if let (Some(date),Some(seed),false) = (info.date,info.seed,*self==Format::Json) { writeln!(w,"{}",provenance(date,seed,info.min_distance,Some((info.prefix,info.digits))))?; }
+        match self {
+            Format::Text | Format::JsonLines => Ok(()),
+            Format::Csv => writeln!(w,"{}",with_extra_columns(CSV_HEADER,info.pin_digits.is_some(),info.display)),
+            Format::Json => {
+                let seed = match info.seed {
+                    Some(Seed::U64(seed)) => seed.to_string(),
+                    Some(seed) => json_string(&seed.to_string()),
+                    None => "null".to_string(),
+                };
+                let pin_digits = match info.pin_digits { Some(digits) => format!(",\"pin_digits\":{}",digits), None => String::new() };
+                write!(w,"{{\"prefix\":{},\"digits\":{},\"min_hamming_distance\":{},\"seed\":{}{},\"codes\":[",json_string(info.prefix),info.digits,info.min_distance,seed,pin_digits)
+            }
+        }
+    }
+
+    /// Write a code, with its PIN and how it is shown if it has them.
+    ///
+    /// With either, `--format json` gives an object with the code and them instead of just the code.
+    /// `Text` just gives how the code is shown, and can't show a PIN.
+    pub fn write_code(&self,w:&mut impl Write,record:&Record) -> std::io::Result<()> {
+        match self {
+            Format::Text => writeln!(w,"{}",record.display.unwrap_or(record.code)),
+            Format::Csv => {
+                write!(w,"{},{},{}",csv_field(record.prefix),csv_field(record.code),record.sequence)?;
+                for (_,value) in record.extras() { write!(w,",{}",csv_field(value))?; }
+                writeln!(w)
+            }
+            Format::Json => {
+                let separator = if record.sequence==1 { "" } else { "," };
+                if record.extras().next().is_none() { return write!(w,"{}\n  {}",separator,json_string(record.code)); }
+                let mut object = serde_json::Map::new();
+                object.insert("code".to_string(),record.code.into());
+                for (name,value) in record.extras() { object.insert(name.to_string(),value.into()); }
+                write!(w,"{}\n  {}",separator,serde_json::Value::Object(object))
+            }
+            Format::JsonLines => {
+                let mut object = serde_json::Map::new();
+                object.insert("prefix".to_string(),record.prefix.into());
+                object.insert("code".to_string(),record.code.into());
+                object.insert("sequence".to_string(),record.sequence.into());
+                for (name,value) in record.extras() { object.insert(name.to_string(),value.into()); }
+                writeln!(w,"{}",serde_json::Value::Object(object))
+            }
+        }
+    }
+
+    /// Write whatever comes after the last code.
+    pub fn write_footer(&self,w:&mut impl Write) -> std::io::Result<()> {
+        match self {
+            Format::Json => writeln!(w,"\n]}}"),
+            _ => Ok(()),
+        }
+    }
+}
+
+/// Get the codes from a file written with `--format json` or `jsonl`, with the (1 based) line number each is on.
+///
+/// For a single JSON document, the line numbers assume one code per line after the first, as written by this program.
+pub fn parse_json_codes(text:&str) -> Result<Vec<(usize,String)>,String> {
+    // codes written with a PIN are objects with the code and the PIN.
+    let as_string = |v:&serde_json::Value| v.get("code").unwrap_or(v).as_str().map(|s|s.to_string()).ok_or_else(||format!("Expecting a string code, found {}",v));
+    if let Ok(serde_json::Value::Object(document)) = serde_json::from_str::<serde_json::Value>(text) {
+        if let Some(codes) = document.get("codes") {
+            return codes.as_array().ok_or_else(||"Expecting codes to be an array".to_string())?.iter().enumerate().map(|(i,v)|Ok((i+2,as_string(v)?))).collect();
+        }
+    }
+    text.lines().enumerate().filter(|(_,l)|!l.trim().is_empty()).map(|(i,line)|{
+        let value : serde_json::Value = serde_json::from_str(line).map_err(|e|format!("Could not parse {} as JSON : {}",line,e))?;
+        Ok((i+1,as_string(value.get("code").ok_or_else(||format!("No code in {}",line))?)?))
+    }).collect()
+}
+
+/// Get the PINs from a file written with `--format json` or `jsonl` and `--with-pin`. Anything without a PIN is ignored.
+pub fn parse_json_pins(text:&str) -> Vec<String> {
+    let pin = |v:&serde_json::Value| v.get("pin").and_then(|p|p.as_str()).map(|p|p.to_string());
+    if let Ok(serde_json::Value::Object(document)) = serde_json::from_str::<serde_json::Value>(text) {
+        return document.get("codes").and_then(|c|c.as_array()).into_iter().flatten().filter_map(pin).collect();
+    }
+    text.lines().filter_map(|line|serde_json::from_str::<serde_json::Value>(line).ok()).filter_map(|v|pin(&v)).collect()
+}
+
+/// Seconds since 1970-01-01 UTC.
+fn now_seconds() -> u64 { SystemTime::now().duration_since(UNIX_EPOCH).map(|d|d.as_secs()).unwrap_or(0) }
+
+/// Convert days since 1970-01-01 to a (year,month,day) civil date, using Howard Hinnant's algorithm.
+fn civil_from_days(days:i64) -> (i64,i64,i64) {
+    let z = days+719468;
+    let era = z.div_euclid(146097);
+    let doe = z.rem_euclid(146097);
+    let yoe = (doe-doe/1460+doe/36524-doe/146096)/365;
+    let doy = doe-(365*yoe+yoe/4-yoe/100);
+    let mp = (5*doy+2)/153;
+    let day = doy-(153*mp+2)/5+1;
+    let month = if mp<10 { mp+3 } else { mp-9 };
+    let year = yoe+era*400+if month<=2 { 1 } else { 0 };
+    (year,month,day)
+}
+
+/// Today's date (UTC) as YYYY-MM-DD.
+pub fn today() -> String {
+    let (year,month,day) = civil_from_days((now_seconds()/86400) as i64);
+    format!("{:04}-{:02}-{:02}",year,month,day)
+}
+
+/// The current time (UTC) as YYYY-MM-DDTHH-MM-SSZ, which is usable in file names.
+pub fn timestamp() -> String {
+    let seconds = now_seconds();
+    let (year,month,day) = civil_from_days((seconds/86400) as i64);
+    let of_day = seconds%86400;
+    format!("{:04}-{:02}-{:02}T{:02}-{:02}-{:02}Z",year,month,day,of_day/3600,(of_day/60)%60,of_day%60)
+}
@@ -0,0 +1,103 @@
+// This program is Copyright 2022 Andrew Conway and licensed under the GPL:
+//
+// This program is free software: you can redistribute it and/or modify it under the terms of the GNU General Public License as published by the Free Software Foundation, either version 3 of the License, or (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful, but WITHOUT ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License along with this program. If not, see <https://www.gnu.org/licenses/>.
+
+//! Progress display while generating the codes for one prefix, or for all of them with `--interleave`.
+
+use std::collections::VecDeque;
+use crate::cli::report;
+use std::time::{Duration, Instant};
+
+/// How many of the most recent codes are used to estimate the current acceptance rate.
+const RECENT_CODES : usize = 100;
+
+/// Shows how far through a prefix we are. On a terminal the line is redrawn in place frequently;
+/// otherwise (e.g. when piped to a file) a new line is printed occasionally.
+pub struct Progress {
+    /// What the codes are for, like "Prefix A".
+    label : String,
+    wanted : usize,
+    found : usize,
+    /// Total candidates tried.
+    tried : u64,
+    /// Candidates tried since the last code was found.
+    tried_this_code : u64,
+    /// Candidates tried for each of the most recent codes found.
+    recent : VecDeque<u64>,
+    start : Instant,
+    last_shown : Option<Instant>,
+    in_place : bool,
+}
+
+impl Progress {
+    /// Start showing progress. If `in_place` is requested but messages are not going to a terminal, occasional lines are printed instead.
+    /// Nothing is shown with `--quiet`.
+    pub fn new(prefix:&str,wanted:usize,in_place:bool) -> Self {
+        Progress::labelled(format!("Prefix {}",prefix),wanted,in_place)
+    }
+
+    /// As [Progress::new], for the codes for all the prefixes together.
+    pub fn all_prefixes(wanted:usize,in_place:bool) -> Self {
+        Progress::labelled("All prefixes".to_string(),wanted,in_place)
+    }
+
+    fn labelled(label:String,wanted:usize,in_place:bool) -> Self {
+        Progress{label,wanted,found:0,tried:0,tried_this_code:0,recent:VecDeque::new(),start:Instant::now(),last_shown:None,in_place:in_place && report::is_terminal()}
+    }
+
+    /// Note that a candidate was rejected.
+    pub fn rejected(&mut self) {
+        self.tried+=1;
+        self.tried_this_code+=1;
+        self.show_if_due();
+    }
+
+    /// Note that a code was found.
+    pub fn found(&mut self) {
+        self.tried+=1;
+        self.tried_this_code+=1;
+        self.found+=1;
+        if self.recent.len()==RECENT_CODES { self.recent.pop_front(); }
+        self.recent.push_back(self.tried_this_code);
+        self.tried_this_code=0;
+        self.show_if_due();
+    }
+
+    /// Show the final state.
+    pub fn finish(&mut self) {
+        self.show();
+        if self.in_place && !report::is_quiet() { report::write(format_args!("\n")); }
+    }
+
+    fn show_if_due(&mut self) {
+        let interval = if self.in_place { Duration::from_millis(100) } else { Duration::from_secs(5) };
+        if self.last_shown.map(|t|t.elapsed()>=interval).unwrap_or(true) { self.show(); }
+    }
+
+    fn show(&mut self) {
+        if report::is_quiet() { return; }
+        let recent_tried : u64 = self.recent.iter().sum::<u64>()+self.tried_this_code;
+        let acceptance = if recent_tried==0 { 1.0 } else { self.recent.len() as f64/recent_tried as f64 };
+        let elapsed = self.start.elapsed().as_secs_f64();
+        let eta = if self.recent.is_empty() || self.tried==0 { "unknown".to_string() } else {
+            let seconds_per_candidate = elapsed/self.tried as f64;
+            let remaining = (self.wanted-self.found) as f64/acceptance.max(f64::MIN_POSITIVE);
+            format_duration(remaining*seconds_per_candidate)
+        };
+        let line = format!("{} : found {} of {}, tried {} candidates, recent acceptance rate {:.2}%, time left {}",self.label,self.found,self.wanted,self.tried,100.0*acceptance,eta);
+        if self.in_place { report::write(format_args!("\r{}\x1b[K",line)); } else { report::write(format_args!("{}\n",line)); }
+        self.last_shown=Some(Instant::now());
+    }
+}
+
+fn format_duration(seconds:f64) -> String {
+    if !seconds.is_finite() { return "unknown".to_string(); }
+    let seconds = seconds.round() as u64;
+    if seconds<60 { format!("{}s",seconds) }
+    else if seconds<3600 { format!("{}m{:02}s",seconds/60,seconds%60) }
+    else { format!("{}h{:02}m",seconds/3600,(seconds%3600)/60) }
+}
@@ -0,0 +1,72 @@
+// This program is Copyright 2022 Andrew Conway and licensed under the GPL:
+//
+// This program is free software: you can redistribute it and/or modify it under the terms of the GNU General Public License as published by the Free Software Foundation, either version 3 of the License, or (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful, but WITHOUT ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License along with this program. If not, see <https://www.gnu.org/licenses/>.
+
+//! Messages to the user. Warnings and errors always go to stderr. Routine messages go to stdout for subcommands whose output they are,
+//! and to stderr when making codes, so stdout is kept for data.
+
+use std::io::{IsTerminal, Write};
+use std::sync::atomic::{AtomicBool, Ordering};
+use make_exam_numbers::distance::Metric;
+use make_exam_numbers::verify::Problem;
+
+static QUIET : AtomicBool = AtomicBool::new(false);
+static TO_STDERR : AtomicBool = AtomicBool::new(false);
+
+/// Set whether routine messages are suppressed, and whether they go to stderr instead of stdout.
+pub fn configure(quiet:bool,to_stderr:bool) {
+    QUIET.store(quiet,Ordering::Relaxed);
+    TO_STDERR.store(to_stderr,Ordering::Relaxed);
+}
+
+/// Whether routine messages are suppressed.
+pub fn is_quiet() -> bool { QUIET.load(Ordering::Relaxed) }
+
+/// Whether messages are going to a terminal.
+pub fn is_terminal() -> bool {
+    if TO_STDERR.load(Ordering::Relaxed) { std::io::stderr().is_terminal() } else { std::io::stdout().is_terminal() }
+}
+
+/// Write a routine message, without adding a new line, and flush it. Used by [report!].
+pub fn write(message:std::fmt::Arguments) {
+    if TO_STDERR.load(Ordering::Relaxed) { write_stderr(message); } else {
+        let mut stdout = std::io::stdout().lock();
+        let _ = stdout.write_fmt(message);
+        let _ = stdout.flush();
+    }
+}
+
+/// Write a message to stderr, without adding a new line, and flush it. Used by [alert!].
+pub fn write_stderr(message:std::fmt::Arguments) {
+    let mut stderr = std::io::stderr().lock();
+    let _ = stderr.write_fmt(message);
+    let _ = stderr.flush();
+}
+
+/// A description of a problem found by [find_problems](make_exam_numbers::verify::find_problems) with distances in units of `metric`, describing each code referred to with `describe`.
+pub fn describe_problem(problem:&Problem,metric:Metric,describe:impl Fn(usize)->String) -> String {
+    match *problem {
+        Problem::Duplicate { first, second } => format!("Duplicate : {} and {}",describe(first),describe(second)),
+        Problem::InconsistentLength { index, length, expected } => format!("Length {} instead of {} : {}",length,expected,describe(index)),
+        Problem::TooClose { first, second, distance } => format!("Distance {} : {} and {}",metric.show_distance(distance),describe(first),describe(second)),
+        Problem::TooCloseReversed { first, second, distance } => format!("Distance {} reversed : {} and {} read backwards",metric.show_distance(distance),describe(first),describe(second)),
+        Problem::InvalidCheckDigit { index } => format!("Invalid check digit : {}",describe(index)),
+        Problem::WrongDigitSum { index, sum, wanted } => format!("Digit sum {} is not {} modulo {} : {}",sum,wanted.remainder,wanted.modulus,describe(index)),
+    }
+}
+
+/// Print a routine message, unless `--quiet` was given.
+macro_rules! report {
+    ($($arg:tt)*) => { if !$crate::cli::report::is_quiet() { $crate::cli::report::write(format_args!("{}\n",format_args!($($arg)*))) } };
+}
+
+/// Print a message to stderr that should be shown even with `--quiet`, such as a warning or error.
+macro_rules! alert {
+    ($($arg:tt)*) => { $crate::cli::report::write_stderr(format_args!("{}\n",format_args!($($arg)*))) };
+}
+
+pub(crate) use {alert, report};
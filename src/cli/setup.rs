@@ -0,0 +1,228 @@
+// This program is Copyright 2022 Andrew Conway and licensed under the GPL:
+//
+// This program is free software: you can redistribute it and/or modify it under the terms of the GNU General Public License as published by the Free Software Foundation, either version 3 of the License, or (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful, but WITHOUT ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License along with this program. If not, see <https://www.gnu.org/licenses/>.
+
+//! Setting up the generator from the options when making codes, checking the options make sense together.
+
+use std::path::Path;
+use make_exam_numbers::{GenerateCodes, Seed, Strategy};
+use make_exam_numbers::alphabet::{Alphabet, CONFUSABLE_CLASSES};
+use make_exam_numbers::filter::Filter;
+use make_exam_numbers::distance::{LengthPolicy, Metric, Scope};
+use crate::cli::error::Error;
+use crate::cli::input::{check_range, LengthHandling, read_blocklist, read_confusable_classes, Validity, with_weights};
+use crate::cli::interrupt;
+use crate::cli::layout::{canonical_layout, check_prefixes, random_part_last};
+use crate::cli::options::Parameters;
+use crate::cli::output::{Case, Format, Grouping};
+use crate::cli::report::{alert, report};
+
+/// The generator, and what else is worked out from the options before any files are read.
+pub struct Setup {
+    pub metric : Metric,
+    /// The minimum distance between codes with the same prefix, in units of the metric.
+    pub min_hamming_distance : usize,
+    /// The minimum distance between codes with different prefixes, if different.
+    pub min_distance_across : Option<usize>,
+    /// Each prefix's own minimum distance.
+    pub min_distances : Vec<usize>,
+    pub digits : usize,
+    pub alphabet : Alphabet,
+    pub seed : Seed,
+    pub generator : GenerateCodes,
+    /// The separate generator for `--with-pin`.
+    pub pins : Option<GenerateCodes>,
+    /// The number of check digit characters at the end of each code.
+    pub check_len : usize,
+    pub grouping : Option<Grouping>,
+    /// What existing codes are checked against.
+    pub validity : Validity,
+}
+
+impl Setup {
+    /// Set up the generator for `args`, or say why the options can't be used together.
+    pub fn new(args:&Parameters) -> Result<Setup,Error> {
+        let metric = with_weights(args.metric,args.weights.as_deref(),Path::new(""))?;
+        let usual = args.min_hamming_distance.as_ref().expect("Required by clap").units(metric).map_err(Error::Arguments)?;
+        let min_hamming_distance = args.min_hamming_within.as_ref().map(|d|d.units(metric)).transpose().map_err(Error::Arguments)?.unwrap_or(usual);
+        let across = args.min_hamming_across.as_ref().map(|d|d.units(metric)).transpose().map_err(Error::Arguments)?.unwrap_or(usual);
+        // with the same distance within and across prefixes, codes are made just as if no prefixes had been given.
+        let min_distance_across = Some(across).filter(|&across|across!=min_hamming_distance);
+        // each prefix's own minimum distance, like PG:300:d5, or the usual one.
+        let min_distances : Vec<usize> = args.prefixes.iter().map(|p| match &p.min_distance {
+            Some(d) => metric.parse_distance(d).map_err(|e|Error::Arguments(format!("Prefix {} : {}",p.name(),e))),
+            None => Ok(min_hamming_distance),
+        }).collect::<Result<_,_>>()?;
+        let digits = args.digits.expect("Required by clap");
+        let alphabet = choose_alphabet(args)?;
+        if let Some(p) = args.prefixes.iter().find(|p|p.number>args.max_codes) {
+            return Err(Error::Arguments(format!("Prefix {:?} asks for {} codes, more than the --max-codes limit of {}. If that many are really wanted, give a larger --max-codes.",p.name(),p.number,args.max_codes)));
+        }
+        check_prefixes(&args.prefixes,&alphabet,digits,args.check_prefix_distance)?;
+        if let Some(p) = args.prefixes.iter().find(|p|!random_part_last(&p.layout(digits))) {
+            // these take the random part to be the last characters of each code.
+            let problem = if args.distance_on_body { Some("--distance-on-body") } else if args.min_numeric_gap.is_some() { Some("--min-numeric-gap") } else { None };
+            if let Some(problem) = problem { return Err(Error::Arguments(format!("{} can't be used with fixed characters after the random ones, as asked for by {}",problem,p.name()))); }
+        }
+        // choose a seed if none was given, so it can be recorded in the manifest.
+        let seed = args.seed.unwrap_or_else(||Seed::Key(rand::random()));
+        // the PINs have their own generator, so the codes are the same as without them.
+        let mut pins = match args.with_pin {
+            Some(0) => return Err(Error::Arguments("--with-pin must be at least 1".to_string())),
+            Some(_) if args.format==Format::Text => return Err(Error::Arguments("--with-pin needs a PIN column, so can't be used with --format text. Try --format csv.".to_string())),
+            Some(pin_digits) => Some(GenerateCodes::from_seed(Some(seed.derive("pin")),pin_digits)),
+            None => None,
+        };
+        let mut generator = GenerateCodes::from_seed(Some(seed),digits);
+        generator.set_interrupt(interrupt::install()?);
+        generator.set_per_prefix_streams(!args.shared_seed_stream);
+        generator.set_strategy(args.strategy);
+        generator.set_alphabet(alphabet.clone());
+        generator.set_metric(metric);
+        for (p,&min_distance) in args.prefixes.iter().zip(&min_distances) {
+            generator.check_construction(&p.prefix,p.digits_or(digits),min_distance)?;
+        }
+        if let Some(across) = min_distance_across {
+            if args.scope==Scope::PerPrefix { return Err(Error::Arguments("--min-hamming-across can't be used with --scope per-prefix, which doesn't compare codes with different prefixes at all".to_string())); }
+            if let Some(p) = args.prefixes.iter().find(|p|p.min_distance.is_some()) { return Err(Error::Arguments(format!("Prefix {} has its own minimum distance, so --min-hamming-within and --min-hamming-across can't be used",p.name()))); }
+            generator.set_min_distance_across(Some(across));
+        }
+        generator.declare_prefixes(args.prefixes.iter().map(|p|p.prefix.as_str()));
+        if args.prefixes.iter().any(|p|p.min_distance.is_some()) {
+            // codes are told apart by their prefixes, so prefixes that start the same way must have the same minimum distance.
+            for (i,(p,&d)) in args.prefixes.iter().zip(&min_distances).enumerate() {
+                if let Some((q,_)) = args.prefixes.iter().zip(&min_distances).skip(i+1).find(|(q,&e)|d!=e && alphabet.normalise(&q.prefix)==alphabet.normalise(&p.prefix)) {
+                    return Err(Error::Arguments(format!("Prefixes {} and {} have the same fixed characters at the start, so can't have different minimum distances",p.name(),q.name())));
+                }
+                generator.set_min_distance_for(&p.prefix,d);
+            }
+        }
+        if args.strategy==Strategy::Exhaustive {
+            for p in &args.prefixes {
+                let p_digits = p.digits_or(digits) as u32;
+                let size = (alphabet.size() as u64).checked_pow(p_digits);
+                if size.map(|size|size>args.exhaustive_limit).unwrap_or(true) {
+                    return Err(Error::Arguments(format!("--strategy exhaustive would need to hold {} possible random parts for {} digits from an alphabet of {}, more than the --exhaustive-limit of {}",
+                        size.map(|s|s.to_string()).unwrap_or_else(||format!("{}^{}",alphabet.size(),p_digits)),p_digits,alphabet.size(),args.exhaustive_limit)));
+                }
+            }
+        }
+        generator.set_check_digit(args.check_digit);
+        generator.set_no_leading_zero(args.no_leading_zero);
+        generator.set_scope(args.scope);
+        generator.set_distance_on_body(args.distance_on_body);
+        if args.include_reversals && args.distance_on_body { return Err(Error::Arguments("--include-reversals compares whole codes, so can't be used with --distance-on-body".to_string())); }
+        generator.set_include_reversals(args.include_reversals);
+        if args.include_rotations && args.distance_on_body { return Err(Error::Arguments("--include-rotations compares whole codes, so can't be used with --distance-on-body".to_string())); }
+        generator.set_include_rotations(args.include_rotations);
+        generator.set_length_policy(match args.length_policy { LengthHandling::Policy(policy) => policy, LengthHandling::Error => LengthPolicy::default() });
+        let check_len = args.check_digit.map(|c|c.num_characters()).unwrap_or(0);
+        let mut expected_lengths : Vec<usize> = args.prefixes.iter().map(|p|canonical_layout(p,&alphabet,digits).len()+check_len).collect();
+        expected_lengths.sort_unstable();
+        expected_lengths.dedup();
+        // existing codes are checked against the shortest random part, as codes with a longer one may be compared against it.
+        let shortest_digits = args.prefixes.iter().map(|p|p.digits_or(digits)).min().unwrap_or(digits);
+        let grouping = match args.group {
+            Some(0) => return Err(Error::Arguments("--group must be at least 1".to_string())),
+            Some(_) if args.separator.chars().any(|c|alphabet.contains(c)) => return Err(Error::Arguments(format!("--separator {:?} can't contain characters from the alphabet {}",args.separator,alphabet))),
+            Some(size) => Some(Grouping{size,separator:args.separator.clone()}),
+            None => None,
+        };
+        let separators : Vec<char> = args.separator.chars().filter(|_|grouping.is_some()).chain([' ','-']).filter(|&c|!alphabet.contains(c)).collect();
+        let layouts : Vec<Vec<Option<char>>> = args.prefixes.iter().filter(|p|!random_part_last(&p.layout(digits))).map(|p|canonical_layout(p,&alphabet,digits)).collect();
+        let lengths = if args.length_policy==LengthHandling::Error && !args.distance_on_body && !expected_lengths.is_empty() { Some(expected_lengths) } else { None };
+        let validity = Validity{alphabet:alphabet.clone(),digits:shortest_digits,check_digit:args.check_digit,lengths,separators,layouts};
+        generator.set_threads(args.threads).map_err(|e|Error::Arguments(format!("Could not start {} threads : {}",args.threads,e)))?;
+        generator.set_max_attempts(if args.max_attempts==0 { None } else { Some(args.max_attempts) });
+        if let Some(pins) = &mut pins { pins.set_max_attempts(if args.max_attempts==0 { None } else { Some(args.max_attempts) }); }
+        add_filters(args,&mut generator,&alphabet,shortest_digits)?;
+        if args.barcodes.is_some() {
+            let cased = |s:&str| args.output_case.apply(s).chars().collect::<Vec<char>>();
+            let fixed : Vec<char> = args.prefixes.iter().flat_map(|p|cased(&p.prefix).into_iter().chain(cased(&p.suffix))).collect();
+            crate::cli::barcodes::check_characters(args.barcode_format,alphabet.characters().iter().flat_map(|c|cased(&c.to_string())).chain(fixed))?;
+        }
+        Ok(Setup{metric,min_hamming_distance,min_distance_across,min_distances,digits,alphabet,seed,generator,pins,check_len,grouping,validity})
+    }
+}
+
+/// The alphabet the random characters are drawn from, with the options that change it applied.
+fn choose_alphabet(args:&Parameters) -> Result<Alphabet,Error> {
+    let mut alphabet = args.charset.clone().unwrap_or_else(||args.alphabet.clone());
+    if args.unambiguous { alphabet = alphabet.unambiguous().map_err(Error::Arguments)?; }
+    if args.case_insensitive { alphabet = alphabet.ignoring_case().map_err(Error::Arguments)?; }
+    if args.confusable_classes { alphabet = alphabet.with_confusables(&CONFUSABLE_CLASSES).map_err(Error::Arguments)?; }
+    if let Some(path) = &args.confusable_classes_file {
+        let groups = read_confusable_classes(path)?;
+        alphabet = alphabet.with_confusables(&groups.iter().map(|g|g.as_str()).collect::<Vec<_>>()).map_err(|e|Error::invalid_input(path,None,e))?;
+    }
+    if args.output_case==Case::Lower && !alphabet.ignores_case() { return Err(Error::Arguments(format!("--output-case lower can only be used with an alphabet that ignores case, not {}. Try --case-insensitive.",alphabet))); }
+    if alphabet!=Alphabet::default() { report!("Using alphabet {}",alphabet); }
+    if args.no_leading_zero && alphabet.characters().iter().all(|&c|c=='0') { return Err(Error::Arguments(format!("--no-leading-zero can't be used with alphabet {} as it has no characters other than 0",alphabet))); }
+    Ok(alphabet)
+}
+
+/// Add the filters that reject some candidates, and the other limits on which random parts can be used.
+/// `shortest_digits` is the fewest random characters any prefix has.
+fn add_filters(args:&Parameters,generator:&mut GenerateCodes,alphabet:&Alphabet,shortest_digits:usize) -> Result<(),Error> {
+    if let Some(max_run) = args.max_run {
+        if max_run==0 { return Err(Error::Arguments("--max-run must be at least 1".to_string())); }
+        generator.add_filter(Filter::MaxRun(max_run));
+    }
+    if let Some(max) = args.max_sequential {
+        if max==0 { return Err(Error::Arguments("--max-sequential must be at least 1".to_string())); }
+        generator.add_filter(Filter::MaxSequential{max,include_prefix:args.sequential_include_prefix,ordering:alphabet.characters().to_vec()});
+    }
+    if let Some(min) = args.min_distinct {
+        if min>shortest_digits || min>alphabet.size() { return Err(Error::Arguments(format!("--min-distinct {} is impossible, as codes have {} random characters from an alphabet of {}",min,shortest_digits,alphabet.size()))); }
+        generator.add_filter(Filter::MinDistinct(min));
+    }
+    if let Some(sum) = args.digit_sum_mod { generator.add_filter(Filter::DigitSum{sum,values:alphabet.characters().to_vec()}); }
+    if let Some(gap) = args.min_numeric_gap {
+        if !alphabet.characters().iter().all(|c|c.is_ascii_digit()) { return Err(Error::Arguments(format!("--min-numeric-gap can only be used with an alphabet of digits, not {}",alphabet))); }
+        generator.set_min_numeric_gap(Some(gap));
+    }
+    if let Some(range) = &args.range {
+        check_range(range,alphabet,shortest_digits,args.no_leading_zero)?;
+        if args.strategy==Strategy::Algebraic { return Err(Error::Arguments("--range can't be used with --strategy algebraic, whose codes come from a construction".to_string())); }
+        generator.set_range(Some(range.clone()));
+    }
+    if !args.exclude_range.is_empty() {
+        if !alphabet.is_decimal() { return Err(Error::Arguments(format!("--exclude-range can only be used with the default alphabet of digits, not {}",alphabet))); }
+        let overall = args.range.clone().unwrap_or(0..10u64.checked_pow(shortest_digits as u32).unwrap_or(u64::MAX));
+        if let Some(r) = args.exclude_range.iter().find(|r|r.start<overall.start || r.end>overall.end) {
+            return Err(Error::Arguments(format!("--exclude-range {}..{} is not within {}..{}, the numbers the random digits are drawn from",r.start,r.end,overall.start,overall.end)));
+        }
+        let mut ranges = args.exclude_range.clone();
+        ranges.sort_by_key(|r|r.start);
+        let mut excluded = 0;
+        let mut end = 0; // the end of the ranges counted so far, so overlaps are only counted once.
+        for r in &ranges {
+            let start = r.start.max(end);
+            if r.end>start { excluded+=r.end-start; end=r.end; }
+        }
+        if excluded>(overall.end-overall.start)/2 {
+            alert!("Warning : --exclude-range excludes {} of the {} numbers in {}..{}, so most candidates will be rejected, and fewer codes can fit",excluded,overall.end-overall.start,overall.start,overall.end);
+        }
+        generator.add_filter(Filter::ExcludeRanges(args.exclude_range.clone()));
+    }
+    if args.balance_first {
+        if args.range.is_some() { return Err(Error::Arguments("--balance-first can't be used with --range".to_string())); }
+        if args.strategy!=Strategy::Random { return Err(Error::Arguments(format!("--balance-first chooses the first random character itself, so can't be used with --strategy {}",args.strategy))); }
+        generator.set_balance_first(true);
+    }
+    if !args.blocklist.is_empty() {
+        let mut substrings = vec![];
+        for path in &args.blocklist {
+            let patterns = read_blocklist(path)?;
+            report!("Read blocklist {} containing {} patterns",path.to_string_lossy(),patterns.len());
+            substrings.extend(patterns);
+        }
+        generator.add_filter(Filter::Blocklist(substrings));
+    }
+    if !args.exclude_regex.is_empty() { generator.add_filter(Filter::ExcludeRegex(args.exclude_regex.clone())); }
+    Ok(())
+}
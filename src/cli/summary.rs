@@ -0,0 +1,91 @@
+// This program is Copyright 2022 Andrew Conway and licensed under the GPL:
+//
+// This program is free software: you can redistribute it and/or modify it under the terms of the GNU General Public License as published by the Free Software Foundation, either version 3 of the License, or (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful, but WITHOUT ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License along with this program. If not, see <https://www.gnu.org/licenses/>.
+
+//! Statistics printed at the end of each prefix and the run, and optionally written as JSON.
+
+use std::path::Path;
+use std::time::Duration;
+use make_exam_numbers::stats::Statistics;
+use serde_json::{json, Value};
+use crate::cli::error::Error;
+
+/// How hard it was to find the codes for one prefix.
+pub struct PrefixSummary {
+    pub prefix : String,
+    pub statistics : Statistics,
+    pub time : Duration,
+    /// With `--balance-first`, the number of codes whose random part starts with each character.
+    pub first_characters : Vec<(char,u64)>,
+}
+
+/// The statistics for each prefix processed so far in the run.
+#[derive(Default)]
+pub struct RunSummary {
+    pub prefixes : Vec<PrefixSummary>,
+}
+
+impl RunSummary {
+    /// The statistics for all the prefixes together.
+    pub fn total(&self) -> (Statistics,Duration) {
+        let mut statistics = Statistics::default();
+        for p in &self.prefixes { statistics.add(&p.statistics); }
+        (statistics,self.prefixes.iter().map(|p|p.time).sum())
+    }
+
+    pub fn to_json(&self) -> Value {
+        let (total,time) = self.total();
+        json!({
+            "prefixes" : self.prefixes.iter().map(|p|{
+                let mut value = serde_json::Map::new();
+                value.insert("prefix".to_string(),json!(p.prefix));
+                if let Value::Object(statistics) = statistics_json(&p.statistics,p.time) { value.extend(statistics); }
+                if !p.first_characters.is_empty() { value.insert("first_characters".to_string(),p.first_characters.iter().map(|(c,count)|(c.to_string(),json!(count))).collect()); }
+                Value::Object(value)
+            }).collect::<Vec<_>>(),
+            "total" : statistics_json(&total,time),
+        })
+    }
+
+    pub fn write(&self,path:&Path) -> Result<(),Error> {
+        let text = serde_json::to_string_pretty(&self.to_json()).expect("Values made by json! can be written");
+        std::fs::write(path,text+"\n").map_err(Error::io(format!("Could not write statistics {}",path.to_string_lossy())))
+    }
+}
+
+fn statistics_json(statistics:&Statistics,time:Duration) -> Value {
+    json!({
+        "candidates" : statistics.candidates,
+        "accepted" : statistics.accepted,
+        "rejected" : statistics.rejected_total(),
+        "rejected_too_close" : statistics.too_close,
+        "rejected_too_close_rotated" : statistics.too_close_rotated,
+        "rotations_compared" : statistics.rotations_compared,
+        "rejected_by_filter" : statistics.filtered,
+        "acceptance_rate" : statistics.acceptance_rate(),
+        "seconds" : time.as_secs_f64(),
+        "recent_candidates_per_code" : statistics.recent_average(),
+        "recent_codes" : statistics.recent_codes(),
+    })
+}
+
+/// The number of codes starting with each character, like "1:12 2:11".
+pub fn describe_first_characters(counts:&[(char,u64)]) -> String {
+    counts.iter().map(|(c,count)|format!("{}:{}",c,count)).collect::<Vec<_>>().join(" ")
+}
+
+/// A one line description of the statistics, e.g. for the end of a prefix.
+pub fn describe(statistics:&Statistics,time:Duration) -> String {
+    let mut res = format!("{} codes from {} candidates",statistics.accepted,statistics.candidates);
+    if let Some(rate) = statistics.acceptance_rate() { res.push_str(&format!(" ({:.2}% accepted)",100.0*rate)); }
+    res.push_str(&format!(", {} rejected as too close",statistics.too_close));
+    if statistics.rotations_compared>0 { res.push_str(&format!(", {} as too close when rotated ({} rotations compared)",statistics.too_close_rotated,statistics.rotations_compared)); }
+    for (name,count) in &statistics.filtered { res.push_str(&format!(", {} by {}",count,name)); }
+    res.push_str(&format!(", in {:.2}s",time.as_secs_f64()));
+    if let Some(average) = statistics.recent_average() { res.push_str(&format!(", {:.2} candidates per code over the last {} codes",average,statistics.recent_codes())); }
+    res
+}
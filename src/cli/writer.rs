@@ -0,0 +1,231 @@
+// This program is Copyright 2022 Andrew Conway and licensed under the GPL:
+//
+// This program is free software: you can redistribute it and/or modify it under the terms of the GNU General Public License as published by the Free Software Foundation, either version 3 of the License, or (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful, but WITHOUT ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License along with this program. If not, see <https://www.gnu.org/licenses/>.
+
+//! Writing the codes made to each prefix's file and to the outputs shared by all the prefixes.
+
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use make_exam_numbers::{GenerateCodes, WantedPrefix};
+use crate::cli::atomic::OutputFile;
+use crate::cli::barcodes::BarcodeWriter;
+use crate::cli::error::Error;
+use crate::cli::options::Parameters;
+use crate::cli::output::{Batches, COMBINED_HEADER, csv_field, expand_template, Format, Grouping, OutputInfo, provenance, Record, spares_path, TemplateValues, today, with_extra_columns};
+use crate::cli::report::report;
+use crate::cli::setup::Setup;
+
+/// Where each prefix's codes are written.
+pub struct OutputPaths {
+    pub dir : PathBuf,
+    /// Today's date, for `--output-template` and `--write-header`.
+    pub date : String,
+    /// The file for each prefix, or the name its batches are numbered from with `--batch-size`.
+    pub paths : Vec<PathBuf>,
+    /// How many spare codes to make for each prefix.
+    pub spares : Vec<usize>,
+    /// The file each prefix's spare codes are written to, if it has any.
+    pub spare_paths : Vec<Option<PathBuf>>,
+}
+
+impl OutputPaths {
+    /// Work out the files for `args`, creating `--output-dir` if needed.
+    pub fn new(args:&Parameters,digits:usize) -> Result<Self,Error> {
+        let dir = args.output_dir.clone().unwrap_or_default();
+        if args.output_dir.is_some() && !args.dry_run {
+            std::fs::create_dir_all(&dir).map_err(Error::io(format!("Could not create output directory {}",dir.to_string_lossy())))?;
+        }
+        let date = today();
+        let mut paths : Vec<PathBuf> = vec![];
+        for p in &args.prefixes {
+            let name = match &args.output_template {
+                Some(template) => expand_template(template,&TemplateValues{prefix:&p.prefix,suffix:&p.suffix,count:p.number,digits:p.digits_or(digits),date:&date}).map_err(|e|Error::Arguments(format!("--output-template : {}",e)))?,
+                None if p.mask.is_some() => format!("mask_{}.{}",p.name(),args.format.extension()),
+                None if p.suffix.is_empty() => format!("prefix_{}.{}",p.prefix,args.format.extension()),
+                None if p.prefix.is_empty() => format!("suffix_{}.{}",p.suffix,args.format.extension()),
+                None => format!("prefix_{}_suffix_{}.{}",p.prefix,p.suffix,args.format.extension()),
+            };
+            let path = dir.join(name);
+            if let Some(previous) = paths.iter().position(|q|*q==path) {
+                return Err(Error::Arguments(format!("Prefixes {}:{} and {}:{} would both be written to {}",args.prefixes[previous].name(),args.prefixes[previous].number,p.name(),p.number,path.to_string_lossy())));
+            }
+            paths.push(path);
+        }
+        if args.batch_size==Some(0) { return Err(Error::Arguments("--batch-size must be at least 1".to_string())); }
+        if args.write_header && args.format==Format::Json { return Err(Error::Arguments("--write-header can't be used with --format json, which records how the codes were made anyway".to_string())); }
+        let spares : Vec<usize> = args.prefixes.iter().map(|p|p.spares_or(args.spares.unwrap_or(0))).collect();
+        let spare_paths : Vec<Option<PathBuf>> = paths.iter().zip(&spares).map(|(path,&spares)|(spares>0).then(||spares_path(path))).collect();
+        if spare_paths.iter().any(|s|s.is_some()) {
+            let problem = if args.stdout { Some("--stdout") } else if args.no_prefix_files { Some("--no-prefix-files") } else if args.append { Some("--append") } else if args.resume { Some("--resume") }
+                else if args.prefixes.iter().zip(&spares).any(|(p,&spares)|p.total && spares>0) { Some("PREFIX:=NUMBER") } else { None };
+            if let Some(problem) = problem { return Err(Error::Arguments(format!("Spare codes are written to a new file of their own, so can't be used with {}",problem))); }
+        }
+        if let Some(path) = spare_paths.iter().flatten().find(|s|paths.contains(s)) { return Err(Error::Arguments(format!("Spare codes would be written to {}, which is also another prefix's file",path.to_string_lossy()))); }
+        Ok(OutputPaths{dir,date,paths,spares,spare_paths})
+    }
+
+    /// The files the codes for prefix `index` will be written to, for checking none are already there.
+    pub fn files(&self,args:&Parameters,index:usize) -> Vec<PathBuf> {
+        let (path,total) = (&self.paths[index],args.prefixes[index].number);
+        match args.batch_size {
+            Some(size) => { let batches = Batches::new(path,total,size); (0..Batches::num_batches(total,size)).map(|index|batches.path(index)).collect() }
+            None => vec![path.clone()],
+        }
+    }
+
+    /// Every file this run writes codes to, none of which are existing codes for `--existing-auto`.
+    pub fn written(&self,args:&Parameters) -> Vec<PathBuf> {
+        (0..self.paths.len()).flat_map(|index|self.files(args,index)).chain(self.spare_paths.iter().flatten().cloned()).chain(args.combined.iter().cloned()).collect()
+    }
+}
+
+/// How to refer to where the codes for prefix `p`, whose file is `path`, are written, in messages.
+pub fn destination(args:&Parameters,p:&WantedPrefix,path:&Path) -> String {
+    if args.stdout { "standard output".to_string() }
+    else if args.no_prefix_files { args.combined.as_ref().expect("Required by clap").to_string_lossy().to_string() }
+    else if let Some(size) = args.batch_size {
+        let batches = Batches::new(path,p.number,size);
+        let num_batches = Batches::num_batches(p.number,size);
+        if num_batches>1 { format!("{} files of at most {} like {}",num_batches,size,batches.path(0).to_string_lossy()) } else { batches.path(0).to_string_lossy().to_string() }
+    }
+    else { path.to_string_lossy().to_string() }
+}
+
+/// The outputs that every prefix's codes are written to, and what goes with each code.
+pub struct SharedOutputs {
+    /// The `--combined` file.
+    combined : Option<OutputFile>,
+    barcodes : Option<BarcodeWriter>,
+    /// The generator for `--with-pin`.
+    pins : Option<GenerateCodes>,
+    pin_min_hamming_distance : usize,
+    grouping : Option<Grouping>,
+    /// The new codes in the order they were written, for `--merge-with`.
+    merged : Option<Vec<String>>,
+}
+
+impl SharedOutputs {
+    /// Open the shared outputs, taking the PIN generator from `setup`. The combined file is added to if `append`.
+    pub fn open(args:&Parameters,setup:&mut Setup,date:&str,append:bool,atomic:bool) -> Result<Self,Error> {
+        let combined = match &args.combined {
+            Some(path) => {
+                let (mut file,has_contents) = if append { OutputFile::append(path,atomic) } else { OutputFile::create(path,atomic).map(|f|(f,false)) }.map_err(Error::io(format!("Could not create combined output file {}",path.to_string_lossy())))?;
+                if !has_contents {
+                    if args.write_header { writeln!(file,"{}",provenance(date,setup.seed,&setup.metric.show_distance(setup.min_hamming_distance),None))?; }
+                    writeln!(file,"{}",with_extra_columns(COMBINED_HEADER,setup.pins.is_some(),setup.grouping.is_some()))?;
+                }
+                Some(file)
+            }
+            None => None,
+        };
+        let barcodes = args.barcodes.as_ref().map(|dir|BarcodeWriter::new(dir,args.barcode_format,args.barcode_png)).transpose()?;
+        Ok(SharedOutputs{combined,barcodes,pins:setup.pins.take(),pin_min_hamming_distance:args.pin_min_hamming_distance,grouping:setup.grouping.clone(),merged:args.merge_with.as_ref().map(|_|vec![])})
+    }
+
+    /// A new PIN, with `--with-pin`.
+    fn new_pin(&mut self) -> Result<Option<String>,Error> {
+        Ok(match &mut self.pins { Some(pins) => Some(pins.new_code("",self.pin_min_hamming_distance)?), None => None })
+    }
+
+    /// Write a code for prefix `prefix` to the outputs, with its PIN and how it is shown, if it has them.
+    fn write(&mut self,prefix:&str,code:&str,pin:Option<&str>,display:Option<&str>) -> Result<(),Error> {
+        if let Some(barcodes) = &mut self.barcodes { barcodes.write(code,display.unwrap_or(code))?; }
+        if let Some(merged) = &mut self.merged { merged.push(code.to_string()); }
+        if let Some(file) = &mut self.combined {
+            write!(file,"{},{}",csv_field(code),csv_field(prefix))?;
+            for value in [pin,display].into_iter().flatten() { write!(file,",{}",csv_field(value))?; }
+            writeln!(file)?;
+        }
+        Ok(())
+    }
+
+    /// Finish the combined file and say how many barcodes were written. Returns the codes for `--merge-with`.
+    pub fn finish(self) -> Result<Vec<String>,Error> {
+        if let Some(barcodes) = &self.barcodes { report!("Wrote {} barcodes to {} as {}",barcodes.count,barcodes.dir().to_string_lossy(),barcodes.describe()); }
+        if let Some(file) = self.combined { file.finish()?; }
+        Ok(self.merged.unwrap_or_default())
+    }
+}
+
+/// Writes the codes for one prefix to its file (or batches of files, or standard output) and to the shared outputs.
+pub struct PrefixWriter<'a> {
+    args : &'a Parameters,
+    info : OutputInfo<'a>,
+    atomic : bool,
+    file : Option<OutputFile>,
+    pub batches : Option<Batches>,
+    /// How many codes were already in the prefix's file, which the sequence numbers carry on from.
+    already : usize,
+    /// How many codes have been written.
+    pub written : usize,
+}
+
+impl<'a> PrefixWriter<'a> {
+    /// Start writing the codes for `p`, the prefix `index`, to `path`, which is added to if `appending` and has `already` codes.
+    pub fn open(args:&'a Parameters,info:OutputInfo<'a>,p:&WantedPrefix,index:usize,path:&Path,appending:bool,already:usize) -> Result<Self,Error> {
+        let mut res = PrefixWriter{args,info,atomic:!args.no_atomic,file:None,batches:args.batch_size.map(|size|Batches::new(path,p.number,size)),already,written:0};
+        res.file = if args.stdout {
+            let mut stdout = OutputFile::stdout();
+            if index==0 || args.format!=Format::Csv { args.format.write_header(&mut stdout,&res.info)?; }
+            Some(stdout)
+        } else if args.no_prefix_files { None } else if let Some(batches) = &res.batches { Some(res.create(&batches.path(0))?) } else {
+            let (mut file,has_contents) = if appending { OutputFile::append(path,res.atomic) } else { OutputFile::create(path,res.atomic).map(|f|(f,false)) }.map_err(Error::io(format!("Could not create output file {}",destination(args,p,path))))?;
+            if !has_contents { args.format.write_header(&mut file,&res.info)?; }
+            Some(file)
+        };
+        Ok(res)
+    }
+
+    /// Create a new file for the prefix's codes, and write its header.
+    fn create(&self,path:&Path) -> Result<OutputFile,Error> {
+        let mut file = OutputFile::create(path,self.atomic).map_err(Error::io(format!("Could not create output file {}",path.to_string_lossy())))?;
+        self.args.format.write_header(&mut file,&self.info)?;
+        Ok(file)
+    }
+
+    /// Write some codes, starting the next batch's file whenever one is full.
+    pub fn write(&mut self,codes:&[String],shared:&mut SharedOutputs) -> Result<(),Error> {
+        for code in codes {
+            let code = &self.args.output_case.apply(code);
+            self.written+=1;
+            let next = match (&mut self.batches,&self.file) { (Some(batches),Some(_)) => batches.add(), _ => None };
+            if let Some(next) = next {
+                let file = self.create(&next)?;
+                let mut full = std::mem::replace(self.file.as_mut().expect("Only batched with a file"),file);
+                self.args.format.write_footer(&mut full)?;
+                full.finish()?;
+            }
+            let pin = shared.new_pin()?;
+            let display = shared.grouping.as_ref().map(|g|g.display(code));
+            let record = Record{prefix:self.info.prefix,code,sequence:self.already+self.written,pin:pin.as_deref(),display:display.as_deref()};
+            if let Some(file) = &mut self.file { self.args.format.write_code(file,&record)?; }
+            shared.write(self.info.prefix,code,pin.as_deref(),display.as_deref())?;
+        }
+        Ok(())
+    }
+
+    /// Finish the prefix's file.
+    pub fn finish(&mut self) -> Result<(),Error> {
+        if let Some(mut file) = self.file.take() { self.args.format.write_footer(&mut file)?; file.finish()?; }
+        Ok(())
+    }
+
+    /// Write the prefix's spare codes to a file of their own at `path`, numbered from 1.
+    pub fn write_spares(&self,path:&Path,codes:&[String],shared:&mut SharedOutputs) -> Result<(),Error> {
+        let mut file = self.create(path)?;
+        for (index,code) in codes.iter().enumerate() {
+            let code = self.args.output_case.apply(code);
+            let pin = shared.new_pin()?;
+            let display = shared.grouping.as_ref().map(|g|g.display(&code));
+            self.args.format.write_code(&mut file,&Record{prefix:self.info.prefix,code:&code,sequence:index+1,pin:pin.as_deref(),display:display.as_deref()})?;
+        }
+        self.args.format.write_footer(&mut file)?;
+        file.finish()?;
+        report!("Wrote {} spare codes to {}",codes.len(),path.to_string_lossy());
+        Ok(())
+    }
+}
@@ -0,0 +1,85 @@
+// This program is Copyright 2022 Andrew Conway and licensed under the GPL:
+//
+// This program is free software: you can redistribute it and/or modify it under the terms of the GNU General Public License as published by the Free Software Foundation, either version 3 of the License, or (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful, but WITHOUT ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License along with this program. If not, see <https://www.gnu.org/licenses/>.
+
+//! Match codes that may have been written down wrongly, such as exam numbers copied by hand, against the codes issued.
+
+use std::collections::HashMap;
+use crate::distance::{LengthPolicy, Metric};
+use crate::index::HammingIndex;
+
+/// What a code written down turned out to be, as found by [Corrector::correct]. Indices are into the issued codes.
+#[derive(Clone,Debug,PartialEq,Eq)]
+pub enum Correction {
+    /// It is exactly an issued code.
+    Exact(usize),
+    /// It is closer to this issued code than to any other, at a distance no more than the maximum allowed.
+    Corrected { index : usize, distance : usize },
+    /// Several issued codes are equally close, and closer than any other, so which was meant can't be told. In order of index.
+    Ambiguous { indices : Vec<usize>, distance : usize },
+    /// No issued code is within the maximum distance.
+    NotFound,
+}
+
+/// Finds the issued code closest to each code written down, if any is close enough.
+///
+/// ```
+/// use make_exam_numbers::correct::{Correction, Corrector};
+/// use make_exam_numbers::distance::{LengthPolicy, Metric};
+///
+/// let issued = ["A1234","A5678","A1299"].map(|s|s.to_string());
+/// let corrector = Corrector::new(&issued,Metric::Hamming,LengthPolicy::default(),1);
+/// assert_eq!(corrector.correct("A5678"),Correction::Exact(1));
+/// assert_eq!(corrector.correct("A5670"),Correction::Corrected{index:1,distance:1});
+/// assert_eq!(corrector.correct("A1294"),Correction::Ambiguous{indices:vec![0,2],distance:1});
+/// assert_eq!(corrector.correct("B9999"),Correction::NotFound);
+/// ```
+pub struct Corrector<'a> {
+    codes : &'a [String],
+    metric : Metric,
+    length_policy : LengthPolicy,
+    max_distance : usize,
+    /// The first index of each distinct issued code.
+    first : HashMap<&'a str,usize>,
+    /// For Hamming distance, the distinct issued codes indexed to find those within the maximum distance quickly.
+    index : Option<HammingIndex>,
+    /// For other metrics, the distinct issued codes as (index, characters).
+    chars : Vec<(usize,Vec<char>)>,
+}
+
+impl <'a> Corrector<'a> {
+    /// A corrector for the issued codes `codes`, allowing codes written down to be up to `max_distance` from them, as measured by `metric`.
+    pub fn new(codes:&'a [String],metric:Metric,length_policy:LengthPolicy,max_distance:usize) -> Self {
+        let mut first : HashMap<&str,usize> = HashMap::new();
+        for (index,code) in codes.iter().enumerate() { first.entry(code.as_str()).or_insert(index); }
+        let mut distinct : Vec<usize> = first.values().copied().collect();
+        distinct.sort_unstable();
+        let mut index = None;
+        let mut chars = vec![];
+        if metric==Metric::Hamming {
+            let mut hamming = HammingIndex::new(max_distance+1,length_policy);
+            for &i in &distinct { hamming.insert(i,&codes[i]); }
+            index = Some(hamming);
+        } else { chars = distinct.iter().map(|&i|(i,codes[i].chars().collect())).collect(); }
+        Corrector{codes,metric,length_policy,max_distance,first,index,chars}
+    }
+
+    /// Which issued code `code` was meant to be.
+    pub fn correct(&self,code:&str) -> Correction {
+        if let Some(&index) = self.first.get(code) { return Correction::Exact(index); }
+        let close : Vec<(usize,usize)> = match &self.index {
+            Some(index) => index.close_to(code,self.codes),
+            None => {
+                let code : Vec<char> = code.chars().collect();
+                self.chars.iter().filter_map(|(i,other)|self.metric.distance_below_with_policy(&code,other,self.max_distance+1,self.length_policy).map(|d|(*i,d))).collect()
+            }
+        };
+        let Some(distance) = close.iter().map(|&(_,d)|d).min() else { return Correction::NotFound; };
+        let indices : Vec<usize> = close.iter().filter(|&&(_,d)|d==distance).map(|&(i,_)|i).collect();
+        if indices.len()==1 { Correction::Corrected{index:indices[0],distance} } else { Correction::Ambiguous{indices,distance} }
+    }
+}
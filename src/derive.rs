@@ -0,0 +1,104 @@
+// This program is Copyright 2022 Andrew Conway and licensed under the GPL:
+//
+// This program is free software: you can redistribute it and/or modify it under the terms of the GNU General Public License as published by the Free Software Foundation, either version 3 of the License, or (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful, but WITHOUT ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License along with this program. If not, see <https://www.gnu.org/licenses/>.
+
+//! Codes derived from a secret key and each student's ID, so a student's code can be worked out again without storing it.
+//!
+//! The random part for attempt `n` at a student's code is HMAC-SHA256 of the key and the bytes of the prefix, a zero byte and
+//! the ID, followed (for `n` > 0) by another zero byte and `n` in decimal. The 256 bit result, as a big endian number, is turned
+//! into characters of the alphabet by repeatedly dividing by the alphabet size, last character first. If the code is rejected,
+//! such as being too close to an earlier code, the next attempt is tried, so the codes only depend on the key, the IDs and their order.
+
+use std::fmt::{Debug, Formatter};
+use std::str::FromStr;
+use hmac::{Hmac, Mac};
+use sha2::Sha256;
+
+/// The fewest bytes allowed in a key.
+pub const MIN_KEY_BYTES : usize = 16;
+
+/// The most bits of the HMAC that are used for the random part, leaving the rest so every random part is almost exactly as likely.
+pub const MAX_BITS : f64 = 192.0;
+
+/// A secret key for deriving codes from IDs. See the [module documentation](self) for how codes are derived.
+#[derive(Clone,PartialEq,Eq)]
+pub struct KeyedDerivation {
+    key : Vec<u8>,
+}
+
+impl KeyedDerivation {
+    pub fn new(key:&[u8]) -> Self { KeyedDerivation{key:key.to_vec()} }
+
+    /// Whether random parts of `num_digits` characters from an alphabet of `alphabet_size` can be derived without noticeable bias,
+    /// which is when they take at most [MAX_BITS] bits.
+    pub fn can_derive(alphabet_size:usize,num_digits:usize) -> bool {
+        (alphabet_size as f64).log2()*num_digits as f64<=MAX_BITS
+    }
+
+    /// The random part for the given attempt (starting from 0) at a code with this prefix for this ID, with `num_digits` characters from `characters`.
+    /// If `first` is given, the first character is from those instead, as for codes without a leading zero.
+    ///
+    /// ```
+    /// use make_exam_numbers::derive::KeyedDerivation;
+    /// let derivation = KeyedDerivation::new(b"a secret key of at least 16 bytes");
+    /// let digits : Vec<char> = ('0'..='9').collect();
+    /// let body = derivation.body("S1","s1234567",0,&digits,None,6);
+    /// assert_eq!(body.len(),6);
+    /// assert_eq!(body,derivation.body("S1","s1234567",0,&digits,None,6));
+    /// assert_ne!(body,derivation.body("S1","s1234567",1,&digits,None,6));
+    /// assert_ne!(body,derivation.body("S1","s1234568",0,&digits,None,6));
+    /// ```
+    pub fn body(&self,prefix:&str,id:&str,attempt:u64,characters:&[char],first:Option<&[char]>,num_digits:usize) -> String {
+        let mut mac = Hmac::<Sha256>::new_from_slice(&self.key).expect("HMAC takes keys of any length");
+        mac.update(prefix.as_bytes());
+        mac.update(&[0]);
+        mac.update(id.as_bytes());
+        if attempt>0 {
+            mac.update(&[0]);
+            mac.update(attempt.to_string().as_bytes());
+        }
+        let mut number : Vec<u8> = mac.finalize().into_bytes().to_vec();
+        let mut body = vec![' ';num_digits];
+        for (i,c) in body.iter_mut().enumerate().rev() {
+            let characters = if i==0 { first.unwrap_or(characters) } else { characters };
+            *c = characters[divide(&mut number,characters.len() as u32) as usize];
+        }
+        body.into_iter().collect()
+    }
+}
+
+/// Divide a big endian number in place, returning the remainder.
+fn divide(number:&mut [u8],divisor:u32) -> u32 {
+    let mut remainder = 0u32;
+    for byte in number.iter_mut() {
+        let value = (remainder<<8)|*byte as u32;
+        *byte = (value/divisor) as u8;
+        remainder = value%divisor;
+    }
+    remainder
+}
+
+/// Parses a key given as hexadecimal, of at least [MIN_KEY_BYTES] bytes.
+impl FromStr for KeyedDerivation {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let hex = s.strip_prefix("0x").unwrap_or(s);
+        if let Some(c) = hex.chars().find(|c|!c.is_ascii_hexdigit()) { return Err(format!("The key should be hexadecimal, but contains {:?}",c)); }
+        if !hex.len().is_multiple_of(2) { return Err("The key should be a whole number of bytes, so an even number of hexadecimal characters".to_string()); }
+        if hex.len()<2*MIN_KEY_BYTES { return Err(format!("The key should be at least {} hexadecimal characters ({} bytes), but has {}",2*MIN_KEY_BYTES,MIN_KEY_BYTES,hex.len())); }
+        let key : Result<Vec<u8>,_> = (0..hex.len()).step_by(2).map(|i|u8::from_str_radix(&hex[i..i+2],16)).collect();
+        Ok(KeyedDerivation{key:key.map_err(|e|e.to_string())?})
+    }
+}
+
+/// Doesn't show the key, which is secret.
+impl Debug for KeyedDerivation {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f,"KeyedDerivation {{ {} byte key }}",self.key.len())
+    }
+}
@@ -0,0 +1,378 @@
+// This program is Copyright 2022 Andrew Conway and licensed under the GPL:
+//
+// This program is free software: you can redistribute it and/or modify it under the terms of the GNU General Public License as published by the Free Software Foundation, either version 3 of the License, or (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful, but WITHOUT ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License along with this program. If not, see <https://www.gnu.org/licenses/>.
+
+//! Ways of measuring how different two codes are.
+
+use std::collections::BTreeMap;
+use std::fmt::{Display, Formatter};
+use std::str::FromStr;
+use std::sync::OnceLock;
+
+/// How the distance between two codes is measured.
+///
+/// Distances are whole numbers. For [Metric::WeightedHamming] they are in units of 1/[WEIGHT_UNITS] of a difference; see [Metric::unit].
+#[derive(Clone,Copy,Debug,PartialEq,Eq,Default)]
+pub enum Metric {
+    /// The number of positions at which the characters differ. This is the default.
+    #[default]
+    Hamming,
+    /// The number of single character insertions, deletions or substitutions needed to change one code into the other.
+    /// This also protects against characters being dropped or added when copying a code, but is slower to compute.
+    Levenshtein,
+    /// Like Levenshtein distance, but swapping two adjacent characters also counts as a single change
+    /// (the optimal string alignment variant of Damerau–Levenshtein distance), so "AB1234" and "AB2134" are distance 1.
+    ///
+    /// ```
+    /// use make_exam_numbers::distance::Metric;
+    ///
+    /// assert_eq!(Metric::Damerau.distance_below("1234","1324",5),Some(1));
+    /// assert_eq!(Metric::Levenshtein.distance_below("1234","1324",5),Some(2));
+    /// assert_eq!(Metric::Hamming.distance_below("1234","1324",5),Some(2));
+    /// assert_eq!(Metric::Damerau.distance_below("1234","1324",1),None); // not less than 1.
+    /// ```
+    Damerau,
+    /// Like Hamming distance, but each position at which the characters differ counts according to the [Weights] of the two characters,
+    /// so characters that are easily mistaken for each other, such as neighbouring keys on a keypad, can count as less than a full difference.
+    /// The weights are `&'static` so the metric can be copied freely; see [Weights::leak] for weights made at run time.
+    WeightedHamming(&'static Weights),
+}
+
+/// [Metric::WeightedHamming] distances are measured in units of 1/WEIGHT_UNITS, so a difference of weight 1 is this many units.
+pub const WEIGHT_UNITS : usize = 100;
+
+/// How much each pair of different characters counts towards a [Metric::WeightedHamming] distance, from 0 to 1.
+///
+/// Pairs not given count as 1, so with no pairs this is the same as Hamming distance.
+///
+/// ```
+/// use make_exam_numbers::distance::{Weights, WEIGHT_UNITS};
+///
+/// let keypad = Weights::keypad();
+/// assert_eq!(keypad.weight('5','8'),WEIGHT_UNITS/2);
+/// assert_eq!(keypad.weight('0','2'),WEIGHT_UNITS/2);
+/// assert_eq!(keypad.weight('1','9'),WEIGHT_UNITS);
+/// assert_eq!(keypad.weight('7','7'),0);
+/// ```
+#[derive(Clone,Debug,PartialEq,Eq,Default)]
+pub struct Weights {
+    /// The weight in units of each pair given, with the smaller character first.
+    pairs : BTreeMap<(char,char),usize>,
+}
+
+impl Weights {
+    /// Set how much a pair of different characters counts, in either order, as a fraction of a full difference from 0 to 1.
+    /// The weight is rounded to the nearest unit ([WEIGHT_UNITS]).
+    pub fn set(&mut self,a:char,b:char,weight:f64) -> Result<(),String> {
+        if a==b { return Err(format!("A weight is for two different characters, not {} and {}",a,b)); }
+        if !(0.0..=1.0).contains(&weight) { return Err(format!("The weight {} for {} and {} should be from 0 to 1",weight,a,b)); }
+        self.pairs.insert((a.min(b),a.max(b)),(weight*WEIGHT_UNITS as f64).round() as usize);
+        Ok(())
+    }
+
+    /// How many units two characters being different counts for: 0 if they are the same, otherwise [WEIGHT_UNITS] unless set otherwise.
+    pub fn weight(&self,a:char,b:char) -> usize {
+        if a==b { 0 } else { self.pairs.get(&(a.min(b),a.max(b))).copied().unwrap_or(WEIGHT_UNITS) }
+    }
+
+    /// Weights for a keyboard laid out in the given rows, in which keys next to each other in a row or column count as half a difference.
+    /// A key wider than one column is repeated, and a space is a gap.
+    fn adjacent_keys(rows:&[&str]) -> Weights {
+        let grid : Vec<Vec<char>> = rows.iter().map(|r|r.chars().collect()).collect();
+        let mut weights = Weights::default();
+        for (r,row) in grid.iter().enumerate() {
+            for (c,&key) in row.iter().enumerate() {
+                for neighbour in [row.get(c+1),grid.get(r+1).and_then(|below|below.get(c))].into_iter().flatten() {
+                    if key!=' ' && *neighbour!=' ' && key!=*neighbour { weights.set(key,*neighbour,0.5).expect("Different characters with weight 0.5"); }
+                }
+            }
+        }
+        weights
+    }
+
+    /// A numeric keypad, with 7, 8 and 9 on the top row and a wide 0 below 1 and 2. Keys next to each other count as half a difference.
+    pub fn keypad() -> &'static Weights {
+        static KEYPAD : OnceLock<Weights> = OnceLock::new();
+        KEYPAD.get_or_init(||Weights::adjacent_keys(&["789","456","123","00"]))
+    }
+
+    /// The row of digits along the top of a keyboard, 1 to 9 then 0. Keys next to each other count as half a difference.
+    pub fn number_row() -> &'static Weights {
+        static NUMBER_ROW : OnceLock<Weights> = OnceLock::new();
+        NUMBER_ROW.get_or_init(||Weights::adjacent_keys(&["1234567890"]))
+    }
+
+    /// The built in weights with the given name, `keypad` or `number-row`.
+    pub fn builtin(name:&str) -> Option<&'static Weights> {
+        match name {
+            "keypad" => Some(Weights::keypad()),
+            "number-row" => Some(Weights::number_row()),
+            _ => None,
+        }
+    }
+
+    /// Make the weights last for the rest of the program, so they can be used in a [Metric]. This is intended for weights read once at the start.
+    pub fn leak(self) -> &'static Weights { Box::leak(Box::new(self)) }
+}
+
+impl FromStr for Metric {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "hamming" => Ok(Metric::Hamming),
+            "levenshtein" => Ok(Metric::Levenshtein),
+            "damerau" => Ok(Metric::Damerau),
+            "weighted-hamming" => Ok(Metric::WeightedHamming(Weights::keypad())),
+            _ => Err(format!("Unknown metric {}, expecting hamming, levenshtein, damerau or weighted-hamming",s)),
+        }
+    }
+}
+
+/// How Hamming distance treats two codes of different lengths.
+///
+/// ```
+/// use make_exam_numbers::distance::LengthPolicy;
+///
+/// assert_eq!(LengthPolicy::Pad.hamming_below("S123","S12345",5),Some(2));
+/// assert_eq!(LengthPolicy::Pad.hamming_below("S123","S12345",2),None); // at least 2 apart, so far enough.
+/// assert_eq!(LengthPolicy::Distinct.hamming_below("S123","S12345",5),None);
+/// assert_eq!(LengthPolicy::Distinct.hamming_below("S123","S124",5),Some(1)); // the same length is compared as usual.
+/// assert_eq!(LengthPolicy::Truncate.hamming_below("S123","S12345",5),Some(0));
+/// assert_eq!(LengthPolicy::Truncate.hamming_below("S123","S22345",5),Some(1));
+/// ```
+#[derive(Clone,Copy,Debug,PartialEq,Eq,Default)]
+pub enum LengthPolicy {
+    /// Each character the longer code has beyond the end of the shorter counts as a difference, as if the shorter were padded. This is the default.
+    #[default]
+    Pad,
+    /// Codes of different lengths are always far enough apart.
+    Distinct,
+    /// Only compare up to the length of the shorter code. This was the behaviour of earlier versions.
+    Truncate,
+}
+
+impl FromStr for LengthPolicy {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "pad" => Ok(LengthPolicy::Pad),
+            "distinct" => Ok(LengthPolicy::Distinct),
+            "truncate" => Ok(LengthPolicy::Truncate),
+            _ => Err(format!("Unknown length policy {}, expecting pad, distinct or truncate",s)),
+        }
+    }
+}
+
+impl LengthPolicy {
+    /// The Hamming distance between two codes, treating different lengths according to this policy, if it is less than `min`, otherwise None.
+    pub fn hamming_below(&self,a:&str,b:&str,min:usize) -> Option<usize> {
+        let extra = self.extra_characters(a.chars().count(),b.chars().count())?;
+        hamming_below(a.chars(),b.chars(),min,extra)
+    }
+
+    /// Like [LengthPolicy::hamming_below], for [Metric::WeightedHamming] with the given weights. Each extra character counts as a full difference.
+    pub fn weighted_hamming_below(&self,weights:&Weights,a:&str,b:&str,min:usize) -> Option<usize> {
+        let extra = self.extra_characters(a.chars().count(),b.chars().count())?;
+        weighted_hamming_below(weights,a.chars(),b.chars(),min,extra*WEIGHT_UNITS)
+    }
+
+    /// How many characters past the end of the shorter code count as differences, or None if codes of these lengths are always far enough apart.
+    fn extra_characters(&self,len_a:usize,len_b:usize) -> Option<usize> {
+        match self {
+            _ if len_a==len_b => Some(0),
+            LengthPolicy::Pad => Some(len_a.abs_diff(len_b)),
+            LengthPolicy::Distinct => None,
+            LengthPolicy::Truncate => Some(0),
+        }
+    }
+}
+
+/// Which used codes a new code must be far enough from.
+#[derive(Clone,Copy,Debug,PartialEq,Eq,Default)]
+pub enum Scope {
+    /// Every other code, whatever its prefix. This is the default.
+    #[default]
+    Global,
+    /// Only codes with the same prefix, for when codes with different prefixes can never be confused.
+    /// A code is taken to have a prefix if it starts with it.
+    PerPrefix,
+}
+
+impl FromStr for Scope {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "global" => Ok(Scope::Global),
+            "per-prefix" => Ok(Scope::PerPrefix),
+            _ => Err(format!("Unknown scope {}, expecting global or per-prefix",s)),
+        }
+    }
+}
+
+/// The name accepted by [FromStr].
+impl Display for LengthPolicy {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            LengthPolicy::Pad => "pad",
+            LengthPolicy::Distinct => "distinct",
+            LengthPolicy::Truncate => "truncate",
+        })
+    }
+}
+
+/// The name accepted by [FromStr].
+impl Display for Scope {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            Scope::Global => "global",
+            Scope::PerPrefix => "per-prefix",
+        })
+    }
+}
+
+/// The name accepted by [FromStr].
+impl Display for Metric {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            Metric::Hamming => "hamming",
+            Metric::Levenshtein => "levenshtein",
+            Metric::Damerau => "damerau",
+            Metric::WeightedHamming(_) => "weighted-hamming",
+        })
+    }
+}
+
+impl Metric {
+    /// The distance between two codes if it is less than `min`, otherwise None.
+    ///
+    /// Hamming distance between codes of different lengths only compares up to the length of the shorter; see [LengthPolicy] for alternatives.
+    /// This is faster than computing the full distance as it can stop as soon as the distance is known to be at least `min`.
+    pub fn distance_below(&self,a:&str,b:&str,min:usize) -> Option<usize> {
+        match self {
+            Metric::Hamming => hamming_below(a.chars(),b.chars(),min,0),
+            Metric::WeightedHamming(weights) => weighted_hamming_below(weights,a.chars(),b.chars(),min,0),
+            _ => self.distance_below_chars(&a.chars().collect::<Vec<char>>(),&b.chars().collect::<Vec<char>>(),min),
+        }
+    }
+
+    /// Like [Metric::distance_below], for codes already split into characters.
+    pub fn distance_below_chars(&self,a:&[char],b:&[char],min:usize) -> Option<usize> {
+        match self {
+            Metric::Hamming => hamming_below(a.iter(),b.iter(),min,0),
+            Metric::Levenshtein => levenshtein_below(a,b,min,false),
+            Metric::Damerau => levenshtein_below(a,b,min,true),
+            Metric::WeightedHamming(weights) => weighted_hamming_below(weights,a.iter().copied(),b.iter().copied(),min,0),
+        }
+    }
+
+    /// Like [Metric::distance_below_chars], but Hamming distances (weighted or not) compare codes of different lengths according to `length_policy`.
+    pub fn distance_below_with_policy(&self,a:&[char],b:&[char],min:usize,length_policy:LengthPolicy) -> Option<usize> {
+        match self {
+            Metric::Hamming => hamming_below(a.iter(),b.iter(),min,length_policy.extra_characters(a.len(),b.len())?),
+            Metric::WeightedHamming(weights) => weighted_hamming_below(weights,a.iter().copied(),b.iter().copied(),min,length_policy.extra_characters(a.len(),b.len())?*WEIGHT_UNITS),
+            _ => self.distance_below_chars(a,b,min),
+        }
+    }
+
+    /// How many units of distance make a whole difference: [WEIGHT_UNITS] for [Metric::WeightedHamming], otherwise 1.
+    pub fn unit(&self) -> usize {
+        match self {
+            Metric::WeightedHamming(_) => WEIGHT_UNITS,
+            _ => 1,
+        }
+    }
+
+    /// The smallest Hamming distance two codes at least `min` apart in this metric can be, for bounds on how many codes can fit.
+    /// No metric is bigger than Hamming distance, as weights are at most 1.
+    pub fn min_hamming(&self,min:usize) -> usize { min.div_ceil(self.unit()) }
+
+    /// A distance such as "3" or, for [Metric::WeightedHamming], "2.5", in units of this metric.
+    ///
+    /// ```
+    /// use make_exam_numbers::distance::Metric;
+    ///
+    /// let weighted : Metric = "weighted-hamming".parse().unwrap();
+    /// assert_eq!(weighted.parse_distance("2.5"),Ok(250));
+    /// assert_eq!(weighted.show_distance(250),"2.5");
+    /// assert_eq!(Metric::Hamming.parse_distance("3"),Ok(3));
+    /// assert!(Metric::Hamming.parse_distance("2.5").is_err());
+    /// ```
+    pub fn parse_distance(&self,s:&str) -> Result<usize,String> {
+        let invalid = || format!("Distance {} should be a number like 3{}",s,if self.unit()>1 { " or 2.5" } else { "" });
+        let (whole,fraction) = s.split_once('.').unwrap_or((s,""));
+        if whole.is_empty() || !whole.chars().chain(fraction.chars()).all(|c|c.is_ascii_digit()) { return Err(invalid()); }
+        let whole : usize = whole.parse().map_err(|_|invalid())?;
+        let places = self.unit().to_string().len()-1; // the unit is a power of 10.
+        let fraction = fraction.trim_end_matches('0');
+        if fraction.len()>places {
+            return Err(if places==0 { format!("Distance {} should be a whole number for the {} metric. Only weighted-hamming allows fractions.",s,self) }
+                else { format!("Distance {} can have at most {} decimal places for the {} metric",s,places,self) });
+        }
+        let fraction : usize = format!("{:0<places$}",fraction).parse().unwrap_or(0);
+        whole.checked_mul(self.unit()).and_then(|w|w.checked_add(fraction)).ok_or_else(invalid)
+    }
+
+    /// A distance in units of this metric as it would be written for [Metric::parse_distance], like "3" or "2.5".
+    pub fn show_distance(&self,distance:usize) -> String {
+        let unit = self.unit();
+        if distance.is_multiple_of(unit) { return (distance/unit).to_string(); }
+        let places = unit.to_string().len()-1;
+        format!("{}.{:0places$}",distance/unit,distance%unit).trim_end_matches('0').to_string()
+    }
+}
+
+/// Hamming distance, comparing positions up to the length of the shorter code, plus `extra`.
+fn hamming_below<T:PartialEq>(a:impl Iterator<Item=T>,b:impl Iterator<Item=T>,min:usize,extra:usize) -> Option<usize> {
+    if extra>=min { return None; }
+    let mut distance = extra;
+    for (x,y) in a.zip(b) {
+        if x!=y {
+            distance+=1;
+            if distance>=min { return None; }
+        }
+    }
+    if distance<min { Some(distance) } else { None }
+}
+
+/// Hamming distance in which each difference counts according to `weights`, comparing positions up to the length of the shorter code, plus `extra`.
+fn weighted_hamming_below(weights:&Weights,a:impl Iterator<Item=char>,b:impl Iterator<Item=char>,min:usize,extra:usize) -> Option<usize> {
+    if extra>=min { return None; }
+    let mut distance = extra;
+    for (x,y) in a.zip(b) {
+        distance+=weights.weight(x,y);
+        if distance>=min { return None; }
+    }
+    Some(distance)
+}
+
+/// Levenshtein distance, or if `transpositions` is true the optimal string alignment distance which also allows swapping adjacent characters.
+fn levenshtein_below(a:&[char],b:&[char],min:usize,transpositions:bool) -> Option<usize> {
+    if a.len().abs_diff(b.len())>=min { return None; }
+    // previous[j] is the distance between the first i-1 characters of a and the first j of b, and before_previous the same for i-2.
+    let mut before_previous : Vec<usize> = vec![0;b.len()+1];
+    let mut previous : Vec<usize> = (0..=b.len()).collect();
+    let mut current = vec![0;b.len()+1];
+    for (i,&x) in a.iter().enumerate() {
+        current[0]=i+1;
+        for (j,&y) in b.iter().enumerate() {
+            let substitute = previous[j]+if x==y { 0 } else { 1 };
+            current[j+1]=substitute.min(previous[j+1]+1).min(current[j]+1);
+            if transpositions && i>0 && j>0 && x==b[j-1] && a[i-1]==y && x!=y {
+                current[j+1]=current[j+1].min(before_previous[j-1]+1);
+            }
+        }
+        // the smallest entry in a row never decreases in later rows, so if they are all too big the answer is too.
+        if current.iter().all(|&d|d>=min) { return None; }
+        std::mem::swap(&mut before_previous,&mut previous);
+        std::mem::swap(&mut previous,&mut current);
+    }
+    let distance = previous[b.len()];
+    if distance<min { Some(distance) } else { None }
+}
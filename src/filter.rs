@@ -0,0 +1,238 @@
+// This program is Copyright 2022 Andrew Conway and licensed under the GPL:
+//
+// This program is free software: you can redistribute it and/or modify it under the terms of the GNU General Public License as published by the Free Software Foundation, either version 3 of the License, or (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful, but WITHOUT ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License along with this program. If not, see <https://www.gnu.org/licenses/>.
+
+//! Extra conditions a candidate code must satisfy, checked before the (more expensive) distance test.
+
+use std::fmt::{Display, Formatter};
+use std::ops::Range;
+use std::str::FromStr;
+use regex::Regex;
+
+/// A condition on a candidate code.
+#[derive(Clone,Debug)]
+pub enum Filter {
+    /// Reject codes with more than this many identical characters in a row anywhere in the whole code. Zero would reject everything.
+    ///
+    /// ```
+    /// use make_exam_numbers::filter::{Candidate, Filter};
+    ///
+    /// let code = |code| Candidate{code,prefix_len:2,body_len:5};
+    /// assert!(Filter::MaxRun(2).accepts(&code("A771232")));
+    /// assert!(!Filter::MaxRun(2).accepts(&code("A777123"))); // the run starts in the prefix and carries on into the random part.
+    /// assert!(Filter::MaxRun(3).accepts(&code("A777123")));
+    /// assert!(!Filter::MaxRun(2).accepts(&code("A712333")));
+    /// ```
+    MaxRun(usize),
+    /// Reject codes with more than `max` characters in a row that go up or down by one in the given ordering (usually the alphabet), like 12345.
+    ///
+    /// Only the random part is checked unless `include_prefix` is true. Characters not in the ordering break any sequence, and the
+    /// ordering does not wrap around, so 9 followed by 0 is not ascending.
+    ///
+    /// ```
+    /// use make_exam_numbers::filter::{Candidate, Filter};
+    ///
+    /// let ordering : Vec<char> = ('0'..='9').collect();
+    /// let filter = |max,include_prefix| Filter::MaxSequential{max,include_prefix,ordering:ordering.clone()};
+    /// let code = |code| Candidate{code,prefix_len:2,body_len:6};
+    /// assert!(filter(4,false).accepts(&code("S1890123"))); // 9 followed by 0 breaks the sequence.
+    /// assert!(!filter(3,false).accepts(&code("S1890123"))); // but 0123 is still 4 ascending.
+    /// assert!(!filter(3,false).accepts(&code("S1597654")));
+    /// assert!(filter(4,false).accepts(&code("S1597654")));
+    /// assert!(filter(2,false).accepts(&code("S1237946"))); // the prefix is not checked...
+    /// assert!(!filter(2,true).accepts(&code("S1237946"))); // ...unless asked, making 123.
+    /// ```
+    MaxSequential { max : usize, include_prefix : bool, ordering : Vec<char> },
+    /// Reject codes containing any of these substrings anywhere in the whole code, ignoring case. The substrings should be lower case.
+    Blocklist(Vec<String>),
+    /// Reject codes whose random part contains fewer than this many different characters, like 353535.
+    ///
+    /// ```
+    /// use make_exam_numbers::filter::{Candidate, Filter};
+    ///
+    /// let code = |code| Candidate{code,prefix_len:1,body_len:4};
+    /// assert!(Filter::MinDistinct(4).accepts(&code("A1234")));
+    /// assert!(!Filter::MinDistinct(4).accepts(&code("A1231")));
+    /// assert!(Filter::MinDistinct(3).accepts(&code("A1231")));
+    /// assert!(Filter::MinDistinct(3).accepts(&code("11234"))); // the prefix doesn't count.
+    /// assert!(!Filter::MinDistinct(2).accepts(&code("A3333")));
+    /// assert!(!Filter::MinDistinct(5).accepts(&code("A1234"))); // more than the number of random characters rejects everything.
+    /// ```
+    MinDistinct(usize),
+    /// Reject codes where any of these regular expressions matches the whole code (anywhere in it, unless anchored with `^` or `$`).
+    ExcludeRegex(Vec<Regex>),
+    /// Reject codes whose whole code doesn't have the digit sum wanted, with each character's value its position in `values` (usually the alphabet).
+    DigitSum { sum : DigitSum, values : Vec<char> },
+    /// Reject codes whose random part, read as a decimal number, is in any of these ranges, such as numbers kept for re-sits.
+    /// A random part that isn't a number is never rejected.
+    ///
+    /// ```
+    /// use make_exam_numbers::filter::{Candidate, Filter};
+    ///
+    /// let code = |code| Candidate{code,prefix_len:1,body_len:6};
+    /// let filter = Filter::ExcludeRanges(vec![100000..110000,550000..560000]);
+    /// assert!(!filter.accepts(&code("S105123")));
+    /// assert!(filter.accepts(&code("S110000")));
+    /// assert!(!filter.accepts(&code("S550000")));
+    /// assert!(filter.accepts(&code("S099999")));
+    /// ```
+    ExcludeRanges(Vec<Range<u64>>),
+}
+
+/// A condition that the values of a code's characters add up to `remainder` modulo `modulus`, a cheap check some scanners make.
+///
+/// Parsed from "9" for sums divisible by 9, or "9:3" for sums leaving 3 when divided by 9.
+/// ```
+/// use make_exam_numbers::filter::DigitSum;
+///
+/// let digits : Vec<char> = ('0'..='9').collect();
+/// let sum : DigitSum = "9".parse().unwrap();
+/// assert_eq!(DigitSum::sum("S0123",&digits),6); // the S is not in the values, so counts as 0.
+/// assert!(sum.accepts("123453",&digits));
+/// assert!(!sum.accepts("123456",&digits));
+/// assert!("9:3".parse::<DigitSum>().unwrap().accepts("123456",&digits)); // 21 leaves 3 when divided by 9.
+/// for bad in ["","x","1","9:9","9:x","9:3:1"] { assert!(bad.parse::<DigitSum>().is_err()); }
+/// ```
+#[derive(Clone,Copy,Debug,PartialEq,Eq)]
+pub struct DigitSum {
+    pub modulus : u64,
+    pub remainder : u64,
+}
+
+impl DigitSum {
+    /// The sum of the values of the characters in `code`, each being its position in `values`, so with the alphabet of digits a digit is worth itself.
+    /// Characters not in `values`, such as the letters of a prefix, count as 0.
+    pub fn sum(code:&str,values:&[char]) -> u64 {
+        code.chars().filter_map(|c|values.iter().position(|&v|v==c)).map(|v|v as u64).sum()
+    }
+
+    /// Whether the values of the characters in `code` add up to the remainder wanted.
+    pub fn accepts(&self,code:&str,values:&[char]) -> bool { DigitSum::sum(code,values)%self.modulus==self.remainder }
+}
+
+impl Display for DigitSum {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result { write!(f,"{}:{}",self.modulus,self.remainder) }
+}
+
+impl FromStr for DigitSum {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (modulus,remainder) = s.split_once(':').unwrap_or((s,"0"));
+        let modulus : u64 = modulus.parse().map_err(|_|format!("Invalid digit sum {:?} : the modulus {:?} is not a number. Expected M or M:R, like 9 or 9:3",s,modulus))?;
+        let remainder : u64 = remainder.parse().map_err(|_|format!("Invalid digit sum {:?} : the remainder {:?} is not a number. Expected M or M:R, like 9 or 9:3",s,remainder))?;
+        if modulus<2 { return Err(format!("Invalid digit sum {:?} : the modulus must be at least 2",s)); }
+        if remainder>=modulus { return Err(format!("Invalid digit sum {:?} : the remainder must be less than the modulus {}",s,modulus)); }
+        Ok(DigitSum{modulus,remainder})
+    }
+}
+
+/// A candidate code, split into its parts so filters can look at just some of them.
+#[derive(Clone,Copy,Debug)]
+pub struct Candidate<'a> {
+    /// The whole code, prefix followed by random part followed by any check digits.
+    pub code : &'a str,
+    /// The length in bytes of the prefix at the start of `code`.
+    pub prefix_len : usize,
+    /// The length in bytes of the random part after the prefix.
+    pub body_len : usize,
+}
+
+impl <'a> Candidate<'a> {
+    /// The random part of the code.
+    pub fn body(&self) -> &'a str { &self.code[self.prefix_len..self.prefix_len+self.body_len] }
+    /// The prefix and the random part, without any check digits.
+    pub fn prefix_and_body(&self) -> &'a str { &self.code[..self.prefix_len+self.body_len] }
+}
+
+/// Why a candidate code was rejected.
+#[derive(Clone,Copy,Debug)]
+pub enum Rejection<'a> {
+    /// Too close to some used code.
+    TooClose,
+    /// Too close to some used code of the same length when rotated left by `rotation` characters ([crate::GenerateCodes::set_include_rotations]).
+    TooCloseRotated { rotation : usize },
+    /// Failed the given filter.
+    Filter(&'a Filter),
+}
+
+impl Display for Rejection<'_> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Rejection::TooClose => write!(f,"too close"),
+            Rejection::TooCloseRotated{rotation} => write!(f,"too close when rotated by {}",rotation),
+            Rejection::Filter(filter) => write!(f,"{}",filter.name()),
+        }
+    }
+}
+
+impl Filter {
+    /// Whether the candidate code passes this filter.
+    pub fn accepts(&self,candidate:&Candidate) -> bool {
+        match self {
+            Filter::MaxRun(max_run) => {
+                let mut run = 0;
+                let mut last = None;
+                for c in candidate.code.chars() {
+                    if Some(c)==last { run+=1 } else { run=1; last=Some(c); }
+                    if run>*max_run { return false; }
+                }
+                true
+            }
+            Filter::Blocklist(substrings) => {
+                let code = candidate.code.to_lowercase();
+                !substrings.iter().any(|s|code.contains(s.as_str()))
+            }
+            Filter::ExcludeRegex(patterns) => !patterns.iter().any(|p|p.is_match(candidate.code)),
+            Filter::DigitSum { sum, values } => sum.accepts(candidate.code,values),
+            Filter::ExcludeRanges(ranges) => match candidate.body().parse::<u64>() {
+                Ok(number) => !ranges.iter().any(|r|r.contains(&number)),
+                Err(_) => true,
+            },
+            Filter::MinDistinct(min) => {
+                let mut seen : Vec<char> = vec![];
+                for c in candidate.body().chars() {
+                    if !seen.contains(&c) {
+                        seen.push(c);
+                        if seen.len()>=*min { return true; }
+                    }
+                }
+                seen.len()>=*min
+            }
+            Filter::MaxSequential { max, include_prefix, ordering } => {
+                let text = if *include_prefix { candidate.prefix_and_body() } else { candidate.body() };
+                let mut ascending = 0;
+                let mut descending = 0;
+                let mut last : Option<usize> = None;
+                for c in text.chars() {
+                    let position = ordering.iter().position(|&o|o==c);
+                    match (last,position) {
+                        (Some(last),Some(position)) if position==last+1 => { ascending+=1; descending=1; }
+                        (Some(last),Some(position)) if position+1==last => { descending+=1; ascending=1; }
+                        _ => { ascending=1; descending=1; }
+                    }
+                    if ascending>*max || descending>*max { return false; }
+                    last=position;
+                }
+                true
+            }
+        }
+    }
+
+    /// A short description of the filter, used when reporting rejections.
+    pub fn name(&self) -> &'static str {
+        match self {
+            Filter::MaxRun(_) => "max run",
+            Filter::MaxSequential{..} => "max sequential",
+            Filter::Blocklist(_) => "blocklist",
+            Filter::ExcludeRegex(_) => "exclude regex",
+            Filter::MinDistinct(_) => "min distinct",
+            Filter::DigitSum{..} => "digit sum",
+            Filter::ExcludeRanges(_) => "exclude range",
+        }
+    }
+}
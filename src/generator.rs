@@ -0,0 +1,1215 @@
+// This program is Copyright 2022 Andrew Conway and licensed under the GPL:
+//
+// This program is free software: you can redistribute it and/or modify it under the terms of the GNU General Public License as published by the Free Software Foundation, either version 3 of the License, or (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful, but WITHOUT ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License along with this program. If not, see <https://www.gnu.org/licenses/>.
+
+//! The actual code generator.
+
+use std::fmt::{Display, Formatter};
+use std::str::FromStr;
+use rand::Rng;
+use rand::seq::SliceRandom;
+use rand_chacha::ChaCha8Rng;
+use rand_chacha::rand_core::SeedableRng;
+use crate::algebraic::Construction;
+use crate::alphabet::Alphabet;
+use crate::check_digit::CheckDigit;
+use crate::derive::KeyedDerivation;
+use crate::distance::{LengthPolicy, Metric, Scope};
+use crate::filter::{Candidate, Filter, Rejection};
+use crate::index::HammingIndex;
+use crate::numeric::NumericIndex;
+use crate::seed::{Seed, stream_number};
+use crate::stats::Statistics;
+use crate::verify::{find_problems, find_problems_grouped, find_reversal_problems, min_distance_for, Problem};
+use rayon::prelude::*;
+use std::collections::{BTreeMap, HashMap, HashSet, VecDeque};
+use std::ops::Range;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::Instant;
+
+/// Reasons why a code could not be generated.
+#[derive(Debug,Clone,PartialEq,Eq)]
+pub enum GenerationError {
+    /// Gave up looking for a code with the given prefix after this many attempts.
+    TooManyAttempts { prefix : String, attempts : u64 },
+    /// Every possible code with the given prefix has been tried, with [Strategy::Exhaustive].
+    Exhausted { prefix : String },
+    /// The flag given to [GenerateCodes::set_interrupt] was set while looking for a code with the given prefix.
+    Interrupted { prefix : String },
+    /// The time given to [GenerateCodes::set_deadline] passed while looking for a code with the given prefix.
+    TimedOut { prefix : String },
+    /// There is no way to make codes with the given prefix with [Strategy::Algebraic] for these parameters, for the reason given.
+    NoConstruction { prefix : String, reason : String },
+}
+
+impl Display for GenerationError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            GenerationError::TooManyAttempts { prefix, attempts } => write!(f,"Could not find a code with prefix {} after {} attempts",prefix,attempts),
+            GenerationError::Exhausted { prefix } => write!(f,"Every possible code with prefix {} has been tried",prefix),
+            GenerationError::Interrupted { prefix } => write!(f,"Interrupted while looking for a code with prefix {}",prefix),
+            GenerationError::TimedOut { prefix } => write!(f,"Ran out of time while looking for a code with prefix {}",prefix),
+            GenerationError::NoConstruction { prefix, reason } => write!(f,"Can't make codes with prefix {} : {}",prefix,reason),
+        }
+    }
+}
+
+impl std::error::Error for GenerationError {}
+
+/// How candidate random parts are chosen.
+#[derive(Clone,Copy,Debug,PartialEq,Eq,Default)]
+pub enum Strategy {
+    /// Each random part is drawn independently, so may be tried more than once. This is the default.
+    #[default]
+    Random,
+    /// Every possible random part is tried once, in a random order. This holds all of them in memory at once,
+    /// so is only suitable for small numbers of digits, but can tell when no more codes are possible.
+    Exhaustive,
+    /// Random parts are chosen randomly from a Reed–Solomon code, so random parts with the same prefix are always far enough
+    /// apart (for Hamming distance) and almost every candidate is accepted. If the prefix is made of characters from the
+    /// alphabet it is included in the code, so codes with different prefixes are far enough apart too. Candidates are still
+    /// checked against all used codes, such as existing ones. Only some combinations of parameters are possible,
+    /// see [GenerateCodes::check_construction].
+    Algebraic,
+}
+
+impl FromStr for Strategy {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "random" => Ok(Strategy::Random),
+            "exhaustive" => Ok(Strategy::Exhaustive),
+            "algebraic" => Ok(Strategy::Algebraic),
+            _ => Err(format!("Unknown strategy {}, expecting random, exhaustive or algebraic",s)),
+        }
+    }
+}
+
+/// The name accepted by [FromStr].
+impl Display for Strategy {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            Strategy::Random => "random",
+            Strategy::Exhaustive => "exhaustive",
+            Strategy::Algebraic => "algebraic",
+        })
+    }
+}
+
+/// The source of random parts for one prefix (or for all of them, see [GenerateCodes::set_per_prefix_streams]).
+struct Stream {
+    prng : ChaCha8Rng,
+    /// Random parts generated but not yet looked at.
+    pending : VecDeque<String>,
+    /// The number of random digits in each of `pending`.
+    num_digits : usize,
+    /// With [Strategy::Exhaustive], whether `pending` has been filled with every possible random part.
+    enumerated : bool,
+}
+
+/// Generate random codes that are not too similar to any code previously generated or loaded.
+pub struct GenerateCodes {
+    /// The generator each stream starts as, before choosing the stream for a prefix.
+    prng : ChaCha8Rng,
+    /// The stream for each (canonical) prefix, or just the empty string if not using a stream per prefix.
+    streams : HashMap<String,Stream>,
+    per_prefix_streams : bool,
+    strategy : Strategy,
+    /// Used for shuffling, separate from `prng` so shuffling doesn't change which codes are generated.
+    shuffle_prng : ChaCha8Rng,
+    /// The number of random digits used unless another is given for a particular code.
+    num_digits : usize,
+    used : Vec<String>,
+    /// The same codes as `used`, for quickly rejecting exact duplicates.
+    used_set : HashSet<String>,
+    /// If distances are measured between just the last `body_len` characters of codes, those characters of each of `used`.
+    bodies : Option<(usize,Vec<String>)>,
+    distance_on_body : bool,
+    max_attempts : Option<u64>,
+    /// How hard it was to find the codes for each canonical prefix.
+    statistics : HashMap<String,Statistics>,
+    /// If present and set, stop looking for codes.
+    interrupt : Option<Arc<AtomicBool>>,
+    deadline : Option<Instant>,
+    check_digit : Option<CheckDigit>,
+    alphabet : Alphabet,
+    no_leading_zero : bool,
+    filters : Vec<Filter>,
+    metric : Metric,
+    scope : Scope,
+    length_policy : LengthPolicy,
+    /// Indexes of `used` to speed up [GenerateCodes::ok], built when needed for the Hamming metric, one for each minimum distance used.
+    indices : Vec<HammingIndex>,
+    /// If present, candidates are checked in parallel on this thread pool.
+    pool : Option<rayon::ThreadPool>,
+    min_numeric_gap : Option<u64>,
+    /// Whether a candidate read backwards must also be far enough from the used codes.
+    include_reversals : bool,
+    /// Whether every rotation of a candidate must also be far enough from the used codes of the same length.
+    include_rotations : bool,
+    /// If present, the numbers decimal random parts are drawn from, instead of all those with the number of digits.
+    range : Option<Range<u64>>,
+    balance_first : bool,
+    /// With `balance_first`, how many codes in each group (by canonical name) start their random part with each allowed character.
+    first_counts : HashMap<String,Vec<u64>>,
+    /// Prefixes whose codes have their own minimum distance, with [GenerateCodes::set_min_distance_for].
+    min_distances : Vec<(String,usize)>,
+    /// The indices in `used` of the codes with each minimum distance from `min_distances`.
+    used_by_min_distance : BTreeMap<usize,Vec<usize>>,
+    /// With [GenerateCodes::set_min_distance_across], the least distance between codes with different prefixes.
+    min_distance_across : Option<usize>,
+    /// The (canonical) prefixes codes have been made with or declared for, see [GenerateCodes::declare_prefixes].
+    prefixes : Vec<String>,
+    /// For each of `used`, the position of its prefix in `prefixes`, if it is known.
+    used_prefixes : Vec<Option<usize>>,
+    /// With `min_numeric_gap`, an index of the random parts of `used` as numbers, for the number of digits currently being made.
+    numeric : Option<NumericIndex>,
+}
+
+/// When checking candidates in parallel, how many to check at once per thread.
+const CANDIDATES_PER_THREAD : usize = 4;
+
+impl GenerateCodes {
+    /// Make a generator for codes with `num_digits` random digits (after any prefix), unless another number is given
+    /// with [GenerateCodes::new_code_with_digits]. See [GenerateCodes::set_alphabet] for non-digit characters.
+    ///
+    /// If a `seed` is given the sequence of codes produced is reproducible, otherwise the generator is seeded from entropy.
+    pub fn new(seed:Option<u64>,num_digits:usize) -> Self {
+        Self::from_seed(seed.map(Seed::U64),num_digits)
+    }
+
+    /// Like [GenerateCodes::new], but the seed may also be a full 256 bit key.
+    pub fn from_seed(seed:Option<Seed>,num_digits:usize) -> Self {
+        let prng = if let Some(seed) = seed { seed.prng() } else { ChaCha8Rng::from_entropy() };
+        let mut shuffle_prng = prng.clone();
+        shuffle_prng.set_stream(1);
+        GenerateCodes {
+            prng,
+            streams: HashMap::new(),
+            per_prefix_streams: true,
+            strategy: Strategy::default(),
+            shuffle_prng,
+            num_digits,
+            used: vec![],
+            used_set: HashSet::new(),
+            bodies: None,
+            distance_on_body: false,
+            max_attempts: None,
+            interrupt: None,
+            deadline: None,
+            statistics: HashMap::new(),
+            check_digit: None,
+            alphabet: Alphabet::default(),
+            no_leading_zero: false,
+            filters: vec![],
+            metric: Metric::default(),
+            scope: Scope::default(),
+            length_policy: LengthPolicy::default(),
+            indices: vec![],
+            pool: None,
+            min_numeric_gap: None,
+            include_reversals: false,
+            include_rotations: false,
+            balance_first: false,
+            first_counts: HashMap::new(),
+            range: None,
+            min_distances: vec![],
+            used_by_min_distance: BTreeMap::new(),
+            min_distance_across: None,
+            prefixes: vec![],
+            used_prefixes: vec![],
+            numeric: None,
+        }
+    }
+
+    /// Give up on a code after this many candidates have been tried. None (the default) means keep trying forever.
+    pub fn set_max_attempts(&mut self,max_attempts:Option<u64>) {
+        self.max_attempts=max_attempts;
+    }
+
+    /// Stop looking for a code, with [GenerationError::Interrupted], when this flag is set (e.g. by a signal handler).
+    /// It is checked before each candidate (or batch of candidates, with [GenerateCodes::set_threads]).
+    pub fn set_interrupt(&mut self,interrupt:Arc<AtomicBool>) {
+        self.interrupt=Some(interrupt);
+    }
+
+    /// Stop looking for a code, with [GenerationError::TimedOut], once this time has passed. None (the default) means no limit.
+    /// Like the interrupt flag, it is checked before each candidate (or batch of candidates), so no search overruns it by much.
+    pub fn set_deadline(&mut self,deadline:Option<Instant>) {
+        self.deadline=deadline;
+    }
+
+    /// Append check digit(s) after the random digits of each code. These are not counted in `num_digits`, but are included in distance comparisons.
+    pub fn set_check_digit(&mut self,check_digit:Option<CheckDigit>) {
+        self.check_digit=check_digit;
+        self.update_bodies(self.num_digits);
+        self.update_numeric(self.num_digits);
+    }
+
+    /// Measure distances between just the random parts (and any check digits) of codes, ignoring whatever prefix they have.
+    ///
+    /// The random part of a code is taken to be its last characters, so this works for existing codes with unknown prefixes.
+    /// Unless [GenerateCodes::set_scope] is [Scope::PerPrefix], this means the random parts must be far enough apart
+    /// even for codes with different prefixes. When making codes with a different number of random digits, that many
+    /// characters (plus any check digits) are compared instead.
+    pub fn set_distance_on_body(&mut self,distance_on_body:bool) {
+        self.distance_on_body=distance_on_body;
+        self.update_bodies(self.num_digits);
+    }
+
+    /// Also reject a candidate if its random part, read as a number, differs by less than `gap` from that of a used code with the same prefix.
+    ///
+    /// This stops codes like 123456 and 123457 being next to each other on lists sorted numerically, whatever their Hamming distance.
+    /// The random part is taken to be the last characters of a code (before any check digits), and codes whose random part
+    /// is not all digits are not compared, so this is only useful with an alphabet of digits. It is checked by [GenerateCodes::ok],
+    /// along with the distance.
+    ///
+    /// ```
+    /// use make_exam_numbers::GenerateCodes;
+    ///
+    /// let mut generator = GenerateCodes::new(Some(1),4);
+    /// generator.load_existing(["1000"]);
+    /// generator.set_min_numeric_gap(Some(10));
+    /// assert!(!generator.ok("1005",1)); // far enough apart by Hamming distance, but numerically too close.
+    /// assert!(generator.ok("2000",1)); // numerically far apart, and far enough by Hamming distance...
+    /// assert!(!generator.ok("2000",2)); // ...unless that has to be at least 2.
+    /// assert!(generator.ok("X1005",1)); // a different prefix.
+    /// ```
+    pub fn set_min_numeric_gap(&mut self,gap:Option<u64>) {
+        self.min_numeric_gap=gap;
+        self.update_numeric(self.num_digits);
+    }
+
+    /// Only draw random parts from this range of numbers (including the start but not the end), zero padded to the number of
+    /// digits, instead of from every number with that many digits. This is only for the default decimal alphabet; other
+    /// alphabets and [Strategy::Algebraic] ignore it. With [GenerateCodes::set_no_leading_zero] too, numbers with fewer digits are left out, and if
+    /// no numbers are left, looking for a code fails with [GenerationError::Exhausted].
+    ///
+    /// ```
+    /// use make_exam_numbers::GenerateCodes;
+    ///
+    /// let mut generator = GenerateCodes::new(Some(1),6);
+    /// generator.set_range(Some(400000..700000));
+    /// let code : u64 = generator.new_code("",3).unwrap().parse().unwrap();
+    /// assert!((400000..700000).contains(&code));
+    /// generator.set_range(Some(5..15));
+    /// let code = generator.new_code("",1).unwrap();
+    /// assert!(code.starts_with("0000") && (5..15).contains(&code.parse().unwrap()));
+    /// ```
+    pub fn set_range(&mut self,range:Option<Range<u64>>) {
+        self.range=range;
+        self.clear_pending();
+    }
+
+    /// The numbers random parts with `num_digits` decimal digits are drawn from.
+    fn body_range(&self,num_digits:usize) -> Range<u64> {
+        let mut range = 0..10u64.pow(num_digits as u32);
+        if self.no_leading_zero && num_digits>0 { range.start=10u64.pow(num_digits as u32-1); }
+        if let Some(wanted) = &self.range { range = range.start.max(wanted.start)..range.end.min(wanted.end); }
+        range
+    }
+
+    /// Make `numeric` consistent with the current settings, for codes with `num_digits` random digits.
+    fn update_numeric(&mut self,num_digits:usize) {
+        let check_len = self.check_digit.map(|c|c.num_characters()).unwrap_or(0);
+        match self.min_numeric_gap {
+            None => self.numeric=None,
+            Some(gap) if !self.numeric.as_ref().map(|n|n.is_for(gap,num_digits,check_len)).unwrap_or(false) => {
+                let mut numeric = NumericIndex::new(gap,num_digits,check_len);
+                for code in &self.used { numeric.insert(code); }
+                self.numeric=Some(numeric);
+            }
+            Some(_) => {}
+        }
+    }
+
+    /// Make `bodies` consistent with the current settings, for codes with `num_digits` random digits.
+    fn update_bodies(&mut self,num_digits:usize) {
+        let body_len = Some(num_digits+self.check_digit.map(|c|c.num_characters()).unwrap_or(0)).filter(|_|self.distance_on_body);
+        if self.bodies.as_ref().map(|(len,_)|*len)!=body_len {
+            self.bodies = body_len.map(|len|(len,self.used.iter().map(|code|body_of(code,len).to_string()).collect()));
+            self.indices.clear();
+        }
+    }
+
+    /// What distances are measured between, for each of the used codes.
+    fn compared(&self) -> &[String] {
+        match &self.bodies {
+            Some((_,bodies)) => bodies,
+            None => &self.used,
+        }
+    }
+
+    /// The part of a (canonical) code distances are measured on.
+    fn compared_part<'a>(&self,code:&'a str) -> &'a str {
+        match &self.bodies {
+            Some((len,_)) => body_of(code,*len),
+            None => code,
+        }
+    }
+
+    /// Draw each random character from the given alphabet instead of the digits 0-9.
+    pub fn set_alphabet(&mut self,alphabet:Alphabet) {
+        self.alphabet=alphabet;
+        self.clear_pending();
+    }
+
+    /// Never generate a '0' as the first random character, for systems that strip leading zeros.
+    /// The other characters are still uniformly distributed. Has no effect if the alphabet contains no other characters.
+    pub fn set_no_leading_zero(&mut self,no_leading_zero:bool) {
+        self.no_leading_zero=no_leading_zero;
+        self.clear_pending();
+    }
+
+    /// Measure distance between codes with this metric instead of Hamming distance. The `min_hamming_distance` arguments then refer to this metric.
+    pub fn set_metric(&mut self,metric:Metric) {
+        self.metric=metric;
+    }
+
+    /// The metric set by [GenerateCodes::set_metric], which distances are measured in units of.
+    pub fn metric(&self) -> Metric { self.metric }
+
+    /// Whether each prefix gets its own independent random stream (the default), derived from the seed and the prefix.
+    ///
+    /// With separate streams, the candidates tried for a prefix don't depend on what other prefixes were generated first,
+    /// though with [Scope::Global] which of them are accepted still can, as a candidate may be too close to another prefix's code.
+    /// With a single shared stream, as in earlier versions, the candidates depend on them too. This should be set before generating any codes.
+    /// With [Strategy::Exhaustive] each prefix still has its own list of the random parts not yet tried, in the same order
+    /// for every prefix, as a random part rejected for one prefix may be fine for another.
+    pub fn set_per_prefix_streams(&mut self,per_prefix_streams:bool) {
+        self.per_prefix_streams=per_prefix_streams;
+        self.streams.clear();
+    }
+
+    /// How candidates are chosen. This should be set before generating any codes.
+    pub fn set_strategy(&mut self,strategy:Strategy) {
+        self.strategy=strategy;
+        self.streams.clear();
+    }
+
+    /// Forget random parts generated but not yet looked at, as the settings used to make them have changed.
+    fn clear_pending(&mut self) {
+        for stream in self.streams.values_mut() { stream.pending.clear(); stream.enumerated=false; }
+    }
+
+    /// A new stream for the given key, which is a canonical prefix (or `prefix*suffix`) if using a stream per prefix.
+    fn new_stream(&self,key:&str) -> Stream {
+        let mut prng = self.prng.clone();
+        if self.per_prefix_streams { prng.set_stream(stream_number(key)); }
+        Stream{prng,pending:VecDeque::new(),num_digits:self.num_digits,enumerated:false}
+    }
+
+    /// Put some codes into a random order. This is reproducible given the seed, and does not change which codes are generated afterwards.
+    pub fn shuffle(&mut self,codes:&mut [String]) {
+        codes.shuffle(&mut self.shuffle_prng);
+    }
+
+    /// How Hamming distance treats codes of different lengths, such as existing codes made with a different number of digits.
+    pub fn set_length_policy(&mut self,length_policy:LengthPolicy) {
+        self.length_policy=length_policy;
+    }
+
+    /// Also require each candidate read backwards, as by a scanner with the page upside down, to be at least the minimum distance
+    /// from every used code, so that no code read backwards could be mistaken for another. As reversing both codes doesn't change
+    /// the distance between them, this is the same as requiring the candidate to be far enough from every used code read backwards.
+    /// A code is not compared with its own reversal, so codes that read the same both ways are allowed.
+    ///
+    /// The whole code is reversed, so this should not be used with [GenerateCodes::set_distance_on_body]. It is checked by [GenerateCodes::ok].
+    ///
+    /// ```
+    /// use make_exam_numbers::GenerateCodes;
+    ///
+    /// let mut generator = GenerateCodes::new(Some(1),6);
+    /// generator.load_existing(["123456"]);
+    /// assert!(generator.ok("654321",3));
+    /// generator.set_include_reversals(true);
+    /// assert!(!generator.ok("654321",3));
+    /// assert!(!generator.ok("654391",3));
+    /// assert!(generator.ok("699391",3));
+    /// ```
+    pub fn set_include_reversals(&mut self,include_reversals:bool) {
+        self.include_reversals=include_reversals;
+    }
+
+    /// Also require every rotation of each candidate, as when a label wrapped round is read from the wrong place, to be at least
+    /// the minimum distance from every used code of the same length. Rotating `123456` left by 2 gives `345612`. This is the same
+    /// as requiring the candidate to be far enough from every rotation of the used codes. It multiplies the work of checking
+    /// a candidate by the length of the code (see [Statistics::rotations_compared]), and means far fewer codes can fit.
+    ///
+    /// The whole code is rotated, so this should not be used with [GenerateCodes::set_distance_on_body]. It is checked by [GenerateCodes::ok].
+    ///
+    /// ```
+    /// use make_exam_numbers::GenerateCodes;
+    ///
+    /// let mut generator = GenerateCodes::new(Some(1),6);
+    /// generator.load_existing(["123456"]);
+    /// assert!(generator.ok("345612",3));
+    /// generator.set_include_rotations(true);
+    /// assert!(!generator.ok("345612",3));
+    /// assert!(!generator.ok("345692",3));
+    /// assert!(generator.ok("999999",3));
+    /// ```
+    pub fn set_include_rotations(&mut self,include_rotations:bool) {
+        self.include_rotations=include_rotations;
+    }
+
+    /// Give the codes starting with `prefix` their own minimum distance, which is then the least distance between them and any
+    /// other code, such as a small group of codes that can afford to be further apart than the rest. A code with several such
+    /// prefixes has the minimum distance of the longest. Two codes must be at least the larger of their minimum distances
+    /// apart (see [crate::verify::required_distance]), where the minimum distance of a candidate is the one it is looked for
+    /// with, so this should also be used for the codes being made, as in `new_code("PG",5)`. It is checked by
+    /// [GenerateCodes::ok], and applies to codes already used as well as those added later.
+    ///
+    /// ```
+    /// use make_exam_numbers::GenerateCodes;
+    ///
+    /// let mut generator = GenerateCodes::new(Some(1),4);
+    /// generator.load_existing(["PG1234"]);
+    /// assert!(generator.ok("UG1299",3));
+    /// generator.set_min_distance_for("PG",5);
+    /// generator.set_min_distance_for("UG",3);
+    /// assert!(!generator.ok("UG1299",3)); // only 3 from PG1234, which must be 5 from every code.
+    /// assert!(generator.ok("UG9999",3));
+    /// ```
+    pub fn set_min_distance_for(&mut self,prefix:&str,min_distance:usize) {
+        let prefix = self.alphabet.normalise(prefix).into_owned();
+        self.min_distances.retain(|(p,_)|*p!=prefix);
+        self.min_distances.push((prefix,min_distance));
+        self.used_by_min_distance.clear();
+        for (i,code) in self.used.iter().enumerate() {
+            let min_distance = min_distance_for(code,0,&self.min_distances);
+            if min_distance>0 { self.used_by_min_distance.entry(min_distance).or_default().push(i); }
+        }
+    }
+
+    /// Spread the codes made for each name (see [GenerateCodes::new_code_with_layout]) evenly over the possible first random
+    /// characters, so the number starting with each differs by at most one. Each code must start with the character used
+    /// least so far, the first in the alphabet if several are, and the rest of the random part is random as usual. Characters
+    /// are counted with [GenerateCodes::first_counts], and codes not made by this generator, as when carrying on earlier work,
+    /// can be counted with [GenerateCodes::count_first].
+    ///
+    /// This is only for [Strategy::Random], and ignores [GenerateCodes::set_range]. If no acceptable code starts with the
+    /// character needed, as when filters reject them all, looking for a code only stops at [GenerateCodes::set_max_attempts].
+    ///
+    /// ```
+    /// use make_exam_numbers::GenerateCodes;
+    ///
+    /// let mut generator = GenerateCodes::new(Some(1),4);
+    /// generator.set_no_leading_zero(true);
+    /// generator.set_balance_first(true);
+    /// for _ in 0..20 { generator.new_code("S",2).unwrap(); }
+    /// let counts = generator.first_counts("S");
+    /// assert_eq!(counts.len(),9);
+    /// assert!(counts.iter().all(|&(c,count)|c!='0' && (2..=3).contains(&count)));
+    /// ```
+    pub fn set_balance_first(&mut self,balance_first:bool) {
+        self.balance_first=balance_first;
+    }
+
+    /// The characters a random part may start with, in alphabet order.
+    fn first_characters(&self) -> Vec<char> {
+        let characters = self.alphabet.characters();
+        characters.iter().copied().filter(|&c|!(c=='0' && self.no_leading_zero && characters.len()>1)).collect()
+    }
+
+    /// With [GenerateCodes::set_balance_first], the number of codes made (or counted) with the given name whose random part
+    /// starts with each possible character, in alphabet order. Empty if there are none.
+    pub fn first_counts(&self,name:&str) -> Vec<(char,u64)> {
+        match self.first_counts.get(self.alphabet.normalise(name).as_ref()) {
+            Some(counts) => self.first_characters().into_iter().zip(counts.iter().copied()).collect(),
+            None => vec![],
+        }
+    }
+
+    /// Count a code laid out as given (see [GenerateCodes::new_code_with_layout]) as made with the given name, for
+    /// [GenerateCodes::set_balance_first]. Codes whose first random character couldn't start a random part are ignored.
+    pub fn count_first(&mut self,name:&str,layout:&[Option<char>],code:&str) {
+        let Some(position) = self.canonical_layout(layout).iter().position(|c|c.is_none()) else { return; };
+        let code = self.alphabet.normalise(code);
+        let Some(first) = code.chars().nth(position) else { return; };
+        self.add_first(name,first);
+    }
+
+    fn add_first(&mut self,name:&str,first:char) {
+        let characters = self.first_characters();
+        if let Some(index) = characters.iter().position(|&c|c==first) {
+            let counts = self.first_counts.entry(self.alphabet.normalise(name).into_owned()).or_insert_with(||vec![0;characters.len()]);
+            counts[index]+=1;
+        }
+    }
+
+    /// With [GenerateCodes::set_balance_first], the character the next code with this (canonical) name has to start its random part with.
+    fn required_first(&self,group:&str) -> Option<char> {
+        if !self.balance_first { return None; }
+        let characters = self.first_characters();
+        let least = match self.first_counts.get(group) {
+            Some(counts) => counts.iter().enumerate().min_by_key(|&(index,&count)|(count,index)).map(|(index,_)|index).unwrap_or(0),
+            None => 0,
+        };
+        characters.get(least).copied()
+    }
+
+    /// Whether new codes must be far enough from all used codes (the default), or only those with the same prefix.
+    pub fn set_scope(&mut self,scope:Scope) {
+        self.scope=scope;
+    }
+
+    /// Check candidates on this many threads at once. The codes produced are exactly the same as with one thread (the default).
+    pub fn set_threads(&mut self,threads:usize) -> Result<(),rayon::ThreadPoolBuildError> {
+        self.pool = if threads>1 { Some(rayon::ThreadPoolBuilder::new().num_threads(threads).build()?) } else { None };
+        Ok(())
+    }
+
+    /// Only accept candidates that pass this filter (as well as any others added). Filters are checked before the distance.
+    pub fn add_filter(&mut self,filter:Filter) {
+        self.filters.push(filter);
+    }
+
+    /// Add codes that must be avoided, typically produced by an earlier run. Returns the number of codes added,
+    /// which leaves out any already used (including duplicates within `existing`).
+    ///
+    /// Codes are stored in the canonical form for the alphabet (see [Alphabet::normalise]), so the alphabet should be set first.
+    ///
+    /// For [GenerateCodes::set_min_distance_across], each code is taken to have the longest prefix it starts with of those
+    /// declared (see [GenerateCodes::declare_prefixes]) or made so far.
+    pub fn load_existing<S:Into<String>>(&mut self,existing:impl IntoIterator<Item=S>) -> usize {
+        self.load(existing,None)
+    }
+
+    /// Like [GenerateCodes::load_existing], but for [GenerateCodes::set_min_distance_across] the codes all have the given prefix,
+    /// whatever they start with, as for the codes in a file that was made for that prefix.
+    pub fn load_existing_with_prefix<S:Into<String>>(&mut self,existing:impl IntoIterator<Item=S>,prefix:&str) -> usize {
+        let prefix = self.prefix_index(prefix);
+        self.load(existing,Some(prefix))
+    }
+
+    /// Add the codes to the used codes, with the prefix at the given position in `prefixes`, or if None, the one they start with.
+    fn load<S:Into<String>>(&mut self,existing:impl IntoIterator<Item=S>,prefix:Option<usize>) -> usize {
+        let start_count = self.used.len();
+        for code in existing {
+            let code : String = code.into();
+            let code = self.alphabet.normalise(&code).into_owned();
+            if !self.used_set.contains(&code) {
+                let prefix = prefix.or_else(||self.infer_prefix(&code));
+                self.push_used(code,prefix);
+            }
+        }
+        self.used.len()-start_count
+    }
+
+    /// Prefixes that codes loaded by [GenerateCodes::load_existing] may have, for [GenerateCodes::set_min_distance_across].
+    /// Codes already loaded without a known prefix are given the longest of them they start with.
+    pub fn declare_prefixes<'a>(&mut self,prefixes:impl IntoIterator<Item=&'a str>) {
+        for prefix in prefixes { self.prefix_index(prefix); }
+        for i in 0..self.used.len() {
+            if self.used_prefixes[i].is_none() { self.used_prefixes[i]=self.infer_prefix(&self.used[i]); }
+        }
+    }
+
+    /// The position of a prefix in `prefixes`, adding it if it is not already there.
+    fn prefix_index(&mut self,prefix:&str) -> usize {
+        let prefix = self.alphabet.normalise(prefix);
+        match self.prefixes.iter().position(|p|*p==prefix) {
+            Some(index) => index,
+            None => { self.prefixes.push(prefix.into_owned()); self.prefixes.len()-1 }
+        }
+    }
+
+    /// The position in `prefixes` of the longest prefix a canonical code starts with, if any.
+    fn infer_prefix(&self,code:&str) -> Option<usize> {
+        self.prefixes.iter().enumerate().filter(|(_,p)|code.starts_with(p.as_str())).max_by_key(|(_,p)|p.len()).map(|(index,_)|index)
+    }
+
+    /// Only require codes with different prefixes to be this far apart, leaving the minimum distance codes are looked for with
+    /// for codes with the same prefix. Confusing two codes for the same exam is usually worse than confusing codes for
+    /// different ones, so this is usually the smaller. It is checked by [GenerateCodes::ok], and is for [Scope::Global].
+    ///
+    /// A code made has the prefix it was made with, the fixed characters before the random part. Codes loaded by
+    /// [GenerateCodes::load_existing] have the longest prefix they start with of those made or declared (see
+    /// [GenerateCodes::declare_prefixes]), or if none, count as having a different prefix to every other code.
+    /// [GenerateCodes::load_existing_with_prefix] gives loaded codes a particular prefix.
+    ///
+    /// ```
+    /// use make_exam_numbers::GenerateCodes;
+    ///
+    /// let mut generator = GenerateCodes::new(Some(1),4);
+    /// generator.set_min_distance_across(Some(2));
+    /// generator.load_existing_with_prefix(["A1234"],"A");
+    /// generator.declare_prefixes(["B"]);
+    /// assert!(!generator.ok("A1299",3)); // the same prefix, so must be 3 apart.
+    /// assert!(generator.ok("B1294",3)); // different prefixes only need to be 2 apart...
+    /// assert!(!generator.ok("B1234",3)); // ...which these aren't.
+    /// ```
+    pub fn set_min_distance_across(&mut self,min_distance:Option<usize>) {
+        self.min_distance_across=min_distance;
+    }
+
+    fn push_used(&mut self,code:String,prefix:Option<usize>) {
+        self.used_prefixes.push(prefix);
+        let compared = self.compared_part(&code).to_string();
+        for index in &mut self.indices { index.insert(self.used.len(),&compared); }
+        if !self.min_distances.is_empty() {
+            let min_distance = min_distance_for(&code,0,&self.min_distances);
+            if min_distance>0 { self.used_by_min_distance.entry(min_distance).or_default().push(self.used.len()); }
+        }
+        if let Some((_,bodies)) = &mut self.bodies { bodies.push(compared); }
+        if let Some(numeric) = &mut self.numeric { numeric.insert(&code); }
+        self.used_set.insert(code.clone());
+        self.used.push(code);
+    }
+
+    /// Make sure the index is built, if it is applicable, for the given minimum distance.
+    fn prepare_index(&mut self,min_hamming_distance:usize) {
+        if self.metric!=Metric::Hamming { self.indices.clear(); }
+        else if !self.indices.iter().any(|i|i.is_for(min_hamming_distance,self.length_policy)) {
+            let mut index = HammingIndex::new(min_hamming_distance,self.length_policy);
+            for (i,code) in self.compared().iter().enumerate() { index.insert(i,code); }
+            self.indices.push(index);
+        }
+    }
+
+    /// How hard it was to find the codes generated so far with the given prefix. For codes with a suffix, this is `prefix*suffix`,
+    /// as given by [crate::WantedPrefix::name].
+    pub fn statistics(&self,prefix:&str) -> Statistics {
+        self.statistics.get(self.alphabet.normalise(prefix).as_ref()).cloned().unwrap_or_default()
+    }
+
+    /// Like [GenerateCodes::statistics], but the counts for the prefix start again from zero afterwards.
+    pub fn take_statistics(&mut self,prefix:&str) -> Statistics {
+        self.statistics.remove(self.alphabet.normalise(prefix).as_ref()).unwrap_or_default()
+    }
+
+    /// All codes generated or loaded so far, in canonical form.
+    pub fn used(&self) -> &[String] { &self.used }
+
+    /// Pairs of used codes closer than `min_hamming_distance`, measured the same way as for new codes, referring to codes by their index in [GenerateCodes::used].
+    ///
+    /// This is mainly useful after [GenerateCodes::load_existing], as codes loaded that way are not checked against each other.
+    /// With [Scope::PerPrefix], each code is taken to have the longest of the `prefixes` it starts with, as for [crate::verify::find_problems_per_prefix].
+    /// With [GenerateCodes::set_min_distance_across], codes with different prefixes only need to be that far apart.
+    pub fn used_problems(&self,min_hamming_distance:usize,prefixes:&[String]) -> Vec<Problem> {
+        let length_policy = Some(self.length_policy);
+        let prefixes : Vec<String> = prefixes.iter().map(|p|self.alphabet.normalise(p).into_owned()).collect();
+        let within = min_hamming_distance;
+        let min_hamming_distance = within.max(self.min_distance_across.unwrap_or(0));
+        let mut problems = match self.scope {
+            Scope::Global => find_problems(self.compared(),min_hamming_distance,self.metric,length_policy),
+            Scope::PerPrefix => find_problems_grouped(self.compared(),&self.used,min_hamming_distance,self.metric,length_policy,&prefixes),
+        };
+        if self.include_reversals {
+            let prefix_of = |index:usize| prefixes.iter().filter(|p|self.used[index].starts_with(p.as_str())).max_by_key(|p|p.len());
+            problems.extend(find_reversal_problems(self.compared(),min_hamming_distance,self.metric,length_policy).into_iter().filter(|problem| match (self.scope,problem) {
+                (Scope::PerPrefix,Problem::TooCloseReversed{first,second,..}) => prefix_of(*first)==prefix_of(*second),
+                _ => true,
+            }));
+        }
+        if let Some(across) = self.min_distance_across {
+            let same_prefix = |a:usize,b:usize| self.used_prefixes[a].is_some() && self.used_prefixes[a]==self.used_prefixes[b];
+            problems.retain(|problem| match *problem {
+                Problem::TooClose { first, second, distance } | Problem::TooCloseReversed { first, second, distance } => distance<if same_prefix(first,second) { within } else { across },
+                _ => true,
+            });
+        }
+        problems
+    }
+
+    /// The random part of a code, with `num_digits` characters.
+    fn generate_body(&self,prng:&mut ChaCha8Rng,num_digits:usize) -> String {
+        if self.alphabet.is_decimal() {
+            let digits = prng.gen_range(self.body_range(num_digits));
+            format!("{:01$}",digits,num_digits)
+        } else {
+            let mut code = String::new();
+            let characters = self.alphabet.characters();
+            let zero = characters.iter().position(|&c|c=='0').filter(|_|self.no_leading_zero && characters.len()>1);
+            for i in 0..num_digits {
+                let index = match zero {
+                    Some(zero) if i==0 => { let index = prng.gen_range(0..characters.len()-1); if index>=zero { index+1 } else { index } }
+                    _ => prng.gen_range(0..characters.len()),
+                };
+                code.push(characters[index]);
+            }
+            code
+        }
+    }
+
+    /// Every possible random part with `num_digits` characters, in a random order.
+    fn enumerate_bodies(&self,prng:&mut ChaCha8Rng,num_digits:usize) -> Vec<String> {
+        let characters = self.alphabet.characters();
+        let no_zero_first = self.no_leading_zero && characters.len()>1;
+        // decimal random parts are numbered by their value, so a range is just some of them.
+        let indices = if self.alphabet.is_decimal() { self.body_range(num_digits) } else { 0..(characters.len() as u64).pow(num_digits as u32) };
+        let mut bodies = Vec::with_capacity(indices.end.saturating_sub(indices.start) as usize);
+        for mut index in indices {
+            let mut body = vec![characters[0];num_digits];
+            for c in body.iter_mut().rev() {
+                *c=characters[(index%characters.len() as u64) as usize];
+                index/=characters.len() as u64;
+            }
+            if no_zero_first && body.first()==Some(&'0') { continue; }
+            bodies.push(body.into_iter().collect());
+        }
+        bodies.shuffle(prng);
+        bodies
+    }
+
+    /// How to make random parts with the given (canonical) prefix for [Strategy::Algebraic].
+    fn construction(&self,prefix:&str,canonical_prefix:&str,num_digits:usize,min_hamming_distance:usize) -> Result<Construction,GenerationError> {
+        let characters = self.alphabet.characters();
+        let prefix_symbols : Option<Vec<u64>> = canonical_prefix.chars().map(|c|characters.iter().position(|&a|a==c).map(|i|i as u64)).collect();
+        // codes from a construction are still checked with the metric, so it only needs the Hamming distance they must have at least.
+        Construction::new(characters.len(),prefix_symbols,num_digits,self.metric.min_hamming(min_hamming_distance)).map_err(|reason|GenerationError::NoConstruction{prefix:prefix.to_string(),reason})
+    }
+
+    /// Check that codes with this prefix can be made with the current strategy. Only [Strategy::Algebraic] has restrictions:
+    /// the number of random characters can be at most the smallest prime at least the alphabet size, and the minimum distance at most the number of random characters.
+    pub fn check_construction(&self,prefix:&str,num_digits:usize,min_hamming_distance:usize) -> Result<(),GenerationError> {
+        if self.strategy==Strategy::Algebraic { self.construction(prefix,&self.alphabet.normalise(prefix),num_digits,min_hamming_distance)?; }
+        Ok(())
+    }
+
+    /// A random part from the construction, or [GenerationError::NoConstruction] if it can't make one. An ordinary random part
+    /// would not be far enough from the others, so the codes would silently not be what [Strategy::Algebraic] promises.
+    fn algebraic_body(&self,prefix:&str,prng:&mut ChaCha8Rng,construction:&Construction) -> Result<String,GenerationError> {
+        let characters = self.alphabet.characters();
+        let leading_zero_ok = |part:&[usize]| !(self.no_leading_zero && characters.len()>1 && part.first().map(|&i|characters[i]=='0').unwrap_or(false));
+        match construction.random_part(prng,leading_zero_ok) {
+            Some(part) => Ok(part.into_iter().map(|i|characters[i]).collect()),
+            None => Err(GenerationError::NoConstruction{prefix:prefix.to_string(),reason:format!("the code only gave random parts using characters outside the alphabet{}",if self.no_leading_zero { " or starting with 0" } else { "" })}),
+        }
+    }
+
+    /// The whole candidate code, given the (canonical) layout and random part, which has a character for each random position.
+    fn assemble_candidate(&self,layout:&[Option<char>],body:&str) -> String {
+        let mut body = body.chars();
+        let code : String = layout.iter().map(|c|c.unwrap_or_else(||body.next().expect("A random character for each random position"))).collect();
+        match self.check_digit {
+            Some(check_digit) => check_digit.append(&code),
+            None => code,
+        }
+    }
+
+    /// Whether the candidate is at least the given distance (Hamming unless [GenerateCodes::set_metric] is used) from every used code, comparing canonical forms.
+    ///
+    /// With [GenerateCodes::set_min_numeric_gap] the candidate must also be numerically far enough from used codes with the same prefix.
+    ///
+    /// This ignores [GenerateCodes::set_scope]; see [GenerateCodes::ok_for_prefix].
+    pub fn ok(&self,candidate:&str,min_hamming_distance:usize) -> bool {
+        let own = self.infer_prefix(&self.alphabet.normalise(candidate));
+        self.closeness(candidate,"",own,min_hamming_distance).is_none()
+    }
+
+    /// Like [GenerateCodes::ok], for a candidate with the given prefix, so with [Scope::PerPrefix] it is only compared against used codes starting with that prefix.
+    pub fn ok_for_prefix(&self,candidate:&str,prefix:&str,min_hamming_distance:usize) -> bool {
+        match self.scope {
+            Scope::Global => self.ok(candidate,min_hamming_distance),
+            Scope::PerPrefix => {
+                let prefix = self.alphabet.normalise(prefix);
+                self.closeness(candidate,&prefix,self.prefixes.iter().position(|p|*p==prefix),min_hamming_distance).is_none()
+            }
+        }
+    }
+
+    /// The distance between two codes if it is below `min`, using the metric and length policy.
+    fn distance_below(&self,a:&str,b:&str,min:usize) -> Option<usize> {
+        match self.metric {
+            Metric::Hamming => self.length_policy.hamming_below(a,b,min),
+            Metric::WeightedHamming(weights) => self.length_policy.weighted_hamming_below(weights,a,b,min),
+            metric => metric.distance_below(a,b,min),
+        }
+    }
+
+    /// Why the candidate is too close to some used code that starts with `prefix` (in canonical form), or None if it is at least the given distance from all of them.
+    /// `own` is the position in `prefixes` of the candidate's prefix, for [GenerateCodes::set_min_distance_across].
+    fn closeness(&self,candidate:&str,prefix:&str,own:Option<usize>,min_hamming_distance:usize) -> Option<Rejection<'static>> {
+        let candidate = self.alphabet.normalise(candidate);
+        if self.numeric.as_ref().map(|n|n.too_close(&candidate)).unwrap_or(false) { return Some(Rejection::TooClose); }
+        // an identical code is at distance 0 whatever the metric, length policy or prefix.
+        if min_hamming_distance>0 && self.used_set.contains(candidate.as_ref()) { return Some(Rejection::TooClose); }
+        let any_used = |i:usize| self.used[i].starts_with(prefix);
+        if !self.far_enough(&candidate,min_hamming_distance,own,any_used) { return Some(Rejection::TooClose); }
+        if self.include_reversals && !self.far_enough(&candidate.chars().rev().collect::<String>(),min_hamming_distance,own,any_used) { return Some(Rejection::TooClose); }
+        if self.include_rotations {
+            let chars : Vec<char> = candidate.chars().collect();
+            let same_length = |i:usize| any_used(i) && self.used[i].len()==candidate.len() && self.used[i].chars().count()==chars.len();
+            for rotation in 1..chars.len() {
+                let rotated : String = chars[rotation..].iter().chain(&chars[..rotation]).collect();
+                if !self.far_enough(&rotated,min_hamming_distance,own,same_length) { return Some(Rejection::TooCloseRotated{rotation}); }
+            }
+        }
+        None
+    }
+
+    /// The number of rotations of an acceptable candidate compared with the used codes, for [Statistics::rotations_compared].
+    fn rotations(&self,candidate:&str) -> u64 {
+        if self.include_rotations { candidate.chars().count().saturating_sub(1) as u64 } else { 0 }
+    }
+
+    /// Like [GenerateCodes::far_from_used], but with [GenerateCodes::set_min_distance_across], used codes without the candidate's
+    /// prefix (`own`, a position in `prefixes`) only need to be that far away.
+    fn far_enough(&self,candidate:&str,min_hamming_distance:usize,own:Option<usize>,compare:impl Fn(usize)->bool) -> bool {
+        match self.min_distance_across {
+            None => self.far_from_used(candidate,min_hamming_distance,compare),
+            Some(across) => {
+                let same = |i:usize| own.is_some() && self.used_prefixes[i]==own;
+                self.far_from_used(candidate,min_hamming_distance,|i|same(i) && compare(i)) && self.far_from_used(candidate,across,|i|!same(i) && compare(i))
+            }
+        }
+    }
+
+    /// Whether a canonical candidate is at least `min_hamming_distance` from all the used codes whose index is accepted by `compare`.
+    fn far_from_used(&self,candidate:&str,min_hamming_distance:usize,compare:impl Fn(usize)->bool) -> bool {
+        let candidate = self.compared_part(candidate);
+        let compared = self.compared();
+        let far = match self.indices.iter().find(|index|index.is_for(min_hamming_distance,self.length_policy)) {
+            Some(index) if self.metric==Metric::Hamming => !index.any_too_close(candidate,compared,&compare),
+            _ => self.used.iter().enumerate().zip(compared).filter(|((i,_),_)|compare(*i)).all(|(_,s)|self.distance_below(s,candidate,min_hamming_distance).is_none()),
+        };
+        // codes with a larger minimum distance of their own must be that far from the candidate.
+        far && self.used_by_min_distance.range(min_hamming_distance+1..).all(|(&min,rows)|rows.iter().all(|&i|!compare(i) || self.distance_below(&compared[i],candidate,min).is_none()))
+    }
+
+    /// Find a new code starting with `prefix` that passes all the filters and is at least `min_hamming_distance` from every used code, and add it to the used codes.
+    pub fn new_code(&mut self,prefix:&str,min_hamming_distance:usize) -> Result<String,GenerationError> {
+        self.new_code_with_progress(prefix,min_hamming_distance,|_,_|{})
+    }
+
+    /// Why the candidate would be rejected, or None if it is acceptable.
+    fn rejection(&self,candidate:&str,prefix_chars:usize,body_chars:usize,min_hamming_distance:usize) -> Option<Rejection<'_>> {
+        if !self.filters.is_empty() {
+            let byte_offset = |chars:usize| candidate.char_indices().nth(chars).map(|(i,_)|i).unwrap_or(candidate.len());
+            let prefix_len = byte_offset(prefix_chars);
+            let parts = Candidate{code:candidate,prefix_len,body_len:byte_offset(prefix_chars+body_chars)-prefix_len};
+            if let Some(filter) = self.filters.iter().find(|f|!f.accepts(&parts)) { return Some(Rejection::Filter(filter)) }
+        }
+        let own_prefix = &candidate[..candidate.char_indices().nth(prefix_chars).map(|(i,_)|i).unwrap_or(candidate.len())];
+        let scope_prefix = match self.scope {
+            Scope::Global => "",
+            Scope::PerPrefix => own_prefix,
+        };
+        let own = if self.min_distance_across.is_some() { self.prefixes.iter().position(|p|p==own_prefix) } else { None };
+        self.closeness(candidate,scope_prefix,own,min_hamming_distance)
+    }
+
+    /// Like [GenerateCodes::new_code], but calls `on_reject` with the candidate and the reason every time a candidate is rejected.
+    pub fn new_code_with_progress(&mut self,prefix:&str,min_hamming_distance:usize,on_reject:impl FnMut(&str,Rejection)) -> Result<String,GenerationError> {
+        self.new_code_with_digits(prefix,self.num_digits,min_hamming_distance,on_reject)
+    }
+
+    /// Like [GenerateCodes::new_code_with_progress], but with `num_digits` random digits instead of the number given when the generator was made.
+    ///
+    /// Codes of different lengths are compared according to [GenerateCodes::set_length_policy].
+    pub fn new_code_with_digits(&mut self,prefix:&str,num_digits:usize,min_hamming_distance:usize,on_reject:impl FnMut(&str,Rejection)) -> Result<String,GenerationError> {
+        self.new_code_with_suffix(prefix,"",num_digits,min_hamming_distance,on_reject)
+    }
+
+    /// Like [GenerateCodes::new_code_with_digits], but each code ends with `suffix` after the random digits (and before any check digits).
+    ///
+    /// The suffix is compared like the rest of the code. Codes with a suffix have their own random stream and statistics,
+    /// named `prefix*suffix` (see [GenerateCodes::statistics]). [GenerateCodes::set_distance_on_body] and
+    /// [GenerateCodes::set_min_numeric_gap] take the random part to be the last characters of a code, so should not be used with a suffix.
+    ///
+    /// ```
+    /// use make_exam_numbers::GenerateCodes;
+    ///
+    /// let mut generator = GenerateCodes::new(Some(3),4);
+    /// let code = generator.new_code_with_suffix("S","X",4,2,|_,_|{}).unwrap();
+    /// assert!(code.starts_with('S') && code.ends_with('X') && code.len()==6);
+    /// assert_eq!(generator.statistics("S*X").accepted,1);
+    /// ```
+    pub fn new_code_with_suffix(&mut self,prefix:&str,suffix:&str,num_digits:usize,min_hamming_distance:usize,on_reject:impl FnMut(&str,Rejection)) -> Result<String,GenerationError> {
+        let layout : Vec<Option<char>> = prefix.chars().map(Some).chain(std::iter::repeat_n(None,num_digits)).chain(suffix.chars().map(Some)).collect();
+        // codes without a suffix keep the names (and so the streams) they had before suffixes were possible.
+        let name = if suffix.is_empty() { prefix.to_string() } else { format!("{}*{}",prefix,suffix) };
+        self.new_code_with_layout(&name,&layout,min_hamming_distance,on_reject)
+    }
+
+    /// Like [GenerateCodes::new_code_with_progress], but each code is laid out as given: `Some` characters are kept, and each `None`
+    /// is a random character, so `[Some('B'),None,Some('2'),None]` gives codes like `B427`. The fixed characters before the first
+    /// random one count as the prefix, for [Scope::PerPrefix].
+    ///
+    /// `name` is used for the random stream and statistics (see [GenerateCodes::statistics]), and in errors. Codes with different
+    /// layouts should have different names, such as the masks of [crate::WantedPrefix::from_mask]. As for [GenerateCodes::new_code_with_suffix],
+    /// [GenerateCodes::set_distance_on_body] and [GenerateCodes::set_min_numeric_gap] should not be used unless the random characters are last.
+    ///
+    /// ```
+    /// use make_exam_numbers::GenerateCodes;
+    ///
+    /// let mut generator = GenerateCodes::new(Some(3),4);
+    /// let layout = [Some('B'),None,Some('2'),None,None,None,Some('7'),None];
+    /// let code = generator.new_code_with_layout("B#2###7#",&layout,3,|_,_|{}).unwrap();
+    /// assert!(code.starts_with('B') && &code[2..3]=="2" && &code[6..7]=="7" && code.len()==8);
+    /// assert_eq!(generator.statistics("B#2###7#").accepted,1);
+    /// ```
+    pub fn new_code_with_layout(&mut self,name:&str,layout:&[Option<char>],min_hamming_distance:usize,on_reject:impl FnMut(&str,Rejection)) -> Result<String,GenerationError> {
+        let num_digits = layout.iter().filter(|c|c.is_none()).count();
+        self.update_bodies(num_digits);
+        self.update_numeric(num_digits);
+        self.prepare_index(min_hamming_distance);
+        if let Some(across) = self.min_distance_across { self.prepare_index(across); }
+        let layout = self.canonical_layout(layout);
+        let group = self.alphabet.normalise(name).into_owned();
+        // every random part must be tried for each prefix, so exhaustive search never shares one list of them.
+        let key = if self.per_prefix_streams || self.strategy==Strategy::Exhaustive { group.clone() } else { String::new() };
+        let mut stream = self.streams.remove(&key).unwrap_or_else(||self.new_stream(&key));
+        if stream.num_digits!=num_digits { stream.pending.clear(); stream.num_digits=num_digits; stream.enumerated=false; }
+        let mut statistics = self.statistics.remove(&group).unwrap_or_default();
+        let res = self.search(name,&layout,min_hamming_distance,&mut stream,&mut statistics,on_reject);
+        self.streams.insert(key,stream);
+        self.statistics.insert(group,statistics);
+        res
+    }
+
+    /// A layout with its fixed characters in canonical form.
+    fn canonical_layout(&self,layout:&[Option<char>]) -> Vec<Option<char>> {
+        let mut res = Vec::with_capacity(layout.len());
+        for c in layout {
+            match c {
+                Some(c) => res.extend(self.alphabet.normalise(c.encode_utf8(&mut [0;4])).chars().map(Some)),
+                None => res.push(None),
+            }
+        }
+        res
+    }
+
+    /// Like [GenerateCodes::new_code], but with random parts derived from the ID with a secret key instead of chosen at random,
+    /// trying attempts 0, 1, 2... until one is acceptable. See [crate::derive] for how. The random number generator is not used,
+    /// so a student's code depends only on the key, the ID and the codes already used.
+    ///
+    /// ```
+    /// use make_exam_numbers::GenerateCodes;
+    /// use make_exam_numbers::derive::KeyedDerivation;
+    /// let derivation = KeyedDerivation::new(b"a secret key of at least 16 bytes");
+    /// let mut generator = GenerateCodes::new(None,6);
+    /// let first = generator.new_derived_code("S","s1234567",3,&derivation).unwrap();
+    /// let mut again = GenerateCodes::new(None,6);
+    /// assert_eq!(first,again.new_derived_code("S","s1234567",3,&derivation).unwrap());
+    /// // a code already used is never given again, so another attempt is used.
+    /// assert_ne!(first,generator.new_derived_code("S","s1234567",3,&derivation).unwrap());
+    /// ```
+    pub fn new_derived_code(&mut self,prefix:&str,id:&str,min_hamming_distance:usize,derivation:&KeyedDerivation) -> Result<String,GenerationError> {
+        self.update_bodies(self.num_digits);
+        self.update_numeric(self.num_digits);
+        self.prepare_index(min_hamming_distance);
+        if let Some(across) = self.min_distance_across { self.prepare_index(across); }
+        let canonical_prefix = self.alphabet.normalise(prefix).into_owned();
+        let prefix_chars = canonical_prefix.chars().count();
+        let layout : Vec<Option<char>> = canonical_prefix.chars().map(Some).chain(std::iter::repeat_n(None,self.num_digits)).collect();
+        let characters = self.alphabet.characters();
+        let without_zero : Vec<char> = characters.iter().copied().filter(|&c|c!='0').collect();
+        let first = if self.no_leading_zero && characters.len()>1 && without_zero.len()<characters.len() { Some(&without_zero[..]) } else { None };
+        let mut statistics = self.statistics.remove(&canonical_prefix).unwrap_or_default();
+        let mut attempt = 0;
+        let res = loop {
+            if let Some(max_attempts) = self.max_attempts {
+                if attempt>=max_attempts { break Err(GenerationError::TooManyAttempts{prefix:prefix.to_string(),attempts:attempt}); }
+            }
+            let body = derivation.body(&canonical_prefix,id,attempt,characters,first,self.num_digits);
+            let candidate = self.assemble_candidate(&layout,&body);
+            attempt+=1;
+            match self.rejection(&candidate,prefix_chars,self.num_digits,min_hamming_distance) {
+                None => { statistics.accepted(); statistics.rotations_compared+=self.rotations(&candidate); break Ok(candidate); }
+                Some(rejection) => statistics.rejected(&rejection),
+            }
+        };
+        self.statistics.insert(canonical_prefix.clone(),statistics);
+        if let Ok(candidate) = &res {
+            let prefix = self.prefix_index(&canonical_prefix);
+            self.push_used(self.alphabet.normalise(candidate).into_owned(),Some(prefix));
+        }
+        res
+    }
+
+    /// Try candidates from the given stream until one is acceptable, or there have been too many attempts.
+    /// `layout` is canonical, and `name` is what errors call the codes being made.
+    fn search(&mut self,name:&str,layout:&[Option<char>],min_hamming_distance:usize,stream:&mut Stream,statistics:&mut Statistics,mut on_reject:impl FnMut(&str,Rejection)) -> Result<String,GenerationError> {
+        let mut attempts = 0;
+        // the fixed characters before the first random one are the prefix, and filters see the rest up to the last random one as the random part.
+        let prefix_chars = layout.iter().take_while(|c|c.is_some()).count();
+        let body_chars = layout.iter().rposition(|c|c.is_none()).map(|last|last+1-prefix_chars).unwrap_or(0);
+        let canonical_prefix : String = layout[..prefix_chars].iter().flatten().collect();
+        let batch_size = self.pool.as_ref().map(|pool|pool.current_num_threads()*CANDIDATES_PER_THREAD).unwrap_or(1);
+        let construction = if self.strategy==Strategy::Algebraic { Some(self.construction(name,&canonical_prefix,stream.num_digits,min_hamming_distance)?) } else { None };
+        if self.strategy==Strategy::Random && self.alphabet.is_decimal() && self.body_range(stream.num_digits).is_empty() { return Err(GenerationError::Exhausted{prefix:name.to_string()}); }
+        // with balance_first every random part starts with the character needed.
+        let first = if stream.num_digits>0 { self.required_first(&self.alphabet.normalise(name)) } else { None };
+        loop {
+            if self.interrupt.as_ref().map(|i|i.load(Ordering::Relaxed)).unwrap_or(false) { return Err(GenerationError::Interrupted{prefix:name.to_string()}); }
+            if self.deadline.map(|deadline|Instant::now()>=deadline).unwrap_or(false) { return Err(GenerationError::TimedOut{prefix:name.to_string()}); }
+            // Random parts are always generated in the same order, and examined in that order, whatever the batch size.
+            // Any that are not looked at because an earlier one in the batch was accepted are left for the next code.
+            match self.strategy {
+                Strategy::Random|Strategy::Algebraic => while stream.pending.len()<batch_size {
+                    let body = match &construction {
+                        Some(construction) => self.algebraic_body(name,&mut stream.prng,construction)?,
+                        None => self.generate_body(&mut stream.prng,stream.num_digits),
+                    };
+                    stream.pending.push_back(body);
+                },
+                Strategy::Exhaustive => {
+                    if !stream.enumerated { stream.pending=self.enumerate_bodies(&mut stream.prng,stream.num_digits).into(); stream.enumerated=true; }
+                    if stream.pending.is_empty() { return Err(GenerationError::Exhausted{prefix:name.to_string()}); }
+                }
+            }
+            let candidates : Vec<String> = stream.pending.iter().take(batch_size).map(|body| match first {
+                Some(first) => self.assemble_candidate(layout,&std::iter::once(first).chain(body.chars().skip(1)).collect::<String>()),
+                None => self.assemble_candidate(layout,body),
+            }).collect();
+            let rejections : Vec<Option<Rejection>> = match &self.pool {
+                Some(pool) => pool.install(||candidates.par_iter().map(|c|self.rejection(c,prefix_chars,body_chars,min_hamming_distance)).collect()),
+                None => candidates.iter().map(|c|self.rejection(c,prefix_chars,body_chars,min_hamming_distance)).collect(),
+            };
+            let mut examined = 0;
+            let mut accepted = None;
+            for (candidate,rejection) in candidates.iter().zip(rejections) {
+                attempts+=1;
+                examined+=1;
+                match rejection {
+                    None => { accepted=Some(candidate); statistics.accepted(); statistics.rotations_compared+=self.rotations(candidate); break; }
+                    Some(rejection) => { statistics.rejected(&rejection); on_reject(candidate,rejection) },
+                }
+                if let Some(max_attempts) = self.max_attempts {
+                    if attempts>=max_attempts { break; }
+                }
+            }
+            let accepted = accepted.cloned();
+            stream.pending.drain(..examined);
+            if let Some(candidate) = accepted {
+                let prefix = self.prefix_index(&canonical_prefix);
+                self.push_used(self.alphabet.normalise(&candidate).into_owned(),Some(prefix));
+                if let Some(first) = first { self.add_first(name,first); }
+                return Ok(candidate);
+            }
+            if let Some(max_attempts) = self.max_attempts {
+                if attempts>=max_attempts { return Err(GenerationError::TooManyAttempts{prefix:name.to_string(),attempts}) }
+            }
+        }
+    }
+}
+
+/// The last `len` characters of a code, or the whole code if it is shorter.
+fn body_of(code:&str,len:usize) -> &str {
+    if len==0 { return ""; }
+    match code.char_indices().rev().nth(len-1) {
+        Some((i,_)) => &code[i..],
+        None => code,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Every pair of codes made after loading some existing codes, including the existing ones, is far enough apart.
+    #[test]
+    fn generated_codes_are_far_from_each_other_and_the_existing_ones() {
+        let mut seeder = GenerateCodes::new(Some(7),5);
+        let existing : Vec<String> = (0..40).map(|_|seeder.new_code("A",3).unwrap()).collect();
+        let mut generator = GenerateCodes::new(Some(42),5);
+        assert_eq!(generator.load_existing(existing.iter().map(|c|c.as_str())),existing.len());
+        let made : Vec<String> = (0..200).map(|_|generator.new_code("A",3).unwrap()).collect();
+        assert!(made.iter().all(|c|!existing.contains(c)));
+        let all : Vec<String> = existing.iter().chain(&made).cloned().collect();
+        assert_eq!(find_problems(&all,3,Metric::Hamming,None),vec![]);
+        assert_eq!(generator.used().len(),all.len());
+    }
+
+    /// With Crockford Base32, existing codes are compared in their canonical form, so one written in lower case or with
+    /// O, I or L is the same as the code it stands for, and is never made again.
+    #[test]
+    fn crockford_codes_are_compared_in_canonical_form() {
+        let mut generator = GenerateCodes::new(Some(42),4);
+        generator.set_alphabet(Alphabet::crockford32());
+        generator.load_existing(["o1l1"]);
+        assert!(!generator.ok("0111",1));
+        assert!(!generator.ok("OIL1",1));
+        assert!(generator.ok("0112",1));
+        let mut seeder = GenerateCodes::new(Some(7),2);
+        seeder.set_alphabet(Alphabet::crockford32());
+        let existing : Vec<String> = (0..300).map(|_|seeder.new_code("",1).unwrap().to_lowercase().replace('0',"o").replace('1',"l")).collect();
+        let mut generator = GenerateCodes::new(Some(42),2);
+        generator.set_alphabet(Alphabet::crockford32());
+        assert_eq!(generator.load_existing(existing.iter().map(|c|c.as_str())),300);
+        let canonical : Vec<String> = existing.iter().map(|c|Alphabet::crockford32().normalise(c).to_string()).collect();
+        for _ in 0..500 {
+            let code = generator.new_code("",1).unwrap();
+            assert!(!canonical.contains(&code),"{} was already used",code);
+        }
+    }
+
+    /// With a shared stream, exhaustive search still tries every random part for each prefix, so the random parts used up by
+    /// one prefix are still available to the next.
+    #[test]
+    fn exhaustive_search_with_shared_stream_tries_everything_for_each_prefix() {
+        let mut generator = GenerateCodes::new(Some(42),1);
+        generator.set_per_prefix_streams(false);
+        generator.set_strategy(Strategy::Exhaustive);
+        let a : Vec<String> = (0..10).map(|_|generator.new_code("A",1).unwrap()).collect();
+        assert!(matches!(generator.new_code("A",1),Err(GenerationError::Exhausted{..})));
+        let b : Vec<String> = (0..10).map(|_|generator.new_code("B",1).unwrap()).collect();
+        assert!(matches!(generator.new_code("B",1),Err(GenerationError::Exhausted{..})));
+        // the prefixes share a stream, so try the random parts in the same order.
+        assert_eq!(a.iter().map(|c|&c[1..]).collect::<Vec<_>>(),b.iter().map(|c|&c[1..]).collect::<Vec<_>>());
+        assert_eq!(find_problems(&[a,b].concat(),1,Metric::Hamming,None),vec![]);
+    }
+
+    /// The same seed and existing codes give the same codes.
+    #[test]
+    fn same_seed_gives_same_codes() {
+        let make = || {
+            let mut generator = GenerateCodes::new(Some(42),5);
+            generator.load_existing(["A12345","A67890"]);
+            (0..20).map(|_|generator.new_code("A",3).unwrap()).collect::<Vec<_>>()
+        };
+        assert_eq!(make(),make());
+    }
+
+    /// With a seed and [Scope::PerPrefix], each prefix gets the same codes whichever order the prefixes are made in, as each has
+    /// its own stream. The codes are dense enough that with [Scope::Global] some candidates would be too close to the other prefix's codes.
+    #[test]
+    fn prefix_order_does_not_change_the_codes_with_per_prefix_scope() {
+        let make = |order:[&str;2]| {
+            let mut generator = GenerateCodes::new(Some(42),4);
+            generator.set_scope(Scope::PerPrefix);
+            let mut codes : Vec<Vec<String>> = order.iter().map(|p|(0..500).map(|_|generator.new_code(p,2).unwrap()).collect()).collect();
+            if order[0]=="B" { codes.reverse(); }
+            codes
+        };
+        let forwards = make(["A","B"]);
+        assert_eq!(forwards,make(["B","A"]));
+        assert!(forwards[0].iter().all(|c|c.starts_with('A')) && forwards[1].iter().all(|c|c.starts_with('B')));
+    }
+
+    /// Checking candidates on several threads gives the same codes as on one. When codes are sparse the first candidate in each batch
+    /// is accepted and the rest are left pending for the next code; when dense, most are rejected and batches run out part way through.
+    #[test]
+    fn threads_do_not_change_the_codes() {
+        let make = |threads:usize,digits:usize,distance:usize,count:usize| {
+            let mut generator = GenerateCodes::new(Some(42),digits);
+            generator.set_threads(threads).unwrap();
+            generator.load_existing(["A1234","B5678"]);
+            ["A","B"].iter().map(|p|(0..count).map(|_|generator.new_code(p,distance).unwrap()).collect::<Vec<_>>()).collect::<Vec<_>>()
+        };
+        assert_eq!(make(4,5,1,50),make(1,5,1,50));
+        assert_eq!(make(4,4,3,40),make(1,4,3,40));
+    }
+
+    /// If the construction can't make an acceptable random part, the search stops with an error rather than trying ordinary random
+    /// parts. An alphabet of 114 characters uses GF(127), and a codeword of 127 characters with 99 check symbols almost never
+    /// has all of them among the first 114 symbols.
+    #[test]
+    fn algebraic_strategy_without_acceptable_random_parts_is_an_error() {
+        let characters : String = (0..114).map(|i|char::from_u32(0x100+i).unwrap()).collect();
+        let mut generator = GenerateCodes::new(Some(42),127);
+        generator.set_alphabet(Alphabet::from_characters(&characters).unwrap());
+        generator.set_strategy(Strategy::Algebraic);
+        assert!(generator.check_construction("X",127,100).is_ok());
+        assert!(matches!(generator.new_code("X",100),Err(GenerationError::NoConstruction{..})));
+    }
+}
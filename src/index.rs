@@ -0,0 +1,144 @@
+// This program is Copyright 2022 Andrew Conway and licensed under the GPL:
+//
+// This program is free software: you can redistribute it and/or modify it under the terms of the GNU General Public License as published by the Free Software Foundation, either version 3 of the License, or (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful, but WITHOUT ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License along with this program. If not, see <https://www.gnu.org/licenses/>.
+
+//! An index of codes that can quickly find codes within a given Hamming distance of a candidate.
+//!
+//! This uses the pigeonhole principle. Split codes of length L into `min_distance` blocks of consecutive positions.
+//! If two codes of the same length differ in fewer than `min_distance` positions, then at least one block must be
+//! identical in both. So only codes sharing some block with the candidate need to be compared properly.
+//!
+//! Codes are usually ASCII, so those of each length are also kept one after another as bytes, and compared with a
+//! candidate eight bytes at a time, rather than character by character through the separate strings.
+
+use std::collections::HashMap;
+use std::ops::Range;
+use crate::distance::LengthPolicy;
+
+/// Codes of one particular length, indexed by the contents of each block.
+struct SameLength {
+    length : usize,
+    blocks : Vec<Range<usize>>,
+    /// For each block, the rows (positions in `members`) of codes with each particular contents of that block.
+    buckets : Vec<HashMap<Vec<char>,Vec<usize>>>,
+    /// The indices of all codes of this length.
+    members : Vec<usize>,
+    /// If all the codes of this length are ASCII, their bytes, `length` per row.
+    ascii : Option<Vec<u8>>,
+}
+
+impl SameLength {
+    fn new(length:usize,min_distance:usize) -> Self {
+        // if there are more blocks than characters, every pair of codes is too close, so no blocks are needed.
+        let num_blocks = if min_distance>length { 0 } else { min_distance };
+        let blocks : Vec<Range<usize>> = (0..num_blocks).map(|i|(i*length/num_blocks)..((i+1)*length/num_blocks)).collect();
+        SameLength{length,buckets:vec![HashMap::new();blocks.len()],blocks,members:vec![],ascii:Some(vec![])}
+    }
+
+    /// The Hamming distance between the code in `row` (stored in `codes`) and a candidate of the same length, if it is less than `min_distance`.
+    /// `ascii` is the candidate's bytes if it is ASCII.
+    fn distance_below(&self,row:usize,candidate:&str,ascii:Option<&[u8]>,codes:&[String],min_distance:usize) -> Option<usize> {
+        match (&self.ascii,ascii) {
+            (Some(bytes),Some(ascii)) => hamming_bytes_below(&bytes[row*self.length..(row+1)*self.length],ascii,min_distance),
+            _ => LengthPolicy::Pad.hamming_below(&codes[self.members[row]],candidate,min_distance),
+        }
+    }
+}
+
+/// The number of positions at which two byte strings of the same length differ, if it is less than `min_distance`.
+/// This compares eight bytes at a time, stopping once there are too many differences.
+fn hamming_bytes_below(a:&[u8],b:&[u8],min_distance:usize) -> Option<usize> {
+    const LOWEST_BITS : u64 = 0x0101010101010101;
+    let mut distance = 0;
+    let mut a_chunks = a.chunks_exact(8);
+    let mut b_chunks = b.chunks_exact(8);
+    for (x,y) in (&mut a_chunks).zip(&mut b_chunks) {
+        let mut different = u64::from_le_bytes(x.try_into().unwrap())^u64::from_le_bytes(y.try_into().unwrap());
+        // set the lowest bit of each byte if any of its bits are set, and count those.
+        different |= different>>4;
+        different |= different>>2;
+        different |= different>>1;
+        distance+=(different&LOWEST_BITS).count_ones() as usize;
+        if distance>=min_distance { return None; }
+    }
+    distance+=a_chunks.remainder().iter().zip(b_chunks.remainder()).filter(|(x,y)|x!=y).count();
+    if distance<min_distance { Some(distance) } else { None }
+}
+
+/// An index of some codes (stored elsewhere and referred to by index) for a particular minimum Hamming distance.
+pub(crate) struct HammingIndex {
+    min_distance : usize,
+    length_policy : LengthPolicy,
+    by_length : HashMap<usize,SameLength>,
+}
+
+
+impl HammingIndex {
+    pub(crate) fn new(min_distance:usize,length_policy:LengthPolicy) -> Self { HammingIndex{min_distance,length_policy,by_length:HashMap::new()} }
+
+    /// Whether this index is for the given parameters.
+    pub(crate) fn is_for(&self,min_distance:usize,length_policy:LengthPolicy) -> bool { self.min_distance==min_distance && self.length_policy==length_policy }
+
+    /// Add the code which is stored at position `index`.
+    pub(crate) fn insert(&mut self,index:usize,code:&str) {
+        let chars : Vec<char> = code.chars().collect();
+        let min_distance = self.min_distance;
+        let same = self.by_length.entry(chars.len()).or_insert_with(||SameLength::new(chars.len(),min_distance));
+        let row = same.members.len();
+        for (block,bucket) in same.blocks.iter().zip(same.buckets.iter_mut()) {
+            bucket.entry(chars[block.clone()].to_vec()).or_default().push(row);
+        }
+        same.members.push(index);
+        if code.is_ascii() { if let Some(bytes) = &mut same.ascii { bytes.extend_from_slice(code.as_bytes()); } }
+        else { same.ascii=None; }
+    }
+
+    /// Whether any of the indexed codes (stored in `codes`) for which `keep` is true is less than the minimum distance from the candidate.
+    pub(crate) fn any_too_close(&self,candidate:&str,codes:&[String],keep:impl Fn(usize)->bool) -> bool {
+        let too_close = |i:usize| keep(i) && self.length_policy.hamming_below(&codes[i],candidate,self.min_distance).is_some();
+        if self.min_distance==0 { return false; }
+        let chars : Vec<char> = candidate.chars().collect();
+        let ascii = if candidate.is_ascii() { Some(candidate.as_bytes()) } else { None };
+        for (&length,same) in &self.by_length {
+            if length==chars.len() {
+                if same.blocks.is_empty() { if same.members.iter().any(|&i|keep(i)) { return true; } }
+                else {
+                    for (block,bucket) in same.blocks.iter().zip(same.buckets.iter()) {
+                        if let Some(rows) = bucket.get(&chars[block.clone()]) {
+                            // the contiguous bytes are quicker to look at than whatever decides which codes to keep.
+                            if rows.iter().any(|&row|same.distance_below(row,candidate,ascii,codes,self.min_distance).is_some() && keep(same.members[row])) { return true; }
+                        }
+                    }
+                }
+            } else if self.length_policy!=LengthPolicy::Distinct && same.members.iter().any(|&i|too_close(i)) { return true; }
+        }
+        false
+    }
+
+    /// The indexed codes (stored in `codes`) less than the minimum distance from the candidate, with their distances, in order of index.
+    pub(crate) fn close_to(&self,candidate:&str,codes:&[String]) -> Vec<(usize,usize)> {
+        let mut res : Vec<(usize,usize)> = vec![];
+        if self.min_distance==0 { return res; }
+        let chars : Vec<char> = candidate.chars().collect();
+        let ascii = if candidate.is_ascii() { Some(candidate.as_bytes()) } else { None };
+        for (&length,same) in &self.by_length {
+            if length==chars.len() && !same.blocks.is_empty() {
+                for (block,bucket) in same.blocks.iter().zip(same.buckets.iter()) {
+                    if let Some(rows) = bucket.get(&chars[block.clone()]) {
+                        res.extend(rows.iter().filter_map(|&row|same.distance_below(row,candidate,ascii,codes,self.min_distance).map(|distance|(same.members[row],distance))));
+                    }
+                }
+            } else if length==chars.len() || self.length_policy!=LengthPolicy::Distinct {
+                res.extend(same.members.iter().filter_map(|&i|self.length_policy.hamming_below(&codes[i],candidate,self.min_distance).map(|distance|(i,distance))));
+            }
+        }
+        // a code sharing several blocks with the candidate is found more than once.
+        res.sort_unstable();
+        res.dedup();
+        res
+    }
+}
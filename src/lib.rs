@@ -0,0 +1,44 @@
+// This program is Copyright 2022 Andrew Conway and licensed under the GPL:
+//
+// This program is free software: you can redistribute it and/or modify it under the terms of the GNU General Public License as published by the Free Software Foundation, either version 3 of the License, or (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful, but WITHOUT ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License along with this program. If not, see <https://www.gnu.org/licenses/>.
+
+//! Library to produce a set of randomish exam numbers such that no two exam numbers are very similar.
+//!
+//! Similar means that number of characters that need to be different between any pair of exam numbers
+//! (the Hamming distance) is at least some specified number, like 3.
+//!
+//! The main entry point is [GenerateCodes]. The command line program `make_exam_numbers` is a thin wrapper around it.
+//!
+//! The library never touches files or prints anything: existing codes are given to [GenerateCodes::load_existing] as strings,
+//! and new codes are returned. The command line program and its dependencies are behind the default `cli` feature, so without it
+//! the library can be built for the browser with `cargo build --lib --no-default-features --target wasm32-unknown-unknown`.
+
+mod algebraic;
+pub mod alphabet;
+pub mod analyze;
+pub mod barcode;
+pub mod capacity;
+pub mod check_digit;
+pub mod correct;
+pub mod derive;
+pub mod distance;
+pub mod filter;
+mod generator;
+mod index;
+mod numeric;
+#[cfg(feature="barcodes")]
+mod png;
+mod prefix;
+mod seed;
+pub mod stats;
+mod stream;
+pub mod verify;
+
+pub use generator::{GenerateCodes, GenerationError, Strategy};
+pub use prefix::WantedPrefix;
+pub use seed::Seed;
+pub use stream::CodeStream;
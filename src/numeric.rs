@@ -0,0 +1,53 @@
+// This program is Copyright 2022 Andrew Conway and licensed under the GPL:
+//
+// This program is free software: you can redistribute it and/or modify it under the terms of the GNU General Public License as published by the Free Software Foundation, either version 3 of the License, or (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful, but WITHOUT ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License along with this program. If not, see <https://www.gnu.org/licenses/>.
+
+//! An index of the numeric values of the random parts of codes, to keep codes with the same prefix numerically far apart.
+
+use std::collections::{BTreeSet, HashMap};
+
+/// The random parts of some codes as numbers, grouped by the rest of the code (the prefix).
+pub(crate) struct NumericIndex {
+    /// Random parts must differ by at least this much.
+    gap : u64,
+    /// The number of random digits taken to be at the end of each code, before any check digits.
+    num_digits : usize,
+    /// The number of check digit characters at the end of each code.
+    check_len : usize,
+    by_prefix : HashMap<String,BTreeSet<u64>>,
+}
+
+impl NumericIndex {
+    pub(crate) fn new(gap:u64,num_digits:usize,check_len:usize) -> Self { NumericIndex{gap,num_digits,check_len,by_prefix:HashMap::new()} }
+
+    /// Whether this index is for the given parameters.
+    pub(crate) fn is_for(&self,gap:u64,num_digits:usize,check_len:usize) -> bool { self.gap==gap && self.num_digits==num_digits && self.check_len==check_len }
+
+    /// The prefix and the value of the random part of a code, or None if the code is too short or the random part is not all digits.
+    fn split<'a>(&self,code:&'a str) -> Option<(&'a str,u64)> {
+        let chars : Vec<(usize,char)> = code.char_indices().collect();
+        let prefix_len = chars.len().checked_sub(self.num_digits+self.check_len)?;
+        let start = chars.get(prefix_len).map(|&(i,_)|i).unwrap_or(code.len());
+        let end = chars.get(prefix_len+self.num_digits).map(|&(i,_)|i).unwrap_or(code.len());
+        let digits = &code[start..end];
+        if !digits.bytes().all(|b|b.is_ascii_digit()) { return None; }
+        Some((&code[..start],digits.parse().ok()?))
+    }
+
+    pub(crate) fn insert(&mut self,code:&str) {
+        if let Some((prefix,value)) = self.split(code) { self.by_prefix.entry(prefix.to_string()).or_default().insert(value); }
+    }
+
+    /// Whether any indexed code with the same prefix as the candidate has a random part numerically less than the gap from the candidate's.
+    pub(crate) fn too_close(&self,candidate:&str) -> bool {
+        if self.gap==0 { return false; }
+        if let Some((prefix,value)) = self.split(candidate) {
+            if let Some(values) = self.by_prefix.get(prefix) { return values.range(value.saturating_sub(self.gap-1)..=value.saturating_add(self.gap-1)).next().is_some(); }
+        }
+        false
+    }
+}
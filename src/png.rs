@@ -0,0 +1,63 @@
+// This program is Copyright 2022 Andrew Conway and licensed under the GPL:
+//
+// This program is free software: you can redistribute it and/or modify it under the terms of the GNU General Public License as published by the Free Software Foundation, either version 3 of the License, or (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful, but WITHOUT ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License along with this program. If not, see <https://www.gnu.org/licenses/>.
+
+//! Just enough of PNG to write the greyscale images of [crate::barcode], without depending on an image library.
+//!
+//! The pixel data is stored uncompressed in the zlib stream, which is valid PNG. Barcodes are small, so the files stay small.
+
+/// A PNG file of an image with 8 bit grey `pixels`, row by row from the top.
+pub(crate) fn encode_grey(width:usize,height:usize,pixels:&[u8]) -> Vec<u8> {
+    let mut raw = Vec::with_capacity((width+1)*height);
+    for row in pixels.chunks(width) {
+        raw.push(0); // no filter.
+        raw.extend_from_slice(row);
+    }
+    let mut zlib = vec![0x78,0x01];
+    let mut blocks = raw.chunks(0xFFFF).peekable();
+    if blocks.peek().is_none() { zlib.extend_from_slice(&[1,0,0,0xFF,0xFF]); }
+    while let Some(block) = blocks.next() {
+        zlib.push(if blocks.peek().is_none() { 1 } else { 0 }); // stored, and whether it is the final block.
+        let length = block.len() as u16;
+        zlib.extend_from_slice(&length.to_le_bytes());
+        zlib.extend_from_slice(&(!length).to_le_bytes());
+        zlib.extend_from_slice(block);
+    }
+    zlib.extend_from_slice(&adler32(&raw).to_be_bytes());
+    let mut header = vec![];
+    header.extend_from_slice(&(width as u32).to_be_bytes());
+    header.extend_from_slice(&(height as u32).to_be_bytes());
+    header.extend_from_slice(&[8,0,0,0,0]); // 8 bit greyscale, with the only compression, filtering and (no) interlacing.
+    let mut png = vec![0x89,b'P',b'N',b'G',0x0D,0x0A,0x1A,0x0A];
+    for (kind,data) in [(b"IHDR",header),(b"IDAT",zlib),(b"IEND",vec![])] {
+        png.extend_from_slice(&(data.len() as u32).to_be_bytes());
+        let start = png.len();
+        png.extend_from_slice(kind);
+        png.extend_from_slice(&data);
+        let crc = crc32(&png[start..]);
+        png.extend_from_slice(&crc.to_be_bytes());
+    }
+    png
+}
+
+fn adler32(data:&[u8]) -> u32 {
+    let (mut a,mut b) = (1u32,0u32);
+    for &byte in data {
+        a = (a+byte as u32)%65521;
+        b = (b+a)%65521;
+    }
+    (b<<16)|a
+}
+
+fn crc32(data:&[u8]) -> u32 {
+    let mut crc = 0xFFFF_FFFFu32;
+    for &byte in data {
+        crc ^= byte as u32;
+        for _ in 0..8 { crc = if crc&1!=0 { (crc>>1)^0xEDB8_8320 } else { crc>>1 }; }
+    }
+    !crc
+}
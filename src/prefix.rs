@@ -0,0 +1,216 @@
+// This program is Copyright 2022 Andrew Conway and licensed under the GPL:
+//
+// This program is free software: you can redistribute it and/or modify it under the terms of the GNU General Public License as published by the Free Software Foundation, either version 3 of the License, or (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful, but WITHOUT ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License along with this program. If not, see <https://www.gnu.org/licenses/>.
+
+//! Parsing of the requested prefixes and counts.
+
+use std::str::FromStr;
+
+/// A request for some number of codes, each starting with a given prefix.
+///
+/// Parsed from a string like "AB3:78" meaning 78 codes starting with "AB3", or
+/// just "78" meaning 78 codes with no prefix. A third field, as in "AB3:78:8", gives
+/// the number of random digits for this prefix instead of the usual number.
+/// "AB3:=78" means enough codes that the prefix's output file ends up with 78 in total.
+/// A last field like `d5`, as in "PG:300:d5" or "PG:300:8:d5", gives the codes their own minimum distance instead of the usual one.
+/// "A:500+50" means 500 codes and 50 spares, to be kept apart from them.
+/// A `*` in the prefix stands for the random digits, so "AB3*X:78" means 78 codes starting with "AB3" and ending with "X",
+/// and "*X:78" ones just ending with "X".
+///
+/// The number of codes may have `_` or `,` between groups of three digits, and a `k` suffix for thousands.
+/// It can't be 0, and a mistake says which argument it was in and what was expected.
+///
+/// For codes with fixed characters in the middle, see [WantedPrefix::from_mask].
+/// ```
+/// use make_exam_numbers::WantedPrefix;
+///
+/// let parse = |s:&str| s.parse::<WantedPrefix>().map(|p|(p.prefix,p.number,p.digits,p.total));
+/// assert_eq!(parse("78"),Ok(("".to_string(),78,None,false)));
+/// assert_eq!(parse("AB3:78"),Ok(("AB3".to_string(),78,None,false)));
+/// assert_eq!(parse("AB3:78:8"),Ok(("AB3".to_string(),78,Some(8),false)));
+/// assert_eq!(parse("AB3:=78"),Ok(("AB3".to_string(),78,None,true)));
+/// assert_eq!(parse("S0:6_000"),Ok(("S0".to_string(),6000,None,false)));
+/// assert_eq!(parse("S0:6,000"),Ok(("S0".to_string(),6000,None,false)));
+/// assert_eq!(parse("S0:1_000_000"),Ok(("S0".to_string(),1000000,None,false)));
+/// assert_eq!(parse("S0:6k"),Ok(("S0".to_string(),6000,None,false)));
+/// assert_eq!(parse("S0:=2K:7"),Ok(("S0".to_string(),2000,Some(7),true)));
+/// assert_eq!(parse("S0:1,500k"),Ok(("S0".to_string(),1500000,None,false)));
+/// assert_eq!(parse("6k"),Ok(("".to_string(),6000,None,false)));
+/// let suffix = |s:&str| s.parse::<WantedPrefix>().map(|p|(p.prefix,p.suffix,p.number));
+/// assert_eq!(suffix("*X:200"),Ok(("".to_string(),"X".to_string(),200)));
+/// assert_eq!(suffix("S0*X:200:7"),Ok(("S0".to_string(),"X".to_string(),200)));
+/// assert_eq!(suffix("S0*:200"),Ok(("S0".to_string(),"".to_string(),200)));
+/// assert_eq!("S0*X:200".parse::<WantedPrefix>().unwrap().name(),"S0*X");
+/// let distance = |s:&str| s.parse::<WantedPrefix>().map(|p|(p.prefix,p.number,p.digits,p.min_distance));
+/// assert_eq!(distance("PG:300:d5"),Ok(("PG".to_string(),300,None,Some("5".to_string()))));
+/// assert_eq!(distance("PG:300:8:d5"),Ok(("PG".to_string(),300,Some(8),Some("5".to_string()))));
+/// assert_eq!(distance("PG:=1k:d2.5"),Ok(("PG".to_string(),1000,None,Some("2.5".to_string()))));
+/// let spares = |s:&str| s.parse::<WantedPrefix>().map(|p|(p.prefix,p.number,p.digits,p.spares));
+/// assert_eq!(spares("A:500+50"),Ok(("A".to_string(),500,None,Some(50))));
+/// assert_eq!(spares("A:2k+0:7"),Ok(("A".to_string(),2000,Some(7),Some(0))));
+/// assert_eq!(spares("500+1k"),Ok(("".to_string(),500,None,Some(1000))));
+/// for bad in ["","S0:","S0:x","S0:6x00","S0:_6","S0:6_","S0:6__000","S0:6,,000","S0:60_00","S0:1,5k","S0:6000_000","S0:k","S0:6kk","S0:6k0","S0:-6",
+///             "S0:0","S0:0k","0","S0:6:","S0:6:x","S0:6:0","S0:=","S0:6:7:8","S0:99999999999999999999","S0:99999999999999999k","A*B*C:5",
+///             "S0:d5","S0:6:d","S0:6:dx","S0:6:d-1","S0:6:d1.2.3","S0:6:7:8:d5","S0:6:d5:7","S0:6:d5:d6",
+///             "S0:6+","S0:+6","S0:6+x","S0:6+1+2","S0:6+-1"] {
+///     let error = parse(bad).unwrap_err();
+///     assert!(error.contains(&format!("{:?}",bad)),"{}",error);
+/// }
+/// ```
+#[derive(Clone,Debug,PartialEq,Eq)]
+pub struct WantedPrefix {
+    pub prefix : String,
+    /// What each code ends with, after the random digits (and before any check digits). Usually empty.
+    pub suffix : String,
+    pub number : usize,
+    /// The number of random digits, if different from the usual number.
+    pub digits : Option<usize>,
+    /// If true, `number` is the total wanted in the prefix's output file, including any codes already in it.
+    pub total : bool,
+    /// For codes from [WantedPrefix::from_mask], the mask, such as `B#2###7#`. The prefix and suffix are then the
+    /// characters before the first `#` and after the last one, and `digits` is the number of `#`s.
+    pub mask : Option<String>,
+    /// The minimum distance between these codes and any other, if different from the usual one, as given (like `5`, or `2.5` for
+    /// weighted Hamming distance), to be read with [crate::distance::Metric::parse_distance].
+    pub min_distance : Option<String>,
+    /// The number of spare codes wanted after the others, if given (possibly 0) instead of the usual number.
+    pub spares : Option<usize>,
+}
+
+impl WantedPrefix {
+    /// The number of random digits for this prefix, given the usual number.
+    pub fn digits_or(&self,default:usize) -> usize { self.digits.unwrap_or(default) }
+
+    /// The number of spare codes for this prefix, given the usual number.
+    pub fn spares_or(&self,default:usize) -> usize { self.spares.unwrap_or(default) }
+
+    /// How to refer to the codes wanted: the mask, the prefix, or with a suffix, `prefix*suffix`.
+    pub fn name(&self) -> String {
+        match &self.mask {
+            Some(mask) => mask.clone(),
+            None if self.suffix.is_empty() => self.prefix.clone(),
+            None => format!("{}*{}",self.prefix,self.suffix),
+        }
+    }
+
+    /// Each character of the codes wanted, given the usual number of digits: `Some` for a fixed character, and None for a random one.
+    pub fn layout(&self,default_digits:usize) -> Vec<Option<char>> {
+        match &self.mask {
+            Some(mask) => mask.chars().map(|c|if c==MASK_RANDOM { None } else { Some(c) }).collect(),
+            None => self.prefix.chars().map(Some).chain(std::iter::repeat_n(None,self.digits_or(default_digits))).chain(self.suffix.chars().map(Some)).collect(),
+        }
+    }
+
+    /// Parse a request for codes laid out by a mask, like "B#2###7#:150" for 150 codes like `B4283271`, where each `#` is a random
+    /// character and the other characters are kept. The number of codes is as for the usual form, including `:=` for a total.
+    /// The mask must have at least one `#`, and can't also give a number of digits, as that is the number of `#`s, but can be
+    /// followed by a minimum distance like `:d5`, as for the usual form.
+    /// ```
+    /// use make_exam_numbers::WantedPrefix;
+    ///
+    /// let wanted = WantedPrefix::from_mask("B#2###7#:150").unwrap();
+    /// assert_eq!((wanted.prefix.as_str(),wanted.suffix.as_str(),wanted.number,wanted.digits),("B","",150,Some(5)));
+    /// assert_eq!(wanted.name(),"B#2###7#");
+    /// assert_eq!(wanted.layout(6),vec![Some('B'),None,Some('2'),None,None,None,Some('7'),None]);
+    /// assert_eq!(WantedPrefix::from_mask("R1##S:=2k").map(|p|(p.prefix,p.suffix,p.number,p.total)),Ok(("R1".to_string(),"S".to_string(),2000,true)));
+    /// assert_eq!(WantedPrefix::from_mask("B#2#:50:d4").unwrap().min_distance,Some("4".to_string()));
+    /// assert_eq!(WantedPrefix::from_mask("B#2#:50+5").unwrap().spares,Some(5));
+    /// for bad in ["B2:150","B#2","B#2:0",":150","B#2:150:6"] {
+    ///     let error = WantedPrefix::from_mask(bad).unwrap_err();
+    ///     assert!(error.contains(&format!("{:?}",bad)),"{}",error);
+    /// }
+    /// ```
+    pub fn from_mask(s:&str) -> Result<Self,String> {
+        let parse = || -> Result<Self,String> {
+            let (mask,number) = s.split_once(':').ok_or("there is no number of codes")?;
+            let (number,min_distance) = split_min_distance(number)?;
+            if number.contains(':') { return Err("the number of digits can't be given, as it is the number of # characters".to_string()); }
+            let digits = mask.chars().filter(|&c|c==MASK_RANDOM).count();
+            if digits==0 { return Err(format!("the mask {:?} has no # characters for the random digits",mask)); }
+            let (number,total) = match number.strip_prefix('=') { Some(number) => (number,true), None => (number,false) };
+            let (number,spares) = split_spares(number)?;
+            let prefix : String = mask.chars().take_while(|&c|c!=MASK_RANDOM).collect();
+            let suffix : String = mask.chars().rev().take_while(|&c|c!=MASK_RANDOM).collect::<Vec<_>>().into_iter().rev().collect();
+            Ok(WantedPrefix{prefix,suffix,number:parse_count(number)?,digits:Some(digits),total,mask:Some(mask.to_string()),min_distance,spares})
+        };
+        parse().map_err(|e|format!("Invalid mask {:?} : {}. Expected MASK:NUMBER or MASK:=NUMBER, where NUMBER may be followed by spares like +50, optionally followed by a minimum distance like :d5, where MASK is like B#2###7# with a # for each random digit",s,e))
+    }
+}
+
+/// The character in a mask standing for a random digit.
+const MASK_RANDOM : char = '#';
+
+/// The forms a [WantedPrefix] can take, for error messages.
+const EXPECTED : &str = "Expected NUMBER, PREFIX:NUMBER, PREFIX:=NUMBER or PREFIX:NUMBER:DIGITS, optionally followed by a minimum distance like :d5, where NUMBER is like 600, 6_000, 6,000 or 6k, perhaps followed by spares like +50, and PREFIX may be like S0*X for codes ending in X";
+
+/// Split a last field giving a minimum distance, like `:d5`, off the number of codes (and digits).
+fn split_min_distance(s:&str) -> Result<(&str,Option<String>),String> {
+    match s.rsplit_once(':') {
+        Some((rest,field)) if field.starts_with('d') => {
+            let distance = &field[1..];
+            let (whole,fraction) = distance.split_once('.').unwrap_or((distance,"0"));
+            let number = |s:&str| !s.is_empty() && s.chars().all(|c|c.is_ascii_digit());
+            if !number(whole) || !number(fraction) { return Err(format!("the minimum distance {:?} is not a number",distance)); }
+            if rest.contains(":d") { return Err("only one minimum distance can be given".to_string()); }
+            Ok((rest,Some(distance.to_string())))
+        }
+        _ => Ok((s,None)),
+    }
+}
+
+/// Split a number of spares, like the `+50` of `500+50`, off the number of codes.
+fn split_spares(s:&str) -> Result<(&str,Option<usize>),String> {
+    match s.split_once('+') {
+        Some((number,spares)) => Ok((number,Some(parse_number(spares,"spares")?))),
+        None => Ok((s,None)),
+    }
+}
+
+/// Parse a number of codes like `6000`, `6_000`, `6,000` or `6k`.
+fn parse_count(s:&str) -> Result<usize,String> {
+    let number = parse_number(s,"codes")?;
+    if number==0 { return Err("the number of codes can't be 0".to_string()); }
+    Ok(number)
+}
+
+/// Parse a number like `6000`, `6_000`, `6,000` or `6k`, called the number of `what` in error messages.
+fn parse_number(s:&str,what:&str) -> Result<usize,String> {
+    let (digits,multiplier) = match s.strip_suffix(['k','K']) { Some(digits) => (digits,1000), None => (s,1) };
+    // with separators, the groups must be of three digits (after the first), so 1,5k is not taken as 15k.
+    let groups : Vec<&str> = digits.split(['_',',']).collect();
+    let valid_group = |(index,group):(usize,&&str)| !group.is_empty() && group.chars().all(|c|c.is_ascii_digit()) && (groups.len()==1 || if index==0 { group.len()<=3 } else { group.len()==3 });
+    if !groups.iter().enumerate().all(valid_group) {
+        return Err(format!("the number of {} {:?} is not a number",what,s));
+    }
+    digits.chars().filter(|c|c.is_ascii_digit()).collect::<String>().parse().ok()
+        .and_then(|n:usize|n.checked_mul(multiplier)).ok_or_else(||format!("the number of {} {:?} is too large",what,s))
+}
+
+impl FromStr for WantedPrefix {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let parse = || -> Result<Self,String> {
+            let (prefix,rest) = s.split_once(':').unwrap_or(("",s));
+            let (rest,min_distance) = split_min_distance(rest)?;
+            let (number,digits) = match rest.split_once(':') {
+                Some((number,digits)) => {
+                    let digits : usize = digits.parse().map_err(|_|format!("the number of digits {:?} is not a number",digits))?;
+                    if digits==0 { return Err("the number of digits can't be 0".to_string()); }
+                    (number,Some(digits))
+                }
+                None => (rest,None),
+            };
+            let (number,total) = match number.strip_prefix('=') { Some(number) if s.contains(':') => (number,true), _ => (number,false) };
+            let (number,spares) = split_spares(number)?;
+            let (prefix,suffix) = prefix.split_once('*').unwrap_or((prefix,""));
+            if suffix.contains('*') { return Err("only one * is allowed, standing for the random digits".to_string()); }
+            Ok(WantedPrefix{prefix:prefix.to_string(),suffix:suffix.to_string(),number:parse_count(number)?,digits,total,mask:None,min_distance,spares})
+        };
+        parse().map_err(|e|format!("Invalid request {:?} : {}. {}",s,e,EXPECTED))
+    }
+}
@@ -0,0 +1,93 @@
+// This program is Copyright 2022 Andrew Conway and licensed under the GPL:
+//
+// This program is free software: you can redistribute it and/or modify it under the terms of the GNU General Public License as published by the Free Software Foundation, either version 3 of the License, or (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful, but WITHOUT ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License along with this program. If not, see <https://www.gnu.org/licenses/>.
+
+//! Seeds for making the random number generator reproducible.
+
+use std::fmt::{Display, Formatter};
+use std::str::FromStr;
+use rand_chacha::ChaCha8Rng;
+use rand_chacha::rand_core::{RngCore, SeedableRng};
+
+/// A seed for the random number generator.
+#[derive(Clone,Copy,Debug,PartialEq,Eq)]
+pub enum Seed {
+    /// A 64 bit number, expanded to a full key as by [SeedableRng::seed_from_u64].
+    U64(u64),
+    /// A full 256 bit ChaCha key.
+    Key([u8;32]),
+}
+
+impl From<u64> for Seed {
+    fn from(seed: u64) -> Self { Seed::U64(seed) }
+}
+
+impl Seed {
+    /// The random number generator this seed gives.
+    pub fn prng(&self) -> ChaCha8Rng {
+        match self {
+            Seed::U64(seed) => ChaCha8Rng::seed_from_u64(*seed),
+            Seed::Key(key) => ChaCha8Rng::from_seed(*key),
+        }
+    }
+
+    /// A different seed made from this one for some other purpose, such as generating PINs to go with the codes.
+    ///
+    /// The same seed and purpose always give the same result, and random numbers from it are independent of those from this seed.
+    ///
+    /// ```
+    /// use make_exam_numbers::Seed;
+    /// let seed = Seed::U64(42);
+    /// assert_eq!(seed.derive("pin"),seed.derive("pin"));
+    /// assert_ne!(seed.derive("pin"),seed.derive("other"));
+    /// assert_ne!(seed.derive("pin"),seed);
+    /// ```
+    pub fn derive(&self,purpose:&str) -> Seed {
+        let mut prng = self.prng();
+        // a stream no prefix can use, as prefixes don't contain a NUL.
+        prng.set_stream(stream_number(&format!("\0{}",purpose)));
+        let mut key = [0u8;32];
+        prng.fill_bytes(&mut key);
+        Seed::Key(key)
+    }
+}
+
+/// The ChaCha stream used for a prefix. This is the FNV-1a hash of the prefix, which is stable between versions and platforms.
+pub(crate) fn stream_number(prefix:&str) -> u64 {
+    prefix.bytes().fold(0xcbf29ce484222325u64,|hash,b|(hash^b as u64).wrapping_mul(0x100000001b3))
+}
+
+/// Parses either a decimal 64 bit number or 64 hexadecimal characters (a 256 bit key).
+impl FromStr for Seed {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if let Ok(seed) = s.parse::<u64>() { return Ok(Seed::U64(seed)); }
+        let hex = s.strip_prefix("0x").unwrap_or(s);
+        if let Some(c) = hex.chars().find(|c|!c.is_ascii_hexdigit()) {
+            return Err(format!("Seed {} should be a number up to {} or 64 hexadecimal characters, but contains {:?}",s,u64::MAX,c));
+        }
+        if hex.len()!=64 {
+            return Err(format!("Seed {} should be a number up to {} or exactly 64 hexadecimal characters, but has {} hexadecimal characters",s,u64::MAX,hex.len()));
+        }
+        let mut key = [0u8;32];
+        for (i,byte) in key.iter_mut().enumerate() {
+            *byte = u8::from_str_radix(&hex[2*i..2*i+2],16).map_err(|e|e.to_string())?;
+        }
+        Ok(Seed::Key(key))
+    }
+}
+
+/// Shows the seed in the same form it is parsed from, a decimal number or 64 hexadecimal characters.
+impl Display for Seed {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Seed::U64(seed) => write!(f,"{}",seed),
+            Seed::Key(key) => { for b in key { write!(f,"{:02x}",b)?; } Ok(()) }
+        }
+    }
+}
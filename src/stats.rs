@@ -0,0 +1,89 @@
+// This program is Copyright 2022 Andrew Conway and licensed under the GPL:
+//
+// This program is free software: you can redistribute it and/or modify it under the terms of the GNU General Public License as published by the Free Software Foundation, either version 3 of the License, or (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful, but WITHOUT ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License along with this program. If not, see <https://www.gnu.org/licenses/>.
+
+//! Counts of how hard the generator had to work to find codes.
+
+use std::collections::{BTreeMap, VecDeque};
+use crate::filter::Rejection;
+
+/// How many of the most recent codes [Statistics::recent_average] is over.
+pub const RECENT_CODES : usize = 100;
+
+/// Counts of candidates tried, accepted and rejected, for one prefix or several.
+#[derive(Clone,Debug,Default,PartialEq)]
+pub struct Statistics {
+    /// The number of candidates looked at.
+    pub candidates : u64,
+    /// The number of candidates accepted as codes.
+    pub accepted : u64,
+    /// The number of candidates rejected as too close to some used code.
+    pub too_close : u64,
+    /// The number of candidates only rejected as too close to some used code when rotated, with [crate::GenerateCodes::set_include_rotations].
+    pub too_close_rotated : u64,
+    /// With [crate::GenerateCodes::set_include_rotations], the number of rotations of candidates compared with the used codes,
+    /// which is the extra work it takes.
+    pub rotations_compared : u64,
+    /// The number of candidates rejected by each filter, by [crate::filter::Filter::name].
+    pub filtered : BTreeMap<&'static str,u64>,
+    /// Candidates looked at for each of the most recent codes accepted.
+    recent : VecDeque<u64>,
+    /// Candidates looked at since the last code was accepted.
+    since_accepted : u64,
+}
+
+impl Statistics {
+    pub(crate) fn rejected(&mut self,rejection:&Rejection) {
+        self.candidates+=1;
+        self.since_accepted+=1;
+        match rejection {
+            Rejection::TooClose => self.too_close+=1,
+            // rotations are tried in order, so this is the number compared.
+            Rejection::TooCloseRotated{rotation} => { self.too_close_rotated+=1; self.rotations_compared+=*rotation as u64; }
+            Rejection::Filter(filter) => *self.filtered.entry(filter.name()).or_default()+=1,
+        }
+    }
+
+    pub(crate) fn accepted(&mut self) {
+        self.candidates+=1;
+        self.accepted+=1;
+        if self.recent.len()==RECENT_CODES { self.recent.pop_front(); }
+        self.recent.push_back(self.since_accepted+1);
+        self.since_accepted=0;
+    }
+
+    /// The number of candidates rejected, for any reason.
+    pub fn rejected_total(&self) -> u64 { self.candidates-self.accepted }
+
+    /// The number of candidates rejected by all the filters.
+    pub fn filtered_total(&self) -> u64 { self.filtered.values().sum() }
+
+    /// The fraction of candidates accepted, or None if there weren't any.
+    pub fn acceptance_rate(&self) -> Option<f64> {
+        if self.candidates==0 { None } else { Some(self.accepted as f64/self.candidates as f64) }
+    }
+
+    /// The average number of candidates looked at per code, over the last [RECENT_CODES] codes accepted, or None if none have been.
+    /// This goes up sharply as the number of codes approaches the capacity.
+    pub fn recent_average(&self) -> Option<f64> {
+        if self.recent.is_empty() { None } else { Some(self.recent.iter().sum::<u64>() as f64/self.recent.len() as f64) }
+    }
+
+    /// The number of codes [Statistics::recent_average] is over.
+    pub fn recent_codes(&self) -> usize { self.recent.len() }
+
+    /// Add the counts from another set of statistics. The recent codes are those of `other`, if it has any, as it is taken to be later.
+    pub fn add(&mut self,other:&Statistics) {
+        self.candidates+=other.candidates;
+        self.accepted+=other.accepted;
+        self.too_close+=other.too_close;
+        self.too_close_rotated+=other.too_close_rotated;
+        self.rotations_compared+=other.rotations_compared;
+        for (name,count) in &other.filtered { *self.filtered.entry(name).or_default()+=count; }
+        if !other.recent.is_empty() { self.recent=other.recent.clone(); self.since_accepted=other.since_accepted; }
+    }
+}
@@ -0,0 +1,74 @@
+// This program is Copyright 2022 Andrew Conway and licensed under the GPL:
+//
+// This program is free software: you can redistribute it and/or modify it under the terms of the GNU General Public License as published by the Free Software Foundation, either version 3 of the License, or (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful, but WITHOUT ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License along with this program. If not, see <https://www.gnu.org/licenses/>.
+
+//! Codes as an [Iterator], so they can be taken lazily.
+
+use std::cell::RefCell;
+use crate::{GenerateCodes, GenerationError};
+
+impl GenerateCodes {
+    /// An endless iterator of new codes with the given prefix, each at least `min_hamming_distance` from every code used before it.
+    ///
+    /// Each code is added to the used codes as it is produced. If a code can't be found (see [GenerateCodes::set_max_attempts])
+    /// the error is produced as the last item. To interleave codes for several prefixes, see [CodeStream].
+    pub fn codes<'a>(&'a mut self,prefix:&'a str,min_hamming_distance:usize) -> impl Iterator<Item=Result<String,GenerationError>> + 'a {
+        let mut failed = false;
+        std::iter::from_fn(move||{
+            if failed { return None; }
+            let res = self.new_code(prefix,min_hamming_distance);
+            failed = res.is_err();
+            Some(res)
+        })
+    }
+}
+
+/// An endless iterator of new codes with one prefix, from a generator that can be shared with other streams.
+///
+/// Every stream sharing the generator adds its codes to the same used codes, so codes from all of them are far enough apart,
+/// whatever order they are taken in. If a code can't be found (see [GenerateCodes::set_max_attempts]) the error is produced
+/// as the last item.
+///
+/// ```
+/// use std::cell::RefCell;
+/// use make_exam_numbers::{CodeStream, GenerateCodes};
+///
+/// let generator = RefCell::new(GenerateCodes::new(Some(42),4));
+/// generator.borrow_mut().set_max_attempts(Some(10000));
+/// let mut morning = CodeStream::new(&generator,"AM",3);
+/// let mut afternoon = CodeStream::new(&generator,"PM",3);
+/// let mut codes = vec![];
+/// for _ in 0..5 {
+///     codes.push(morning.next().unwrap().unwrap());
+///     codes.push(afternoon.next().unwrap().unwrap());
+/// }
+/// assert!(codes.iter().zip(["AM","PM"].iter().cycle()).all(|(code,prefix)|code.starts_with(prefix)));
+/// assert_eq!(generator.borrow().used().len(),10);
+/// ```
+pub struct CodeStream<'a> {
+    generator : &'a RefCell<GenerateCodes>,
+    prefix : String,
+    min_hamming_distance : usize,
+    failed : bool,
+}
+
+impl <'a> CodeStream<'a> {
+    pub fn new(generator:&'a RefCell<GenerateCodes>,prefix:&str,min_hamming_distance:usize) -> Self {
+        CodeStream{generator,prefix:prefix.to_string(),min_hamming_distance,failed:false}
+    }
+}
+
+impl Iterator for CodeStream<'_> {
+    type Item = Result<String,GenerationError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.failed { return None; }
+        let res = self.generator.borrow_mut().new_code(&self.prefix,self.min_hamming_distance);
+        self.failed = res.is_err();
+        Some(res)
+    }
+}
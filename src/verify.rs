@@ -0,0 +1,265 @@
+// This program is Copyright 2022 Andrew Conway and licensed under the GPL:
+//
+// This program is free software: you can redistribute it and/or modify it under the terms of the GNU General Public License as published by the Free Software Foundation, either version 3 of the License, or (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful, but WITHOUT ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License along with this program. If not, see <https://www.gnu.org/licenses/>.
+
+//! Check that a set of codes actually satisfies the minimum distance property.
+
+use std::collections::HashMap;
+use crate::check_digit::CheckDigit;
+use crate::filter::DigitSum;
+use crate::distance::{LengthPolicy, Metric};
+use crate::index::HammingIndex;
+
+/// Something wrong with a set of codes. Codes are referred to by their index in the list checked.
+#[derive(Debug,Clone,PartialEq,Eq)]
+pub enum Problem {
+    /// The two codes are identical.
+    Duplicate { first : usize, second : usize },
+    /// The code has a different number of characters to the first code.
+    InconsistentLength { index : usize, length : usize, expected : usize },
+    /// The two codes are closer than allowed.
+    TooClose { first : usize, second : usize, distance : usize },
+    /// The first code and the second read backwards are closer than allowed.
+    TooCloseReversed { first : usize, second : usize, distance : usize },
+    /// The code does not end with the correct check digit(s).
+    InvalidCheckDigit { index : usize },
+    /// The code's digit sum is `sum`, which doesn't satisfy the condition `wanted`.
+    WrongDigitSum { index : usize, sum : u64, wanted : DigitSum },
+}
+
+/// Find all problems with a set of codes that should be pairwise at least `min_distance` apart, as measured by `metric`.
+///
+/// For Hamming distance (weighted or not) with no `length_policy`, pairs of codes of different lengths are not compared, as the length problem is reported instead.
+/// With a `length_policy`, codes of different lengths are expected and compared according to it.
+/// Other metrics can compare codes of different lengths, so lengths are not checked.
+/// Duplicates are reported as such rather than as being too close.
+///
+/// For Hamming distance this uses the same index as the generator, so tens of thousands of codes can be checked quickly.
+pub fn find_problems(codes:&[String],min_distance:usize,metric:Metric,length_policy:Option<LengthPolicy>) -> Vec<Problem> {
+    let mut problems = vec![];
+    let chars : Vec<Vec<char>> = codes.iter().map(|s|s.chars().collect()).collect();
+    let check_lengths = matches!(metric,Metric::Hamming|Metric::WeightedHamming(_)) && length_policy.is_none();
+    if check_lengths {
+        let expected = chars.first().map(|c|c.len()).unwrap_or(0);
+        for (index,c) in chars.iter().enumerate() {
+            if c.len()!=expected { problems.push(Problem::InconsistentLength{index,length:c.len(),expected}); }
+        }
+    }
+    // first occurrence of each code, so duplicates can be found without comparing.
+    let mut seen : HashMap<&str,usize> = HashMap::new();
+    let mut distinct : Vec<usize> = vec![];
+    for (index,code) in codes.iter().enumerate() {
+        if let Some(&first) = seen.get(code.as_str()) { problems.push(Problem::Duplicate{first,second:index}); }
+        else { seen.insert(code,index); distinct.push(index); }
+    }
+    if metric==Metric::Hamming {
+        // without a length policy codes of different lengths are not compared, which is what Distinct does.
+        let mut index = HammingIndex::new(min_distance,length_policy.unwrap_or(LengthPolicy::Distinct));
+        let mut too_close : Vec<(usize,usize,usize)> = vec![];
+        for &second in &distinct {
+            too_close.extend(index.close_to(&codes[second],codes).into_iter().map(|(first,distance)|(first,second,distance)));
+            index.insert(second,&codes[second]);
+        }
+        // report in the same order as comparing each code against all the later ones.
+        too_close.sort_unstable();
+        problems.extend(too_close.into_iter().map(|(first,second,distance)|Problem::TooClose{first,second,distance}));
+        return problems;
+    }
+    for (i,&first) in distinct.iter().enumerate() {
+        let a = &chars[first];
+        for &second in &distinct[i+1..] {
+            if let Some(distance) = metric.distance_below_with_policy(a,&chars[second],min_distance,length_policy.unwrap_or(LengthPolicy::Distinct)) { problems.push(Problem::TooClose{first,second,distance}); }
+        }
+    }
+    problems
+}
+
+/// Find all pairs of codes where one read backwards is closer than `min_distance` to the other, as measured by `metric`,
+/// for codes that must not be confused when read upside down ([crate::GenerateCodes::set_include_reversals]). Reversing both
+/// codes doesn't change their distance, so each pair is reported once, with `first` before `second`. A code is not
+/// compared with its own reversal. Codes of different lengths are compared according to `length_policy`, or not at all without one.
+///
+/// ```
+/// use make_exam_numbers::distance::Metric;
+/// use make_exam_numbers::verify::{find_reversal_problems, Problem};
+///
+/// let codes = ["123456","654399","111111","121212"].map(|s|s.to_string());
+/// assert_eq!(find_reversal_problems(&codes,3,Metric::Hamming,None),vec![Problem::TooCloseReversed{first:0,second:1,distance:2}]);
+/// assert_eq!(find_reversal_problems(&codes,3,Metric::Levenshtein,None),vec![Problem::TooCloseReversed{first:0,second:1,distance:2}]);
+/// ```
+pub fn find_reversal_problems(codes:&[String],min_distance:usize,metric:Metric,length_policy:Option<LengthPolicy>) -> Vec<Problem> {
+    let reversed : Vec<String> = codes.iter().map(|c|c.chars().rev().collect()).collect();
+    let length_policy = length_policy.unwrap_or(LengthPolicy::Distinct);
+    let mut too_close : Vec<(usize,usize,usize)> = vec![];
+    if metric==Metric::Hamming {
+        let mut index = HammingIndex::new(min_distance,length_policy);
+        for (second,code) in codes.iter().enumerate() {
+            too_close.extend(index.close_to(&reversed[second],codes).into_iter().map(|(first,distance)|(first,second,distance)));
+            index.insert(second,code);
+        }
+        too_close.sort_unstable();
+    } else {
+        let chars : Vec<Vec<char>> = codes.iter().map(|s|s.chars().collect()).collect();
+        let reversed : Vec<Vec<char>> = reversed.iter().map(|s|s.chars().collect()).collect();
+        for (first,a) in chars.iter().enumerate() {
+            for (second,b) in reversed.iter().enumerate().skip(first+1) {
+                if let Some(distance) = metric.distance_below_with_policy(a,b,min_distance,length_policy) { too_close.push((first,second,distance)); }
+            }
+        }
+    }
+    too_close.into_iter().map(|(first,second,distance)|Problem::TooCloseReversed{first,second,distance}).collect()
+}
+
+/// Like [find_problems], but codes are only compared against others with the same prefix ([crate::distance::Scope::PerPrefix]).
+///
+/// Each code is taken to have the longest of the `prefixes` it starts with. Codes starting with none of them are compared with each other.
+pub fn find_problems_per_prefix(codes:&[String],min_distance:usize,metric:Metric,length_policy:Option<LengthPolicy>,prefixes:&[String]) -> Vec<Problem> {
+    find_problems_grouped(codes,codes,min_distance,metric,length_policy,prefixes)
+}
+
+/// Like [find_problems_per_prefix], but the prefix of `codes[i]` is taken to be that of `prefixed[i]`, for when what is compared is not the whole code.
+pub(crate) fn find_problems_grouped(codes:&[String],prefixed:&[String],min_distance:usize,metric:Metric,length_policy:Option<LengthPolicy>,prefixes:&[String]) -> Vec<Problem> {
+    let mut groups : HashMap<Option<&str>,Vec<usize>> = HashMap::new();
+    for (index,code) in prefixed.iter().enumerate() {
+        groups.entry(longest_prefix(code,prefixes)).or_default().push(index);
+    }
+    let mut groups : Vec<Vec<usize>> = groups.into_values().collect();
+    groups.sort(); // so the problems are reported in a consistent order.
+    let mut problems = vec![];
+    for group in groups {
+        let group_codes : Vec<String> = group.iter().map(|&i|codes[i].clone()).collect();
+        problems.extend(find_problems(&group_codes,min_distance,metric,length_policy).into_iter().map(|problem| match problem {
+            Problem::Duplicate { first, second } => Problem::Duplicate{first:group[first],second:group[second]},
+            Problem::InconsistentLength { index, length, expected } => Problem::InconsistentLength{index:group[index],length,expected},
+            Problem::TooClose { first, second, distance } => Problem::TooClose{first:group[first],second:group[second],distance},
+            Problem::TooCloseReversed { first, second, distance } => Problem::TooCloseReversed{first:group[first],second:group[second],distance},
+            Problem::InvalidCheckDigit { index } => Problem::InvalidCheckDigit{index:group[index]},
+            Problem::WrongDigitSum { index, sum, wanted } => Problem::WrongDigitSum{index:group[index],sum,wanted},
+        }));
+    }
+    problems
+}
+
+/// The longest of the `prefixes` the code starts with, if any.
+pub(crate) fn longest_prefix<'a>(code:&str,prefixes:&'a [String]) -> Option<&'a str> {
+    prefixes.iter().filter(|p|code.starts_with(p.as_str())).max_by_key(|p|p.len()).map(|p|p.as_str())
+}
+
+/// Keep only the problems that are problems when codes with the same prefix must be at least `within` apart and codes with
+/// different prefixes at least `across`, for problems found with the larger of the two. Each code has the longest of the
+/// `prefixes` it starts with, as for [find_problems_per_prefix], and codes starting with none of them count as having
+/// different prefixes to every other code.
+///
+/// ```
+/// use make_exam_numbers::distance::Metric;
+/// use make_exam_numbers::verify::{find_problems, keep_prefix_problems, Problem};
+///
+/// let codes = ["A1234","A1299","B1294","B9999"].map(|s|s.to_string());
+/// let prefixes = ["A".to_string(),"B".to_string()];
+/// let problems = keep_prefix_problems(find_problems(&codes,3,Metric::Hamming,None),&codes,&prefixes,3,2);
+/// assert_eq!(problems,vec![Problem::TooClose{first:0,second:1,distance:2}]); // A1234 and B1294 are only 2 apart, which is enough.
+/// ```
+pub fn keep_prefix_problems(problems:Vec<Problem>,codes:&[String],prefixes:&[String],within:usize,across:usize) -> Vec<Problem> {
+    let same_prefix = |a:usize,b:usize| longest_prefix(&codes[a],prefixes).is_some() && longest_prefix(&codes[a],prefixes)==longest_prefix(&codes[b],prefixes);
+    problems.into_iter().filter(|problem| match *problem {
+        Problem::TooClose { first, second, distance } | Problem::TooCloseReversed { first, second, distance } => distance<if same_prefix(first,second) { within } else { across },
+        _ => true,
+    }).collect()
+}
+
+/// The minimum distance for a code when groups of codes have their own, given as (prefix, minimum distance): that of the
+/// longest prefix the code starts with, or `default` if it starts with none of them.
+///
+/// ```
+/// use make_exam_numbers::verify::{min_distance_for, required_distance};
+///
+/// let min_distances = [("P".to_string(),4),("PG".to_string(),5),("PGR".to_string(),2)];
+/// assert_eq!(min_distance_for("P1234",3,&min_distances),4);
+/// assert_eq!(min_distance_for("PG1234",3,&min_distances),5);
+/// assert_eq!(min_distance_for("PGR1234",3,&min_distances),2); // the longest prefix wins, even with a smaller distance.
+/// assert_eq!(min_distance_for("UG1234",3,&min_distances),3);
+/// assert_eq!(min_distance_for("1234",3,&[]),3);
+/// // codes in different groups must be the larger of their two distances apart.
+/// assert_eq!(required_distance("PGR1234","P5678",3,&min_distances),4);
+/// assert_eq!(required_distance("PGR1234","UG5678",3,&min_distances),3);
+/// assert_eq!(required_distance("PGR1234","PGR5678",3,&min_distances),2);
+/// ```
+pub fn min_distance_for(code:&str,default:usize,min_distances:&[(String,usize)]) -> usize {
+    min_distances.iter().filter(|(p,_)|code.starts_with(p.as_str())).max_by_key(|(p,_)|p.len()).map(|&(_,d)|d).unwrap_or(default)
+}
+
+/// The distance two codes must be apart when groups of codes have their own minimum distances (see [min_distance_for]), which
+/// is the larger of the two codes' minimum distances. So a group with a large minimum distance keeps its codes that far from
+/// every other code, and two groups with small minimum distances only need to be that far apart.
+///
+/// ```
+/// use make_exam_numbers::verify::required_distance;
+///
+/// let min_distances = [("PG".to_string(),5),("UG".to_string(),3)];
+/// assert_eq!(required_distance("PG1234","PG5678",3,&min_distances),5);
+/// assert_eq!(required_distance("UG1234","PG5678",3,&min_distances),5);
+/// assert_eq!(required_distance("UG1234","UG5678",3,&min_distances),3);
+/// assert_eq!(required_distance("UG1234","XX5678",2,&min_distances),3);
+/// assert_eq!(required_distance("XX1234","XX5678",2,&min_distances),2);
+/// ```
+pub fn required_distance(a:&str,b:&str,default:usize,min_distances:&[(String,usize)]) -> usize {
+    min_distance_for(a,default,min_distances).max(min_distance_for(b,default,min_distances))
+}
+
+/// Keep only the problems that are problems when groups of codes have their own minimum distances, for problems found with
+/// the largest of them. Pairs of codes are too close if they are less than [required_distance] apart.
+///
+/// ```
+/// use make_exam_numbers::distance::Metric;
+/// use make_exam_numbers::verify::{find_problems, keep_group_problems, Problem};
+///
+/// let codes = ["PG1234","PG1299","UG5678","UG5699"].map(|s|s.to_string());
+/// let min_distances = [("PG".to_string(),3)];
+/// let problems = keep_group_problems(find_problems(&codes,3,Metric::Hamming,None),&codes,2,&min_distances);
+/// assert_eq!(problems,vec![Problem::TooClose{first:0,second:1,distance:2}]);
+/// ```
+pub fn keep_group_problems(problems:Vec<Problem>,codes:&[String],default:usize,min_distances:&[(String,usize)]) -> Vec<Problem> {
+    problems.into_iter().filter(|problem| match *problem {
+        Problem::TooClose { first, second, distance } | Problem::TooCloseReversed { first, second, distance } => distance<required_distance(&codes[first],&codes[second],default,min_distances),
+        _ => true,
+    }).collect()
+}
+
+/// Go through the codes in order, keeping each one unless it is less than `min_distance` from (or identical to) a code already kept.
+///
+/// Returns, for each code, None if it is kept, or the index of the first kept code it is too close to.
+/// Codes of different lengths are compared with Hamming distance (weighted or not) according to `length_policy`.
+pub fn keep_compatible(codes:&[String],min_distance:usize,metric:Metric,length_policy:LengthPolicy) -> Vec<Option<usize>> {
+    let mut res = Vec::with_capacity(codes.len());
+    if metric==Metric::Hamming {
+        let mut index = HammingIndex::new(min_distance.max(1),length_policy);
+        for (i,code) in codes.iter().enumerate() {
+            let conflict = index.close_to(code,codes).first().map(|&(kept,_)|kept);
+            if conflict.is_none() { index.insert(i,code); }
+            res.push(conflict);
+        }
+    } else {
+        let chars : Vec<Vec<char>> = codes.iter().map(|s|s.chars().collect()).collect();
+        let mut kept : Vec<usize> = vec![];
+        for (i,code) in chars.iter().enumerate() {
+            let conflict = kept.iter().copied().find(|&k|metric.distance_below_with_policy(&chars[k],code,min_distance.max(1),length_policy).is_some());
+            if conflict.is_none() { kept.push(i); }
+            res.push(conflict);
+        }
+    }
+    res
+}
+
+/// Find all codes whose digit sum, with each character's value its position in `values`, doesn't satisfy `wanted`.
+pub fn find_digit_sum_problems(codes:&[String],wanted:DigitSum,values:&[char]) -> Vec<Problem> {
+    codes.iter().enumerate().filter(|(_,code)|!wanted.accepts(code,values)).map(|(index,code)|Problem::WrongDigitSum{index,sum:DigitSum::sum(code,values),wanted}).collect()
+}
+
+/// Find all codes that do not end with the correct check digit(s) for the given scheme.
+pub fn find_check_digit_problems(codes:&[String],check_digit:CheckDigit) -> Vec<Problem> {
+    codes.iter().enumerate().filter(|(_,code)|!check_digit.is_valid(code)).map(|(index,_)|Problem::InvalidCheckDigit{index}).collect()
+}
@@ -0,0 +1,63 @@
+// This program is Copyright 2022 Andrew Conway and licensed under the GPL:
+//
+// This program is free software: you can redistribute it and/or modify it under the terms of the GNU General Public License as published by the Free Software Foundation, either version 3 of the License, or (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful, but WITHOUT ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License along with this program. If not, see <https://www.gnu.org/licenses/>.
+
+//! Run the program itself, checking it exits with the status listed in `--help` for each kind of failure.
+
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+/// An empty directory to run the program in, removed afterwards.
+struct Scratch(PathBuf);
+
+impl Scratch {
+    fn new(name:&str) -> Self {
+        let dir = std::env::temp_dir().join(format!("make_exam_numbers_{}_{}",name,std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        Scratch(dir)
+    }
+    fn path(&self) -> &Path { &self.0 }
+}
+
+impl Drop for Scratch {
+    fn drop(&mut self) { let _ = std::fs::remove_dir_all(&self.0); }
+}
+
+/// The exit status of the program run in `dir` with `args`.
+fn status(dir:&Path,args:&[&str]) -> i32 {
+    let output = Command::new(env!("CARGO_BIN_EXE_make_exam_numbers")).current_dir(dir).args(args).output().expect("Could not run the program");
+    output.status.code().expect("Killed by a signal")
+}
+
+/// A run that succeeds exits with 0, and a second the same exits with 2 as its output file already exists.
+#[test]
+fn existing_output_file_exits_with_2() {
+    let dir = Scratch::new("exists");
+    assert_eq!(status(dir.path(),&["-q","--no-manifest","--seed","1","3","5","A:30"]),0);
+    assert!(dir.path().join("prefix_A.txt").exists());
+    assert_eq!(status(dir.path(),&["-q","--no-manifest","--seed","1","3","5","A:30"]),2);
+    assert_eq!(status(dir.path(),&["-q","--no-manifest","--force","--seed","1","3","5","A:30"]),0);
+}
+
+/// An `--existing` file with a line that isn't a code exits with 3.
+#[test]
+fn invalid_existing_code_exits_with_3() {
+    let dir = Scratch::new("invalid");
+    std::fs::write(dir.path().join("existing.txt"),"12345\nxx\n").unwrap();
+    assert_eq!(status(dir.path(),&["-q","--no-manifest","--existing","existing.txt","3","5","A:30"]),3);
+    assert!(!dir.path().join("prefix_A.txt").exists());
+}
+
+/// Asking for more codes than can fit exits with 4, as does reaching `--max-attempts`.
+#[test]
+fn infeasible_or_exhausted_exits_with_4() {
+    let dir = Scratch::new("infeasible");
+    assert_eq!(status(dir.path(),&["-q","--no-manifest","3","5","A:100000"]),4);
+    assert!(!dir.path().join("prefix_A.txt").exists());
+    assert_eq!(status(dir.path(),&["-q","--no-manifest","--max-attempts","1","--seed","1","3","5","A:200"]),4);
+}